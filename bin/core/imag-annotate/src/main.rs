@@ -98,7 +98,7 @@ fn main() {
 fn add(rt: &Runtime) {
     let scmd = rt.cli().subcommand_matches("add").unwrap(); // safed by main()
     let mut ids = rt
-        .ids::<crate::ui::PathProvider>()
+        .ids_or_pick::<crate::ui::PathProvider>()
         .map_err_trace_exit_unwrap()
         .unwrap_or_else(|| {
             error!("No StoreId supplied");
@@ -152,7 +152,7 @@ fn remove(rt: &Runtime) {
     let annotation_name = scmd.value_of("annotation_name").unwrap(); // safed by clap
     let delete = scmd.is_present("delete-annotation");
     let ids = rt
-        .ids::<crate::ui::PathProvider>()
+        .ids_or_pick::<crate::ui::PathProvider>()
         .map_err_trace_exit_unwrap()
         .unwrap_or_else(|| {
             error!("No ids supplied");
@@ -196,7 +196,7 @@ fn list(rt: &Runtime) {
     let scmd = rt.cli().subcommand_matches("list").unwrap(); // safed by clap
     let with_text = scmd.is_present("list-with-text");
     let ids = rt
-        .ids::<crate::ui::PathProvider>()
+        .ids_or_pick::<crate::ui::PathProvider>()
         .map_err_trace_exit_unwrap()
         .unwrap_or_else(|| {
             error!("No ids supplied");
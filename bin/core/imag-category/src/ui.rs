@@ -49,7 +49,7 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                          .required(true)
                          .multiple(false)
                          .help("The name of the category to delete")
-                         .value_name("NAME"))
+                         .value_name("CATEGORY"))
                    )
 
         .subcommand(SubCommand::with_name("list-categories")
@@ -65,7 +65,7 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                          .required(true)
                          .multiple(false)
                          .help("The name of the category to list all entries for")
-                         .value_name("NAME"))
+                         .value_name("CATEGORY"))
                    )
 
         .subcommand(SubCommand::with_name("set")
@@ -77,7 +77,7 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                          .required(true)
                          .multiple(false)
                          .help("The name of the category to list all entries for")
-                         .value_name("NAME"))
+                         .value_name("CATEGORY"))
 
                     .arg(Arg::with_name("set-ids")
                          .index(2)
@@ -0,0 +1,310 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+#![forbid(unsafe_code)]
+
+#![deny(
+    non_camel_case_types,
+    non_snake_case,
+    path_statements,
+    trivial_numeric_casts,
+    unstable_features,
+    unused_allocation,
+    unused_import_braces,
+    unused_imports,
+    unused_must_use,
+    unused_mut,
+    unused_qualifications,
+    while_true,
+)]
+
+#[macro_use] extern crate log;
+extern crate clap;
+extern crate toml;
+extern crate toml_query;
+extern crate toml_edit;
+#[macro_use] extern crate failure;
+
+#[macro_use] extern crate libimagrt;
+extern crate libimagerror;
+extern crate libimagutil;
+extern crate libimagstore;
+extern crate libimaginteraction;
+extern crate libimagentryref;
+
+use std::fs::OpenOptions;
+use std::io::Read;
+use std::io::Write;
+use std::io::stdout;
+use std::path::PathBuf;
+
+use failure::Error;
+use failure::Fallible as Result;
+use failure::err_msg;
+use toml::Value;
+use toml_query::read::TomlValueReadExt;
+use toml_query::insert::TomlValueInsertExt;
+use clap::ArgMatches;
+
+use libimagrt::runtime::get_rtp_match;
+use libimagrt::setup::generate_config_setup;
+use libimagrt::configuration::fetch_config;
+use libimagrt::configuration::find_config_file;
+use libimagrt::configuration::override_config;
+use libimagrt::profile::profiles;
+use libimagrt::schema::ConfigKey;
+use libimagrt::schema::ConfigSchema;
+use libimagrt::schema::Problem;
+use libimagrt::schema::ValueKind;
+use libimagerror::trace::MapErrTrace;
+use libimagerror::exit::ExitUnwrap;
+use libimagerror::io::ToExitCode;
+use libimagutil::warn_exit::warn_exit;
+
+mod ui;
+
+use crate::ui::build_ui;
+
+fn main() {
+    // The store is not opened, so that a broken configuration can be validated and fixed
+    let version           = make_imag_version!();
+    let (matches, config) = generate_config_setup("imag-config",
+                                                  &version,
+                                                  "Read, validate and edit the configuration",
+                                                  build_ui);
+
+    match matches.subcommand_name() {
+        Some("get")      => get(&matches, config.as_ref()),
+        Some("set")      => set(&matches),
+        Some("validate") => validate(&matches),
+        Some("dump")     => dump(&matches, config.as_ref()),
+        Some(other)      => {
+            debug!("Unknown command");
+            warn_exit(&format!("No such command: 'imag-config {}'", other), 1)
+        },
+        None => debug!("No command"),
+    }
+}
+
+/// All known configuration keys
+fn schema() -> ConfigSchema {
+    let mut schema = ConfigSchema::new();
+    ::libimagrt::schema::register_config_schema(&mut schema);
+    ::libimagrt::logger::register_config_schema(&mut schema);
+    ::libimagstore::schema::register_config_schema(&mut schema);
+    ::libimaginteraction::ui::register_config_schema(&mut schema);
+    ::libimagentryref::util::register_config_schema(&mut schema);
+    register_command_schema(&mut schema);
+    schema
+}
+
+/// The keys which are read by the imag commands themselves
+///
+/// The commands are separate binaries, so their keys are declared here. Asking the installed
+/// commands for their keys would make the result of the validation depend on `$PATH`.
+fn register_command_schema(schema: &mut ConfigSchema) {
+    use libimagrt::schema::ValueKind::*;
+
+    schema
+        .register(ConfigKey::new("bookmark.default_collection", String, "imag-bookmark: The collection used if none is passed"))
+        .register(ConfigKey::new("contact.list_format", String, "imag-contact: Handlebars template for listing contacts"))
+        .register(ConfigKey::new("contact.show_format", String, "imag-contact: Handlebars template for showing a contact"))
+        .register(ConfigKey::new("diary.default_diary", String, "imag-diary: The diary used if none is passed"))
+        .register_table("diary.diaries", "imag-diary: Settings for single diaries")
+        .register(ConfigKey::new("git.execute_in_store", Boolean, "imag-git: Run git in the store directory")
+                  .with_default(false))
+        .register(ConfigKey::new("log.logs", Array, "imag-log: The names of the logs"))
+        .register(ConfigKey::new("log.default", String, "imag-log: The log used if none is passed"))
+        .register(ConfigKey::new("mail.ref_collection_name", String, "imag-mail: The ref collection mails are in"))
+        .register_table("view.viewers", "imag-view: Viewer commands by name");
+}
+
+/// The path to search the configuration file in, as the runtime does it
+fn config_searchpath(matches: &ArgMatches) -> PathBuf {
+    matches
+        .value_of("config")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| get_rtp_match(matches).map_err_trace_exit_unwrap())
+}
+
+fn get(matches: &ArgMatches, config: Option<&Value>) {
+    let scmd   = matches.subcommand_matches("get").unwrap();
+    let key    = scmd.value_of("key").unwrap(); // safe by clap
+    let schema = schema();
+
+    let value = config
+        .map(|config| config.read(key).map_err(Error::from).map_err_trace_exit_unwrap().cloned())
+        .unwrap_or(None)
+        .or_else(|| {
+            let default = schema.key(key).and_then(ConfigKey::default).cloned();
+            if default.is_some() {
+                info!("'{}' is not set, showing the default", key);
+            }
+            default
+        })
+        .unwrap_or_else(|| warn_exit(&format!("'{}' is not set", key), 1));
+
+    let _ = writeln!(stdout(), "{}", display_value(&value))
+        .to_exit_code()
+        .unwrap_or_exit();
+}
+
+fn set(matches: &ArgMatches) {
+    let scmd   = matches.subcommand_matches("set").unwrap();
+    let key    = scmd.value_of("key").unwrap(); // safe by clap
+    let value  = scmd.value_of("value").unwrap(); // safe by clap
+    let schema = schema();
+
+    let path = find_config_file(&config_searchpath(matches))
+        .unwrap_or_else(|| warn_exit("No configuration file found", 1));
+
+    let mut config = fetch_config(&path)
+        .map_err_trace_exit_unwrap()
+        .unwrap_or_else(|| warn_exit(&format!("Cannot parse {}", path.display()), 1));
+
+    // An existing value keeps its type, a new one gets the type from the schema
+    let new_value = if config.read(key).map_err(Error::from).map_err_trace_exit_unwrap().is_some() {
+        if override_config(&mut config, vec![format!("{}={}", key, value)]).is_err() {
+            warn_exit(&format!("'{}' is not a valid value for '{}'", value, key), 1)
+        }
+        config.read(key).map_err(Error::from).map_err_trace_exit_unwrap().cloned().unwrap()
+    } else {
+        match schema.key(key).map(ConfigKey::kind) {
+            Some(kind) => kind.parse(value),
+            None       => ValueKind::Array.parse(value)
+                .or_else(|| ValueKind::Table.parse(value))
+                .or_else(|| ValueKind::Integer.parse(value))
+                .or_else(|| ValueKind::Float.parse(value))
+                .or_else(|| ValueKind::Boolean.parse(value))
+                .or_else(|| ValueKind::String.parse(value)),
+        }
+        .unwrap_or_else(|| warn_exit(&format!("'{}' is not a valid value for '{}'", value, key), 1))
+    };
+
+    let mut single = Value::Table(Default::default());
+    let _ = single.insert(key, new_value.clone()).map_err(Error::from).map_err_trace_exit_unwrap();
+    let problems = schema.validate(&single);
+    if !problems.is_empty() {
+        report_problems(&problems);
+        ::std::process::exit(1)
+    }
+
+    write_value(&path, key, &new_value).map_err_trace_exit_unwrap();
+    info!("Set '{}' in {}", key, path.display());
+}
+
+/// Set `key` in the configuration file at `path`, keeping the comments and formatting of the file
+fn write_value(path: &PathBuf, key: &str, value: &Value) -> Result<()> {
+    let mut buf = String::new();
+    let _ = OpenOptions::new().read(true).open(path)?.read_to_string(&mut buf)?;
+
+    let mut document = buf
+        .parse::<toml_edit::Document>()
+        .map_err(|e| format_err!("Cannot parse {}: {}", path.display(), e))?;
+
+    let value = format!("{}", value)
+        .parse::<toml_edit::Value>()
+        .map_err(|e| format_err!("Cannot convert value: {}", e))?;
+
+    let mut parts = key.split('.').collect::<Vec<_>>();
+    let last      = parts.pop().ok_or_else(|| err_msg("Empty key"))?;
+    let mut item  = &mut document.root;
+    for part in parts {
+        item = &mut item[part];
+    }
+    item[last] = toml_edit::value(value);
+
+    OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(path)?
+        .write_all(document.to_string().as_bytes())
+        .map_err(Error::from)
+}
+
+fn validate(matches: &ArgMatches) {
+    let path = find_config_file(&config_searchpath(matches))
+        .unwrap_or_else(|| warn_exit("No configuration file found", 1));
+
+    let config = fetch_config(&path)
+        .map_err_trace_exit_unwrap()
+        .unwrap_or_else(|| warn_exit(&format!("Cannot parse {}", path.display()), 1));
+
+    let schema = schema();
+    let mut problems = schema.validate(&config);
+
+    // The configuration of a profile is merged into the configuration, so it has the same keys
+    for profile in profiles(&config).map_err_trace_exit_unwrap() {
+        if let Some(profile_config) = profile.config() {
+            let prefix = format!("profiles.{}.config", profile.name());
+            problems.extend(schema.validate(profile_config).into_iter().map(|p| match p {
+                Problem::UnknownKey { path, suggestion } => Problem::UnknownKey {
+                    path: format!("{}.{}", prefix, path),
+                    suggestion,
+                },
+                Problem::WrongType { path, expected, found } => Problem::WrongType {
+                    path: format!("{}.{}", prefix, path),
+                    expected,
+                    found,
+                },
+            }));
+        }
+    }
+
+    if problems.is_empty() {
+        let _ = writeln!(stdout(), "{}: ok", path.display())
+            .to_exit_code()
+            .unwrap_or_exit();
+    } else {
+        report_problems(&problems);
+        ::std::process::exit(1)
+    }
+}
+
+fn report_problems(problems: &[Problem]) {
+    for problem in problems {
+        error!("{}", problem);
+    }
+}
+
+fn dump(matches: &ArgMatches, config: Option<&Value>) {
+    let scmd   = matches.subcommand_matches("dump").unwrap();
+    let config = if scmd.is_present("defaults") {
+        schema().defaults().map_err_trace_exit_unwrap()
+    } else {
+        config
+            .cloned()
+            .unwrap_or_else(|| warn_exit("No configuration", 1))
+    };
+
+    let _ = writeln!(stdout(), "{}", display_value(&config).trim_end())
+        .to_exit_code()
+        .unwrap_or_exit();
+}
+
+/// Strings are printed without quotes, tables as TOML document, everything else as TOML value
+fn display_value(value: &Value) -> String {
+    match *value {
+        Value::String(ref s) => s.clone(),
+        Value::Table(_)      => ::toml::ser::to_string_pretty(value)
+            .map_err(Error::from)
+            .map_err_trace_exit_unwrap(),
+        _                    => format!("{}", value),
+    }
+}
@@ -0,0 +1,66 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use clap::{Arg, App, SubCommand};
+
+pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
+    app
+        .subcommand(SubCommand::with_name("get")
+                   .about("Print a configuration value, or its default if it is not set")
+                   .version("0.1")
+                   .arg(Arg::with_name("key")
+                        .index(1)
+                        .takes_value(true)
+                        .required(true)
+                        .multiple(false)
+                        .help("The key, for example 'store.backend'")
+                        .value_name("KEY")))
+
+        .subcommand(SubCommand::with_name("set")
+                   .about("Set a configuration value in the configuration file")
+                   .version("0.1")
+                   .arg(Arg::with_name("key")
+                        .index(1)
+                        .takes_value(true)
+                        .required(true)
+                        .multiple(false)
+                        .help("The key, for example 'store.backend'")
+                        .value_name("KEY"))
+                   .arg(Arg::with_name("value")
+                        .index(2)
+                        .takes_value(true)
+                        .required(true)
+                        .multiple(false)
+                        .help("The new value. Everything but strings is parsed as TOML, for example '[ \"a\", \"b\" ]'")
+                        .value_name("VALUE")))
+
+        .subcommand(SubCommand::with_name("validate")
+                   .about("Report unknown keys and values of the wrong type in the configuration file")
+                   .version("0.1"))
+
+        .subcommand(SubCommand::with_name("dump")
+                   .about("Print the configuration")
+                   .version("0.1")
+                   .arg(Arg::with_name("defaults")
+                        .long("defaults")
+                        .takes_value(false)
+                        .required(false)
+                        .multiple(false)
+                        .help("Print the default values of all keys which have one instead")))
+}
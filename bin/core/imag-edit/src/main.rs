@@ -65,7 +65,7 @@ fn main() {
     let edit_header_only = rt.cli().is_present("edit-header-only");
 
     let sids = rt
-        .ids::<crate::ui::PathProvider>()
+        .ids_or_pick::<crate::ui::PathProvider>()
         .map_err_trace_exit_unwrap()
         .unwrap_or_else(|| {
             error!("No ids supplied");
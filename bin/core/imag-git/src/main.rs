@@ -51,16 +51,17 @@ use toml_query::read::TomlValueReadExt;
 
 use libimagerror::exit::ExitUnwrap;
 use libimagerror::io::ToExitCode;
-use libimagrt::setup::generate_runtime_setup;
+use libimagrt::setup::generate_runtime_setup_with_config;
 
 mod ui;
 
 fn main() {
     let version = make_imag_version!();
-    let rt = generate_runtime_setup("imag-git",
-                                    &version,
-                                    "Helper to call git in the store",
-                                    ui::build_ui);
+    let rt = generate_runtime_setup_with_config("imag-git",
+                                                &version,
+                                                "Helper to call git in the store",
+                                                &["git"],
+                                                ui::build_ui);
 
     let execute_in_store = rt
         .config()
@@ -43,12 +43,18 @@ extern crate libimagstore;
 extern crate libimagerror;
 
 use std::io::Write;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 
 use regex::Regex;
 
 use libimagrt::runtime::Runtime;
+use libimagrt::pipe::{PipeRecord, ANNOTATION_MATCHES};
 use libimagrt::setup::generate_runtime_setup;
 use libimagstore::store::Entry;
+use libimagstore::storeid::StoreId;
+use libimagstore::iter::get::StoreIdGetIteratorExtension;
+use libimagstore::index::tokenize;
 use libimagerror::trace::MapErrTrace;
 use libimagerror::exit::ExitUnwrap;
 use libimagerror::io::ToExitCode;
@@ -74,21 +80,22 @@ fn main() {
 
     let mut count : usize = 0;
 
-    let pattern = rt
-        .cli()
-        .value_of("pattern")
-        .map(Regex::new)
-        .unwrap() // ensured by clap
+    let raw_pattern = rt.cli().value_of("pattern").unwrap(); // ensured by clap
+    let pattern     = Regex::new(raw_pattern)
         .unwrap_or_else(|e| {
             error!("Regex building error: {:?}", e);
             ::std::process::exit(1)
         });
 
-    let overall_count = rt
-        .store()
-        .entries()
-        .map_err_trace_exit_unwrap()
-        .into_get_iter()
+    let iter = match candidates_from_index(&rt, raw_pattern) {
+        Some(ids) => {
+            debug!("Using index, {} candidates", ids.len());
+            ids.into_iter().map(Ok).into_get_iter(rt.store())
+        },
+        None => rt.store().entries().map_err_trace_exit_unwrap().into_get_iter(),
+    };
+
+    let overall_count = iter
         .filter_map(|res| res.map_err_trace_exit_unwrap())
         .filter(|entry| pattern.is_match(entry.get_content()))
         .map(|entry| show(&rt, &entry, &pattern, &opts, &mut count))
@@ -106,6 +113,42 @@ fn main() {
     }
 }
 
+/// Get the ids of the entries which might match `pattern` from the store index
+///
+/// This only works if the store has an index and the pattern is a plain string without any regex
+/// special characters. Every entry which matches such a pattern contains all words of the pattern
+/// as part of its words, so we can ask the index for these entries and only need to check them.
+///
+/// Returns `None` if the index cannot be used.
+fn candidates_from_index(rt: &Runtime, pattern: &str) -> Option<Vec<StoreId>> {
+    let index = rt.store().index()?;
+
+    let is_literal = pattern
+        .chars()
+        .all(|c| c.is_alphanumeric() || ::regex::escape(&c.to_string()) == c.to_string());
+
+    if !is_literal {
+        debug!("Pattern is not a literal, cannot use index");
+        return None
+    }
+
+    let mut candidates : Option<BTreeSet<StoreId>> = None;
+    for word in tokenize(pattern) {
+        let ids = index
+            .ids_with_term_containing(&word)
+            .map_err_trace_exit_unwrap()
+            .into_iter()
+            .collect::<BTreeSet<_>>();
+
+        candidates = Some(match candidates {
+            None      => ids,
+            Some(acc) => acc.intersection(&ids).cloned().collect(),
+        });
+    }
+
+    candidates.map(|c| c.into_iter().collect())
+}
+
 fn show(rt: &Runtime, e: &Entry, re: &Regex, opts: &Options, count: &mut usize) {
     if opts.files_with_matches {
         let _ = writeln!(rt.stdout(), "{}", e.get_location()).to_exit_code().unwrap_or_exit();
@@ -125,6 +168,15 @@ fn show(rt: &Runtime, e: &Entry, re: &Regex, opts: &Options, count: &mut usize)
         *count += 1;
     }
 
-    let _ = rt.report_touched(e.get_location()).unwrap_or_exit();
+    let matches = re
+        .find_iter(e.get_content())
+        .map(|m| vec![("start", m.start()), ("end", m.end())].into_iter().collect::<BTreeMap<_, _>>())
+        .collect::<Vec<_>>();
+
+    let record = PipeRecord::new(e.get_location().clone())
+        .with_annotation(ANNOTATION_MATCHES, matches)
+        .map_err_trace_exit_unwrap();
+
+    let _ = rt.report_touched_record(record).unwrap_or_exit();
 }
 
@@ -0,0 +1,204 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+#![forbid(unsafe_code)]
+
+#![deny(
+    non_camel_case_types,
+    non_snake_case,
+    path_statements,
+    trivial_numeric_casts,
+    unstable_features,
+    unused_allocation,
+    unused_import_braces,
+    unused_imports,
+    unused_must_use,
+    unused_mut,
+    unused_qualifications,
+    while_true,
+)]
+
+#[macro_use] extern crate log;
+extern crate clap;
+
+#[macro_use] extern crate libimagrt;
+extern crate libimagstore;
+extern crate libimagerror;
+extern crate libimagutil;
+
+use std::io::Write;
+use std::ops::Deref;
+use std::path::PathBuf;
+
+use clap::ArgMatches;
+
+use libimagrt::runtime::Runtime;
+use libimagrt::setup::generate_runtime_setup;
+use libimagerror::trace::MapErrTrace;
+use libimagerror::exit::ExitUnwrap;
+use libimagerror::io::ToExitCode;
+use libimagstore::history::History;
+use libimagstore::history::LineDiff;
+use libimagstore::history::RevisionKind;
+use libimagstore::history::diff_lines;
+use libimagstore::storeid::StoreId;
+use libimagutil::warn_exit::warn_exit;
+
+mod ui;
+
+use crate::ui::build_ui;
+
+fn main() {
+    let version = make_imag_version!();
+    let rt      = generate_runtime_setup("imag-history",
+                                         &version,
+                                         "List, compare and restore earlier versions of entries",
+                                         build_ui);
+
+    let command = rt.cli().subcommand_name().map(String::from);
+
+    if let Some(command) = command {
+        debug!("Call: {}", command);
+        match command.deref() {
+            "list"    => list(&rt),
+            "show"    => show(&rt),
+            "diff"    => diff(&rt),
+            "restore" => restore(&rt),
+            other     => {
+                debug!("Unknown command");
+                let _ = rt.handle_unknown_subcommand("imag-history", other, rt.cli())
+                    .map_err_trace_exit_unwrap()
+                    .code()
+                    .map(::std::process::exit);
+            },
+        };
+    } else {
+        debug!("No command");
+    }
+}
+
+fn list(rt: &Runtime) {
+    let scmd      = rt.cli().subcommand_matches("list").unwrap();
+    let id        = get_id(scmd);
+    let revisions = history(rt).revisions(&id).map_err_trace_exit_unwrap();
+    let mut out   = rt.stdout();
+
+    if revisions.is_empty() {
+        warn_exit(&format!("No history for '{}'", id), 1);
+    }
+
+    for rev in revisions {
+        let _ = match rev.moved_from() {
+            Some(from) => writeln!(out, "{:>4} {} {} (from {})", rev.number(), rev.time(), rev.kind().as_str(), from),
+            None       => writeln!(out, "{:>4} {} {}", rev.number(), rev.time(), rev.kind().as_str()),
+        }.to_exit_code().unwrap_or_exit();
+    }
+
+    let _ = rt.report_touched(&id).unwrap_or_exit();
+}
+
+fn show(rt: &Runtime) {
+    let scmd = rt.cli().subcommand_matches("show").unwrap();
+    let id   = get_id(scmd);
+    let rev  = get_revision(scmd, "revision").unwrap(); // unwrap safe by clap
+    let text = history(rt).text_at(&id, rev).map_err_trace_exit_unwrap();
+
+    let _ = writeln!(rt.stdout(), "{}", text).to_exit_code().unwrap_or_exit();
+    let _ = rt.report_touched(&id).unwrap_or_exit();
+}
+
+fn diff(rt: &Runtime) {
+    let scmd    = rt.cli().subcommand_matches("diff").unwrap();
+    let id      = get_id(scmd);
+    let history = history(rt);
+    let from    = get_revision(scmd, "from").unwrap(); // unwrap safe by clap
+    let to      = get_revision(scmd, "to").unwrap_or_else(|| {
+        history
+            .revisions(&id)
+            .map_err_trace_exit_unwrap()
+            .last()
+            .map(|r| r.number())
+            .unwrap_or(0)
+    });
+
+    let old     = history.text_at(&id, from).map_err_trace_exit_unwrap();
+    let new     = history.text_at(&id, to).map_err_trace_exit_unwrap();
+    let mut out = rt.stdout();
+
+    let _ = writeln!(out, "--- {} revision {}\n+++ {} revision {}", id, from, id, to)
+        .to_exit_code()
+        .unwrap_or_exit();
+
+    for line in diff_lines(&old, &new) {
+        let _ = match line {
+            LineDiff::Same(l)    => writeln!(out, " {}", l),
+            LineDiff::Removed(l) => writeln!(out, "-{}", l),
+            LineDiff::Added(l)   => writeln!(out, "+{}", l),
+        }.to_exit_code().unwrap_or_exit();
+    }
+
+    let _ = rt.report_touched(&id).unwrap_or_exit();
+}
+
+fn restore(rt: &Runtime) {
+    let scmd = rt.cli().subcommand_matches("restore").unwrap();
+    let id   = get_id(scmd);
+    let rev  = get_revision(scmd, "revision").unwrap(); // unwrap safe by clap
+
+    let revision = history(rt)
+        .revisions(&id)
+        .map_err_trace_exit_unwrap()
+        .into_iter()
+        .find(|r| r.number() == rev)
+        .unwrap_or_else(|| warn_exit(&format!("No revision {} of '{}'", rev, id), 1));
+
+    if revision.kind() == RevisionKind::Delete {
+        warn_exit(&format!("Revision {} of '{}' is a deletion, nothing to restore", rev, id), 1);
+    }
+
+    let text = history(rt).text_at(&id, rev).map_err_trace_exit_unwrap();
+
+    {
+        let mut entry = rt.store().retrieve(id.clone()).map_err_trace_exit_unwrap();
+        let _ = entry.replace_from_buffer(&text).map_err_trace_exit_unwrap();
+    }
+
+    info!("Restored '{}' to revision {}", id, rev);
+    let _ = rt.report_touched(&id).unwrap_or_exit();
+}
+
+fn history<'a>(rt: &'a Runtime) -> &'a History {
+    rt.store().history().unwrap_or_else(|| {
+        warn_exit("The history is not enabled. Set 'store.history.enabled = true' to enable it", 1)
+    })
+}
+
+fn get_id(scmd: &ArgMatches) -> StoreId {
+    scmd.value_of("id")
+        .map(PathBuf::from)
+        .map(StoreId::new)
+        .unwrap() // unwrap safe by clap
+        .map_err_trace_exit_unwrap()
+}
+
+fn get_revision(scmd: &ArgMatches, name: &str) -> Option<usize> {
+    scmd.value_of(name).map(|r| {
+        r.parse::<usize>().unwrap_or_else(|_| warn_exit(&format!("Not a revision: {}", r), 1))
+    })
+}
@@ -0,0 +1,68 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use clap::{Arg, App, SubCommand};
+
+pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
+    app
+        .subcommand(SubCommand::with_name("list")
+                   .about("List the revisions of an entry")
+                   .version("0.1")
+                   .arg(id_arg()))
+
+        .subcommand(SubCommand::with_name("show")
+                   .about("Print an entry as it was in a revision")
+                   .version("0.1")
+                   .arg(id_arg())
+                   .arg(revision_arg("revision", 2, true, "The revision to print")))
+
+        .subcommand(SubCommand::with_name("diff")
+                   .about("Show the changes between two revisions of an entry")
+                   .version("0.1")
+                   .arg(id_arg())
+                   .arg(revision_arg("from", 2, true, "The older revision"))
+                   .arg(revision_arg("to", 3, false, "The newer revision (default: the latest one)")))
+
+        .subcommand(SubCommand::with_name("restore")
+                   .about("Restore an entry to the state of a revision")
+                   .version("0.1")
+                   .arg(id_arg())
+                   .arg(revision_arg("revision", 2, true, "The revision to restore")))
+}
+
+fn id_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("id")
+        .index(1)
+        .takes_value(true)
+        .required(true)
+        .multiple(false)
+        .help("The entry")
+        .value_name("ENTRY")
+}
+
+fn revision_arg<'a, 'b>(name: &'a str, index: u64, required: bool, help: &'a str) -> Arg<'a, 'b> {
+    Arg::with_name(name)
+        .index(index)
+        .takes_value(true)
+        .required(required)
+        .multiple(false)
+        .help(help)
+        .value_name("REVISION")
+        .validator(::libimagutil::cli_validators::is_integer)
+}
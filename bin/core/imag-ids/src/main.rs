@@ -39,17 +39,27 @@ extern crate clap;
 extern crate toml;
 extern crate toml_query;
 #[macro_use] extern crate failure;
+extern crate filters;
 
 #[cfg(test)]
 extern crate env_logger;
 
 extern crate libimagerror;
 extern crate libimagstore;
+extern crate libimagentryfilter;
 #[macro_use] extern crate libimagrt;
 
 use std::io::Write;
+use std::collections::BTreeSet;
+
+use toml_query::read::TomlValueReadExt;
+use failure::Error;
+use filters::failable::filter::FailableFilter;
 
 use libimagstore::storeid::StoreId;
+use libimagstore::index::tokenize;
+use libimagentryfilter::query::Query;
+use libimagrt::runtime::Runtime;
 use libimagrt::setup::generate_runtime_setup;
 use libimagerror::trace::MapErrTrace;
 use libimagerror::iter::TraceIterator;
@@ -68,6 +78,7 @@ fn main() {
                                     build_ui);
 
     let print_storepath = rt.cli().is_present("print-storepath");
+    let filter          = EntryFilter::new(&rt);
 
     let iterator = if rt.ids_from_stdin() {
         debug!("Fetching IDs from stdin...");
@@ -85,6 +96,7 @@ fn main() {
             as Box<Iterator<Item = Result<StoreId, _>>>
     }
     .trace_unwrap_exit()
+    .filter(|id| filter.matches(&rt, id))
     .map(|id| if print_storepath {
         (Some(rt.store().path()), id)
     } else {
@@ -111,3 +123,93 @@ fn main() {
     })
 }
 
+/// Filter for the ids, built from the "--has-header", "--has-term" and "--query" arguments
+struct EntryFilter {
+    headers: Vec<String>,
+    terms: Vec<String>,
+    query: Option<Query>,
+
+    /// The ids which match the filter, if the store index could be used to find them
+    from_index: Option<BTreeSet<StoreId>>,
+}
+
+impl EntryFilter {
+
+    fn new(rt: &Runtime) -> EntryFilter {
+        let values = |name| rt
+            .cli()
+            .values_of(name)
+            .map(|vals| vals.map(String::from).collect())
+            .unwrap_or_else(Vec::new);
+
+        let headers : Vec<String> = values("has-header");
+        let terms   : Vec<String> = values("has-term");
+        let query = rt
+            .cli()
+            .value_of("query")
+            .map(|q| Query::parse(q).map_err_trace_exit_unwrap());
+
+        let from_index = if headers.is_empty() && terms.is_empty() {
+            None
+        } else {
+            rt.store().index().map(|index| {
+                debug!("Using index for filtering");
+                let by_header = headers
+                    .iter()
+                    .map(|h| index.ids_with_header(h).map_err_trace_exit_unwrap());
+                let by_term = Some(index.ids_with_all_terms(&terms).map_err_trace_exit_unwrap())
+                    .into_iter()
+                    .filter(|_| !terms.is_empty());
+
+                by_header
+                    .chain(by_term)
+                    .map(|ids| ids.into_iter().collect::<BTreeSet<_>>())
+                    .fold(None, |acc : Option<BTreeSet<StoreId>>, ids| match acc {
+                        None      => Some(ids),
+                        Some(acc) => Some(acc.intersection(&ids).cloned().collect()),
+                    })
+                    .unwrap_or_else(BTreeSet::new)
+            })
+        };
+
+        EntryFilter { headers, terms, query, from_index }
+    }
+
+    fn matches(&self, rt: &Runtime, id: &StoreId) -> bool {
+        if self.headers.is_empty() && self.terms.is_empty() && self.query.is_none() {
+            return true
+        }
+
+        if let Some(ref ids) = self.from_index {
+            if !ids.contains(id) {
+                return false
+            }
+
+            if self.query.is_none() {
+                return true
+            }
+        }
+
+        let entry = rt.store().get_copy(id.clone()).map_err_trace_exit_unwrap();
+
+        if self.from_index.is_none() {
+            let has_headers = self.headers.iter().all(|h| {
+                entry.get_header().read(h).map_err(Error::from).map_err_trace_exit_unwrap().is_some()
+            });
+
+            let has_terms = has_headers && {
+                let words = tokenize(entry.get_content()).collect::<BTreeSet<_>>();
+                self.terms.iter().all(|t| words.contains(&t.to_lowercase()))
+            };
+
+            if !has_terms {
+                return false
+            }
+        }
+
+        self.query
+            .as_ref()
+            .map(|q| q.filter(&entry).map_err_trace_exit_unwrap())
+            .unwrap_or(true)
+    }
+}
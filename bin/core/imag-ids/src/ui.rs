@@ -31,6 +31,31 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
              .required(false)
              .multiple(false)
              .help("Print the storepath for each id"))
+
+        .arg(Arg::with_name("has-header")
+             .long("has-header")
+             .takes_value(true)
+             .required(false)
+             .multiple(true)
+             .value_name("HEADER.PATH")
+             .help("Only print ids of entries which have this header field (multiple allowed). Uses the store index if it is enabled."))
+
+        .arg(Arg::with_name("has-term")
+             .long("has-term")
+             .takes_value(true)
+             .required(false)
+             .multiple(true)
+             .value_name("WORD")
+             .help("Only print ids of entries which contain this word in their content, case-insensitive (multiple allowed). Uses the store index if it is enabled."))
+
+        .arg(Arg::with_name("query")
+             .long("query")
+             .short("q")
+             .takes_value(true)
+             .required(false)
+             .multiple(false)
+             .value_name("QUERY")
+             .help("Only print ids of entries which match this query, for example 'header.imag.version > \"0.9\" and tag:work and not content ~ /TODO/'. See the documentation of libimagentryfilter for the syntax."))
 }
 
 pub struct PathProvider;
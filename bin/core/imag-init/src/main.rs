@@ -53,6 +53,7 @@ use std::process::Command;
 use libimagerror::exit::ExitUnwrap;
 use libimagerror::io::ToExitCode;
 use libimagrt::runtime::Runtime;
+use libimagrt::plugin::{plugin_info_request, PluginInfo};
 
 const CONFIGURATION_STR : &'static str = include_str!("../imagrc.toml");
 
@@ -75,6 +76,13 @@ fn main() {
         "imag-init",
         version.as_str(),
         "Intializes the imag store, optionally with git"));
+
+    // imag-init does not build a runtime, so it answers the plugin info query itself
+    if plugin_info_request() {
+        let code = if PluginInfo::from_app(&app, &[]).print().is_ok() { 0 } else { 1 };
+        ::std::process::exit(code);
+    }
+
     let matches = app.get_matches();
     let mut out = ::std::io::stdout();
 
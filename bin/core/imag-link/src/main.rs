@@ -58,18 +58,27 @@ extern crate libimagutil;
 
 use std::io::Write;
 use std::path::PathBuf;
+use std::collections::BTreeMap;
 
 use failure::Error;
 use failure::err_msg;
 
 use libimagentryurl::linker::UrlLinker;
+use libimagentryurl::graph::StoreUrlGraphExt;
 use libimagentrylink::linkable::Linkable;
+use libimagentrylink::iter::LinkIter;
+use libimagentrylink::label::LinkDirection;
 use libimagentrylink::storecheck::StoreLinkConsistentExt;
+use libimagentrylink::graph::GraphFormat;
+use libimagentrylink::graph::LinkGraph;
+use libimagentrylink::graph::StoreLinkGraphExt;
 use libimagerror::trace::{MapErrTrace, trace_error};
 use libimagerror::exit::ExitUnwrap;
 use libimagerror::io::ToExitCode;
 use libimagrt::runtime::Runtime;
 use libimagrt::setup::generate_runtime_setup;
+use libimagrt::io::EntryRecord;
+use libimagrt::pipe::{PipeRecord, ANNOTATION_LINKS};
 use libimagstore::store::FileLockEntry;
 use libimagstore::storeid::StoreId;
 use libimagutil::warn_exit::warn_exit;
@@ -109,6 +118,8 @@ fn main() {
                 "remove" => remove_linking(&rt),
                 "unlink" => unlink(&rt),
                 "list"   => list_linkings(&rt),
+                "graph"  => graph(&rt),
+                "path"   => path(&rt),
                 other    => {
                     debug!("Unknown command");
                     let _ = rt.handle_unknown_subcommand("imag-link", other, rt.cli())
@@ -148,9 +159,21 @@ fn link_from_to<'a, I>(rt: &'a Runtime, from: &'a str, to: I)
         },
     };
 
+    let label  = rt.cli().value_of("label");
+    let reason = rt.cli().value_of("reason");
+
+    // all internally linked entries are written in one transaction
+    let mut transaction = rt.store().transaction();
+    let mut to_entries  = vec![];
+
     for entry in to {
         debug!("Handling 'to' entry: {:?}", entry);
         if !rt.store().get(PathBuf::from(entry)).map_err_trace_exit_unwrap().is_some() {
+            if label.is_some() {
+                error!("Labels are only supported for links between entries, not for '{}'", entry);
+                ::std::process::exit(1)
+            }
+
             debug!("Linking externally: {:?} -> {:?}", from, entry);
             let url = Url::parse(entry).unwrap_or_else(|e| {
                 error!("Error parsing URL: {:?}", e);
@@ -181,24 +204,33 @@ fn link_from_to<'a, I>(rt: &'a Runtime, from: &'a str, to: I)
                     ::std::process::exit(1)
                 },
             };
-            let _ = from_entry
-                .add_link(&mut to_entry)
-                .map_err_trace_exit_unwrap();
+            let _ = match label {
+                Some(label) => from_entry.add_labeled_link_to(&mut to_entry, label, reason),
+                None        => from_entry.add_link(&mut to_entry),
+            }
+            .map_err_trace_exit_unwrap();
 
+            let _ = transaction.update(&to_entry).map_err_trace_exit_unwrap();
             let _ = rt.report_touched(to_entry.get_location()).unwrap_or_exit();
+            to_entries.push(to_entry);
         }
 
 
         info!("Ok: {} -> {}", from, entry);
     }
 
+    let _ = transaction.update(&from_entry).map_err_trace_exit_unwrap();
+    let _ = transaction.commit().map_err_trace_exit_unwrap();
     let _ = rt.report_touched(from_entry.get_location()).unwrap_or_exit();
 }
 
 fn remove_linking(rt: &Runtime) {
-    let mut from = rt.cli()
+    let cmd = rt.cli()
         .subcommand_matches("remove")
-        .unwrap() // safe, we know there is an "remove" subcommand
+        .unwrap(); // safe, we know there is an "remove" subcommand
+    let label = cmd.value_of("label");
+
+    let mut from = cmd
         .value_of("from")
         .map(PathBuf::from)
         .map(|id| {
@@ -210,8 +242,12 @@ fn remove_linking(rt: &Runtime) {
         })
         .unwrap();
 
+    // all entries which are unlinked from 'from' are written in one transaction
+    let mut transaction = rt.store().transaction();
+    let mut to_entries  = vec![];
+
     rt
-        .ids::<crate::ui::PathProvider>()
+        .ids_or_pick::<crate::ui::PathProvider>()
         .map_err_trace_exit_unwrap()
         .unwrap_or_else(|| {
             error!("No ids supplied");
@@ -221,11 +257,15 @@ fn remove_linking(rt: &Runtime) {
         .for_each(|id| match rt.store().get(id.clone()) {
             Err(e) => trace_error(&e),
             Ok(Some(mut to_entry)) => {
-                let _ = to_entry
-                    .remove_link(&mut from)
-                    .map_err_trace_exit_unwrap();
+                let _ = match label {
+                    Some(label) => from.remove_labeled_link_to(&mut to_entry, label),
+                    None        => to_entry.remove_link(&mut from),
+                }
+                .map_err_trace_exit_unwrap();
 
+                let _ = transaction.update(&to_entry).map_err_trace_exit_unwrap();
                 let _ = rt.report_touched(to_entry.get_location()).unwrap_or_exit();
+                to_entries.push(to_entry);
             },
             Ok(None) => {
                 // looks like this is not an entry, but a filesystem URI and therefor an
@@ -247,12 +287,14 @@ fn remove_linking(rt: &Runtime) {
             }
         });
 
+    let _ = transaction.update(&from).map_err_trace_exit_unwrap();
+    let _ = transaction.commit().map_err_trace_exit_unwrap();
     let _ = rt.report_touched(from.get_location()).unwrap_or_exit();
 }
 
 fn unlink(rt: &Runtime) {
     rt
-        .ids::<crate::ui::PathProvider>()
+        .ids_or_pick::<crate::ui::PathProvider>()
         .map_err_trace_exit_unwrap()
         .unwrap_or_else(|| {
             error!("No ids supplied");
@@ -274,6 +316,69 @@ fn unlink(rt: &Runtime) {
         });
 }
 
+/// The links of `entry`, or only the ones with the label `label`
+fn get_links(entry: &FileLockEntry, label: Option<&str>) -> LinkIter {
+    match label {
+        Some(label) => entry.links_with_label(label),
+        None        => entry.links(),
+    }
+    .map_err_trace_exit_unwrap()
+}
+
+/// The labels of the links of `entry` by link target, for displaying
+fn get_link_labels(entry: &FileLockEntry) -> BTreeMap<String, Vec<String>> {
+    let mut labels = BTreeMap::new();
+
+    for labeled in entry.labeled_links().map_err_trace_exit_unwrap() {
+        let target = labeled
+            .target()
+            .and_then(|id| id.to_str())
+            .map_err_trace_exit_unwrap();
+
+        let label = match labeled.direction() {
+            LinkDirection::To   => String::from(labeled.label()),
+            LinkDirection::From => format!("{} (incoming)", labeled.label()),
+        };
+
+        labels.entry(target).or_insert_with(Vec::new).push(label);
+    }
+
+    labels
+}
+
+/// The links of `entry` as record, for `--format`
+fn link_record(rt: &Runtime, entry: &FileLockEntry, list_externals: bool, label: Option<&str>) -> EntryRecord {
+    let links = get_links(entry, label)
+        .map(|link| link.to_str())
+        .collect::<Result<Vec<_>>>()
+        .map_err_trace_exit_unwrap();
+
+    let labels = entry
+        .labeled_links()
+        .map_err_trace_exit_unwrap()
+        .into_iter()
+        .filter(|l| label.map(|label| l.label() == label).unwrap_or(true))
+        .collect::<Vec<_>>();
+
+    let record = EntryRecord::new(entry.get_location())
+        .with_field("links", links)
+        .and_then(|record| record.with_field("labels", labels))
+        .map_err_trace_exit_unwrap();
+
+    if list_externals {
+        let urls = entry
+            .get_urls(rt.store())
+            .map_err_trace_exit_unwrap()
+            .map(|url| url.map(|url| url.into_string()))
+            .collect::<Result<Vec<_>>>()
+            .map_err_trace_exit_unwrap();
+
+        record.with_field("external", urls).map_err_trace_exit_unwrap()
+    } else {
+        record
+    }
+}
+
 fn list_linkings(rt: &Runtime) {
     let cmd = rt.cli()
         .subcommand_matches("list")
@@ -281,12 +386,15 @@ fn list_linkings(rt: &Runtime) {
 
     let list_externals  = cmd.is_present("list-externals-too");
     let list_plain      = cmd.is_present("list-plain");
+    let label           = cmd.value_of("label");
+    let structured      = rt.output_format().is_structured();
+    let mut records     = vec![];
 
     let mut tab = ::prettytable::Table::new();
-    tab.set_titles(row!["#", "Link"]);
+    tab.set_titles(row!["#", "Link", "Labels"]);
 
     rt
-        .ids::<crate::ui::PathProvider>()
+        .ids_or_pick::<crate::ui::PathProvider>()
         .map_err_trace_exit_unwrap()
         .unwrap_or_else(|| {
             error!("No ids supplied");
@@ -295,20 +403,34 @@ fn list_linkings(rt: &Runtime) {
         .into_iter()
         .for_each(|id| {
             match rt.store().get(id.clone()) {
+                Ok(Some(ref entry)) if structured => records.push(link_record(rt, entry, list_externals, label)),
                 Ok(Some(entry)) => {
-                    for (i, link) in entry.links().map_err_trace_exit_unwrap().enumerate() {
+                    let mut record_links = vec![];
+                    let link_labels      = get_link_labels(&entry);
+
+                    for (i, link) in get_links(&entry, label).enumerate() {
                         let link = link
                             .to_str()
                             .map_warn_err(|e| format!("Failed to convert StoreId to string: {:?}", e))
                             .ok();
 
                         if let Some(link) = link {
+                            record_links.push(link.clone());
+                            let labels = link_labels
+                                .get(&link)
+                                .map(|labels| labels.join(", "))
+                                .unwrap_or_default();
+
                             if list_plain {
-                                let _ = writeln!(rt.stdout(), "{: <3}: {}", i, link)
-                                    .to_exit_code()
-                                    .unwrap_or_exit();
+                                if labels.is_empty() {
+                                    writeln!(rt.stdout(), "{: <3}: {}", i, link)
+                                } else {
+                                    writeln!(rt.stdout(), "{: <3}: {} [{}]", i, link, labels)
+                                }
+                                .to_exit_code()
+                                .unwrap_or_exit();
                             } else {
-                                tab.add_row(row![i, link]);
+                                tab.add_row(row![i, link, labels]);
                             }
                         }
                     }
@@ -327,22 +449,30 @@ fn list_linkings(rt: &Runtime) {
                                         .to_exit_code()
                                         .unwrap_or_exit();
                                 } else {
-                                    tab.add_row(row![i, link]);
+                                    tab.add_row(row![i, link, ""]);
                                 }
                             })
                     }
 
-                    let _ = rt.report_touched(entry.get_location()).unwrap_or_exit();
-
+                    let record = PipeRecord::new(id)
+                        .with_annotation(ANNOTATION_LINKS, record_links)
+                        .map_err_trace_exit_unwrap();
+                    let _ = rt.report_touched_record(record).unwrap_or_exit();
+                },
+                Ok(None) => {
+                    warn!("Not found: {}", id);
+                    let _ = rt.report_touched(&id).unwrap_or_exit();
+                },
+                Err(e) => {
+                    trace_error(&e);
+                    let _ = rt.report_touched(&id).unwrap_or_exit();
                 },
-                Ok(None)        => warn!("Not found: {}", id),
-                Err(e)          => trace_error(&e),
             }
-
-            let _ = rt.report_touched(&id).unwrap_or_exit();
         });
 
-    if !list_plain {
+    if structured {
+        rt.write_records(records).map_err_trace_exit_unwrap();
+    } else if !list_plain {
         let out      = rt.stdout();
         let mut lock = out.lock();
         tab.print(&mut lock)
@@ -351,6 +481,99 @@ fn list_linkings(rt: &Runtime) {
     }
 }
 
+fn build_graph(rt: &Runtime, resolve_urls: bool) -> LinkGraph {
+    if resolve_urls {
+        rt.store().link_graph_with_urls()
+    } else {
+        rt.store().link_graph()
+    }
+    .map_err_trace_exit_unwrap()
+}
+
+fn graph(rt: &Runtime) {
+    let cmd = rt.cli()
+        .subcommand_matches("graph")
+        .unwrap(); // safed by clap
+
+    let graph = build_graph(rt, !cmd.is_present("no-urls"));
+
+    // The graph is the output of this command, not a list of touched ids, so it always goes to
+    // stdout, even when imag pipes ids
+    let out      = ::std::io::stdout();
+    let mut lock = out.lock();
+
+    if cmd.is_present("components") {
+        for (i, component) in graph.connected_components().into_iter().enumerate() {
+            let _ = writeln!(lock, "{: <3}: {}", i, component.join(", "))
+                .to_exit_code()
+                .unwrap_or_exit();
+        }
+    } else if cmd.is_present("orphans") {
+        for node in graph.orphans() {
+            let _ = writeln!(lock, "{}", node.id())
+                .to_exit_code()
+                .unwrap_or_exit();
+        }
+    } else if let Some(n) = cmd.value_of("hubs") {
+        let n = n.parse::<usize>().unwrap(); // safed by clap validator
+        for (node, degree) in graph.hubs(n) {
+            let _ = writeln!(lock, "{: <5} {}", degree, node.id())
+                .to_exit_code()
+                .unwrap_or_exit();
+        }
+    } else {
+        let format = cmd
+            .value_of("export")
+            .and_then(GraphFormat::from_name)
+            .unwrap(); // safed by clap
+
+        let exported = graph.export(format).map_err_trace_exit_unwrap();
+
+        if let Some(file) = cmd.value_of("output") {
+            ::std::fs::write(file, exported)
+                .map_err(Error::from)
+                .map_err_trace_exit_unwrap();
+        } else {
+            let _ = write!(lock, "{}", exported)
+                .to_exit_code()
+                .unwrap_or_exit();
+        }
+    }
+}
+
+fn path(rt: &Runtime) {
+    let cmd = rt.cli()
+        .subcommand_matches("path")
+        .unwrap(); // safed by clap
+
+    let from     = cmd.value_of("from").unwrap(); // safed by clap
+    let to       = cmd.value_of("to").unwrap(); // safed by clap
+    let directed = cmd.is_present("directed");
+    let graph    = build_graph(rt, false);
+
+    for id in [from, to].iter() {
+        if graph.node(id).is_none() {
+            error!("Entry not found: {}", id);
+            ::std::process::exit(1);
+        }
+    }
+
+    match graph.shortest_path(from, to, directed) {
+        Some(path) => for id in path {
+            let _ = writeln!(rt.stdout(), "{}", id)
+                .to_exit_code()
+                .unwrap_or_exit();
+
+            let id = StoreId::new(PathBuf::from(id)).map_err_trace_exit_unwrap();
+            let _  = rt.report_touched(&id).unwrap_or_exit();
+        },
+        None => {
+            info!("No link path from {} to {}", from, to);
+            ::std::process::exit(1);
+        },
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::link_from_to;
@@ -367,6 +590,7 @@ mod tests {
     use libimagrt::runtime::Runtime;
     use libimagstore::storeid::StoreId;
     use libimagstore::store::{FileLockEntry, Entry};
+    use libimagentrylink::linkable::Linkable;
 
     fn setup_logging() {
         let _ = ::env_logger::try_init();
@@ -541,6 +765,42 @@ mod tests {
         assert_eq!(*test_links3, links_toml_value(vec!["test1"]));
     }
 
+    #[test]
+    fn test_labeled_linking() {
+        setup_logging();
+        let rt = generate_test_runtime(vec!["--label", "blocks", "--reason", "testing", "test1", "test2"])
+            .unwrap();
+
+        let test_id1 = create_test_default_entry(&rt, "test1").unwrap();
+        let test_id2 = create_test_default_entry(&rt, "test2").unwrap();
+
+        link_from_to(&rt, "test1", vec!["test2"].into_iter());
+
+        {
+            let test_entry1 = rt.store().get(test_id1.clone()).unwrap().unwrap();
+            let test_entry2 = rt.store().get(test_id2.clone()).unwrap().unwrap();
+
+            let labels = test_entry1.labeled_links().unwrap();
+            assert_eq!(labels.len(), 1);
+            assert_eq!(labels[0].label(), "blocks");
+            assert_eq!(labels[0].reason(), Some("testing"));
+            assert_eq!(test_entry1.directional_links_to().unwrap().count(), 1);
+            assert_eq!(test_entry2.links_with_label("blocks").unwrap().count(), 1);
+        }
+
+        let rt = reset_test_runtime(vec!["remove", "--label", "blocks", "test1", "test2"], rt)
+            .unwrap();
+
+        remove_linking(&rt);
+
+        let test_entry1 = rt.store().get(test_id1).unwrap().unwrap();
+        let test_entry2 = rt.store().get(test_id2).unwrap().unwrap();
+
+        assert!(test_entry1.labeled_links().unwrap().is_empty());
+        assert!(test_entry2.labeled_links().unwrap().is_empty());
+        assert_eq!(test_entry1.directional_links_to().unwrap().count(), 0);
+    }
+
     // Remove tests
 
     #[test]
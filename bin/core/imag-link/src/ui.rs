@@ -25,6 +25,7 @@ use failure::Fallible as Result;
 use libimagstore::storeid::StoreId;
 use libimagstore::storeid::IntoStoreId;
 use libimagrt::runtime::IdPathProvider;
+use libimagentrylink::graph::GraphFormat;
 
 pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
     app
@@ -45,6 +46,14 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                      .multiple(true)
                      .help("Remove links to these entries")
                      .value_name("ENTRIES"))
+                .arg(Arg::with_name("label")
+                     .long("label")
+                     .short("l")
+                     .takes_value(true)
+                     .required(false)
+                     .multiple(false)
+                     .help("Only remove this label from the links. The link is removed when its last label is removed")
+                     .value_name("LABEL"))
                 )
         .subcommand(SubCommand::with_name("unlink")
                 .about("Remove all links from an entry")
@@ -81,6 +90,89 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                      .takes_value(false)
                      .required(false)
                      .help("List plain rather than in ASCII table"))
+
+                .arg(Arg::with_name("label")
+                     .long("label")
+                     .short("l")
+                     .takes_value(true)
+                     .required(false)
+                     .multiple(false)
+                     .help("Only list links with this label")
+                     .value_name("LABEL"))
+                )
+
+        .subcommand(SubCommand::with_name("graph")
+                .about("Export or analyze the link graph of the whole store")
+                .version("0.1")
+                .arg(Arg::with_name("export")
+                     .long("export")
+                     .short("e")
+                     .takes_value(true)
+                     .required(false)
+                     .multiple(false)
+                     .possible_values(GraphFormat::names())
+                     .default_value("dot")
+                     .help("Export the graph in this format")
+                     .value_name("FORMAT"))
+                .arg(Arg::with_name("output")
+                     .long("output")
+                     .short("o")
+                     .takes_value(true)
+                     .required(false)
+                     .multiple(false)
+                     .help("Write the exported graph to this file instead of stdout")
+                     .value_name("FILE"))
+                .arg(Arg::with_name("no-urls")
+                     .long("no-urls")
+                     .takes_value(false)
+                     .required(false)
+                     .help("Do not resolve the URLs of external link entries"))
+                .arg(Arg::with_name("components")
+                     .long("components")
+                     .takes_value(false)
+                     .required(false)
+                     .conflicts_with_all(&["orphans", "hubs"])
+                     .help("List the connected components instead of exporting the graph"))
+                .arg(Arg::with_name("orphans")
+                     .long("orphans")
+                     .takes_value(false)
+                     .required(false)
+                     .conflicts_with_all(&["components", "hubs"])
+                     .help("List entries without links instead of exporting the graph"))
+                .arg(Arg::with_name("hubs")
+                     .long("hubs")
+                     .takes_value(true)
+                     .required(false)
+                     .multiple(false)
+                     .conflicts_with_all(&["components", "orphans"])
+                     .validator(::libimagutil::cli_validators::is_integer)
+                     .help("List the N entries with the most links instead of exporting the graph")
+                     .value_name("N"))
+                )
+
+        .subcommand(SubCommand::with_name("path")
+                .about("Print the shortest chain of links between two entries")
+                .version("0.1")
+                .arg(Arg::with_name("from")
+                     .index(1)
+                     .takes_value(true)
+                     .required(true)
+                     .multiple(false)
+                     .help("Start at this entry")
+                     .value_name("ENTRY"))
+                .arg(Arg::with_name("to")
+                     .index(2)
+                     .takes_value(true)
+                     .required(true)
+                     .multiple(false)
+                     .help("End at this entry")
+                     .value_name("ENTRY"))
+                .arg(Arg::with_name("directed")
+                     .long("directed")
+                     .short("d")
+                     .takes_value(false)
+                     .required(false)
+                     .help("Follow directional links only in their direction"))
                 )
 
         .arg(Arg::with_name("check-consistency")
@@ -90,6 +182,25 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
              .required(false)
              .help("Check the link-consistency in the store (might be time-consuming)"))
 
+        .arg(Arg::with_name("label")
+             .long("label")
+             .short("l")
+             .takes_value(true)
+             .required(false)
+             .multiple(false)
+             .requires("to")
+             .help("Make directional links with this label, for example 'blocks' or 'parent-of'")
+             .value_name("LABEL"))
+
+        .arg(Arg::with_name("reason")
+             .long("reason")
+             .takes_value(true)
+             .required(false)
+             .multiple(false)
+             .requires("label")
+             .help("Store why the labeled links were made")
+             .value_name("REASON"))
+
         .arg(Arg::with_name("from")
              .index(1)
              .takes_value(true)
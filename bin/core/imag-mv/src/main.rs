@@ -54,8 +54,6 @@ use libimagerror::trace::MapErrTrace;
 use libimagerror::iter::TraceIterator;
 use libimagerror::exit::ExitUnwrap;
 use libimagstore::storeid::StoreId;
-use libimagstore::store::Store;
-use libimagstore::store::FileLockEntry;
 use libimagentrylink::linkable::Linkable;
 use libimagstore::iter::get::StoreIdGetIteratorExtension;
 
@@ -84,13 +82,14 @@ fn main() {
         .unwrap() // unwrap safe by clap
         .map_err_trace_exit_unwrap();
 
-    // remove links to entry, and re-add them later
+    // let the links of all linked entries point to the new location, and move the entry, all in
+    // one transaction
     let mut linked_entries = {
         rt.store()
             .get(sourcename.clone())
             .map_err_trace_exit_unwrap()
             .unwrap_or_else(|| {
-                error!("Funny things happened: Entry moved to destination did not fail, but entry does not exist");
+                error!("Source Entry does not exist");
                 exit(1)
             })
             .links()
@@ -107,50 +106,23 @@ fn main() {
             .collect::<Vec<_>>()
     };
 
-    { // remove links to linked entries from source
-        let mut entry = rt
-            .store()
-            .get(sourcename.clone())
-            .map_err_trace_exit_unwrap()
-            .unwrap_or_else(|| {
-                error!("Source Entry does not exist");
-                exit(1)
-            });
+    let mut transaction = rt.store().transaction();
+
+    for linked in linked_entries.iter_mut() {
+        let _ = linked
+            .relocate_link(&sourcename, &destname)
+            .map_err_trace_exit_unwrap();
 
-        for link in linked_entries.iter_mut() {
-            let _ = entry.remove_link(link).map_err_trace_exit_unwrap();
-        }
+        let _ = transaction.update(&linked).map_err_trace_exit_unwrap();
     }
 
-    let _ = rt
-        .store()
+    let _ = transaction
         .move_by_id(sourcename.clone(), destname.clone())
-        .map_err(|e| { // on error, re-add links
-            debug!("Re-adding links to source entry because moving failed");
-            relink(rt.store(), sourcename.clone(), &mut linked_entries);
-            e
-        })
         .map_err_trace_exit_unwrap();
 
-    let _ = rt.report_touched(&destname).unwrap_or_exit();
+    let _ = transaction.commit().map_err_trace_exit_unwrap();
 
-    // re-add links to moved entry
-    relink(rt.store(), destname, &mut linked_entries);
+    let _ = rt.report_touched(&destname).unwrap_or_exit();
 
     info!("Ok.");
 }
-
-fn relink<'a>(store: &'a Store, target: StoreId, linked_entries: &mut Vec<FileLockEntry<'a>>) {
-    let mut entry = store
-        .get(target)
-        .map_err_trace_exit_unwrap()
-        .unwrap_or_else(|| {
-            error!("Funny things happened: Entry moved to destination did not fail, but entry does not exist");
-            exit(1)
-        });
-
-
-    for mut link in linked_entries {
-        let _ = entry.add_link(&mut link).map_err_trace_exit_unwrap();
-    }
-}
@@ -0,0 +1,145 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+#![forbid(unsafe_code)]
+
+#![deny(
+    non_camel_case_types,
+    non_snake_case,
+    path_statements,
+    trivial_numeric_casts,
+    unstable_features,
+    unused_allocation,
+    unused_import_braces,
+    unused_imports,
+    unused_must_use,
+    unused_mut,
+    unused_qualifications,
+    while_true,
+)]
+
+#[macro_use] extern crate log;
+extern crate clap;
+extern crate toml;
+extern crate failure;
+
+#[macro_use] extern crate libimagrt;
+extern crate libimagerror;
+extern crate libimagutil;
+
+use std::io::Write;
+use std::ops::Deref;
+use std::path::PathBuf;
+
+use failure::Error;
+
+use libimagrt::runtime::Runtime;
+use libimagrt::setup::generate_runtime_setup;
+use libimagrt::profile::Profile;
+use libimagrt::profile::profiles;
+use libimagerror::trace::MapErrTrace;
+use libimagerror::exit::ExitUnwrap;
+use libimagerror::io::ToExitCode;
+use libimagutil::warn_exit::warn_exit;
+
+mod ui;
+
+use crate::ui::build_ui;
+
+fn main() {
+    let version = make_imag_version!();
+    let rt      = generate_runtime_setup("imag-profile",
+                                         &version,
+                                         "List and show the profiles from the configuration",
+                                         build_ui);
+
+    let command = rt.cli().subcommand_name().map(String::from);
+
+    if let Some(command) = command {
+        debug!("Call: {}", command);
+        match command.deref() {
+            "list" => list(&rt),
+            "show" => show(&rt),
+            other  => {
+                debug!("Unknown command");
+                let _ = rt.handle_unknown_subcommand("imag-profile", other, rt.cli())
+                    .map_err_trace_exit_unwrap()
+                    .code()
+                    .map(::std::process::exit);
+            },
+        };
+    } else {
+        debug!("No command");
+    }
+}
+
+fn list(rt: &Runtime) {
+    let active = rt.profile().map(Profile::name);
+    let mut out = rt.stdout();
+
+    for profile in all_profiles(rt) {
+        let marker = if Some(profile.name()) == active { "*" } else { " " };
+        let store  = profile
+            .storepath(rt.rtp())
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| String::from("<default store>"));
+
+        let _ = writeln!(out, "{} {} ({})", marker, profile.name(), store)
+            .to_exit_code()
+            .unwrap_or_exit();
+    }
+}
+
+fn show(rt: &Runtime) {
+    let scmd    = rt.cli().subcommand_matches("show").unwrap();
+    let profile = match scmd.value_of("name") {
+        Some(name) => all_profiles(rt)
+            .into_iter()
+            .find(|p| p.name() == name)
+            .unwrap_or_else(|| warn_exit(&format!("No profile '{}' in the configuration", name), 1)),
+        None => rt
+            .profile()
+            .cloned()
+            .unwrap_or_else(|| warn_exit("No profile selected, use --profile or IMAG_PROFILE", 1)),
+    };
+
+    let store = profile
+        .storepath(rt.rtp())
+        .unwrap_or_else(|| rt.rtp().join(PathBuf::from("store")));
+
+    let mut out = rt.stdout();
+    let _ = writeln!(out, "Profile: {}", profile.name()).to_exit_code().unwrap_or_exit();
+    let _ = writeln!(out, "Store:   {}", store.display()).to_exit_code().unwrap_or_exit();
+
+    if let Some(config) = profile.config() {
+        let config = ::toml::ser::to_string_pretty(config)
+            .map_err(Error::from)
+            .map_err_trace_exit_unwrap();
+
+        let _ = writeln!(out, "\nConfiguration overrides:\n{}", config.trim_end())
+            .to_exit_code()
+            .unwrap_or_exit();
+    }
+}
+
+fn all_profiles(rt: &Runtime) -> Vec<Profile> {
+    rt.config()
+        .map(|config| profiles(config).map_err_trace_exit_unwrap())
+        .unwrap_or_else(Vec::new)
+}
@@ -0,0 +1,38 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use clap::{Arg, App, SubCommand};
+
+pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
+    app
+        .subcommand(SubCommand::with_name("list")
+                   .about("List the profiles from the configuration, the active one is marked with '*'")
+                   .version("0.1"))
+
+        .subcommand(SubCommand::with_name("show")
+                   .about("Show the store and the configuration overrides of a profile")
+                   .version("0.1")
+                   .arg(Arg::with_name("name")
+                        .index(1)
+                        .takes_value(true)
+                        .required(false)
+                        .multiple(false)
+                        .help("The profile to show (default: the active one)")
+                        .value_name("PROFILE")))
+}
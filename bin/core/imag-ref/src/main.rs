@@ -44,31 +44,67 @@ extern crate libimagentryref;
 extern crate libimagerror;
 extern crate libimaginteraction;
 extern crate libimagutil;
+extern crate walkdir;
 
 mod ui;
 use crate::ui::build_ui;
 
 use std::process::exit;
 use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::collections::HashSet;
+use std::collections::BTreeMap;
 
+use clap::ArgMatches;
 use failure::Error;
+use failure::Fallible as Result;
+use failure::ResultExt;
+use walkdir::WalkDir;
 
 use libimagerror::trace::MapErrTrace;
+use libimagerror::trace::trace_error;
 use libimagerror::exit::ExitUnwrap;
-use libimagrt::setup::generate_runtime_setup;
+use libimagrt::setup::generate_runtime_setup_with_config;
 use libimagrt::runtime::Runtime;
 use libimagentryref::reference::Ref;
 use libimagentryref::reference::MutRef;
 use libimagentryref::reference::RefFassade;
+use libimagentryref::reference::Config as RefConfig;
+use libimagentryref::hasher::Hasher;
 use libimagentryref::hasher::default::DefaultHasher;
+use libimagentryref::hasher::sha1::Sha1Hasher;
+use libimagentryref::hasher::sha256::Sha256Hasher;
+use libimagentryref::hasher::blake2b::Blake2bHasher;
+use libimagentryref::hasher::partial::PartialHasher;
+use libimagentryref::hasher::hash_with_name;
 use libimagentryref::util::get_ref_config;
+use libimagstore::storeid::StoreId;
+use libimagstore::storeid::IntoStoreId;
+
+/// Call `$f` with the hasher selected by the "hasher" argument in `$cmd` as type parameter
+macro_rules! with_hasher {
+    ($cmd:expr, $f:ident($($arg:expr),*)) => {
+        match $cmd.value_of("hasher").unwrap() { // default by clap
+            Sha1Hasher::NAME    => $f::<Sha1Hasher>($($arg),*),
+            Sha256Hasher::NAME  => $f::<Sha256Hasher>($($arg),*),
+            Blake2bHasher::NAME => $f::<Blake2bHasher>($($arg),*),
+            PartialHasher::NAME => $f::<PartialHasher>($($arg),*),
+            other => {
+                error!("Unknown hasher: {}", other);
+                exit(1)
+            },
+        }
+    }
+}
 
 fn main() {
     let version = make_imag_version!();
-    let rt = generate_runtime_setup("imag-ref",
-                                    &version,
-                                    "Reference files outside of the store",
-                                    build_ui);
+    let rt = generate_runtime_setup_with_config("imag-ref",
+                                                &version,
+                                                "Reference files outside of the store",
+                                                &["ref"],
+                                                build_ui);
     rt.cli()
         .subcommand_name()
         .map(|name| {
@@ -76,8 +112,11 @@ fn main() {
             match name {
                 "deref"     => deref(&rt),
                 "create"    => create(&rt),
+                "import"    => import(&rt),
                 "remove"    => remove(&rt),
                 "list-dead" => list_dead(&rt),
+                "repair"    => repair(&rt),
+                "rehash"    => rehash(&rt),
                 other => {
                     debug!("Unknown command");
                     let _ = rt.handle_unknown_subcommand("imag-ref", other, rt.cli())
@@ -221,7 +260,403 @@ fn list_dead(rt: &Runtime) {
         });
 }
 
-fn create(_rt: &Runtime) {
-    unimplemented!()
+fn repair(rt: &Runtime) {
+    let cmd = rt.cli().subcommand_matches("repair").unwrap(); // safe by main()
+    with_hasher!(cmd, repair_with_hasher(rt, cmd))
+}
+
+/// A dead reference and the file it was found at
+struct Relocation {
+    id: StoreId,
+    old_path: PathBuf,
+    new_path: PathBuf,
+    basepath_name: String,
+}
+
+fn repair_with_hasher<H: Hasher>(rt: &Runtime, cmd: &ArgMatches) {
+    use libimaginteraction::ask::ask_bool;
+
+    let cfg     = get_ref_config(rt, "imag-ref").map_err_trace_exit_unwrap();
+    let dry_run = cmd.is_present("dry-run");
+    let yes     = cmd.is_present("yes");
+
+    let ids      = ids_or_all_entries(rt);
+    let mut dead = dead_refs::<H>(rt, &cfg, ids).map_err_trace_exit_unwrap();
+    if dead.is_empty() {
+        info!("No dead references found");
+        return
+    }
+
+    let relocations = find_moved_files::<H>(&cfg, &mut dead);
+    for (id, _) in dead.values().flatten() {
+        warn!("No file found for dead reference: {}", id);
+    }
+
+    let mut input = if yes || dry_run {
+        None
+    } else {
+        Some(rt.stdin().unwrap_or_else(|| {
+            error!("No input stream. Cannot ask for permission");
+            exit(1)
+        }))
+    };
+    let mut output = rt.stdout();
+
+    for reloc in relocations {
+        writeln!(output, "{}: {} -> {}", reloc.id, reloc.old_path.display(), reloc.new_path.display())
+            .map_err(Error::from)
+            .map_err_trace_exit_unwrap();
+
+        if dry_run {
+            continue
+        }
+
+        if let Some(ref mut input) = input {
+            if !ask_bool("Repair this reference", Some(false), input, &mut output).map_err_trace_exit_unwrap() {
+                info!("Skipping {}", reloc.id);
+                continue
+            }
+        }
+
+        let mut entry = rt
+            .store()
+            .get(reloc.id.clone())
+            .map_err_trace_exit_unwrap()
+            .unwrap_or_else(|| {
+                error!("No entry for id '{}' found", reloc.id);
+                exit(1)
+            });
+
+        let _ = entry
+            .as_ref_with_hasher_mut::<H>()
+            .relocate(&reloc.new_path, &reloc.basepath_name, &cfg)
+            .map_err_trace_exit_unwrap();
+
+        let _ = rt.report_touched(&reloc.id).unwrap_or_exit();
+    }
+}
+
+/// Find the refs in `ids` which point to a file that does not exist, indexed by their hash
+fn dead_refs<H: Hasher>(rt: &Runtime, cfg: &RefConfig, ids: Vec<StoreId>)
+    -> Result<BTreeMap<String, Vec<(StoreId, PathBuf)>>>
+{
+    let mut dead = BTreeMap::new();
+
+    for id in ids {
+        let entry = rt.store().get_copy(id.clone())?;
+        if !entry.is_ref()? {
+            continue
+        }
+
+        let entry_ref = entry.as_ref_with_hasher::<H>();
+        let path      = match entry_ref.get_path(cfg) {
+            Ok(path) => path,
+            Err(e)   => {
+                warn!("Cannot find path of {}, skipping: {}", id, e);
+                continue
+            },
+        };
+
+        if path.exists() {
+            continue
+        }
+
+        match entry_ref.get_hash() {
+            Ok(hash) => dead.entry(String::from(hash)).or_insert_with(Vec::new).push((id, path)),
+            Err(_)   => warn!("{} has no '{}' hash, cannot repair it", id, H::NAME),
+        }
+    }
+
+    Ok(dead)
+}
+
+/// Walk the configured basepathes and find the files whose hash matches one of the `dead` refs
+///
+/// Found refs are removed from `dead`.
+fn find_moved_files<H: Hasher>(cfg: &RefConfig, dead: &mut BTreeMap<String, Vec<(StoreId, PathBuf)>>)
+    -> Vec<Relocation>
+{
+    let mut relocations = Vec::new();
+
+    for basepath in cfg.values() {
+        if !basepath.is_dir() {
+            debug!("Basepath does not exist, skipping: {}", basepath.display());
+            continue
+        }
+
+        for entry in WalkDir::new(basepath).into_iter() {
+            if dead.is_empty() {
+                return relocations
+            }
+
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e)    => {
+                    warn!("Cannot read {}: {}", basepath.display(), e);
+                    continue
+                },
+            };
+
+            if !entry.file_type().is_file() {
+                continue
+            }
+
+            let hash = match H::hash(entry.path()) {
+                Ok(hash) => hash,
+                Err(e)   => {
+                    debug!("Cannot hash {}, skipping: {}", entry.path().display(), e);
+                    continue
+                },
+            };
+
+            if let Some(refs) = dead.remove(&hash) {
+                // nested basepathes: use the most specific one, as `create` does
+                let basepath_name = cfg
+                    .find_basepath_for(entry.path())
+                    .cloned()
+                    .unwrap(); // the file is located in at least this basepath
+
+                for (id, old_path) in refs {
+                    relocations.push(Relocation {
+                        id,
+                        old_path,
+                        new_path: entry.path().to_path_buf(),
+                        basepath_name: basepath_name.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    relocations
+}
+
+fn rehash(rt: &Runtime) {
+    let cmd = rt.cli().subcommand_matches("rehash").unwrap(); // safe by main()
+    with_hasher!(cmd, rehash_with_hasher(rt, cmd))
+}
+
+fn rehash_with_hasher<H: Hasher>(rt: &Runtime, cmd: &ArgMatches) {
+    let cfg        = get_ref_config(rt, "imag-ref").map_err_trace_exit_unwrap();
+    let remove_old = cmd.is_present("remove-old");
+    let force      = cmd.is_present("force");
+
+    for id in ids_or_all_entries(rt) {
+        let mut entry = rt.store()
+            .get(id.clone())
+            .map_err_trace_exit_unwrap()
+            .unwrap_or_else(|| {
+                error!("No entry for id '{}' found", id);
+                exit(1)
+            });
+
+        if !entry.is_ref().map_err_trace_exit_unwrap() {
+            continue
+        }
+
+        let path = {
+            let entry_ref = entry.as_ref_with_hasher::<H>();
+            let path      = entry_ref.get_path(&cfg).map_err_trace_exit_unwrap();
+
+            if !path.exists() {
+                warn!("{} refers to a file which does not exist, skipping: {}", id, path.display());
+                continue
+            }
+
+            if !force {
+                let changed = entry_ref
+                    .get_hashes()
+                    .map_err_trace_exit_unwrap()
+                    .into_iter()
+                    .filter(|(name, _)| name != H::NAME)
+                    .filter_map(|(name, hash)| hash_with_name(&name, &path).map(|h| (name, hash, h)))
+                    .map(|(name, hash, new)| new.map(|new| (name, new != hash)))
+                    .collect::<Result<Vec<_>>>()
+                    .map_err_trace_exit_unwrap()
+                    .into_iter()
+                    .find(|(_, changed)| *changed);
+
+                if let Some((name, _)) = changed {
+                    warn!("{} changed since it was referenced ({} hash differs), skipping. Use --force to rehash anyway: {}",
+                          id, name, path.display());
+                    continue
+                }
+            }
+
+            path
+        };
+
+        let _ = entry
+            .as_ref_with_hasher_mut::<H>()
+            .rehash(&cfg, !remove_old)
+            .map_err_trace_exit_unwrap();
+
+        info!("Rehashed: {}", path.display());
+        let _ = rt.report_touched(&id).unwrap_or_exit();
+    }
+}
+
+/// Get the ids passed to the command, or the ids of all entries in the store if there are none
+fn ids_or_all_entries(rt: &Runtime) -> Vec<StoreId> {
+    match rt.ids::<::ui::PathProvider>().map_err_trace_exit_unwrap() {
+        Some(ids) => ids,
+        None      => rt
+            .store()
+            .entries()
+            .and_then(|entries| entries.collect::<Result<Vec<_>>>())
+            .map_err_trace_exit_unwrap(),
+    }
+}
+
+fn create(rt: &Runtime) {
+    let cmd = rt.cli().subcommand_matches("create").unwrap(); // safe by main()
+    with_hasher!(cmd, create_with_hasher(rt, cmd))
+}
+
+fn create_with_hasher<H: Hasher>(rt: &Runtime, cmd: &ArgMatches) {
+    let cfg        = get_ref_config(rt, "imag-ref").map_err_trace_exit_unwrap();
+    let force      = cmd.is_present("force");
+    let path       = cmd.value_of("path").map(absolute_path).unwrap(); // safe by clap
+    let collection = get_collection_name(cmd, &cfg, &path);
+    let id         = cmd
+        .value_of("ID")
+        .map(PathBuf::from)
+        .unwrap() // safe by clap
+        .into_storeid()
+        .map_err_trace_exit_unwrap();
+
+    let mut entry = rt.store().retrieve(id).map_err_trace_exit_unwrap();
+    let _ = entry
+        .as_ref_with_hasher_mut::<H>()
+        .make_ref(&path, &collection, &cfg, force)
+        .map_err_trace_exit_unwrap();
+
+    let _ = rt.report_touched(entry.get_location()).unwrap_or_exit();
+}
+
+fn import(rt: &Runtime) {
+    let cmd = rt.cli().subcommand_matches("import").unwrap(); // safe by main()
+    with_hasher!(cmd, import_with_hasher(rt, cmd))
+}
+
+fn import_with_hasher<H: Hasher>(rt: &Runtime, cmd: &ArgMatches) {
+    let cfg        = get_ref_config(rt, "imag-ref").map_err_trace_exit_unwrap();
+    let force      = cmd.is_present("force");
+    let prefix     = cmd.value_of("prefix").unwrap(); // default by clap
+    let path       = cmd.value_of("path").map(absolute_path).unwrap(); // safe by clap
+    let collection = get_collection_name(cmd, &cfg, &path);
+
+    if !path.is_dir() {
+        error!("Not a directory: {}", path.display());
+        exit(1)
+    }
+
+    let basepath = cfg
+        .get(&collection)
+        .ok_or_else(|| format_err!("Configuration missing for basepath: '{}'", collection))
+        .map_err_trace_exit_unwrap();
+
+    let mut known_hashes = referenced_hashes::<H>(rt).map_err_trace_exit_unwrap();
+    let mut failed       = false;
+
+    for entry in WalkDir::new(&path).min_depth(1).into_iter() {
+        let entry = entry.map_err(Error::from).map_err_trace_exit_unwrap();
+        if !entry.file_type().is_file() {
+            continue
+        }
+
+        let file = entry.path();
+        match import_file::<H>(rt, file, basepath, &collection, prefix, &cfg, force, &mut known_hashes) {
+            Ok(Some(id)) => {
+                info!("Imported: {}", file.display());
+                let _ = rt.report_touched(&id).unwrap_or_exit();
+            },
+            Ok(None) => info!("Already referenced, skipping: {}", file.display()),
+            Err(e)   => {
+                trace_error(&e);
+                failed = true;
+            },
+        }
+    }
+
+    if failed {
+        exit(1)
+    }
+}
+
+/// Create a reference entry for `file`, unless a file with the same hash is already referenced
+///
+/// Returns the id of the created entry, or `None` if the file was skipped.
+#[allow(clippy::too_many_arguments)]
+fn import_file<H: Hasher>(rt: &Runtime,
+                          file: &Path,
+                          basepath: &Path,
+                          collection: &str,
+                          prefix: &str,
+                          cfg: &RefConfig,
+                          force: bool,
+                          known_hashes: &mut HashSet<String>)
+    -> Result<Option<StoreId>>
+{
+    let hash = H::hash(file).context(format_err!("Failed to hash '{}'", file.display()))?;
+    if known_hashes.contains(&hash) {
+        return Ok(None)
+    }
+
+    let id = PathBuf::from(prefix)
+        .join(collection)
+        .join(file.strip_prefix(basepath)?)
+        .into_storeid()?;
+
+    let mut entry = rt.store().retrieve(id)?;
+    let _ = entry
+        .as_ref_with_hasher_mut::<H>()
+        .make_ref(file, collection, cfg, force)
+        .context(format_err!("Failed to import '{}'", file.display()))?;
+
+    let _ = known_hashes.insert(hash);
+    Ok(Some(entry.get_location().clone()))
+}
+
+/// Collect the hashes (as computed by `H`) of all files which are referenced from the store
+fn referenced_hashes<H: Hasher>(rt: &Runtime) -> Result<HashSet<String>> {
+    let mut hashes = HashSet::new();
+
+    for id in rt.store().entries()? {
+        let entry = rt.store().get_copy(id?)?;
+
+        if entry.is_ref()? {
+            if let Ok(hash) = entry.as_ref_with_hasher::<H>().get_hash() {
+                let _ = hashes.insert(String::from(hash));
+            }
+        }
+    }
+
+    Ok(hashes)
+}
+
+/// Get the basepath name from the commandline or find the one `path` is located in
+fn get_collection_name(cmd: &ArgMatches, cfg: &RefConfig, path: &Path) -> String {
+    cmd.value_of("collection")
+        .map(String::from)
+        .or_else(|| cfg.find_basepath_for(path).cloned())
+        .unwrap_or_else(|| {
+            error!("No basepath configured for '{}'", path.display());
+            exit(1)
+        })
+}
+
+/// Make `path` absolute, without resolving symlinks (so it can be matched against the basepathes)
+fn absolute_path(path: &str) -> PathBuf {
+    let path = PathBuf::from(path);
+
+    if path.is_absolute() {
+        path
+    } else {
+        ::std::env::current_dir()
+            .map_err(Error::from)
+            .map_err_trace_exit_unwrap()
+            .join(path)
+    }
 }
 
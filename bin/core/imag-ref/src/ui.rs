@@ -25,6 +25,9 @@ use failure::Fallible as Result;
 use libimagstore::storeid::StoreId;
 use libimagstore::storeid::IntoStoreId;
 use libimagrt::runtime::IdPathProvider;
+use libimagentryref::hasher;
+use libimagentryref::hasher::Hasher;
+use libimagentryref::hasher::default::DefaultHasher;
 
 pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
     app
@@ -91,7 +94,7 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                      .required(true)
                      .multiple(false)
                      .help("The path to refer to. If there is no basepath configuration in the config file for the path this file is located at, the operation will error.")
-                     .value_name("ID"))
+                     .value_name("PATH"))
 
                 .arg(Arg::with_name("force")
                      .long("force")
@@ -99,6 +102,99 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                      .required(false)
                      .multiple(false)
                      .help("Use force to override existing references"))
+
+                .arg(collection_arg())
+                .arg(hasher_arg())
+                )
+
+        .subcommand(SubCommand::with_name("import")
+                .about("Recursively create references to all files in a directory. Files which are already referenced (matched by hash) are skipped")
+                .version("0.1")
+                .arg(Arg::with_name("path")
+                     .index(1)
+                     .takes_value(true)
+                     .required(true)
+                     .multiple(false)
+                     .help("The directory to import. If there is no basepath configuration in the config file for the path this directory is located at, the operation will error.")
+                     .value_name("PATH"))
+
+                .arg(Arg::with_name("prefix")
+                     .long("prefix")
+                     .short("p")
+                     .takes_value(true)
+                     .required(false)
+                     .multiple(false)
+                     .default_value("ref")
+                     .help("The collection in the store to create the entries in. Entries are named <prefix>/<basepath name>/<path relative to basepath>")
+                     .value_name("PREFIX"))
+
+                .arg(Arg::with_name("force")
+                     .long("force")
+                     .takes_value(false)
+                     .required(false)
+                     .multiple(false)
+                     .help("Use force to override existing references at the ids the files are imported to"))
+
+                .arg(collection_arg())
+                .arg(hasher_arg())
+                )
+
+        .subcommand(SubCommand::with_name("repair")
+                .about("Repair dead references by searching the configured basepathes for files with the referenced hash")
+                .version("0.1")
+                .arg(Arg::with_name("ID")
+                     .index(1)
+                     .takes_value(true)
+                     .required(false)
+                     .multiple(true)
+                     .help("Repair these references. If none are passed, all references in the store are checked")
+                     .value_name("ENTRIES"))
+
+                .arg(Arg::with_name("dry-run")
+                     .long("dry-run")
+                     .short("n")
+                     .takes_value(false)
+                     .required(false)
+                     .multiple(false)
+                     .help("Only print the files found for dead references, do not alter any entry"))
+
+                .arg(Arg::with_name("yes")
+                     .long("yes")
+                     .short("y")
+                     .takes_value(false)
+                     .required(false)
+                     .multiple(false)
+                     .help("Do not ask for confirmation before repairing a reference"))
+
+                .arg(hasher_arg())
+                )
+
+        .subcommand(SubCommand::with_name("rehash")
+                .about("Hash referenced files with another hasher and store the hash in the ref. This can be used to migrate refs to another hasher")
+                .version("0.1")
+                .arg(Arg::with_name("ID")
+                     .index(1)
+                     .takes_value(true)
+                     .required(false)
+                     .multiple(true)
+                     .help("Rehash these references. If none are passed, all references in the store are rehashed")
+                     .value_name("ENTRIES"))
+
+                .arg(Arg::with_name("remove-old")
+                     .long("remove-old")
+                     .takes_value(false)
+                     .required(false)
+                     .multiple(false)
+                     .help("Remove the hashes of all other hashers from the references. Note that some tools (like imag-mail) rely on their own hasher"))
+
+                .arg(Arg::with_name("force")
+                     .long("force")
+                     .takes_value(false)
+                     .required(false)
+                     .multiple(false)
+                     .help("Rehash even if the file does not match the hashes already stored in the reference"))
+
+                .arg(hasher_arg())
                 )
 
         .subcommand(SubCommand::with_name("list-dead")
@@ -131,6 +227,29 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
 
 }
 
+fn collection_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("collection")
+        .long("collection")
+        .short("C")
+        .takes_value(true)
+        .required(false)
+        .multiple(false)
+        .help("The name of the basepath (as configured in 'ref.basepathes') to create the reference in. Defaults to the most specific basepath the path is located in.")
+        .value_name("COLLECTION")
+}
+
+fn hasher_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("hasher")
+        .long("hasher")
+        .takes_value(true)
+        .required(false)
+        .multiple(false)
+        .possible_values(hasher::NAMES)
+        .default_value(DefaultHasher::NAME)
+        .help("The hasher to use for hashing the referenced file(s)")
+        .value_name("HASHER")
+}
+
 pub struct PathProvider;
 impl IdPathProvider for PathProvider {
     fn get_ids(matches: &ArgMatches) -> Result<Option<Vec<StoreId>>> {
@@ -149,7 +268,10 @@ impl IdPathProvider for PathProvider {
             ("deref", Some(subm)) => get_id_paths(subm),
             ("remove", Some(subm)) => get_id_paths(subm),
             ("list-dead", Some(subm)) => get_id_paths(subm),
+            ("repair", Some(subm)) => get_id_paths(subm),
+            ("rehash", Some(subm)) => get_id_paths(subm),
             ("create", _) => Err(format_err!("Command does not get IDs as input")),
+            ("import", _) => Err(format_err!("Command does not get IDs as input")),
             (other, _) => Err(format_err!("Not a known command: {}", other)),
         }
     }
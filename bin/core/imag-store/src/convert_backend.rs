@@ -0,0 +1,60 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use std::io::Write;
+
+use libimagrt::runtime::Runtime;
+use libimagstore::file_abstraction::BackendKind;
+use libimagstore::file_abstraction::copy_entries;
+use libimagutil::warn_exit::warn_exit;
+use libimagerror::trace::MapErrTrace;
+use libimagerror::exit::ExitUnwrap;
+use libimagerror::io::ToExitCode;
+
+/// Copy all entries of the store from the configured backend to another one.
+///
+/// This function is not intended to be called by normal programs but only by `imag-store`.
+pub fn convert_backend(rt: &Runtime) {
+    let scmd     = rt.cli().subcommand_matches("convert-backend").unwrap();
+    let location = rt.store().path();
+    let from     = BackendKind::from_config(&rt.config().cloned()).map_err_trace_exit_unwrap();
+    let to       = scmd
+        .value_of("to")
+        .map(BackendKind::from_name)
+        .unwrap() // safe by clap
+        .map_err_trace_exit_unwrap();
+
+    if from == to {
+        warn_exit(&format!("The store already uses the '{}' backend", to.name()), 1)
+    }
+
+    let from_backend = from.create_backend(location).map_err_trace_exit_unwrap();
+    let to_backend   = to.create_backend(location).map_err_trace_exit_unwrap();
+    let remove       = scmd.is_present("remove-old");
+
+    let n = copy_entries(location, from_backend, to_backend, remove).map_err_trace_exit_unwrap();
+
+    let mut out = rt.stdout();
+    let _ = writeln!(out, "Copied {} entries from '{}' to '{}'", n, from.name(), to.name())
+        .to_exit_code()
+        .unwrap_or_exit();
+    let _ = writeln!(out, "Set 'store.backend = \"{}\"' in the configuration to use it", to.name())
+        .to_exit_code()
+        .unwrap_or_exit();
+}
@@ -0,0 +1,54 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use std::io::Write;
+
+use libimagrt::runtime::Runtime;
+use libimagutil::warn_exit::warn_exit;
+use libimagerror::trace::MapErrTrace;
+use libimagerror::exit::ExitUnwrap;
+use libimagerror::io::ToExitCode;
+
+/// Maintain the store index.
+///
+/// This function is not intended to be called by normal programs but only by `imag-store`.
+pub fn index(rt: &Runtime) {
+    let scmd  = rt.cli().subcommand_matches("index").unwrap();
+    let index = rt.store().index().unwrap_or_else(|| {
+        warn_exit("The store index is not enabled. Set 'store.index.enabled = true' to enable it", 1)
+    });
+
+    match scmd.subcommand_name() {
+        Some("rebuild") => {
+            rt.store().rebuild_index().map_err_trace_exit_unwrap();
+            info!("Index rebuilt");
+        },
+
+        Some("status") | None => {
+            let len = index.len().map_err_trace_exit_unwrap();
+            let _   = writeln!(rt.stdout(), "{} entries indexed", len)
+                .to_exit_code()
+                .unwrap_or_exit();
+        },
+
+        Some(other) => {
+            warn_exit(&format!("Unknown subcommand: {}", other), 1)
+        },
+    }
+}
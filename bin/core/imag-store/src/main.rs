@@ -39,10 +39,12 @@ extern crate clap;
 extern crate toml;
 #[cfg(test)] extern crate toml_query;
 extern crate failure;
+extern crate serde_json;
 
 #[macro_use] extern crate libimagrt;
 extern crate libimagstore;
 extern crate libimagerror;
+extern crate libimagentrylink;
 
 #[cfg(test)]
 #[macro_use]
@@ -54,24 +56,43 @@ extern crate libimagutil;
 use libimagrt::setup::generate_runtime_setup;
 use libimagerror::trace::MapErrTrace;
 
+#[cfg(feature = "sqlite")]
+mod convert_backend;
 mod create;
 mod delete;
 mod get;
+mod index;
+mod migrate;
+#[cfg(feature = "encryption")]
+mod rekey;
 mod retrieve;
+mod transfer;
 mod ui;
 mod update;
 mod verify;
+#[cfg(feature = "watch")]
+mod watch;
 mod util;
 
 use std::ops::Deref;
 
+#[cfg(feature = "sqlite")]
+use crate::convert_backend::convert_backend;
 use crate::create::create;
 use crate::delete::delete;
 use crate::get::get;
+use crate::index::index;
+use crate::migrate::migrate;
+#[cfg(feature = "encryption")]
+use crate::rekey::rekey;
 use crate::retrieve::retrieve;
+use crate::transfer::export_to;
+use crate::transfer::import_from;
 use crate::ui::build_ui;
 use crate::update::update;
 use crate::verify::verify;
+#[cfg(feature = "watch")]
+use crate::watch::watch;
 
 fn main() {
     let version = make_imag_version!();
@@ -85,13 +106,23 @@ fn main() {
     if let Some(command) = command {
         debug!("Call: {}", command);
         match command.deref() {
-            "create"   => create(&rt),
-            "delete"   => delete(&rt),
-            "get"      => get(&rt),
-            "retrieve" => retrieve(&rt),
-            "update"   => update(&rt),
-            "verify"   => verify(&rt),
-            other      => {
+            #[cfg(feature = "sqlite")]
+            "convert-backend" => convert_backend(&rt),
+            "create"          => create(&rt),
+            "delete"          => delete(&rt),
+            "export-to"       => export_to(&rt),
+            "get"             => get(&rt),
+            "import-from"     => import_from(&rt),
+            "index"           => index(&rt),
+            "migrate"         => migrate(&rt),
+            #[cfg(feature = "encryption")]
+            "rekey"           => rekey(&rt),
+            "retrieve"        => retrieve(&rt),
+            "update"          => update(&rt),
+            "verify"          => verify(&rt),
+            #[cfg(feature = "watch")]
+            "watch"           => watch(&rt),
+            other             => {
                 debug!("Unknown command");
                 let _ = rt.handle_unknown_subcommand("imag-store", other, rt.cli())
                     .map_err_trace_exit_unwrap()
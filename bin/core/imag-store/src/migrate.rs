@@ -0,0 +1,83 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use std::io::Write;
+
+use libimagrt::runtime::Runtime;
+use libimagstore::migration::MigrationRegistry;
+use libimagerror::trace::MapErrTrace;
+use libimagerror::trace::trace_error;
+use libimagerror::exit::ExitUnwrap;
+use libimagerror::io::ToExitCode;
+
+/// Upgrade the entries of the store with the migrations of all libraries.
+///
+/// This function is not intended to be called by normal programs but only by `imag-store`.
+pub fn migrate(rt: &Runtime) {
+    let scmd     = rt.cli().subcommand_matches("migrate").unwrap();
+    let dry_run  = scmd.is_present("dry-run");
+    let registry = registry();
+    let mut out  = rt.stdout();
+
+    if scmd.is_present("list") {
+        for migration in registry.migrations() {
+            let _ = writeln!(out, "{} {}", migration.version(), migration.name())
+                .to_exit_code()
+                .unwrap_or_exit();
+        }
+        return
+    }
+
+    let report = registry.migrate_store(rt.store(), dry_run).map_err_trace_exit_unwrap();
+
+    for &(ref id, ref applied) in report.migrated() {
+        let applied = if applied.is_empty() {
+            String::from("version only")
+        } else {
+            applied.join(", ")
+        };
+
+        let _ = writeln!(out, "{}: {}", id, applied).to_exit_code().unwrap_or_exit();
+    }
+
+    for &(ref id, ref e) in report.failed() {
+        error!("Failed to migrate {}", id);
+        trace_error(e);
+    }
+
+    let _ = writeln!(out, "{} {} entries to {}, {} up to date, {} failed",
+                     if dry_run { "Would migrate" } else { "Migrated" },
+                     report.migrated().len(),
+                     registry.current_version(),
+                     report.up_to_date(),
+                     report.failed().len())
+        .to_exit_code()
+        .unwrap_or_exit();
+
+    if !report.failed().is_empty() {
+        ::std::process::exit(1)
+    }
+}
+
+/// All migrations known to imag-store
+fn registry() -> MigrationRegistry {
+    let mut registry = MigrationRegistry::new();
+    ::libimagentrylink::migration::register_migrations(&mut registry).map_err_trace_exit_unwrap();
+    registry
+}
@@ -0,0 +1,57 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use libimagrt::runtime::Runtime;
+use libimagstore::file_abstraction::crypt::Secret;
+use libimagerror::trace::MapErrTrace;
+use libimagerror::exit::ExitUnwrap;
+use libimagerror::io::ToExitCode;
+use libimagutil::warn_exit::warn_exit;
+
+/// The environment variable the new passphrase is read from if no new keyfile is passed
+const NEW_PASSPHRASE_ENV_VAR: &str = "IMAG_STORE_NEW_PASSPHRASE";
+
+/// Re-write all entries of an encrypted store with a new key.
+///
+/// This function is not intended to be called by normal programs but only by `imag-store`.
+pub fn rekey(rt: &Runtime) {
+    let scmd   = rt.cli().subcommand_matches("rekey").unwrap();
+    let secret = match scmd.value_of("new-keyfile") {
+        Some(path) => Secret::Keyfile(PathBuf::from(path)),
+        None       => match ::std::env::var(NEW_PASSPHRASE_ENV_VAR) {
+            Ok(passphrase) => Secret::Passphrase(passphrase),
+            Err(_)         => warn_exit(&format!("Pass --new-keyfile or set ${}", NEW_PASSPHRASE_ENV_VAR), 1),
+        },
+    };
+
+    let n = rt.store().rekey(&secret).map_err_trace_exit_unwrap();
+
+    let _ = writeln!(rt.stdout(), "Re-wrote {} entries with the new key", n)
+        .to_exit_code()
+        .unwrap_or_exit();
+
+    match secret {
+        Secret::Keyfile(_)    => info!("Set 'store.encryption.keyfile' to the new keyfile now"),
+        Secret::Passphrase(_) => info!("Use the new passphrase in ${} from now on",
+                                       ::libimagstore::file_abstraction::crypt::PASSPHRASE_ENV_VAR),
+    }
+}
@@ -0,0 +1,119 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use clap::ArgMatches;
+
+use libimagrt::runtime::Runtime;
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+use libimagentrylink::transfer::TransferOptions;
+use libimagentrylink::transfer::TransferReport;
+use libimagentrylink::transfer::transfer;
+use libimagerror::trace::MapErrTrace;
+use libimagerror::exit::ExitUnwrap;
+use libimagerror::io::ToExitCode;
+
+/// Copy entries from the store of the runtime into another store.
+///
+/// This function is not intended to be called by normal programs but only by `imag-store`.
+pub fn export_to(rt: &Runtime) {
+    let scmd  = rt.cli().subcommand_matches("export-to").unwrap();
+    let other = open_other_store(rt, scmd);
+    let ids   = ids(scmd);
+
+    let report = transfer(rt.store(), &other, ids, &options(scmd)).map_err_trace_exit_unwrap();
+    print_report(rt, &report);
+}
+
+/// Copy entries from another store into the store of the runtime.
+///
+/// This function is not intended to be called by normal programs but only by `imag-store`.
+pub fn import_from(rt: &Runtime) {
+    let scmd  = rt.cli().subcommand_matches("import-from").unwrap();
+    let other = open_other_store(rt, scmd);
+    let ids   = ids(scmd);
+
+    let report = transfer(&other, rt.store(), ids, &options(scmd)).map_err_trace_exit_unwrap();
+
+    for id in report.copied().iter().chain(report.overwritten().iter()) {
+        let _ = rt.report_touched(id).unwrap_or_exit();
+    }
+
+    print_report(rt, &report);
+}
+
+fn open_other_store(rt: &Runtime, scmd: &ArgMatches) -> Store {
+    let path = scmd.value_of("store").map(PathBuf::from).unwrap(); // safe by clap
+    debug!("Opening other store at {}", path.display());
+    Store::new(path, &rt.config().cloned()).map_err_trace_exit_unwrap()
+}
+
+fn ids(scmd: &ArgMatches) -> Vec<StoreId> {
+    scmd.values_of("id")
+        .unwrap() // safe by clap
+        .map(PathBuf::from)
+        .map(StoreId::new)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err_trace_exit_unwrap()
+}
+
+fn options(scmd: &ArgMatches) -> TransferOptions {
+    TransferOptions::default()
+        .with_links(scmd.is_present("with-links"))
+        .overwrite(scmd.is_present("overwrite"))
+}
+
+fn print_report(rt: &Runtime, report: &TransferReport) {
+    let mut out = rt.stdout();
+
+    for id in report.copied() {
+        let _ = writeln!(out, "{}: copied", id).to_exit_code().unwrap_or_exit();
+    }
+
+    for id in report.overwritten() {
+        let _ = writeln!(out, "{}: overwritten", id).to_exit_code().unwrap_or_exit();
+    }
+
+    for id in report.identical() {
+        let _ = writeln!(out, "{}: identical, skipped", id).to_exit_code().unwrap_or_exit();
+    }
+
+    for &(ref id, ref link) in report.dropped_links() {
+        warn!("{}: dropped link to {}, which is not in the target store", id, link);
+    }
+
+    for &(ref id, ref conflict) in report.conflicts() {
+        error!("{}: {}", id, conflict);
+    }
+
+    let _ = writeln!(out, "{} copied, {} overwritten, {} identical, {} conflicts",
+                     report.copied().len(),
+                     report.overwritten().len(),
+                     report.identical().len(),
+                     report.conflicts().len())
+        .to_exit_code()
+        .unwrap_or_exit();
+
+    if !report.conflicts().is_empty() {
+        ::std::process::exit(1)
+    }
+}
@@ -20,6 +20,21 @@
 use clap::{Arg, App, ArgGroup, SubCommand};
 
 pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
+    let app = build_common_ui(app);
+
+    #[cfg(feature = "sqlite")]
+    let app = build_convert_backend_ui(app);
+
+    #[cfg(feature = "encryption")]
+    let app = build_rekey_ui(app);
+
+    #[cfg(feature = "watch")]
+    let app = build_watch_ui(app);
+
+    app
+}
+
+fn build_common_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
     app.subcommand(SubCommand::with_name("create")
                    .about("Create an entry from the store")
                    .version("0.1")
@@ -190,4 +205,144 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                    .about("Verify the store")
                    .version("0.1")
                    )
+
+       .subcommand(SubCommand::with_name("index")
+                   .about("Maintain the store index (only available if 'store.index.enabled' is set)")
+                   .version("0.1")
+                   .subcommand(SubCommand::with_name("rebuild")
+                               .about("Rebuild the index from all entries in the store")
+                               .version("0.1")
+                               )
+                   .subcommand(SubCommand::with_name("status")
+                               .about("Print the number of indexed entries")
+                               .version("0.1")
+                               )
+                   )
+
+       .subcommand(SubCommand::with_name("migrate")
+                   .about("Upgrade all entries which were written by an older version of imag")
+                   .version("0.1")
+                   .arg(Arg::with_name("dry-run")
+                        .long("dry-run")
+                        .short("n")
+                        .takes_value(false)
+                        .required(false)
+                        .multiple(false)
+                        .help("Only report what would be migrated, do not write any entry"))
+                   .arg(Arg::with_name("list")
+                        .long("list")
+                        .takes_value(false)
+                        .required(false)
+                        .multiple(false)
+                        .help("List the known migrations and exit"))
+                   )
+
+       .subcommand(SubCommand::with_name("export-to")
+                   .about("Copy entries into another store, keeping their links, tags and refs")
+                   .version("0.1")
+                   .arg(Arg::with_name("store")
+                        .index(1)
+                        .takes_value(true)
+                        .required(true)
+                        .value_name("STORE")
+                        .help("Path of the store to copy to"))
+                   .arg(Arg::with_name("id")
+                        .index(2)
+                        .takes_value(true)
+                        .required(true)
+                        .multiple(true)
+                        .value_name("ID")
+                        .help("The ids of the entries to copy"))
+                   .arg(Arg::with_name("with-links")
+                        .long("with-links")
+                        .short("l")
+                        .takes_value(false)
+                        .required(false)
+                        .help("Also copy all entries which are reachable via links"))
+                   .arg(Arg::with_name("overwrite")
+                        .long("overwrite")
+                        .takes_value(false)
+                        .required(false)
+                        .help("Replace entries which exist with different content instead of reporting a conflict"))
+                   )
+
+       .subcommand(SubCommand::with_name("import-from")
+                   .about("Copy entries from another store into this one, keeping their links, tags and refs")
+                   .version("0.1")
+                   .arg(Arg::with_name("store")
+                        .index(1)
+                        .takes_value(true)
+                        .required(true)
+                        .value_name("STORE")
+                        .help("Path of the store to copy from"))
+                   .arg(Arg::with_name("id")
+                        .index(2)
+                        .takes_value(true)
+                        .required(true)
+                        .multiple(true)
+                        .value_name("ID")
+                        .help("The ids of the entries to copy"))
+                   .arg(Arg::with_name("with-links")
+                        .long("with-links")
+                        .short("l")
+                        .takes_value(false)
+                        .required(false)
+                        .help("Also copy all entries which are reachable via links"))
+                   .arg(Arg::with_name("overwrite")
+                        .long("overwrite")
+                        .takes_value(false)
+                        .required(false)
+                        .help("Replace entries which exist with different content instead of reporting a conflict"))
+                   )
+}
+
+#[cfg(feature = "sqlite")]
+fn build_convert_backend_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
+    app.subcommand(SubCommand::with_name("convert-backend")
+                   .about("Copy all entries to another backend. Set 'store.backend' in the configuration afterwards to use it")
+                   .version("0.1")
+                   .arg(Arg::with_name("to")
+                        .long("to")
+                        .takes_value(true)
+                        .required(true)
+                        .possible_values(&["filesystem", "sqlite"])
+                        .value_name("BACKEND")
+                        .help("The backend to copy the entries to"))
+                   .arg(Arg::with_name("remove-old")
+                        .long("remove-old")
+                        .takes_value(false)
+                        .required(false)
+                        .multiple(false)
+                        .help("Remove the entries from the current backend after copying them"))
+                   )
+}
+
+#[cfg(feature = "encryption")]
+fn build_rekey_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
+    app.subcommand(SubCommand::with_name("rekey")
+                   .about("Re-write all entries of an encrypted store with a new key. The new passphrase is read from $IMAG_STORE_NEW_PASSPHRASE")
+                   .version("0.1")
+                   .arg(Arg::with_name("new-keyfile")
+                        .long("new-keyfile")
+                        .takes_value(true)
+                        .required(false)
+                        .value_name("PATH")
+                        .help("Derive the new key from this file instead of a passphrase"))
+                   )
+}
+
+#[cfg(feature = "watch")]
+fn build_watch_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
+    app.subcommand(SubCommand::with_name("watch")
+                   .about("Print changes of entries as JSON lines, including changes made by other programs")
+                   .version("0.1")
+                   .arg(Arg::with_name("delay")
+                        .long("delay")
+                        .takes_value(true)
+                        .required(false)
+                        .default_value("200")
+                        .validator(::libimagutil::cli_validators::is_integer)
+                        .value_name("MILLISECONDS")
+                        .help("Collect filesystem events for this long before printing them"))
+                   )
 }
@@ -0,0 +1,56 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use std::io::Write;
+use std::time::Duration;
+
+use libimagrt::runtime::Runtime;
+use libimagstore::file_abstraction::BackendKind;
+use libimagerror::trace::MapErrTrace;
+use libimagerror::exit::ExitUnwrap;
+use libimagerror::io::ToExitCode;
+
+/// Print all changes of entries in the store as JSON lines, until the process is killed.
+///
+/// This function is not intended to be called by normal programs but only by `imag-store`.
+pub fn watch(rt: &Runtime) {
+    let scmd  = rt.cli().subcommand_matches("watch").unwrap();
+    let delay = scmd
+        .value_of("delay")
+        .map(|d| d.parse::<u64>().map(Duration::from_millis))
+        .unwrap() // default value by clap
+        .unwrap_or_else(|_| ::libimagutil::warn_exit::warn_exit("Invalid delay", 1));
+
+    if BackendKind::from_config(&rt.config().cloned()).map_err_trace_exit_unwrap() != BackendKind::Filesystem {
+        warn!("Changes by other programs can only be seen with the filesystem backend");
+    }
+
+    let events   = rt.store().subscribe();
+    let _watcher = rt.store().watch_external(delay).map_err_trace_exit_unwrap();
+    // The events are the output of this command, they must not end up on stderr when piping
+    let out      = ::std::io::stdout();
+    let mut out  = out.lock();
+
+    info!("Watching {}", rt.store().path().display());
+    for event in events {
+        let line = ::serde_json::to_string(&event).map_err(::failure::Error::from).map_err_trace_exit_unwrap();
+        let _    = writeln!(out, "{}", line).to_exit_code().unwrap_or_exit();
+        let _    = out.flush().to_exit_code().unwrap_or_exit();
+    }
+}
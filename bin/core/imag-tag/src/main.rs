@@ -62,6 +62,8 @@ use std::io::Write;
 
 use libimagrt::runtime::Runtime;
 use libimagrt::setup::generate_runtime_setup;
+use libimagrt::io::EntryRecord;
+use libimagrt::io::OutputFormat;
 use libimagentrytag::tagable::Tagable;
 use libimagentrytag::tag::Tag;
 use libimagerror::trace::trace_error;
@@ -85,7 +87,7 @@ fn main() {
                                     build_ui);
 
     let ids = rt
-        .ids::<crate::ui::PathProvider>()
+        .ids_or_pick::<crate::ui::PathProvider>()
         .map_err_trace_exit_unwrap()
         .unwrap_or_else(|| {
             error!("No ids supplied");
@@ -96,8 +98,12 @@ fn main() {
     rt.cli()
         .subcommand_name()
         .map(|name| match name {
-            "list" => for id in ids {
-                list(id, &rt)
+            "list" => if rt.output_format().is_structured() {
+                list_records(ids, &rt)
+            } else {
+                for id in ids {
+                    list(id, &rt)
+                }
             },
             "remove" => for id in ids {
                 let add = None;
@@ -188,7 +194,9 @@ fn list(path: StoreId, rt: &Runtime) {
     let tags = entry.get_tags().map_err_trace_exit_unwrap();
 
     if json_out {
-        unimplemented!()
+        let _ = rt.stdout()
+            .write_records(OutputFormat::Json, tags.iter())
+            .map_err_trace_exit_unwrap();
     }
 
     if line_out {
@@ -215,6 +223,23 @@ fn list(path: StoreId, rt: &Runtime) {
     let _ = rt.report_touched(&path).unwrap_or_exit();
 }
 
+/// Print the tags of all entries as records, for `--format`
+fn list_records<I: Iterator<Item = StoreId>>(ids: I, rt: &Runtime) {
+    let records = ids
+        .map(|id| {
+            let entry = match rt.store().get(id.clone()).map_err_trace_exit_unwrap() {
+                Some(e) => e,
+                None    => warn_exit(&format!("No entry found: {}", id), 1),
+            };
+
+            let tags = entry.get_tags().map_err_trace_exit_unwrap();
+            EntryRecord::new(&id).with_field("tags", tags).map_err_trace_exit_unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    rt.write_records(records).map_err_trace_exit_unwrap();
+}
+
 /// Get the tags which should be added from the commandline
 ///
 /// Returns none if the argument was not specified
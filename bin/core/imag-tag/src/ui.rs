@@ -44,7 +44,7 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                            .takes_value(true)
                            .required(true)
                            .multiple(true)
-                           .value_name("tags")
+                           .value_name("TAGS")
                            .validator(is_tag)
                            .help("Add these tags"))
                    )
@@ -57,7 +57,7 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                            .takes_value(true)
                            .required(true)
                            .multiple(true)
-                           .value_name("tags")
+                           .value_name("TAGS")
                            .validator(is_tag)
                            .help("Remove these tags"))
                    )
@@ -59,7 +59,7 @@ use toml_query::read::TomlValueReadTypeExt;
 use failure::Error;
 use failure::err_msg;
 
-use libimagrt::setup::generate_runtime_setup;
+use libimagrt::setup::generate_runtime_setup_with_config;
 use libimagerror::trace::MapErrTrace;
 use libimagerror::iter::TraceIterator;
 use libimagerror::io::ToExitCode;
@@ -75,15 +75,16 @@ use crate::ui::build_ui;
 
 fn main() {
     let version = make_imag_version!();
-    let rt = generate_runtime_setup( "imag-view",
-                                     &version,
-                                     "View entries (readonly)",
-                                     build_ui);
+    let rt = generate_runtime_setup_with_config( "imag-view",
+                                                 &version,
+                                                 "View entries (readonly)",
+                                                 &["view"],
+                                                 build_ui);
 
     let view_header  = rt.cli().is_present("view-header");
     let hide_content = rt.cli().is_present("not-view-content");
     let entries      = rt
-        .ids::<::ui::PathProvider>()
+        .ids_or_pick::<::ui::PathProvider>()
         .map_err_trace_exit_unwrap()
         .unwrap_or_else(|| {
             error!("No ids supplied");
@@ -0,0 +1,89 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! The shell scripts printed by `imag completions <shell>`
+//!
+//! The scripts do not contain any candidates themselves. They call the command which is completed
+//! with `--imag-complete` and the words on the commandline, so the candidates are always up to
+//! date with the command and the store (see `libimagrt::completion`).
+
+/// The shells a completion script can be generated for
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+/// Generate the completion script for `shell`, registering the completion function for `imag`
+/// and the `imag-*` binaries in `commands`
+pub fn script(shell: &str, commands: &[String]) -> Option<String> {
+    let binaries = Some(String::from("imag"))
+        .into_iter()
+        .chain(commands.iter().map(|cmd| format!("imag-{}", cmd)))
+        .collect::<Vec<_>>();
+
+    match shell {
+        "bash" => Some(bash(&binaries)),
+        "zsh"  => Some(zsh(&binaries)),
+        "fish" => Some(fish(&binaries)),
+        _      => None,
+    }
+}
+
+fn bash(binaries: &[String]) -> String {
+    format!(r#"# bash completion for imag, load with: source <(imag completions bash)
+_imag() {{
+    local IFS=$'\n'
+    COMPREPLY=( $("${{COMP_WORDS[0]}}" --imag-complete "${{COMP_WORDS[@]:1:COMP_CWORD}}" 2>/dev/null) )
+}}
+complete -o default -F _imag {}
+"#, binaries.join(" "))
+}
+
+fn zsh(binaries: &[String]) -> String {
+    format!(r#"#compdef {binaries}
+# zsh completion for imag, load with: source <(imag completions zsh)
+_imag() {{
+    local -a candidates
+    candidates=("${{(@f)$(${{words[1]}} --imag-complete "${{(@)words[2,CURRENT]}}" 2>/dev/null)}}")
+    if [[ -n "${{candidates[1]}}" ]]; then
+        compadd -a candidates
+    else
+        _files
+    fi
+}}
+compdef _imag {binaries}
+"#, binaries = binaries.join(" "))
+}
+
+fn fish(binaries: &[String]) -> String {
+    let registrations = binaries
+        .iter()
+        .map(|bin| format!("complete -c {} -f -a '(__imag_complete)'\n", bin))
+        .collect::<String>();
+
+    format!(r#"# fish completion for imag, load with: imag completions fish | source
+function __imag_complete
+    set -l words (commandline -opc)
+    set -l current (commandline -ct)
+    set -l candidates ($words[1] --imag-complete $words[2..-1] "$current" 2>/dev/null)
+    if test (count $candidates) -gt 0
+        printf '%s\n' $candidates
+    else
+        __fish_complete_path "$current"
+    end
+end
+{}"#, registrations)
+}
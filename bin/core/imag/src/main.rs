@@ -39,10 +39,16 @@ extern crate clap;
 extern crate walkdir;
 extern crate toml;
 extern crate toml_query;
+extern crate serde;
+#[macro_use] extern crate serde_derive;
+extern crate serde_json;
 
 #[macro_use] extern crate libimagrt;
 extern crate libimagerror;
 
+mod completions;
+mod plugins;
+
 use std::env;
 use std::process::exit;
 use std::process::Command;
@@ -53,10 +59,12 @@ use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 use walkdir::WalkDir;
-use clap::{Arg, ArgMatches, AppSettings, SubCommand};
+use clap::{App, Arg, ArgMatches, AppSettings, SubCommand};
+use clap::ErrorKind as ClapErrorKind;
 use toml::Value;
 use toml_query::read::TomlValueReadExt;
 
+use libimagrt::completion::{self, completion_request, first_positional, COMPLETE_FLAG};
 use libimagrt::runtime::Runtime;
 use libimagrt::spec::CliSpec;
 use libimagerror::io::ToExitCode;
@@ -64,9 +72,8 @@ use libimagerror::exit::ExitUnwrap;
 use libimagerror::trace::trace_error;
 use libimagrt::configuration::InternalConfiguration;
 
-/// Returns the helptext, putting the Strings in cmds as possible
-/// subcommands into it
-fn help_text(cmds: Vec<String>) -> String {
+/// Returns the helptext, putting the listing of the available commands into it
+fn help_text(listing: String) -> String {
     format!(r#"
 
      _
@@ -85,11 +92,7 @@ fn help_text(cmds: Vec<String>) -> String {
     called "modules". Each module implements one PIM aspect and all of these
     modules can be used independently.
 
-    Available commands:
-
-    {imagbins}
-
-    Call a command with 'imag <command> <args>'
+{imagbins}    Call a command with 'imag <command> <args>'
     Each command can be called with "--help" to get the respective helptext.
 
     Please visit https://github.com/matthiasbeyer/imag to view the source code,
@@ -98,13 +101,7 @@ fn help_text(cmds: Vec<String>) -> String {
     imag is free software. It is released under the terms of LGPLv2.1
 
     (c) 2015-2018 Matthias Beyer and contributors"#,
-        imagbins = cmds
-            .into_iter()
-            .map(|cmd| format!("\t{}\n", cmd))
-            .fold(String::new(), |s, c| {
-                let s = s + c.as_str();
-                s
-            }))
+        imagbins = listing)
 }
 
 /// Returns the list of imag-* executables found in $PATH
@@ -156,8 +153,7 @@ fn main() {
     let about    = "imag - the PIM suite for the commandline";
     let mut out  = stdout();
     let commands = get_commands(&mut out);
-    let helptext = help_text(commands.clone());
-    let mut app  = Runtime::get_default_cli_builder(appname, &version, about)
+    let app      = Runtime::get_default_cli_builder(appname, &version, about)
         .settings(&[AppSettings::AllowExternalSubcommands, AppSettings::ArgRequiredElseHelp])
         .arg(Arg::with_name("version")
              .long("version")
@@ -172,7 +168,33 @@ fn main() {
              .multiple(false)
              .help("Get the versions of the imag commands"))
         .subcommand(SubCommand::with_name("help").help("Show help"))
-        .after_help(helptext.as_str());
+        .subcommand(SubCommand::with_name("completions")
+                    .about("Print the shell completion script for imag and all imag commands")
+                    .arg(Arg::with_name("shell")
+                         .index(1)
+                         .takes_value(true)
+                         .required(true)
+                         .possible_values(completions::SHELLS)
+                         .value_name("SHELL")
+                         .help("The shell to print the script for")));
+
+    if let Some(words) = completion_request() {
+        complete(&app, &commands, &words);
+        exit(0);
+    }
+
+    // Asking the commands for their infos is only worth it if the help text is printed
+    let print_help = match app.clone().get_matches_safe() {
+        Ok(matches) => matches.subcommand_name().map(|h| h == "help").unwrap_or(false),
+        Err(e)      => e.kind == ClapErrorKind::HelpDisplayed
+                        || e.kind == ClapErrorKind::MissingArgumentOrSubcommand,
+    };
+    let helptext = if print_help {
+        help_text(plugins::help_listing(&plugins::load_plugins(&commands)))
+    } else {
+        String::new()
+    };
+    let mut app = app.after_help(helptext.as_str());
 
     let long_help = {
         let mut v = vec![];
@@ -195,6 +217,22 @@ fn main() {
     let enable_logging = app.enable_logging();
     let matches = app.matches();
 
+    // Printing the completion script does not need a configuration
+    if let Some(scmd) = matches.subcommand_matches("completions") {
+        match scmd.value_of("shell").and_then(|shell| completions::script(shell, &commands)) {
+            Some(script) => {
+                let _ = write!(out, "{}", script)
+                    .to_exit_code()
+                    .unwrap_or_exit();
+                exit(0);
+            },
+            None => {
+                eprintln!("Unknown shell");
+                exit(1);
+            },
+        }
+    }
+
     let rtp = ::libimagrt::runtime::get_rtp_match(&matches)
         .unwrap_or_else(|e| {
             trace_error(&e);
@@ -287,6 +325,11 @@ fn main() {
             let subcommand = String::from(subcommand);
             let subcommand = aliases.get(&subcommand).cloned().unwrap_or(subcommand);
 
+            if plugins::load_plugin(&subcommand).is_incompatible() {
+                warn!("imag-{} was built with a libimagrt which is not compatible with imag {}",
+                      subcommand, ::libimagrt::plugin::RT_VERSION);
+            }
+
             debug!("Calling 'imag-{}' with args: {:?}", subcommand, subcommand_args);
 
             // Create a Command, and pass it the gathered arguments
@@ -341,6 +384,71 @@ fn main() {
     }
 }
 
+/// Answer a completion request for `imag <words>`
+///
+/// The command is completed here, from the commands found in $PATH and the configured aliases.
+/// The words following the command are completed by `imag-<command> --imag-complete`, which also
+/// gets the options given to `imag`, so it completes from the same store.
+fn complete(app: &App, commands: &[String], words: &[String]) {
+    let first   = first_positional(app, words);
+    let options = &words[..first.unwrap_or_else(|| words.len().saturating_sub(1))];
+    let aliases = completion_config(app, options)
+        .and_then(|config| fetch_aliases(Some(&config)).ok())
+        .unwrap_or_default();
+
+    match first {
+        Some(i) if i + 1 < words.len() && app.p.subcommands.iter().all(|s| s.p.meta.name != words[i]) => {
+            let command = aliases.get(&words[i]).cloned().unwrap_or_else(|| words[i].clone());
+            debug!("Forwarding completion to 'imag-{}'", command);
+
+            let _ = Command::new(format!("imag-{}", command))
+                .stdin(Stdio::null())
+                .stdout(Stdio::inherit())
+                .stderr(Stdio::null())
+                .arg(COMPLETE_FLAG)
+                .args(options)
+                .args(&words[i + 1..])
+                .status();
+        },
+
+        _ => {
+            let mut candidates = completion::complete(app, words, None);
+            if first.is_some() && first == words.len().checked_sub(1) {
+                let current = &words[words.len() - 1];
+                candidates.extend(commands
+                    .iter()
+                    .chain(aliases.keys())
+                    .filter(|cmd| cmd.starts_with(current.as_str()))
+                    .cloned());
+            }
+
+            candidates.sort();
+            candidates.dedup();
+
+            let mut out = stdout();
+            for candidate in candidates {
+                let _ = writeln!(out, "{}", candidate);
+            }
+        },
+    }
+}
+
+/// Load the configuration selected by the `imag` options of a completion request
+fn completion_config(app: &App, options: &[String]) -> Option<Value> {
+    let args    = Some(String::from("imag")).into_iter().chain(options.iter().cloned());
+    let matches = app.clone()
+        .unset_setting(AppSettings::ArgRequiredElseHelp)
+        .get_matches_from_safe(args)
+        .ok()?;
+
+    let rtp        = ::libimagrt::runtime::get_rtp_match(&matches).ok()?;
+    let configpath = matches
+        .value_of("config")
+        .map_or_else(|| rtp.clone(), PathBuf::from);
+
+    ::libimagrt::configuration::fetch_config(&configpath).ok()?
+}
+
 fn fetch_aliases(config: Option<&Value>) -> Result<BTreeMap<String, String>, String> {
     let cfg   = config.ok_or_else(|| String::from("No configuration found"))?;
     let value = cfg
@@ -413,7 +521,9 @@ fn forward_commandline_arguments(m: &ArgMatches, scmd: &mut Vec<String>) {
     push(Some("override-config"), "config-override", m , scmd);
     push(Some("rtp"), "runtimepath", m , scmd);
     push(Some("store"), "storepath", m , scmd);
+    push(Some("profile"), "profile", m , scmd);
     push(Some("editor"), "editor", m , scmd);
     push(Some("ignore-ids"), "ignore-ids", m , scmd);
+    push(Some("format"), "output-format", m , scmd);
 }
 
@@ -0,0 +1,294 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! The plugin infos of the found commands (see `libimagrt::plugin`)
+//!
+//! The infos of all commands are only needed for the help text, when dispatching only the info of
+//! the called command is looked up. Asking the commands is still slow, so the infos are cached in
+//! `$XDG_CACHE_HOME/imag/plugins.json` (or `~/.cache/imag/plugins.json`). A command is asked again
+//! when its executable changed.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+use libimagrt::plugin::PluginInfo;
+use libimagrt::plugin::RT_VERSION;
+
+/// The commands of the core distribution
+const CORE_COMMANDS: &[&str] = &[
+    "annotate", "category", "config", "diagnostics", "edit", "git", "gps", "grep", "header",
+    "history", "id-in-collection", "ids", "init", "link", "markdown", "mv", "profile", "ref",
+    "store", "tag", "view",
+];
+
+/// The domain commands of the core distribution
+const DOMAIN_COMMANDS: &[&str] = &[
+    "bookmark", "contact", "diary", "habit", "log", "mail", "notes", "timetrack", "todo", "wiki",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+    Core,
+    Domain,
+    ThirdParty,
+}
+
+impl Category {
+
+    pub fn of(command: &str) -> Category {
+        if CORE_COMMANDS.contains(&command) {
+            Category::Core
+        } else if DOMAIN_COMMANDS.contains(&command) {
+            Category::Domain
+        } else {
+            Category::ThirdParty
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Category::Core       => "Core commands",
+            Category::Domain     => "Domain commands",
+            Category::ThirdParty => "Third party commands",
+        }
+    }
+
+}
+
+/// A command found in $PATH, with its info if it answered the query
+#[derive(Debug)]
+pub struct Plugin {
+    pub command: String,
+    pub info: Option<PluginInfo>,
+}
+
+impl Plugin {
+
+    /// Whether the command was built with a libimagrt which is not compatible with the one of imag
+    pub fn is_incompatible(&self) -> bool {
+        self.info.as_ref().map(|info| !info.is_compatible()).unwrap_or(false)
+    }
+
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cache {
+    commands: BTreeMap<String, CacheEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    path: PathBuf,
+    modified: u64,
+    info: Option<PluginInfo>,
+}
+
+/// Get the infos of `commands`, from the cache or by asking the commands
+///
+/// Commands which are not in `commands` anymore are removed from the cache.
+pub fn load_plugins(commands: &[String]) -> Vec<Plugin> {
+    let cache_path = cache_path();
+    let mut cache  = read_cache(cache_path.as_ref());
+
+    let mut changed = false;
+    let plugins = commands
+        .iter()
+        .map(|command| {
+            let (info, asked) = lookup(&mut cache, command);
+            changed = changed || asked;
+            Plugin { command: command.clone(), info }
+        })
+        .collect::<Vec<_>>();
+
+    let count = cache.commands.len();
+    cache.commands.retain(|command, _| commands.contains(command));
+    changed = changed || count != cache.commands.len();
+
+    if changed {
+        write_cache(cache_path, &cache);
+    }
+
+    plugins
+}
+
+/// Get the info of `command`, from the cache or by asking the command
+pub fn load_plugin(command: &str) -> Plugin {
+    let cache_path    = cache_path();
+    let mut cache     = read_cache(cache_path.as_ref());
+    let (info, asked) = lookup(&mut cache, command);
+
+    if asked {
+        write_cache(cache_path, &cache);
+    }
+
+    Plugin { command: String::from(command), info }
+}
+
+/// Look up the info of `command` in `cache`, ask the command if it is not cached or outdated
+///
+/// Returns whether the cache was changed.
+fn lookup(cache: &mut Cache, command: &str) -> (Option<PluginInfo>, bool) {
+    let path     = find_executable(&format!("imag-{}", command));
+    let modified = path.as_ref().and_then(modification_time);
+
+    let cached = cache.commands
+        .get(command)
+        .filter(|e| Some(&e.path) == path.as_ref() && Some(e.modified) == modified)
+        .map(|e| e.info.clone());
+
+    if let Some(info) = cached {
+        return (info, false)
+    }
+
+    debug!("Asking 'imag-{}' for its plugin info", command);
+    let info = path
+        .as_ref()
+        .and_then(|p| PluginInfo::query(p).unwrap_or(None));
+
+    match (path, modified) {
+        (Some(path), Some(modified)) => {
+            let entry = CacheEntry { path, modified, info: info.clone() };
+            cache.commands.insert(String::from(command), entry);
+            (info, true)
+        },
+        _ => (info, false),
+    }
+}
+
+fn read_cache(path: Option<&PathBuf>) -> Cache {
+    path.and_then(|path| fs::read(path).ok())
+        .and_then(|buf| serde_json::from_slice::<Cache>(&buf).ok())
+        .unwrap_or_default()
+}
+
+fn write_cache(path: Option<PathBuf>, cache: &Cache) {
+    let path = match path {
+        Some(path) => path,
+        None       => return,
+    };
+
+    let written = path
+        .parent()
+        .map(fs::create_dir_all)
+        .unwrap_or(Ok(()))
+        .and_then(|_| {
+            let buf = serde_json::to_vec(cache)?;
+            fs::write(&path, buf)
+        });
+
+    if let Err(e) = written {
+        debug!("Could not write plugin cache {}: {:?}", path.display(), e);
+    }
+}
+
+/// The listing of the commands for the help text, grouped by category
+pub fn help_listing(plugins: &[Plugin]) -> String {
+    let mut categories : BTreeMap<Category, Vec<&Plugin>> = BTreeMap::new();
+    for plugin in plugins {
+        categories.entry(Category::of(&plugin.command)).or_default().push(plugin);
+    }
+
+    let mut listing = String::new();
+    for (category, plugins) in categories {
+        listing.push_str(&format!("    {}:\n\n", category.title()));
+        for plugin in plugins {
+            let description = plugin.info.as_ref().map(PluginInfo::description).unwrap_or("");
+            let line = format!("\t{:<20} {}", plugin.command, description);
+            listing.push_str(line.trim_end());
+            listing.push('\n');
+        }
+        listing.push('\n');
+    }
+
+    let incompatible = plugins.iter().filter(|p| p.is_incompatible()).collect::<Vec<_>>();
+    if !incompatible.is_empty() {
+        listing.push_str("    Warning, these commands were built with an incompatible libimagrt:\n\n");
+        for plugin in incompatible {
+            let rt_version = plugin.info.as_ref().map(PluginInfo::rt_version).unwrap_or("");
+            listing.push_str(&format!("\timag-{} uses libimagrt {}, imag uses {}\n",
+                                      plugin.command, rt_version, RT_VERSION));
+        }
+        listing.push('\n');
+    }
+
+    listing
+}
+
+fn cache_path() -> Option<PathBuf> {
+    env::var("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| env::var("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .ok()
+        .map(|dir| dir.join("imag").join("plugins.json"))
+}
+
+/// Find the executable `name` in $PATH, like the shell would
+fn find_executable(name: &str) -> Option<PathBuf> {
+    env::var("PATH")
+        .ok()?
+        .split(':')
+        .map(|dir| PathBuf::from(dir).join(name))
+        .find(|path| path.is_file())
+}
+
+fn modification_time(path: &PathBuf) -> Option<u64> {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+}
+
+#[cfg(test)]
+mod test {
+    use libimagrt::plugin::PluginInfo;
+
+    use super::*;
+
+    #[test]
+    fn test_categories() {
+        assert_eq!(Category::of("tag"), Category::Core);
+        assert_eq!(Category::of("diary"), Category::Domain);
+        assert_eq!(Category::of("weather"), Category::ThirdParty);
+    }
+
+    #[test]
+    fn test_help_listing() {
+        let info = |json: &str| Some(serde_json::from_str::<PluginInfo>(json).unwrap());
+        let plugins = vec![
+            Plugin {
+                command: String::from("weather"),
+                info: info(r#"{"name":"imag-weather","description":"Weather","version":"0.1.0",
+                               "rt_version":"0.1.0","subcommands":[],"config_sections":[]}"#),
+            },
+            Plugin { command: String::from("tag"), info: None },
+        ];
+
+        let listing = help_listing(&plugins);
+        let core    = listing.find("Core commands").unwrap();
+        let third   = listing.find("Third party commands").unwrap();
+        assert!(core < third);
+        assert!(!listing.contains("Domain commands"));
+        assert!(listing.contains("\tweather              Weather\n"));
+        assert!(listing.contains("imag-weather uses libimagrt 0.1.0"));
+    }
+}
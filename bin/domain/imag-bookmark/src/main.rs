@@ -54,7 +54,7 @@ use toml_query::read::TomlValueReadTypeExt;
 use failure::Error;
 
 use libimagrt::runtime::Runtime;
-use libimagrt::setup::generate_runtime_setup;
+use libimagrt::setup::generate_runtime_setup_with_config;
 use libimagbookmark::collection::BookmarkCollection;
 use libimagbookmark::collection::BookmarkCollectionStore;
 use libimagbookmark::link::Link as BookmarkLink;
@@ -71,10 +71,11 @@ use crate::ui::build_ui;
 
 fn main() {
     let version = make_imag_version!();
-    let rt = generate_runtime_setup("imag-bookmark",
-                                    &version,
-                                    "Bookmark collection tool",
-                                    build_ui);
+    let rt = generate_runtime_setup_with_config("imag-bookmark",
+                                                &version,
+                                                "Bookmark collection tool",
+                                                &["bookmark"],
+                                                build_ui);
 
     rt.cli()
         .subcommand_name()
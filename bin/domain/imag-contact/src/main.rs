@@ -68,11 +68,13 @@ use failure::Error;
 use failure::err_msg;
 
 use libimagrt::runtime::Runtime;
-use libimagrt::setup::generate_runtime_setup;
+use libimagrt::io::EntryRecord;
+use libimagrt::setup::generate_runtime_setup_with_config;
 use libimagerror::trace::MapErrTrace;
 use libimagerror::io::ToExitCode;
 use libimagerror::exit::ExitUnwrap;
 use libimagerror::iter::TraceIterator;
+use libimagstore::storeid::StoreId;
 use libimagcontact::store::ContactStore;
 use libimagcontact::contact::Contact;
 use libimagcontact::deser::DeserVcard;
@@ -89,10 +91,11 @@ use crate::edit::edit;
 
 fn main() {
     let version = make_imag_version!();
-    let rt = generate_runtime_setup("imag-contact",
-                                    &version,
-                                    "Contact management tool",
-                                    build_ui);
+    let rt = generate_runtime_setup_with_config("imag-contact",
+                                                &version,
+                                                "Contact management tool",
+                                                &["contact"],
+                                                build_ui);
 
 
     rt.cli()
@@ -134,13 +137,19 @@ fn list(rt: &Runtime) {
             let _ = rt.report_touched(fle.get_location()).unwrap_or_exit();
             fle
         })
-        .map(|e| e.deser())
+        .map(|e| e.deser().map(|card| (e.get_location().clone(), card)))
         .trace_unwrap_exit()
         .enumerate();
 
-    if scmd.is_present("json") {
+    if rt.output_format().is_structured() {
+        let records = iterator
+            .map(|(_, (id, card))| contact_record(&id, &card))
+            .trace_unwrap_exit();
+
+        rt.write_records(records).map_err_trace_exit_unwrap();
+    } else if scmd.is_present("json") {
         debug!("Listing as JSON");
-        let v : Vec<DeserVcard> = iterator.map(|tpl| tpl.1).collect();
+        let v : Vec<DeserVcard> = iterator.map(|tpl| (tpl.1).1).collect();
 
         match ::serde_json::to_string(&v) {
             Ok(s) => writeln!(rt.stdout(), "{}", s).to_exit_code().unwrap_or_exit(),
@@ -154,7 +163,7 @@ fn list(rt: &Runtime) {
         let output     = rt.stdout();
         let mut output = output.lock();
         iterator
-            .map(|(i, dvcard)| build_data_object_for_handlebars(i, &dvcard))
+            .map(|(i, (_, dvcard))| build_data_object_for_handlebars(i, &dvcard))
             .map(|data| list_format.render("format", &data).map_err(Error::from))
             .trace_unwrap_exit()
             .for_each(|s| {
@@ -221,6 +230,16 @@ fn show(rt: &Runtime) {
     let scmd        = rt.cli().subcommand_matches("show").unwrap();
     let hash        = scmd.value_of("hash").map(String::from).unwrap(); // safed by clap
     let show_format = get_contact_print_format("contact.show_format", rt, &scmd);
+
+    if rt.output_format().is_structured() {
+        let records = util::find_contact_by_hash(rt, hash)
+            .map(|elem| elem.deser().and_then(|card| contact_record(elem.get_location(), &card)))
+            .trace_unwrap_exit();
+
+        rt.write_records(records).map_err_trace_exit_unwrap();
+        return
+    }
+
     let out         = rt.stdout();
     let mut outlock = out.lock();
 
@@ -285,7 +304,13 @@ fn find(rt: &Runtime) {
         })
         .enumerate();
 
-    if !rt.output_is_pipe() || rt.ignore_ids() {
+    if rt.output_format().is_structured() {
+        let records = iterator
+            .map(|(_, (entry, card))| contact_record(entry.get_location(), &card))
+            .trace_unwrap_exit();
+
+        rt.write_records(records).map_err_trace_exit_unwrap();
+    } else if !rt.output_is_pipe() || rt.ignore_ids() {
         if scmd.is_present("json") {
             let v : Vec<DeserVcard> = iterator.map(|(_, tlp)| tlp.1).collect();
 
@@ -336,6 +361,11 @@ fn find(rt: &Runtime) {
     }
 }
 
+/// The contact as a record for `--format`, with the vcard data in the "contact" field
+fn contact_record(id: &StoreId, card: &DeserVcard) -> Result<EntryRecord, Error> {
+    EntryRecord::new(id).with_field("contact", card)
+}
+
 fn get_contact_print_format(config_value_path: &'static str, rt: &Runtime, scmd: &ArgMatches) -> Handlebars {
     let fmt = scmd
         .value_of("format")
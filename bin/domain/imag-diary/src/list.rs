@@ -21,6 +21,7 @@ use std::io::Write;
 
 use libimagdiary::diary::Diary;
 use libimagrt::runtime::Runtime;
+use libimagrt::io::EntryRecord;
 use libimagutil::warn_exit::warn_exit;
 use libimagerror::trace::MapErrTrace;
 use libimagerror::iter::TraceIterator;
@@ -51,6 +52,15 @@ pub fn list(rt: &Runtime) {
         [id.year() as u32, id.month(), id.day(), id.hour(), id.minute(), id.second()]
     });
 
+    if rt.output_format().is_structured() {
+        let records = ids
+            .into_iter()
+            .map(|id| diary_record(rt, id))
+            .trace_unwrap_exit();
+
+        return rt.write_records(records).map_err_trace_exit_unwrap()
+    }
+
     ids.into_iter()
         .map(IntoStoreId::into_storeid)
         .trace_unwrap_exit()
@@ -63,3 +73,16 @@ pub fn list(rt: &Runtime) {
         });
 }
 
+
+/// The diary and the date of an entry, for `--format`
+fn diary_record(rt: &Runtime, id: DiaryId) -> Result<EntryRecord> {
+    let date = format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+                       id.year(), id.month(), id.day(), id.hour(), id.minute(), id.second());
+    let name = id.diary_name().clone();
+    let id   = id.into_storeid()?;
+    let _    = rt.report_touched(&id).unwrap_or_exit();
+
+    EntryRecord::new(&id)
+        .with_field("diary", name)?
+        .with_field("date", date)
+}
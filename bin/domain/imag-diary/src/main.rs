@@ -54,7 +54,7 @@ extern crate libimagutil;
 
 use std::io::Write;
 
-use libimagrt::setup::generate_runtime_setup;
+use libimagrt::setup::generate_runtime_setup_with_config;
 use libimagrt::runtime::Runtime;
 use libimagerror::trace::MapErrTrace;
 
@@ -74,10 +74,11 @@ use crate::view::view;
 
 fn main() {
     let version = make_imag_version!();
-    let rt = generate_runtime_setup("imag-diary",
-                                    &version,
-                                    "Personal Diary/Diaries",
-                                    ui::build_ui);
+    let rt = generate_runtime_setup_with_config("imag-diary",
+                                                &version,
+                                                "Personal Diary/Diaries",
+                                                &["diary"],
+                                                ui::build_ui);
 
     rt.cli()
         .subcommand_name()
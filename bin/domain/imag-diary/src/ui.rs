@@ -26,6 +26,7 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
             .short("d")
             .takes_value(true)
             .required(false)
+            .value_name("DIARY")
             .help("Use other than default diary"))
 
        .subcommand(SubCommand::with_name("diaries")
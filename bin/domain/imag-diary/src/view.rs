@@ -20,6 +20,7 @@
 use libimagdiary::diary::Diary;
 use libimagdiary::viewer::DiaryViewer as DV;
 use libimagrt::runtime::Runtime;
+use libimagrt::io::EntryRecord;
 use libimagerror::trace::MapErrTrace;
 use libimagerror::iter::TraceIterator;
 use libimagerror::exit::ExitUnwrap;
@@ -50,6 +51,19 @@ pub fn view(rt: &Runtime) {
         e
     });
 
+    if rt.output_format().is_structured() {
+        let records = entries.map(|e| {
+            let record = EntryRecord::new(e.get_location()).with_content(e.get_content().to_string());
+            if hdr {
+                record.with_header(e.get_header().clone())
+            } else {
+                record
+            }
+        });
+
+        return rt.write_records(records).map_err_trace_exit_unwrap()
+    }
+
     let out = rt.stdout();
     let mut outlock = out.lock();
 
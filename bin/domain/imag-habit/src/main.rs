@@ -60,6 +60,7 @@ use failure::Error;
 
 use libimagrt::runtime::Runtime;
 use libimagrt::setup::generate_runtime_setup;
+use libimagrt::io::EntryRecord;
 use libimagerror::trace::{MapErrTrace, trace_error};
 use libimagerror::iter::TraceIterator;
 use libimagerror::exit::ExitUnwrap;
@@ -434,8 +435,10 @@ fn list(rt: &Runtime) {
         .map(|s| Cell::new(s))
         .collect::<Vec<Cell>>();
 
-    let mut empty = true;
-    let mut table = Table::new();
+    let structured  = rt.output_format().is_structured();
+    let mut records = vec![];
+    let mut empty   = true;
+    let mut table   = Table::new();
     table.set_titles(Row::new(header));
 
     let _ = rt
@@ -456,23 +459,52 @@ fn list(rt: &Runtime) {
         })
         .enumerate()
         .for_each(|(i, e)| {
-            let mut v = vec![format!("{}", i)];
-            let mut list = lister_fn(&e);
-
             {
                 let _ = rt.report_touched(e.get_location()).unwrap_or_exit();
             }
 
+            if structured {
+                records.push(habit_record(&e));
+                return
+            }
+
+            let mut v = vec![format!("{}", i)];
+            let mut list = lister_fn(&e);
+
             v.append(&mut list);
             table.add_row(v.iter().map(|s| Cell::new(s)).collect());
             empty = false;
         });
 
-    if !empty {
+    if structured {
+        rt.write_records(records).map_err_trace_exit_unwrap();
+    } else if !empty {
         let _ = table.print(&mut rt.stdout()).to_exit_code().unwrap_or_exit();
     }
 }
 
+/// The habit template as record, for `--format`
+fn habit_record(h: &FileLockEntry) -> EntryRecord {
+    let record = EntryRecord::new(h.get_location())
+        .with_field("name", h.habit_name().map_err_trace_exit_unwrap())
+        .and_then(|r| r.with_field("basedate", h.habit_basedate().map_err_trace_exit_unwrap()))
+        .and_then(|r| r.with_field("recurrence", h.habit_recur_spec().map_err_trace_exit_unwrap()))
+        .and_then(|r| r.with_field("comment", h.habit_comment().map_err_trace_exit_unwrap()))
+        .map_err_trace_exit_unwrap();
+
+    // Finished habits have no next due date
+    match h.next_instance_date().map_err_trace_exit_unwrap() {
+        Some(date) => {
+            let done = h.instance_exists_for_date(&date).map_err_trace_exit_unwrap();
+            record
+                .with_field("next_due", date_to_string_helper(date))
+                .and_then(|r| r.with_field("done", done))
+                .map_err_trace_exit_unwrap()
+        },
+        None => record,
+    }
+}
+
 fn show(rt: &Runtime) {
     let scmd = rt.cli().subcommand_matches("show").unwrap();          // safe by call from main()
     let name = scmd
@@ -495,7 +527,9 @@ fn show(rt: &Runtime) {
         .map(|s| Cell::new(s))
         .collect::<Vec<Cell>>();
 
-    let mut table = Table::new();
+    let structured  = rt.output_format().is_structured();
+    let mut records = vec![];
+    let mut table   = Table::new();
     table.set_titles(Row::new(header));
 
     let _ = rt
@@ -507,6 +541,33 @@ fn show(rt: &Runtime) {
         .filter(|h| h.habit_name().map(|n| name == n).map_err_trace_exit_unwrap())
         .enumerate()
         .map(|(i, habit)| {
+            if structured {
+                let record    = habit_record(&habit);
+                let instances = habit
+                    .linked_instances()
+                    .map_err_trace_exit_unwrap()
+                    .trace_unwrap_exit()
+                    .filter_map(|instance_id| rt.store().get(instance_id).map_err_trace_exit_unwrap())
+                    .collect::<Vec<_>>();
+
+                // See below, the template must not be borrowed while the instances are listed
+                drop(habit);
+
+                let instances = instances
+                    .iter()
+                    .map(|instance| {
+                        let mut v = instance_lister_fn(&rt, instance).into_iter();
+                        let mut t = ::std::collections::BTreeMap::new();
+                        let _     = t.insert("date", v.next().unwrap_or_default());
+                        let _     = t.insert("comment", v.next().unwrap_or_default());
+                        t
+                    })
+                    .collect::<Vec<_>>();
+
+                records.push(record.with_field("instances", instances).map_err_trace_exit_unwrap());
+                return
+            }
+
             let name     = habit.habit_name().map_err_trace_exit_unwrap();
             let basedate = habit.habit_basedate().map_err_trace_exit_unwrap();
             let recur    = habit.habit_recur_spec().map_err_trace_exit_unwrap();
@@ -557,6 +618,10 @@ fn show(rt: &Runtime) {
             }
         })
         .collect::<Vec<_>>();
+
+    if structured {
+        rt.write_records(records).map_err_trace_exit_unwrap();
+    }
 }
 
 fn done(rt: &Runtime) {
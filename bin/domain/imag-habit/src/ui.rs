@@ -100,7 +100,7 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                         .multiple(false)
                         .required(true)
                         .takes_value(true)
-                        .value_name("NAME")
+                        .value_name("HABIT")
                         .help("Name of the habit"))
                    )
 
@@ -124,7 +124,7 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                         .multiple(false)
                         .required(true)
                         .takes_value(true)
-                        .value_name("NAME")
+                        .value_name("HABIT")
                         .help("Name of the habit to show"))
                    )
 
@@ -163,7 +163,7 @@ pub fn build_ui<'a>(app: App<'a, 'a>) -> App<'a, 'a> {
                         .multiple(true)
                         .required(true)
                         .takes_value(true)
-                        .value_name("NAME")
+                        .value_name("HABIT")
                         .help("The names of the habits to be marked as done."))
                     )
 }
@@ -58,7 +58,7 @@ use failure::Error;
 use failure::err_msg;
 
 use libimagrt::runtime::Runtime;
-use libimagrt::setup::generate_runtime_setup;
+use libimagrt::setup::generate_runtime_setup_with_config;
 use libimagerror::trace::MapErrTrace;
 use libimagerror::io::ToExitCode;
 use libimagerror::exit::ExitUnwrap;
@@ -78,10 +78,11 @@ use itertools::Itertools;
 
 fn main() {
     let version = make_imag_version!();
-    let rt = generate_runtime_setup("imag-log",
-                                    &version,
-                                    "Overlay to imag-diary to 'log' single lines of text",
-                                    build_ui);
+    let rt = generate_runtime_setup_with_config("imag-log",
+                                                &version,
+                                                "Overlay to imag-diary to 'log' single lines of text",
+                                                &["log"],
+                                                build_ui);
 
 
     if let Some(scmd) = rt.cli() .subcommand_name() {
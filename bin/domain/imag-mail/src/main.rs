@@ -63,7 +63,7 @@ use libimagmail::util;
 use libimagentryref::reference::{Ref, RefFassade};
 use libimagentryref::util::get_ref_config;
 use libimagrt::runtime::Runtime;
-use libimagrt::setup::generate_runtime_setup;
+use libimagrt::setup::generate_runtime_setup_with_config;
 use libimagutil::info_result::*;
 use libimagstore::store::FileLockEntry;
 use libimagstore::storeid::StoreIdIterator;
@@ -75,10 +75,11 @@ use crate::ui::build_ui;
 
 fn main() {
     let version = make_imag_version!();
-    let rt = generate_runtime_setup("imag-mail",
-                                    &version,
-                                    "Mail collection tool",
-                                    build_ui);
+    let rt = generate_runtime_setup_with_config("imag-mail",
+                                                &version,
+                                                "Mail collection tool",
+                                                &["mail", "ref"],
+                                                build_ui);
 
     rt.cli()
         .subcommand_name()
@@ -17,6 +17,8 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
+use std::collections::BTreeMap;
+
 use chrono::NaiveDateTime;
 use filters::filter::Filter;
 use prettytable::Table;
@@ -39,6 +41,8 @@ use libimagtimetrack::store::TimeTrackStore;
 use libimagtimetrack::timetracking::TimeTracking;
 
 use libimagrt::runtime::Runtime;
+use libimagrt::io::EntryRecord;
+use libimagrt::pipe::{PipeRecord, ANNOTATION_TIMESTAMPS};
 
 pub fn list(rt: &Runtime) -> i32 {
     let (_, cmd) = rt.cli().subcommand();
@@ -120,6 +124,16 @@ pub fn list_impl(rt: &Runtime,
 
     let filter = start_time_filter.and(end_time_filter);
 
+    let timetrackings = rt.store()
+        .get_timetrackings()
+        .map_err_trace_exit_unwrap()
+        .trace_unwrap()
+        .filter(|e| filter.filter(e));
+
+    if rt.output_format().is_structured() {
+        return write_records(rt, timetrackings)
+    }
+
     let mut table = Table::new();
     let title_row = if !show_duration {
         Row::new(["Tag", "Start", "End"].iter().map(|s| Cell::new(s)).collect())
@@ -130,11 +144,7 @@ pub fn list_impl(rt: &Runtime,
 
     let mut table_empty = true;
 
-    let table = rt.store()
-        .get_timetrackings()
-        .map_err_trace_exit_unwrap()
-        .trace_unwrap()
-        .filter(|e| filter.filter(e))
+    let table = timetrackings
         .fold(Ok(table), |acc: Result<_>, e| {
             acc.and_then(|mut tab: Table| {
                 debug!("Processing {:?}", e.get_location());
@@ -148,6 +158,12 @@ pub fn list_impl(rt: &Runtime,
                 let end   = e.get_end_datetime()?;
                 debug!(" -> end = {:?}", end);
 
+                let timestamps = start
+                    .map(|s| ("start", format_datetime(&s)))
+                    .into_iter()
+                    .chain(end.map(|e| ("end", format_datetime(&e))))
+                    .collect::<BTreeMap<_, _>>();
+
                 let v = match (start, end) {
                     (None, _)          => {
                         let mut v = vec![String::from(tag.as_str()), String::from(""), String::from("")];
@@ -197,7 +213,9 @@ pub fn list_impl(rt: &Runtime,
                     .collect();
                 tab.add_row(Row::new(cells));
 
-                let _ = rt.report_touched(e.get_location()).unwrap_or_exit();
+                let record = PipeRecord::new(e.get_location().clone())
+                    .with_annotation(ANNOTATION_TIMESTAMPS, timestamps)?;
+                let _ = rt.report_touched_record(record).unwrap_or_exit();
 
                 table_empty = false;
                 Ok(tab)
@@ -217,3 +235,43 @@ pub fn list_impl(rt: &Runtime,
     }
 }
 
+
+/// Write the timetrackings as records, for `--format`
+pub fn write_records<'a, I>(rt: &Runtime, timetrackings: I) -> i32
+    where I: Iterator<Item = FileLockEntry<'a>>
+{
+    let records = timetrackings
+        .map(|e| {
+            let record = timetracking_record(&e)?;
+            let _ = rt.report_touched(e.get_location()).unwrap_or_exit();
+            Ok(record)
+        })
+        .collect::<Result<Vec<_>>>()
+        .map_err_trace_exit_unwrap();
+
+    rt.write_records(records).map(|_| 0).map_err_trace().unwrap_or(1)
+}
+
+/// The tag, start and end of a timetracking, and the duration in seconds if it has ended
+fn timetracking_record(e: &FileLockEntry) -> Result<EntryRecord> {
+    let tag    = e.get_timetrack_tag()?;
+    let start  = e.get_start_datetime()?;
+    let end    = e.get_end_datetime()?;
+
+    let mut record = EntryRecord::new(e.get_location()).with_field("tag", tag.as_str())?;
+    if let Some(ref s) = start {
+        record = record.with_field("start", format_datetime(s))?;
+    }
+    if let Some(ref e) = end {
+        record = record.with_field("end", format_datetime(e))?;
+    }
+    if let (Some(s), Some(e)) = (start, end) {
+        record = record.with_field("duration", (e - s).num_seconds())?;
+    }
+
+    Ok(record)
+}
+
+fn format_datetime(dt: &NaiveDateTime) -> String {
+    dt.format("%Y-%m-%dT%H:%M:%S").to_string()
+}
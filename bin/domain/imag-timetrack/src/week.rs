@@ -100,11 +100,17 @@ pub fn week(rt: &Runtime) -> i32 {
         tags_filter.and(start_time_filter).and(end_time_filter)
     };
 
-    rt.store()
+    let timetrackings = rt.store()
         .get_timetrackings()
         .map_err_trace_exit_unwrap()
         .trace_unwrap()
-        .filter(|e| filter.filter(e))
+        .filter(|e| filter.filter(e));
+
+    if rt.output_format().is_structured() {
+        return crate::list::write_records(rt, timetrackings)
+    }
+
+    timetrackings
         .map(|e| -> Result<_, Error> {
             debug!("Processing {:?}", e.get_location());
 
@@ -76,6 +76,9 @@ pub enum ErrorMsg {
     #[fail(display = "Entry not found: {}", _0)]
     EntryNotFound(String),
 
+    #[fail(display = "Entry is locked by another process: {}", _0)]
+    EntryLocked(String),
+
     #[fail(display = "Entry header error")]
     EntryHeaderError,
 
@@ -0,0 +1,424 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Dynamic shell completion
+//!
+//! Every binary which is set up with `setup::generate_runtime_setup()` answers completion
+//! requests: if it is called as `imag-<cmd> --imag-complete <words>...`, it prints the candidates
+//! for the last of the words (which may be empty) one per line and exits. The shell scripts
+//! printed by `imag completions <shell>` call this for the dispatcher and all `imag-*` commands.
+//!
+//! Static candidates (subcommands, flags, possible values) are taken from the `clap::App` of the
+//! binary. Arguments whose value name is one of the known value names (`ENTRY`, `ID`, `TAG`,
+//! `CATEGORY`, `DIARY`, `WIKI`, `HABIT`, ...) are completed from the store, which is opened with
+//! the global options (`--rtp`, `--config`, `--store`, `--profile`) found in the words. The store
+//! is opened with `Store::new_read_only()`, so completing does not replay journals or touch the
+//! index.
+
+use std::collections::BTreeSet;
+use std::io::Write;
+
+use clap::{App, ArgSettings};
+use toml_query::read::TomlValueReadExt;
+use failure::Fallible as Result;
+
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+
+use crate::runtime::open_store_read_only;
+
+/// The first argument which turns a binary into a completion engine
+pub const COMPLETE_FLAG: &str = "--imag-complete";
+
+/// The global options which select the store and take a value
+const STORE_OPTIONS: &[&str] = &["--config", "--override-config", "--rtp", "--store", "--profile"];
+
+/// Get the words to complete, if the program was called with `COMPLETE_FLAG`
+pub fn completion_request() -> Option<Vec<String>> {
+    let mut args = ::std::env::args().skip(1);
+    if args.next().map(|a| a == COMPLETE_FLAG).unwrap_or(false) {
+        Some(args.collect())
+    } else {
+        None
+    }
+}
+
+/// Print the candidates for the last of `words` to stdout, one per line
+///
+/// `defaults` is the default commandline interface (`Runtime::get_default_cli_builder()`), which
+/// is used to open the store for the dynamic candidates. If the store cannot be opened, only the
+/// static candidates are printed.
+pub fn print_completions<'a>(app: &App<'a, 'a>, defaults: App<'a, 'a>, words: &[String]) {
+    let store   = open_store(defaults, words);
+    let out     = ::std::io::stdout();
+    let mut out = out.lock();
+
+    for candidate in complete(app, words, store.as_ref()) {
+        let _ = writeln!(out, "{}", candidate);
+    }
+}
+
+/// Compute the candidates for the last of `words`, the words following the binary name
+///
+/// Without a store, arguments which are completed from the store yield no candidates.
+pub fn complete(app: &App, words: &[String], store: Option<&Store>) -> Vec<String> {
+    let (current, previous) = match words.split_last() {
+        Some((current, previous)) => (current.as_str(), previous),
+        None                      => ("", words),
+    };
+
+    let mut command     = app;
+    let mut positional  = 0;
+    let mut pending     = None;
+    let mut values_only = false;
+
+    for word in previous {
+        if pending.take().is_some() {
+            continue
+        }
+
+        if values_only || word == "-" || !word.starts_with('-') {
+            match find_subcommand(command, word) {
+                Some(sub) if !values_only => {
+                    command    = sub;
+                    positional = 0;
+                },
+                _ => positional += 1,
+            }
+        } else if word == "--" {
+            values_only = true;
+        } else if word.starts_with("--") {
+            if !word.contains('=') {
+                pending = find_long_option(command, &word[2..]);
+            }
+        } else {
+            // A cluster of short flags. If an option in it is not the last character, the rest
+            // of the cluster is its value
+            let shorts = word.chars().skip(1).collect::<Vec<_>>();
+            pending = shorts
+                .iter()
+                .position(|c| find_short_option(command, *c).is_some())
+                .filter(|pos| *pos == shorts.len() - 1)
+                .and_then(|pos| find_short_option(command, shorts[pos]));
+        }
+    }
+
+    let mut candidates = BTreeSet::new();
+    if let Some(arg) = pending {
+        candidates.extend(arg.candidates(store));
+    } else if current.starts_with('-') && !values_only {
+        candidates.extend(long_flags(command));
+    } else {
+        if !values_only {
+            candidates.extend(command.p.subcommands.iter().map(|s| s.p.meta.name.clone()));
+        }
+
+        if let Some(arg) = find_positional(command, positional) {
+            candidates.extend(arg.candidates(store));
+        }
+    }
+
+    candidates.into_iter().filter(|c| c.starts_with(current)).collect()
+}
+
+/// The index of the first word which is neither an option of `app` nor the value of one
+///
+/// This is the subcommand the words are meant for, if there is one.
+pub fn first_positional(app: &App, words: &[String]) -> Option<usize> {
+    let mut skip_next = false;
+    for (i, word) in words.iter().enumerate() {
+        if skip_next {
+            skip_next = false;
+        } else if word.starts_with("--") && !word.contains('=') {
+            skip_next = find_long_option(app, &word[2..]).is_some();
+        } else if word.starts_with('-') && word.len() == 2 {
+            skip_next = word.chars().nth(1).and_then(|c| find_short_option(app, c)).is_some();
+        } else if !word.starts_with('-') {
+            return Some(i)
+        }
+    }
+    None
+}
+
+/// What is known about an argument which takes a value
+struct ValueArg {
+    value_name: String,
+    possible_values: Vec<String>,
+}
+
+impl ValueArg {
+
+    fn candidates(&self, store: Option<&Store>) -> Vec<String> {
+        if !self.possible_values.is_empty() {
+            return self.possible_values.clone()
+        }
+
+        match (ValueHint::from_value_name(&self.value_name), store) {
+            // Completion must not fail because of a broken entry, so errors are ignored
+            (Some(hint), Some(store)) => hint.candidates(store).unwrap_or_default(),
+            _ => vec![],
+        }
+    }
+}
+
+/// The kinds of values which are completed from the store
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ValueHint {
+    StoreId,
+    Tag,
+    Category,
+    Diary,
+    Wiki,
+    Habit,
+}
+
+impl ValueHint {
+
+    fn from_value_name(name: &str) -> Option<ValueHint> {
+        match name {
+            "ENTRY" | "ENTRIES" | "ID" | "IDS" | "IDs" => Some(ValueHint::StoreId),
+            "TAG" | "TAGS"                             => Some(ValueHint::Tag),
+            "CATEGORY"                                 => Some(ValueHint::Category),
+            "DIARY"                                    => Some(ValueHint::Diary),
+            "WIKI"                                     => Some(ValueHint::Wiki),
+            "HABIT"                                    => Some(ValueHint::Habit),
+            _                                          => None,
+        }
+    }
+
+    fn candidates(self, store: &Store) -> Result<Vec<String>> {
+        let ids = store.entries()?.into_storeid_iter().collect::<Result<Vec<StoreId>>>()?;
+
+        match self {
+            ValueHint::StoreId  => Ok(ids.iter().map(StoreId::local_display_string).collect()),
+            ValueHint::Category => Ok(names_in_collection(&ids, &["category"])),
+            ValueHint::Diary    => Ok(names_in_collection(&ids, &["diary"])),
+            ValueHint::Wiki     => Ok(names_in_collection(&ids, &["wiki"])),
+            ValueHint::Habit    => Ok(names_in_collection(&ids, &["habit", "template"])),
+            ValueHint::Tag      => {
+                let mut tags = BTreeSet::new();
+                for id in ids {
+                    // get_copy() does not write the entry back, as dropping a FileLockEntry would
+                    let entry = store.get_copy(id)?;
+                    if let Some(values) = entry.get_header().read("tag.values")? {
+                        if let Some(values) = values.as_array() {
+                            tags.extend(values.iter().filter_map(|v| v.as_str()).map(String::from));
+                        }
+                    }
+                }
+                Ok(tags.into_iter().collect())
+            },
+        }
+    }
+}
+
+/// The names of the things in `collection`, which are the id components right after it
+fn names_in_collection(ids: &[StoreId], collection: &[&str]) -> Vec<String> {
+    ids.iter()
+        .map(StoreId::local_display_string)
+        .filter_map(|id| {
+            let components = id.split('/').collect::<Vec<_>>();
+            if components.len() > collection.len() && components.starts_with(collection) {
+                Some(components[collection.len()].to_string())
+            } else {
+                None
+            }
+        })
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+// clap 2 has no public API to inspect an `App`, so the helpers below use the parser internals,
+// which are public but hidden from the documentation.
+
+macro_rules! value_arg {
+    ($arg:expr) => {
+        value_arg($arg.b.name,
+                  $arg.v.val_names.as_ref().and_then(|names| names.values().next().cloned()),
+                  $arg.v.possible_vals.as_ref())
+    }
+}
+
+fn find_subcommand<'x, 'a, 'b>(app: &'x App<'a, 'b>, name: &str) -> Option<&'x App<'a, 'b>> {
+    app.p.subcommands.iter().find(|sub| {
+        sub.p.meta.name == name || sub.p.meta
+            .aliases
+            .as_ref()
+            .map(|aliases| aliases.iter().any(|&(alias, _)| alias == name))
+            .unwrap_or(false)
+    })
+}
+
+fn find_long_option(app: &App, long: &str) -> Option<ValueArg> {
+    app.p.opts
+        .iter()
+        .find(|o| o.s.long == Some(long))
+        .map(|o| value_arg!(o))
+}
+
+fn find_short_option(app: &App, short: char) -> Option<ValueArg> {
+    app.p.opts
+        .iter()
+        .find(|o| o.s.short == Some(short))
+        .map(|o| value_arg!(o))
+}
+
+/// The positional argument which takes the value at `index`, counted from zero
+fn find_positional(app: &App, index: usize) -> Option<ValueArg> {
+    let positionals = app.p.positionals.values().collect::<Vec<_>>();
+    positionals
+        .iter()
+        .find(|p| p.index == index as u64 + 1)
+        .or_else(|| positionals.last().filter(|p| p.b.is_set(ArgSettings::Multiple)))
+        .map(|p| value_arg!(p))
+}
+
+fn long_flags(app: &App) -> Vec<String> {
+    let flags = app.p.flags
+        .iter()
+        .filter(|f| !f.b.is_set(ArgSettings::Hidden))
+        .filter_map(|f| f.s.long);
+
+    let opts = app.p.opts
+        .iter()
+        .filter(|o| !o.b.is_set(ArgSettings::Hidden))
+        .filter_map(|o| o.s.long);
+
+    flags.chain(opts)
+        .chain(vec!["help", "version"])
+        .map(|long| format!("--{}", long))
+        .collect()
+}
+
+fn value_arg(name: &str, value_name: Option<&str>, possible_values: Option<&Vec<&str>>)
+    -> ValueArg
+{
+    ValueArg {
+        value_name: value_name.map(String::from).unwrap_or_else(|| name.to_uppercase()),
+        possible_values: possible_values
+            .map(|vals| vals.iter().map(|v| v.to_string()).collect())
+            .unwrap_or_default(),
+    }
+}
+
+/// Open the store selected by the global options in `words`
+fn open_store<'a>(defaults: App<'a, 'a>, words: &[String]) -> Option<Store> {
+    let mut args = vec![defaults.get_name().to_string()];
+    let mut iter = words.iter().take(words.len().saturating_sub(1));
+    while let Some(word) = iter.next() {
+        if STORE_OPTIONS.iter().any(|o| word.starts_with(o) && word[o.len()..].starts_with('=')) {
+            args.push(word.clone());
+        } else if STORE_OPTIONS.contains(&word.as_str()) {
+            if let Some(value) = iter.next() {
+                args.push(word.clone());
+                args.push(value.clone());
+            }
+        }
+    }
+
+    let matches = defaults.get_matches_from_safe(args).unwrap_or_default();
+    open_store_read_only(&matches).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use clap::{App, Arg, SubCommand};
+    use toml_query::insert::TomlValueInsertExt;
+
+    use libimagstore::store::Store;
+
+    use super::*;
+
+    fn app() -> App<'static, 'static> {
+        App::new("imag-test")
+            .arg(Arg::with_name("verbosity")
+                 .long("verbose")
+                 .short("v")
+                 .takes_value(true)
+                 .possible_values(&["debug", "info"]))
+            .arg(Arg::with_name("debugging").long("debug"))
+            .subcommand(SubCommand::with_name("add")
+                        .alias("new")
+                        .arg(Arg::with_name("id").index(1).value_name("ENTRY"))
+                        .arg(Arg::with_name("tags").index(2).multiple(true).value_name("TAGS")))
+            .subcommand(SubCommand::with_name("list"))
+    }
+
+    fn words(ws: &[&str]) -> Vec<String> {
+        ws.iter().map(|w| w.to_string()).collect()
+    }
+
+    fn store() -> Store {
+        use std::path::PathBuf;
+        let store = Store::new_inmemory(PathBuf::from("/"), &None).unwrap();
+        let mut a = store.create(PathBuf::from("diary/work/2019/01/01")).unwrap();
+        let _ = a.get_header_mut().insert("tag.values", toml::Value::Array(vec![
+            toml::Value::String("foo".into()),
+            toml::Value::String("bar".into()),
+        ]));
+        drop(a);
+        let _ = store.create(PathBuf::from("diary/home/2019/01/02")).unwrap();
+        let _ = store.create(PathBuf::from("notes/n")).unwrap();
+        store
+    }
+
+    #[test]
+    fn test_static_candidates() {
+        let app = app();
+        assert_eq!(complete(&app, &words(&[""]), None), words(&["add", "list"]));
+        assert_eq!(complete(&app, &words(&["a"]), None), words(&["add"]));
+        assert_eq!(complete(&app, &words(&["--d"]), None), words(&["--debug"]));
+        assert_eq!(complete(&app, &words(&["--verbose", ""]), None), words(&["debug", "info"]));
+        assert_eq!(complete(&app, &words(&["-v", "i"]), None), words(&["info"]));
+        assert_eq!(complete(&app, &words(&["--debug", "l"]), None), words(&["list"]));
+        assert_eq!(complete(&app, &words(&["new", "--"]), None), words(&["--help", "--version"]));
+    }
+
+    #[test]
+    fn test_dynamic_candidates() {
+        let store = store();
+        let app   = app();
+
+        let ids = complete(&app, &words(&["add", ""]), Some(&store));
+        assert!(ids.contains(&"notes/n".to_string()));
+        assert_eq!(ids.len(), 3);
+
+        let tags = complete(&app, &words(&["add", "notes/n", "b"]), Some(&store));
+        assert_eq!(tags, words(&["bar"]));
+
+        let tags = complete(&app, &words(&["add", "notes/n", "bar", ""]), Some(&store));
+        assert_eq!(tags, words(&["bar", "foo"]));
+    }
+
+    #[test]
+    fn test_names_in_collection() {
+        let store = store();
+        assert_eq!(ValueHint::Diary.candidates(&store).unwrap(), words(&["home", "work"]));
+        assert!(ValueHint::Wiki.candidates(&store).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_first_positional() {
+        let app = app();
+        assert_eq!(first_positional(&app, &words(&["-v", "info", "add", "x"])), Some(2));
+        assert_eq!(first_positional(&app, &words(&["--debug", "list"])), Some(1));
+        assert_eq!(first_positional(&app, &words(&["--verbose=info"])), None);
+    }
+}
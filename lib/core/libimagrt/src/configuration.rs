@@ -34,38 +34,15 @@ use libimagerror::errors::ErrorMsg as EM;
 /// names are tested. If that does not work, the home directory and the XDG basedir are tested
 /// with all variants.
 pub fn fetch_config(searchpath: &PathBuf) -> Result<Option<Value>> {
-    use std::env;
     use std::fs::File;
     use std::io::Read;
     use std::io::Write;
     use std::io::stderr;
 
-    use libimagutil::variants::generate_variants as gen_vars;
     use libimagerror::trace::trace_error;
 
-    let variants : Vec<&'static str> = vec!["config", "config.toml", "imagrc", "imagrc.toml"];
-    let modifier = |base: &PathBuf, v: &&str| {
-        let mut base = base.clone();
-        base.push(String::from(*v));
-        base
-    };
-
-    let vals = vec![
-        vec![searchpath.clone()],
-        gen_vars(searchpath, variants.iter(), &modifier),
-
-        env::var("HOME")
-            .map(|home| gen_vars(&PathBuf::from(home), variants.iter(), &modifier))
-            .unwrap_or(vec![]),
-
-        xdg_basedir::get_data_home()
-            .map(|data_dir| gen_vars(&data_dir, variants.iter(), &modifier))
-            .unwrap_or(vec![]),
-    ];
-
-    let config = vals
+    let config = config_file_candidates(searchpath)
         .iter()
-        .flatten()
         .filter(|path| path.exists() && path.is_file())
         .filter_map(|path| {
             let content = {
@@ -100,6 +77,42 @@ pub fn fetch_config(searchpath: &PathBuf) -> Result<Option<Value>> {
     Ok(config)
 }
 
+/// Get the path of the configuration file `fetch_config()` reads for `searchpath`.
+pub fn find_config_file(searchpath: &PathBuf) -> Option<PathBuf> {
+    config_file_candidates(searchpath)
+        .into_iter()
+        .find(|path| path.exists() && path.is_file())
+}
+
+/// The paths which are searched for the configuration file, in order
+fn config_file_candidates(searchpath: &PathBuf) -> Vec<PathBuf> {
+    use std::env;
+
+    use libimagutil::variants::generate_variants as gen_vars;
+
+    let variants : Vec<&'static str> = vec!["config", "config.toml", "imagrc", "imagrc.toml"];
+    let modifier = |base: &PathBuf, v: &&str| {
+        let mut base = base.clone();
+        base.push(String::from(*v));
+        base
+    };
+
+    let vals = vec![
+        vec![searchpath.clone()],
+        gen_vars(searchpath, variants.iter(), &modifier),
+
+        env::var("HOME")
+            .map(|home| gen_vars(&PathBuf::from(home), variants.iter(), &modifier))
+            .unwrap_or(vec![]),
+
+        xdg_basedir::get_data_home()
+            .map(|data_dir| gen_vars(&data_dir, variants.iter(), &modifier))
+            .unwrap_or(vec![]),
+    ];
+
+    vals.into_iter().flatten().collect()
+}
+
 /// Override the configuration.
 /// The `v` parameter is expected to contain 'key=value' pairs where the key is a path in the
 /// TOML tree, the value to be an appropriate value.
@@ -18,10 +18,135 @@
 //
 
 //! Proxy objects for std::io::Stdin, std::io::Stdout, std::io::Stderr
+//!
+//! This module also contains the structured output of imag commands: With `--format json`,
+//! `--format jsonl` or `--format toml`, commands which support it write their results as records
+//! (see `Runtime::write_records()`) instead of formatted text.
 
+use std::collections::BTreeMap;
 use std::fmt::Debug;
 use std::io::Write;
 
+use serde::Serialize;
+use serde::Serializer;
+use serde::ser::SerializeMap;
+use toml::Value;
+use failure::Fallible as Result;
+use failure::ResultExt;
+use failure::Error;
+
+use libimagerror::errors::ErrorMsg as EM;
+use libimagstore::store::Entry;
+use libimagstore::storeid::StoreId;
+
+/// The format of the output of imag commands, selected with `--format`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable text, the default
+    Text,
+
+    /// One JSON array with all records
+    Json,
+
+    /// One JSON object per line and record
+    JsonLines,
+
+    /// One TOML document with all records in the `record` array of tables
+    Toml,
+}
+
+impl OutputFormat {
+
+    /// The names which can be passed to `--format`
+    pub fn names() -> &'static [&'static str] {
+        &["text", "json", "jsonl", "toml"]
+    }
+
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        match name {
+            "text"  => Some(OutputFormat::Text),
+            "json"  => Some(OutputFormat::Json),
+            "jsonl" => Some(OutputFormat::JsonLines),
+            "toml"  => Some(OutputFormat::Toml),
+            _       => None,
+        }
+    }
+
+    /// Whether records are written instead of text
+    pub fn is_structured(&self) -> bool {
+        *self != OutputFormat::Text
+    }
+
+}
+
+/// A record describing an entry
+///
+/// Serializes to an object with the `id` of the entry, optionally its `header` and `content`, and
+/// the command specific fields added with `EntryRecord::with_field()`.
+#[derive(Debug, Clone)]
+pub struct EntryRecord {
+    id: String,
+    header: Option<Value>,
+    content: Option<String>,
+    fields: BTreeMap<String, Value>,
+}
+
+impl EntryRecord {
+
+    pub fn new(id: &StoreId) -> EntryRecord {
+        EntryRecord {
+            id: id.local_display_string(),
+            header: None,
+            content: None,
+            fields: BTreeMap::new(),
+        }
+    }
+
+    /// A record with the id, the header and the content of `entry`
+    pub fn from_entry(entry: &Entry) -> EntryRecord {
+        EntryRecord::new(entry.get_location())
+            .with_header(entry.get_header().clone())
+            .with_content(entry.get_content().to_string())
+    }
+
+    pub fn with_header(mut self, header: Value) -> Self {
+        self.header = Some(header);
+        self
+    }
+
+    pub fn with_content(mut self, content: String) -> Self {
+        self.content = Some(content);
+        self
+    }
+
+    /// Add a command specific field
+    ///
+    /// Fails if the value cannot be represented in TOML (for example `None`).
+    pub fn with_field<V: Serialize>(mut self, name: &str, value: V) -> Result<Self> {
+        let value = Value::try_from(value).context(format_err!("Cannot serialize field '{}'", name))?;
+        self.fields.insert(String::from(name), value);
+        Ok(self)
+    }
+
+}
+
+impl Serialize for EntryRecord {
+    fn serialize<S: Serializer>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("id", &self.id)?;
+        for (name, value) in self.fields.iter() {
+            map.serialize_entry(name, value)?;
+        }
+        if let Some(ref content) = self.content {
+            map.serialize_entry("content", content)?;
+        }
+        if let Some(ref header) = self.header {
+            map.serialize_entry("header", header)?;
+        }
+        map.end()
+    }
+}
+
 /// Proxy object for output
 ///
 /// This is returned by `Runtime::stdout()` does implement `Write`. So you can
@@ -56,7 +181,7 @@ impl Write for OutputProxy {
 }
 
 impl Debug for OutputProxy {
-    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> Result<(), ::std::fmt::Error> {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::result::Result<(), ::std::fmt::Error> {
         match *self {
             OutputProxy::Out(..) => write!(f, "OutputProxy(Stdout)"),
             OutputProxy::Err(..) => write!(f, "OutputProxy(Stderr)"),
@@ -73,6 +198,60 @@ impl OutputProxy {
             OutputProxy::Sink       => LockedOutputProxy::Sink,
         }
     }
+
+    /// Write `records` in the structured `format`
+    ///
+    /// Fails for `OutputFormat::Text`, as there is no generic text representation of a record.
+    pub fn write_records<R, I>(&mut self, format: OutputFormat, records: I) -> Result<()>
+        where R: Serialize,
+              I: IntoIterator<Item = R>
+    {
+        let text = format_records(format, records)?;
+        self.write_all(text.as_bytes())
+            .and_then(|_| self.flush())
+            .context(EM::IO)
+            .map_err(Error::from)
+    }
+}
+
+/// Render `records` in the structured `format`
+pub fn format_records<R, I>(format: OutputFormat, records: I) -> Result<String>
+    where R: Serialize,
+          I: IntoIterator<Item = R>
+{
+    match format {
+        OutputFormat::Text => Err(format_err!("Records cannot be written as text")),
+
+        OutputFormat::Json => {
+            let records = records.into_iter().collect::<Vec<_>>();
+            ::serde_json::to_string_pretty(&records)
+                .map(|s| s + "\n")
+                .context(format_err!("Cannot serialize records to JSON"))
+                .map_err(Error::from)
+        },
+
+        OutputFormat::JsonLines => records
+            .into_iter()
+            .map(|record| ::serde_json::to_string(&record).map(|s| s + "\n"))
+            .collect::<::std::result::Result<String, _>>()
+            .context(format_err!("Cannot serialize records to JSON"))
+            .map_err(Error::from),
+
+        // Going through toml::Value, which orders the values before the tables
+        OutputFormat::Toml => {
+            let records = records
+                .into_iter()
+                .map(Value::try_from)
+                .collect::<::std::result::Result<Vec<_>, _>>()
+                .context(format_err!("Cannot serialize records to TOML"))?;
+
+            let mut document = ::toml::map::Map::new();
+            document.insert(String::from("record"), Value::Array(records));
+            ::toml::ser::to_string(&Value::Table(document))
+                .context(format_err!("Cannot serialize records to TOML"))
+                .map_err(Error::from)
+        },
+    }
 }
 
 pub enum LockedOutputProxy<'a> {
@@ -101,7 +280,7 @@ impl<'a> Write for LockedOutputProxy<'a> {
 }
 
 impl<'a> Debug for LockedOutputProxy<'a> {
-    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> Result<(), ::std::fmt::Error> {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::result::Result<(), ::std::fmt::Error> {
         match *self {
             LockedOutputProxy::Out(..) => write!(f, "LockedOutputProxy(Stdout)"),
             LockedOutputProxy::Err(..) => write!(f, "LockedOutputProxy(Stderr)"),
@@ -110,3 +289,39 @@ impl<'a> Debug for LockedOutputProxy<'a> {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn records() -> Vec<EntryRecord> {
+        let id = StoreId::new(PathBuf::from("test/entry")).unwrap();
+        vec![
+            EntryRecord::new(&id).with_field("tags", vec!["a", "b"]).unwrap(),
+            EntryRecord::new(&id).with_content(String::from("content")),
+        ]
+    }
+
+    #[test]
+    fn test_jsonl() {
+        let text = format_records(OutputFormat::JsonLines, records()).unwrap();
+        assert_eq!(text, "{\"id\":\"test/entry\",\"tags\":[\"a\",\"b\"]}\n\
+                          {\"id\":\"test/entry\",\"content\":\"content\"}\n");
+    }
+
+    #[test]
+    fn test_json_and_toml_roundtrip() {
+        let json: ::serde_json::Value = ::serde_json::from_str(&format_records(OutputFormat::Json, records()).unwrap()).unwrap();
+        assert_eq!(json[0]["tags"][1], "b");
+        assert_eq!(json[1]["content"], "content");
+
+        let toml: Value = ::toml::de::from_str(&format_records(OutputFormat::Toml, records()).unwrap()).unwrap();
+        let records = toml.get("record").and_then(Value::as_array).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].get("id").and_then(Value::as_str), Some("test/entry"));
+
+        assert!(format_records(OutputFormat::Text, records.iter()).is_err());
+    }
+}
@@ -42,6 +42,7 @@ extern crate itertools;
 extern crate ansi_term;
 extern crate handlebars;
 extern crate serde;
+extern crate serde_json;
 #[macro_use] extern crate serde_derive;
 #[macro_use] extern crate failure;
 #[macro_use] extern crate toml_query;
@@ -49,16 +50,23 @@ extern crate serde;
 extern crate clap;
 extern crate toml;
 extern crate atty;
+extern crate chrono;
+#[cfg(test)] extern crate tempdir;
 
 extern crate libimagstore;
 extern crate libimagutil;
 extern crate libimagerror;
 extern crate libimaginteraction;
 
+pub mod completion;
 pub mod configuration;
 pub mod logger;
+pub mod profile;
 pub mod io;
+pub mod pipe;
+pub mod plugin;
 pub mod runtime;
+pub mod schema;
 pub mod setup;
 pub mod spec;
 pub mod version;
@@ -20,6 +20,9 @@
 use std::io::Write;
 use std::io::stderr;
 use std::collections::BTreeMap;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::ops::Deref;
@@ -29,6 +32,9 @@ use failure::Fallible as Result;
 use failure::Error;
 use failure::err_msg;
 use clap::ArgMatches;
+use chrono::DateTime;
+use chrono::Local;
+use chrono::NaiveDate;
 use log::{Log, Level, Record, Metadata};
 use toml::Value;
 use toml_query::read::TomlValueReadExt;
@@ -37,12 +43,15 @@ use handlebars::Handlebars;
 
 use libimagerror::errors::ErrorMsg as EM;
 
+use crate::schema::ConfigKey;
+use crate::schema::ConfigSchema;
+
 type ModuleName = String;
 
 #[derive(Debug)]
 enum LogDestination {
     Stderr,
-    File(Arc<Mutex<::std::fs::File>>),
+    File(Arc<Mutex<LogFile>>),
 }
 
 impl Default for LogDestination {
@@ -51,12 +60,107 @@ impl Default for LogDestination {
     }
 }
 
+/// When a log file is rotated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rotation {
+    /// Before the file grows over this many bytes
+    Size(u64),
+
+    /// Before the first line of a new day is written
+    Daily,
+}
+
+/// A log file, which is optionally rotated
+///
+/// Rotating moves `imag.log` to `imag.log.1`, `imag.log.1` to `imag.log.2` and so on, keeping at
+/// most `keep` old files.
+#[derive(Debug)]
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    rotation: Option<Rotation>,
+    keep: usize,
+    size: u64,
+    day: NaiveDate,
+}
+
+impl LogFile {
+
+    fn open(path: PathBuf, rotation: Option<Rotation>, keep: usize) -> Result<LogFile> {
+        let file = open_append(&path)?;
+        let meta = file.metadata().context(EM::IO)?;
+        let day  = meta
+            .modified()
+            .map(|time| DateTime::<Local>::from(time).naive_local().date())
+            .unwrap_or_else(|_| today());
+
+        Ok(LogFile { path, file, rotation, keep, size: meta.len(), day })
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.needs_rotation(len) {
+            let _ = self.rotate()?;
+        }
+
+        writeln!(self.file, "{}", line).context(EM::IO)?;
+        self.size += len;
+        Ok(())
+    }
+
+    fn needs_rotation(&self, len: u64) -> bool {
+        self.size > 0 && match self.rotation {
+            Some(Rotation::Size(max)) => self.size + len > max,
+            Some(Rotation::Daily)     => self.day != today(),
+            None                      => false,
+        }
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        let rotated = |n: usize| {
+            let mut path = self.path.clone().into_os_string();
+            path.push(format!(".{}", n));
+            PathBuf::from(path)
+        };
+
+        if self.keep == 0 {
+            let _ = ::std::fs::remove_file(&self.path).context(EM::IO)?;
+        } else {
+            for n in (1..self.keep).rev() {
+                if rotated(n).exists() {
+                    let _ = ::std::fs::rename(rotated(n), rotated(n + 1)).context(EM::IO)?;
+                }
+            }
+            let _ = ::std::fs::rename(&self.path, rotated(1)).context(EM::IO)?;
+        }
+
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+        self.day  = today();
+        Ok(())
+    }
+
+}
+
+fn open_append(path: &PathBuf) -> Result<File> {
+    OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .context(format_err!("Failed to open log file {}", path.display()))
+        .map_err(Error::from)
+}
+
+fn today() -> NaiveDate {
+    Local::now().naive_local().date()
+}
+
 #[derive(Debug)]
 struct ModuleSettings {
     enabled:        bool,
     level:          Option<Level>,
 
-    #[allow(unused)]
+    /// Replace the global destinations for the module, if set
     destinations:   Option<Vec<LogDestination>>,
 }
 
@@ -64,8 +168,6 @@ struct ModuleSettings {
 #[derive(Debug)]
 pub struct ImagLogger {
     global_loglevel     : Level,
-
-    #[allow(unused)]
     global_destinations : Vec<LogDestination>,
     // global_format_trace : ,
     // global_format_debug : ,
@@ -147,9 +249,7 @@ impl Log for ImagLogger {
                 let _ = arc_mutex_logdest
                     .deref()
                     .lock()
-                    .map(|mut logdest| {
-                        write!(logdest, "{}\n", logtext)
-                    });
+                    .map(|mut logdest| logdest.write_line(&logtext));
             }
         };
 
@@ -169,12 +269,13 @@ impl Log for ImagLogger {
                     module_setting.level.unwrap_or(self.global_loglevel) >= record.level();
 
                 if set {
-                    module_setting.destinations.as_ref().map(|destinations| for d in destinations {
-                        // If there's an error, we cannot do anything, can we?
-                        let _ = log_to_destination(&d);
-                    });
+                    // The destinations of the module replace the global ones
+                    let destinations = module_setting
+                        .destinations
+                        .as_ref()
+                        .unwrap_or(&self.global_destinations);
 
-                    for d in self.global_destinations.iter() {
+                    for d in destinations.iter() {
                         // If there's an error, we cannot do anything, can we?
                         let _ = log_to_destination(&d);
                     }
@@ -241,37 +342,55 @@ fn aggregate_global_loglevel(matches: &ArgMatches, config: Option<&Value>) -> Re
     }
 }
 
-fn translate_destination(raw: &str) -> Result<LogDestination> {
-    use std::fs::OpenOptions;
-
-    match raw {
-        "-" => Ok(LogDestination::Stderr),
-        other => {
-            OpenOptions::new()
-                .append(true)
-                .create(true)
-                .open(other)
-                .map(Mutex::new)
-                .map(Arc::new)
-                .map(LogDestination::File)
-                .map_err(Error::from)
-                .context(EM::IO)
-                .map_err(Error::from)
-        }
+/// Translate a destination from the configuration
+///
+/// A destination is either `"-"` for stderr, the path of a file, or a table for a rotated file:
+///
+/// ```toml
+/// { file = "/home/user/.imag.log", rotate = "size", max-size = 1048576, keep = 5 }
+/// ```
+///
+/// `rotate` is either "size" or "daily".
+fn translate_destination(raw: &Value) -> Result<LogDestination> {
+    #[derive(Deserialize, Debug)]
+    #[serde(rename_all = "kebab-case", deny_unknown_fields)]
+    struct FileDestination {
+        file: PathBuf,
+        rotate: Option<String>,
+        max_size: Option<u64>,
+        keep: Option<usize>,
+    }
+
+    let file = |path, rotation, keep| LogFile::open(path, rotation, keep)
+        .map(Mutex::new)
+        .map(Arc::new)
+        .map(LogDestination::File);
+
+    match *raw {
+        Value::String(ref s) if s == "-" => Ok(LogDestination::Stderr),
+        Value::String(ref s) => file(PathBuf::from(s), None, 0),
+        Value::Table(_) => {
+            let dest : FileDestination = raw
+                .clone()
+                .try_into()
+                .context(err_msg("Invalid file destination in logging configuration"))?;
+
+            let rotation = match dest.rotate.as_ref().map(String::as_str) {
+                None          => None,
+                Some("size")  => Some(Rotation::Size(dest.max_size.unwrap_or(1024 * 1024))),
+                Some("daily") => Some(Rotation::Daily),
+                Some(other)   => return Err(format_err!("Invalid log rotation '{}', expected 'size' or 'daily'", other)),
+            };
+
+            file(dest.file, rotation, dest.keep.unwrap_or(5))
+        },
+        _ => Err(err_msg("Type error in logging destinations, expected String or Table")),
     }
 }
 
 
 fn translate_destinations(raw: &Vec<Value>) -> Result<Vec<LogDestination>> {
-    raw.iter()
-        .map(|val| {
-            val.as_str()
-                .ok_or_else(|| "Type error at 'imag.logging.modules.<mod>.destinations', expected Array<String>")
-                .map_err(err_msg)
-                .map_err(Error::from)
-                .and_then(|s| translate_destination(s))
-        })
-        .collect()
+    raw.iter().map(translate_destination).collect()
 }
 
 fn aggregate_global_destinations(config: Option<&Value>)
@@ -361,7 +480,7 @@ fn aggregate_module_settings(_matches: &ArgMatches, config: Option<&Value>)
 
     #[derive(Serialize, Deserialize, Debug)]
     struct LoggingModuleConfig {
-        pub destinations: Option<Vec<String>>,
+        pub destinations: Option<Vec<Value>>,
         pub level: Option<Level>,
         pub enabled: bool,
     }
@@ -380,13 +499,10 @@ fn aggregate_module_settings(_matches: &ArgMatches, config: Option<&Value>)
                 map.insert(key, ModuleSettings {
                     enabled:      value.enabled,
                     level:        value.level.map(Into::into),
+                    // An empty list means "use the global destinations"
                     destinations: match value.destinations {
-                        None     => None,
-                        Some(ds) => Some(ds
-                            .iter()
-                            .map(Deref::deref)
-                            .map(translate_destination) // This is why we do this whole thing
-                            .collect::<Result<Vec<LogDestination>>>()?)
+                        Some(ref ds) if !ds.is_empty() => Some(translate_destinations(ds)?), // This is why we do this whole thing
+                        _                              => None,
                     },
                 });
             }
@@ -410,3 +526,72 @@ fn aggregate_module_settings(_matches: &ArgMatches, config: Option<&Value>)
     }
 }
 
+/// Register the keys which are read by the logger
+pub fn register_config_schema(schema: &mut ConfigSchema) {
+    use crate::schema::ValueKind::*;
+
+    schema
+        .register(ConfigKey::new("imag.logging.level", String, "The log level")
+                  .with_default("info"))
+        .register(ConfigKey::new("imag.logging.destinations", Array, "Where log lines go: '-' for stderr, a file or a rotated file")
+                  .with_default(vec![Value::String("-".into())]))
+        .register(ConfigKey::new("imag.logging.format.trace", String, "Handlebars template for trace log lines"))
+        .register(ConfigKey::new("imag.logging.format.debug", String, "Handlebars template for debug log lines"))
+        .register(ConfigKey::new("imag.logging.format.info", String, "Handlebars template for info log lines"))
+        .register(ConfigKey::new("imag.logging.format.warn", String, "Handlebars template for warn log lines"))
+        .register(ConfigKey::new("imag.logging.format.error", String, "Handlebars template for error log lines"))
+        .register_table("imag.logging.modules", "Log settings for single modules");
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+
+    use tempdir::TempDir;
+    use toml::Value;
+
+    use super::*;
+
+    #[test]
+    fn test_size_rotation() {
+        let dir  = TempDir::new("imag-logger").unwrap();
+        let path = dir.path().join("imag.log");
+        let mut log = LogFile::open(path.clone(), Some(Rotation::Size(10)), 2).unwrap();
+
+        for line in &["first", "second", "third", "fourth"] {
+            log.write_line(line).unwrap();
+        }
+
+        assert_eq!(read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(read_to_string(dir.path().join("imag.log.1")).unwrap(), "third\n");
+        assert_eq!(read_to_string(dir.path().join("imag.log.2")).unwrap(), "second\n");
+        assert!(!dir.path().join("imag.log.3").exists());
+    }
+
+    #[test]
+    fn test_translate_destination() {
+        let dir  = TempDir::new("imag-logger").unwrap();
+        let file = dir.path().join("imag.log").display().to_string();
+
+        let dest = |s: &str| translate_destination(&::toml::de::from_str::<Value>(s).unwrap()["d"]);
+
+        match dest(r#"d = "-""#).unwrap() {
+            LogDestination::Stderr => {},
+            other                  => panic!("Expected stderr, got {:?}", other),
+        }
+
+        let table = format!(r#"d = {{ file = "{}", rotate = "daily", keep = 3 }}"#, file);
+        match dest(&table).unwrap() {
+            LogDestination::File(f) => {
+                let f = f.lock().unwrap();
+                assert_eq!(f.rotation, Some(Rotation::Daily));
+                assert_eq!(f.keep, 3);
+            },
+            other => panic!("Expected file, got {:?}", other),
+        }
+
+        assert!(dest(&format!(r#"d = {{ file = "{}", rotate = "weekly" }}"#, file)).is_err());
+        assert!(dest("d = 1").is_err());
+    }
+}
@@ -0,0 +1,261 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! The protocol imag commands use to pass entries through a pipe
+//!
+//! By default, imag commands write the ids of the entries they touched to stdout if stdout is a
+//! pipe, one id per line, and read ids from stdin the same way. If `IMAG_PIPE_PROTOCOL=jsonl` is
+//! set in the environment, they write one JSON object per line instead, which carries the id of
+//! the entry and annotations:
+//!
+//! ```json
+//! {"id":"notes/shopping","annotations":{"matches":[{"start":10,"end":14}]}}
+//! ```
+//!
+//! Reading is not affected by the variable: every line which starts with `{` is read as JSON
+//! object, every other line as plain id. So commands which only write ids still work as producers
+//! in a pipeline which uses the JSON protocol, and the other way round.
+//!
+//! Annotations are arbitrary JSON values. The annotations of entries read from stdin are passed on
+//! when the entry is reported as touched again, so they survive pipelines like
+//! `imag-grep foo | imag-tag add bar | imag-view`. The keys below are used by the core commands.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use failure::Fallible as Result;
+use failure::ResultExt;
+use failure::Error;
+
+use libimagstore::storeid::StoreId;
+
+/// The environment variable which selects the pipe protocol
+pub const PIPE_PROTOCOL_ENV: &str = "IMAG_PIPE_PROTOCOL";
+
+/// Annotation: the matches of a search in the content of the entry, as list of objects with the
+/// `start` and `end` byte offsets
+pub const ANNOTATION_MATCHES: &str = "matches";
+
+/// Annotation: the ids of the entries the entry links to, as list of strings
+pub const ANNOTATION_LINKS: &str = "links";
+
+/// Annotation: named points in time of the entry, as object mapping the name to a
+/// `%Y-%m-%dT%H:%M:%S` timestamp
+pub const ANNOTATION_TIMESTAMPS: &str = "timestamps";
+
+/// The format imag commands write to a pipe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipeProtocol {
+    /// One plain id per line, the default
+    Ids,
+
+    /// One JSON object per line, with the id and the annotations
+    JsonLines,
+}
+
+impl PipeProtocol {
+
+    /// The names which can be set in `IMAG_PIPE_PROTOCOL`
+    pub fn names() -> &'static [&'static str] {
+        &["ids", "jsonl"]
+    }
+
+    pub fn from_name(name: &str) -> Option<PipeProtocol> {
+        match name {
+            "ids"   => Some(PipeProtocol::Ids),
+            "jsonl" => Some(PipeProtocol::JsonLines),
+            _       => None,
+        }
+    }
+
+    /// Get the protocol selected with `IMAG_PIPE_PROTOCOL`
+    ///
+    /// Fails if the variable is set to an unknown protocol.
+    pub fn from_env() -> Result<PipeProtocol> {
+        match ::std::env::var(PIPE_PROTOCOL_ENV) {
+            Err(_)   => Ok(PipeProtocol::Ids),
+            Ok(name) => PipeProtocol::from_name(&name).ok_or_else(|| {
+                format_err!("Unknown pipe protocol '{}' in {}, expected one of: {}",
+                            name, PIPE_PROTOCOL_ENV, PipeProtocol::names().join(", "))
+            }),
+        }
+    }
+
+}
+
+/// An entry passed through a pipe, with its annotations
+#[derive(Debug, Clone, PartialEq)]
+pub struct PipeRecord {
+    id: StoreId,
+    annotations: BTreeMap<String, Value>,
+}
+
+/// The JSON representation of a `PipeRecord`
+#[derive(Serialize, Deserialize)]
+struct WireRecord {
+    id: String,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    annotations: BTreeMap<String, Value>,
+}
+
+impl PipeRecord {
+
+    pub fn new(id: StoreId) -> PipeRecord {
+        PipeRecord { id, annotations: BTreeMap::new() }
+    }
+
+    /// Read a record from a line, either a plain id or a JSON object
+    pub fn parse(line: &str) -> Result<PipeRecord> {
+        let line = line.trim();
+        let wire = if line.starts_with('{') {
+            serde_json::from_str::<WireRecord>(line)
+                .context(format_err!("Cannot parse pipe record: {}", line))?
+        } else {
+            WireRecord { id: String::from(line), annotations: BTreeMap::new() }
+        };
+
+        Ok(PipeRecord {
+            id: StoreId::new(PathBuf::from(wire.id))?,
+            annotations: wire.annotations,
+        })
+    }
+
+    /// Write the record as line (without the newline) in `protocol`
+    pub fn to_line(&self, protocol: PipeProtocol) -> Result<String> {
+        match protocol {
+            PipeProtocol::Ids       => Ok(self.id.to_string()),
+            PipeProtocol::JsonLines => {
+                let wire = WireRecord {
+                    id: self.id.to_string(),
+                    annotations: self.annotations.clone(),
+                };
+
+                serde_json::to_string(&wire).map_err(Error::from)
+            },
+        }
+    }
+
+    pub fn id(&self) -> &StoreId {
+        &self.id
+    }
+
+    pub fn into_id(self) -> StoreId {
+        self.id
+    }
+
+    pub fn annotations(&self) -> &BTreeMap<String, Value> {
+        &self.annotations
+    }
+
+    /// Attach an annotation, replacing an existing one with the same key
+    pub fn annotate<V: Serialize>(&mut self, key: &str, value: V) -> Result<()> {
+        let value = serde_json::to_value(value)
+            .context(format_err!("Cannot serialize annotation '{}'", key))?;
+        self.annotations.insert(String::from(key), value);
+        Ok(())
+    }
+
+    pub fn with_annotation<V: Serialize>(mut self, key: &str, value: V) -> Result<Self> {
+        self.annotate(key, value)?;
+        Ok(self)
+    }
+
+    /// Read an annotation
+    ///
+    /// Returns `Ok(None)` if there is no annotation with this key and fails if it cannot be read as
+    /// a `T`.
+    pub fn annotation<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        match self.annotations.get(key) {
+            None        => Ok(None),
+            Some(value) => serde_json::from_value(value.clone())
+                .context(format_err!("Annotation '{}' has an unexpected type", key))
+                .map(Some)
+                .map_err(Error::from),
+        }
+    }
+
+    /// Add the annotations of `annotations` which this record does not have yet
+    pub fn inherit(&mut self, annotations: &BTreeMap<String, Value>) {
+        for (key, value) in annotations {
+            self.annotations.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use libimagstore::storeid::StoreId;
+
+    use super::*;
+
+    fn id(s: &str) -> StoreId {
+        StoreId::new(PathBuf::from(s)).unwrap()
+    }
+
+    #[test]
+    fn test_plain_ids_and_json_lines_are_read() {
+        let plain = PipeRecord::parse("notes/a\n").unwrap();
+        assert_eq!(plain, PipeRecord::new(id("notes/a")));
+
+        let json = PipeRecord::parse(r#"{"id":"notes/b","annotations":{"links":["notes/a"]}}"#).unwrap();
+        assert_eq!(json.id(), &id("notes/b"));
+        assert_eq!(json.annotation::<Vec<String>>(ANNOTATION_LINKS).unwrap(),
+                   Some(vec![String::from("notes/a")]));
+        assert!(json.annotation::<u64>(ANNOTATION_LINKS).is_err());
+        assert_eq!(json.annotation::<u64>(ANNOTATION_MATCHES).unwrap(), None);
+
+        assert!(PipeRecord::parse("{not json").is_err());
+    }
+
+    #[test]
+    fn test_records_roundtrip() {
+        let record = PipeRecord::new(id("notes/a"))
+            .with_annotation(ANNOTATION_MATCHES, vec![(1, 4)])
+            .unwrap();
+
+        assert_eq!(record.to_line(PipeProtocol::Ids).unwrap(), "notes/a");
+
+        let line = record.to_line(PipeProtocol::JsonLines).unwrap();
+        assert_eq!(line, r#"{"id":"notes/a","annotations":{"matches":[[1,4]]}}"#);
+        assert_eq!(PipeRecord::parse(&line).unwrap(), record);
+
+        let plain = PipeRecord::new(id("notes/a")).to_line(PipeProtocol::JsonLines).unwrap();
+        assert_eq!(plain, r#"{"id":"notes/a"}"#);
+    }
+
+    #[test]
+    fn test_inherit_keeps_own_annotations() {
+        let received = PipeRecord::new(id("a"))
+            .with_annotation("x", 1).unwrap()
+            .with_annotation("y", 2).unwrap();
+
+        let mut record = PipeRecord::new(id("a")).with_annotation("x", 3).unwrap();
+        record.inherit(received.annotations());
+
+        assert_eq!(record.annotation::<u64>("x").unwrap(), Some(3));
+        assert_eq!(record.annotation::<u64>("y").unwrap(), Some(2));
+    }
+}
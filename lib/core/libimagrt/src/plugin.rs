@@ -0,0 +1,248 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! The plugin protocol between the `imag` dispatcher and the `imag-*` commands
+//!
+//! Every command which is set up with `libimagrt::setup` answers `imag-<command>
+//! --imag-plugin-info` with a `PluginInfo` as JSON object on stdout: its description, version,
+//! subcommands and the configuration sections it reads, and the version of libimagrt it was built
+//! with. The dispatcher uses this for `imag --help` and to warn about commands which were built
+//! with an incompatible libimagrt.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use clap::App;
+use failure::Fallible as Result;
+use failure::ResultExt;
+use failure::Error;
+
+/// The first argument which makes a command print its `PluginInfo`
+pub const PLUGIN_INFO_FLAG: &str = "--imag-plugin-info";
+
+/// The version of libimagrt
+pub const RT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// How long `PluginInfo::query()` waits for a command to answer
+pub const PLUGIN_INFO_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Check whether the program was called with `PLUGIN_INFO_FLAG`
+pub fn plugin_info_request() -> bool {
+    ::std::env::args().nth(1).map(|arg| arg == PLUGIN_INFO_FLAG).unwrap_or(false)
+}
+
+/// Check whether a command built with libimagrt `version` is compatible with this libimagrt
+///
+/// Only commands built with the same major and minor version of libimagrt are compatible, as the
+/// minor version is bumped for breaking changes as long as imag is at version 0.y.z.
+pub fn is_compatible_rt_version(version: &str) -> bool {
+    let major_minor = |v: &str| v.split('.').take(2).map(String::from).collect::<Vec<_>>();
+    major_minor(version) == major_minor(RT_VERSION)
+}
+
+/// Find the `imag-*` commands in $PATH
+///
+/// Returns the names of the commands without the "imag-" prefix, with the path of the executable.
+/// If a command is in several directories of $PATH, the first one wins, as in the shell.
+pub fn find_commands() -> BTreeMap<String, PathBuf> {
+    let mut commands = BTreeMap::new();
+    let path = match env::var_os("PATH") {
+        Some(path) => path,
+        None       => return commands,
+    };
+
+    for dir in env::split_paths(&path) {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_)      => continue,
+        };
+
+        for entry in entries.filter_map(|e| e.ok()) {
+            let name = match entry.file_name().into_string() {
+                Ok(name) => name,
+                Err(_)   => continue,
+            };
+
+            // cargo puts "imag-*.d" files next to the executables, in case the target directory
+            // is in $PATH
+            if name.starts_with("imag-") && !name.ends_with(".d") && entry.path().is_file() {
+                let _ = commands.entry(String::from(&name["imag-".len()..])).or_insert_with(|| entry.path());
+            }
+        }
+    }
+
+    commands
+}
+
+/// What a command tells about itself
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PluginInfo {
+    name: String,
+    description: String,
+    version: String,
+    rt_version: String,
+    subcommands: Vec<String>,
+    config_sections: Vec<String>,
+}
+
+impl PluginInfo {
+
+    /// Build the info from the commandline interface of a command
+    pub fn from_app(app: &App, config_sections: &[&str]) -> PluginInfo {
+        // clap 2 has no public API to inspect an `App`, see `libimagrt::completion`
+        PluginInfo {
+            name: app.p.meta.name.clone(),
+            description: app.p.meta.about.map(String::from).unwrap_or_default(),
+            version: app.p.meta.version.map(String::from).unwrap_or_default(),
+            rt_version: String::from(RT_VERSION),
+            subcommands: app.p.subcommands.iter().map(|s| s.p.meta.name.clone()).collect(),
+            config_sections: config_sections.iter().map(|s| String::from(*s)).collect(),
+        }
+    }
+
+    /// Ask `command` for its info
+    ///
+    /// Returns `Ok(None)` if the command does not answer with an info, for example because it is
+    /// not built with libimagrt. A command which does not exit within `PLUGIN_INFO_TIMEOUT` is
+    /// killed and treated the same way.
+    pub fn query<S: AsRef<OsStr>>(command: S) -> Result<Option<PluginInfo>> {
+        let name      = command.as_ref().to_string_lossy().into_owned();
+        let mut child = Command::new(command.as_ref())
+            .arg(PLUGIN_INFO_FLAG)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .context(format_err!("Cannot call '{}'", name))?;
+
+        // The info is small enough to fit into the pipe, so the command does not block on writing
+        // it while we wait for it to exit
+        let deadline = Instant::now() + PLUGIN_INFO_TIMEOUT;
+        let status   = loop {
+            if let Some(status) = child.try_wait().context(format_err!("Cannot wait for '{}'", name))? {
+                break status
+            }
+
+            if Instant::now() >= deadline {
+                debug!("'{}' did not answer within {:?}, killing it", name, PLUGIN_INFO_TIMEOUT);
+                let _ = child.kill();
+                let _ = child.wait();
+                return Ok(None)
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        };
+
+        if !status.success() {
+            return Ok(None)
+        }
+
+        let mut stdout = vec![];
+        if let Some(mut out) = child.stdout.take() {
+            let _ = out.read_to_end(&mut stdout).context(format_err!("Cannot read output of '{}'", name))?;
+        }
+
+        Ok(serde_json::from_slice(&stdout).ok())
+    }
+
+    /// Print the info as JSON to stdout
+    pub fn print(&self) -> Result<()> {
+        let out = ::std::io::stdout();
+        serde_json::to_writer(out.lock(), self).map_err(Error::from)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// The version of libimagrt the command was built with
+    pub fn rt_version(&self) -> &str {
+        &self.rt_version
+    }
+
+    pub fn subcommands(&self) -> &[String] {
+        &self.subcommands
+    }
+
+    /// The top level sections of the configuration the command reads, besides the ones of the
+    /// runtime and the store
+    pub fn config_sections(&self) -> &[String] {
+        &self.config_sections
+    }
+
+    pub fn is_compatible(&self) -> bool {
+        is_compatible_rt_version(&self.rt_version)
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use clap::{App, SubCommand};
+
+    use super::*;
+
+    #[test]
+    fn test_info_from_app() {
+        let app = App::new("imag-test")
+            .version("0.10.0")
+            .about("Test things")
+            .subcommand(SubCommand::with_name("add"))
+            .subcommand(SubCommand::with_name("list"));
+
+        let info = PluginInfo::from_app(&app, &["test"]);
+        assert_eq!(info.name(), "imag-test");
+        assert_eq!(info.description(), "Test things");
+        assert_eq!(info.version(), "0.10.0");
+        assert_eq!(info.rt_version(), RT_VERSION);
+        assert_eq!(info.subcommands(), &[String::from("add"), String::from("list")]);
+        assert_eq!(info.config_sections(), &[String::from("test")]);
+
+        let json = serde_json::to_string(&info).unwrap();
+        assert_eq!(serde_json::from_str::<PluginInfo>(&json).unwrap(), info);
+    }
+
+    #[test]
+    fn test_compatible_rt_versions() {
+        let mut parts = RT_VERSION.split('.').map(|p| p.parse::<u64>().unwrap());
+        let (major, minor) = (parts.next().unwrap(), parts.next().unwrap());
+
+        assert!(is_compatible_rt_version(RT_VERSION));
+        assert!(is_compatible_rt_version(&format!("{}.{}.999", major, minor)));
+        assert!(!is_compatible_rt_version(&format!("{}.{}.0", major, minor + 1)));
+        assert!(!is_compatible_rt_version(&format!("{}.{}.0", major + 1, minor)));
+    }
+}
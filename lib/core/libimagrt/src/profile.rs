@@ -0,0 +1,217 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Named profiles in the configuration
+//!
+//! A profile selects a store and overrides parts of the configuration:
+//!
+//! ```toml
+//! [profiles.work]
+//! store = "/home/user/work/store"
+//!
+//! [profiles.work.config.imag.logging]
+//! level = "info"
+//! ```
+//!
+//! The profile is selected with `--profile <name>` or the `IMAG_PROFILE` environment variable.
+//! The tables in `config` are merged into the configuration, values from the profile replace the
+//! ones from the configuration. A relative `store` path is relative to the runtimepath.
+//! `--store` and `--override-config` still take precedence over the profile.
+
+use std::env;
+use std::path::PathBuf;
+
+use clap::ArgMatches;
+use toml::Value;
+use toml_query::read::TomlValueReadExt;
+use failure::Fallible as Result;
+use failure::ResultExt;
+use failure::err_msg;
+
+use libimagerror::errors::ErrorMsg as EM;
+
+/// The environment variable which selects a profile if `--profile` is not passed
+pub const PROFILE_ENV_VAR: &str = "IMAG_PROFILE";
+
+/// A profile from the "profiles" section of the configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    name: String,
+    store: Option<PathBuf>,
+    config: Option<Value>,
+}
+
+impl Profile {
+
+    /// Read the profile `name` from the configuration, fails if there is no such profile
+    pub fn from_config(config: &Value, name: &str) -> Result<Profile> {
+        let profile = config
+            .read(&format!("profiles.{}", name))
+            .context(EM::TomlQueryError)?
+            .ok_or_else(|| format_err!("No profile '{}' in the configuration", name))?;
+
+        Profile::from_value(name, profile)
+    }
+
+    fn from_value(name: &str, profile: &Value) -> Result<Profile> {
+        let table = profile
+            .as_table()
+            .ok_or_else(|| format_err!("Config key 'profiles.{}' must be a table", name))?;
+
+        let store = match table.get("store") {
+            Some(&Value::String(ref s)) => Some(PathBuf::from(s)),
+            Some(_) => return Err(format_err!("Config key 'profiles.{}.store' must be a string", name)),
+            None    => None,
+        };
+
+        let config = match table.get("config") {
+            Some(t @ &Value::Table(_)) => Some(t.clone()),
+            Some(_) => return Err(format_err!("Config key 'profiles.{}.config' must be a table", name)),
+            None    => None,
+        };
+
+        Ok(Profile { name: String::from(name), store, config })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The store path of the profile, as written in the configuration
+    pub fn store(&self) -> Option<&PathBuf> {
+        self.store.as_ref()
+    }
+
+    /// The configuration overrides of the profile
+    pub fn config(&self) -> Option<&Value> {
+        self.config.as_ref()
+    }
+
+    /// The store path of the profile, relative paths are resolved against `rtp`
+    pub fn storepath(&self, rtp: &PathBuf) -> Option<PathBuf> {
+        self.store.as_ref().map(|store| if store.is_relative() {
+            rtp.join(store)
+        } else {
+            store.clone()
+        })
+    }
+
+    /// Merge the configuration overrides of the profile into `config`
+    pub fn apply(&self, config: &mut Value) {
+        if let Some(ref overrides) = self.config {
+            merge(config, overrides);
+        }
+    }
+
+}
+
+/// All profiles from the configuration, ordered by name
+pub fn profiles(config: &Value) -> Result<Vec<Profile>> {
+    match config.read("profiles").context(EM::TomlQueryError)? {
+        None                          => Ok(vec![]),
+        Some(&Value::Table(ref table)) => table
+            .iter()
+            .map(|(name, profile)| Profile::from_value(name, profile))
+            .collect(),
+        Some(_) => Err(err_msg("Config key 'profiles' must be a table")),
+    }
+}
+
+/// The name of the selected profile, from `--profile` or the environment
+pub fn selected_profile_name(matches: &ArgMatches) -> Result<Option<String>> {
+    if let Some(name) = matches.value_of("profile") {
+        return Ok(Some(String::from(name)))
+    }
+
+    match env::var(PROFILE_ENV_VAR) {
+        Ok(ref name) if name.is_empty() => Ok(None),
+        Ok(name)                        => Ok(Some(name)),
+        Err(env::VarError::NotPresent)  => Ok(None),
+        Err(env::VarError::NotUnicode(_)) => {
+            Err(format_err!("Environment variable '{}' does not contain valid Unicode", PROFILE_ENV_VAR))
+        },
+    }
+}
+
+/// Merge `overrides` into `base`, tables are merged recursively, all other values are replaced
+fn merge(base: &mut Value, overrides: &Value) {
+    match (base, overrides) {
+        (&mut Value::Table(ref mut base), &Value::Table(ref overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(key) {
+                    Some(existing) => merge(existing, value),
+                    None           => { let _ = base.insert(key.clone(), value.clone()); },
+                }
+            }
+        },
+        (base, overrides) => *base = overrides.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use toml::Value;
+    use toml_query::read::TomlValueReadTypeExt;
+
+    use super::*;
+
+    fn config() -> Value {
+        ::toml::de::from_str(r#"
+        [imag.logging]
+        level = "debug"
+        destinations = [ "-" ]
+
+        [profiles.work]
+        store = "work-store"
+
+        [profiles.work.config.imag.logging]
+        level = "info"
+
+        [profiles.home]
+        store = "/home/user/store"
+        "#).unwrap()
+    }
+
+    #[test]
+    fn test_profiles() {
+        let config = config();
+        let names  = profiles(&config).unwrap().into_iter().map(|p| String::from(p.name())).collect::<Vec<_>>();
+        assert_eq!(names, vec!["home", "work"]);
+
+        let rtp = PathBuf::from("/rtp");
+        let work = Profile::from_config(&config, "work").unwrap();
+        assert_eq!(work.storepath(&rtp), Some(PathBuf::from("/rtp/work-store")));
+
+        let home = Profile::from_config(&config, "home").unwrap();
+        assert_eq!(home.storepath(&rtp), Some(PathBuf::from("/home/user/store")));
+
+        assert!(Profile::from_config(&config, "missing").is_err());
+    }
+
+    #[test]
+    fn test_apply_profile() {
+        let mut config = config();
+        Profile::from_config(&config.clone(), "work").unwrap().apply(&mut config);
+
+        assert_eq!(config.read_string("imag.logging.level").unwrap(), Some(String::from("info")));
+        assert!(config.read("imag.logging.destinations").unwrap().is_some());
+    }
+}
@@ -24,6 +24,8 @@ use std::process::exit;
 use std::io::Stdin;
 use std::io::StdoutLock;
 use std::borrow::Borrow;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::result::Result as RResult;
 
 pub use clap::App;
@@ -40,6 +42,11 @@ use failure::err_msg;
 use crate::configuration::{fetch_config, override_config, InternalConfiguration};
 use crate::logger::ImagLogger;
 use crate::io::OutputProxy;
+use crate::io::OutputFormat;
+use crate::pipe::PipeProtocol;
+use crate::pipe::PipeRecord;
+use crate::profile::Profile;
+use crate::profile::selected_profile_name;
 
 use libimagerror::exit::ExitCode;
 use libimagerror::errors::ErrorMsg as EM;
@@ -47,6 +54,7 @@ use libimagerror::trace::*;
 use libimagerror::io::ToExitCode;
 use libimagstore::store::Store;
 use libimagstore::storeid::StoreId;
+use libimaginteraction::pick::Picker;
 use libimagutil::debug_result::DebugResult;
 use crate::spec::CliSpec;
 use atty;
@@ -64,7 +72,14 @@ pub struct Runtime<'a> {
     has_output_pipe: bool,
     has_input_pipe: bool,
 
-    ignore_ids: bool
+    ignore_ids: bool,
+    output_format: OutputFormat,
+    profile: Option<Profile>,
+
+    pipe_protocol: PipeProtocol,
+
+    /// The annotations of the records read from stdin, passed on when the entries are reported
+    received_annotations: RefCell<BTreeMap<StoreId, BTreeMap<String, ::serde_json::Value>>>,
 }
 
 impl<'a> Runtime<'a> {
@@ -79,14 +94,7 @@ impl<'a> Runtime<'a> {
     {
         let matches = cli_app.clone().matches();
 
-        let rtp = get_rtp_match(&matches)?;
-
-        let configpath = matches.value_of("config")
-                                .map_or_else(|| rtp.clone(), PathBuf::from);
-
-        debug!("Config path = {:?}", configpath);
-
-        let config = match fetch_config(&configpath)? {
+        let (config, profile) = match load_config_and_profile(&matches)? {
             None => {
                 return Err(err_msg("No configuration file found"))
                     .context(err_msg("Maybe try to use 'imag-init' to initialize imag?"))
@@ -94,43 +102,35 @@ impl<'a> Runtime<'a> {
                     .context(err_msg("Cannot instantiate runtime"))
                     .map_err(Error::from);
             },
-            Some(mut config) => {
-                if let Err(e) = override_config(&mut config, get_override_specs(&matches)) {
-                    error!("Could not apply config overrides");
-                    trace_error(&e);
-                }
-
-                Some(config)
-            }
+            Some((config, profile)) => (Some(config), profile),
         };
 
-        Runtime::_new(cli_app, matches, config)
+        Runtime::_new(cli_app, matches, config, profile)
     }
 
     /// Builds the Runtime object using the given `config`.
-    pub fn with_configuration<C>(cli_app: C, config: Option<Value>) -> Result<Runtime<'a>>
+    ///
+    /// The profile selected with `--profile` or `IMAG_PROFILE` is applied to `config`.
+    pub fn with_configuration<C>(cli_app: C, mut config: Option<Value>) -> Result<Runtime<'a>>
         where C: Clone + CliSpec<'a> + InternalConfiguration
     {
         let matches = cli_app.clone().matches();
-        Runtime::_new(cli_app, matches, config)
+        let profile = load_profile(&matches, config.as_mut())?;
+        Runtime::_new(cli_app, matches, config, profile)
     }
 
-    fn _new<C>(cli_app: C, matches: ArgMatches<'a>, config: Option<Value>) -> Result<Runtime<'a>>
+    fn _new<C>(cli_app: C, matches: ArgMatches<'a>, config: Option<Value>, profile: Option<Profile>)
+        -> Result<Runtime<'a>>
     where C: Clone + CliSpec<'a> + InternalConfiguration
     {
         if cli_app.enable_logging() {
             Runtime::init_logger(&matches, config.as_ref())
         }
 
-        let rtp = get_rtp_match(&matches)?;
-
-        let storepath = matches.value_of("storepath")
-                                .map_or_else(|| {
-                                    let mut spath = rtp.clone();
-                                    spath.push("store");
-                                    spath
-                                }, PathBuf::from);
+        let rtp       = get_rtp_match(&matches)?;
+        let storepath = get_storepath(&matches, &rtp, profile.as_ref());
 
+        debug!("Profile     = {:?}", profile.as_ref().map(Profile::name));
         debug!("RTP path    = {:?}", rtp);
         debug!("Store path  = {:?}", storepath);
         debug!("CLI         = {:?}", matches);
@@ -145,10 +145,17 @@ impl<'a> Runtime<'a> {
         let has_output_pipe = !atty::is(atty::Stream::Stdout);
         let has_input_pipe  = !atty::is(atty::Stream::Stdin);
         let ignore_ids      = matches.is_present("ignore-ids");
+        let output_format   = matches
+            .value_of("output-format")
+            .and_then(OutputFormat::from_name) // validated by clap
+            .unwrap_or(OutputFormat::Text);
+        let pipe_protocol   = PipeProtocol::from_env()?;
 
         debug!("has output pipe = {}", has_output_pipe);
         debug!("has input pipe  = {}", has_input_pipe);
         debug!("ignore ids      = {}", ignore_ids);
+        debug!("output format   = {:?}", output_format);
+        debug!("pipe protocol   = {:?}", pipe_protocol);
 
         store_result.map(|store| Runtime {
             cli_matches: matches,
@@ -159,6 +166,10 @@ impl<'a> Runtime<'a> {
             has_output_pipe,
             has_input_pipe,
             ignore_ids,
+            output_format,
+            profile,
+            pipe_protocol,
+            received_annotations: RefCell::new(BTreeMap::new()),
         })
         .context(err_msg("Cannot instantiate runtime"))
         .map_err(Error::from)
@@ -173,6 +184,7 @@ impl<'a> Runtime<'a> {
     ///   * -c <file> | --config <file> for alternative configuration file
     ///   * -r <path> | --rtp <path> for alternative runtimepath
     ///   * --store <path> for alternative store path
+    ///   * --profile <name> for selecting a profile from the configuration
     /// Each has the appropriate help text included.
     ///
     /// The `appname` shall be "imag-<command>".
@@ -235,6 +247,13 @@ impl<'a> Runtime<'a> {
                 .validator(::libimagutil::cli_validators::is_directory)
                 .takes_value(true))
 
+            .arg(Arg::with_name("profile")
+                .long("profile")
+                .help("Use this profile from the configuration. Can also be set with the IMAG_PROFILE environment variable")
+                .required(false)
+                .takes_value(true)
+                .value_name("PROFILE"))
+
             .arg(Arg::with_name("editor")
                 .long("editor")
                 .help("Set editor")
@@ -248,6 +267,14 @@ impl<'a> Runtime<'a> {
                 .required(false)
                 .takes_value(false))
 
+            .arg(Arg::with_name("output-format")
+                .long("format")
+                .help("Output format. With a structured format, commands which support it print records to stdout, all other output goes to stderr")
+                .required(false)
+                .takes_value(true)
+                .possible_values(OutputFormat::names())
+                .value_name("FORMAT"))
+
     }
 
     /// Extract the Store object from the Runtime object, destroying the Runtime object
@@ -288,7 +315,7 @@ impl<'a> Runtime<'a> {
     /// initializes a env-logger instance. Errors are ignored in this case.
     /// If the environment variable is not set, this initializes the internal imag logger. On
     /// error, this exits (as there is nothing we can do about that)
-    fn _init_logger(matches: &ArgMatches, config: Option<&Value>) {
+    pub(crate) fn _init_logger(matches: &ArgMatches, config: Option<&Value>) {
         use log::set_max_level;
         use log::set_boxed_logger;
         use std::env::var as env_var;
@@ -340,27 +367,58 @@ impl<'a> Runtime<'a> {
     }
 
     pub fn ids<T: IdPathProvider>(&self) -> Result<Option<Vec<StoreId>>> {
+        self.records::<T>()
+            .map(|records| records.map(|rs| rs.into_iter().map(PipeRecord::into_id).collect()))
+    }
+
+    /// Get the entries to operate on with their annotations (see `libimagrt::pipe`)
+    ///
+    /// Like `Runtime::ids()`, this reads from stdin if it is a pipe and asks `T` for the ids from
+    /// the commandline otherwise. Lines on stdin may be plain ids or JSON records. Ids from the
+    /// commandline have no annotations.
+    pub fn records<T: IdPathProvider>(&self) -> Result<Option<Vec<PipeRecord>>> {
         use std::io::Read;
 
         if self.has_input_pipe {
-            trace!("Getting IDs from stdin...");
+            trace!("Getting records from stdin...");
             let stdin    = ::std::io::stdin();
             let mut lock = stdin.lock();
 
             let mut buf = String::new();
             lock.read_to_string(&mut buf)
-                .context("Failed to read stdin to buffer")
-                .map_err(Error::from)
-                .and_then(|_| {
-                    trace!("Got IDs = {}", buf);
-                    buf.lines()
-                        .map(PathBuf::from)
-                        .map(|id| StoreId::new(id).map_err(Error::from))
-                        .collect()
-                })
-                .map(Some)
+                .context("Failed to read stdin to buffer")?;
+            trace!("Got records = {}", buf);
+
+            let records = buf.lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(PipeRecord::parse)
+                .collect::<Result<Vec<_>>>()?;
+
+            {
+                let mut received = self.received_annotations.borrow_mut();
+                for record in records.iter().filter(|r| !r.annotations().is_empty()) {
+                    received.insert(record.id().clone(), record.annotations().clone());
+                }
+            }
+
+            Ok(Some(records))
         } else {
-            Ok(T::get_ids(self.cli())?)
+            Ok(T::get_ids(self.cli())?.map(|ids| ids.into_iter().map(PipeRecord::new).collect()))
+        }
+    }
+
+    /// Get the ids like `Runtime::ids()`, but let the user pick entries with the fuzzy finder from
+    /// `libimaginteraction::pick` if none were passed and stdin is a terminal
+    ///
+    /// Returns `Ok(None)` if no ids were passed and the user did not pick any.
+    pub fn ids_or_pick<T: IdPathProvider>(&self) -> Result<Option<Vec<StoreId>>> {
+        match self.ids::<T>()? {
+            None if !self.has_input_pipe => {
+                debug!("No ids passed, starting picker");
+                let ids = Picker::new(self.store()).multi(true).pick()?;
+                Ok(if ids.is_empty() { None } else { Some(ids) })
+            },
+            ids => Ok(ids),
         }
     }
 
@@ -409,6 +467,21 @@ impl<'a> Runtime<'a> {
         self.has_output_pipe
     }
 
+    /// Get the output format selected with `--format`
+    pub fn output_format(&self) -> OutputFormat {
+        self.output_format
+    }
+
+    /// The profile which is in use, if any
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Get the protocol selected with `IMAG_PIPE_PROTOCOL` for reporting touched entries
+    pub fn pipe_protocol(&self) -> PipeProtocol {
+        self.pipe_protocol
+    }
+
     /// Check whether the runtime ignores touched ids
     ///
     /// "Ignoring" in this context means whether the runtime prints them or not.
@@ -416,14 +489,37 @@ impl<'a> Runtime<'a> {
         self.ignore_ids
     }
 
+    /// Get the proxy for human readable output
+    ///
+    /// Writes to stderr if stdout is reserved for touched ids (see `Runtime::report_touched()`) or
+    /// for records (see `Runtime::write_records()`).
     pub fn stdout(&self) -> OutputProxy {
-        if self.output_is_pipe() && !self.ignore_ids {
+        if (self.output_is_pipe() && !self.ignore_ids) || self.output_format.is_structured() {
             OutputProxy::Err(::std::io::stderr())
         } else {
             OutputProxy::Out(::std::io::stdout())
         }
     }
 
+    /// Write records to stdout in the format selected with `--format`
+    ///
+    /// Fails if no structured format is selected, commands check `Runtime::output_format()`
+    /// before building records:
+    ///
+    /// ```ignore
+    /// if rt.output_format().is_structured() {
+    ///     rt.write_records(entries.iter().map(EntryRecord::from_entry))?;
+    /// } else {
+    ///     // print text to rt.stdout()
+    /// }
+    /// ```
+    pub fn write_records<R, I>(&self, records: I) -> Result<()>
+        where R: ::serde::Serialize,
+              I: IntoIterator<Item = R>
+    {
+        OutputProxy::Out(::std::io::stdout()).write_records(self.output_format, records)
+    }
+
     pub fn stderr(&self) -> OutputProxy {
         OutputProxy::Err(::std::io::stderr())
     }
@@ -536,13 +632,37 @@ impl<'a> Runtime<'a> {
         Ok(())
     }
 
+    /// Report a touched entry with annotations
+    ///
+    /// With the default pipe protocol, only the id is written. The annotations the entry had when
+    /// it was read from stdin are added to the ones of `record`.
+    pub fn report_touched_record(&self, record: PipeRecord) -> RResult<(), ExitCode> {
+        let out      = ::std::io::stdout();
+        let mut lock = out.lock();
+
+        self.report_record(record, &mut lock)
+    }
+
     #[inline]
     fn report_touched_id(&self, id: &StoreId, output: &mut StdoutLock) -> RResult<(), ExitCode> {
+        self.report_record(PipeRecord::new(id.clone()), output)
+    }
+
+    fn report_record(&self, mut record: PipeRecord, output: &mut StdoutLock) -> RResult<(), ExitCode> {
         use std::io::Write;
 
-        if self.output_is_pipe() && !self.ignore_ids {
-            trace!("Reporting: {} to {:?}", id, output);
-            writeln!(output, "{}", id).to_exit_code()
+        if self.output_is_pipe() && !self.ignore_ids && !self.output_format.is_structured() {
+            if let Some(received) = self.received_annotations.borrow().get(record.id()) {
+                record.inherit(received);
+            }
+
+            let line = record.to_line(self.pipe_protocol).map_err(|e| {
+                trace_error(&e);
+                ExitCode::from(1)
+            })?;
+
+            trace!("Reporting: {} to {:?}", line, output);
+            writeln!(output, "{}", line).to_exit_code()
         } else {
             Ok(())
         }
@@ -611,6 +731,75 @@ pub fn get_rtp_match<'a>(matches: &ArgMatches<'a>) -> Result<PathBuf> {
         })
 }
 
+/// Load the configuration like `Runtime::new()` does, but without building a `Runtime`
+///
+/// The profile selected with `--profile` or `IMAG_PROFILE` and the overrides from the commandline
+/// are applied. Returns `None` if there is no configuration file.
+pub fn load_config(matches: &ArgMatches) -> Result<Option<Value>> {
+    load_config_and_profile(matches).map(|c| c.map(|(config, _)| config))
+}
+
+/// Open the store selected by `matches` with `Store::new_read_only()`
+///
+/// The configuration, the profile and the store path are looked up like `Runtime::new()` does.
+pub(crate) fn open_store_read_only(matches: &ArgMatches) -> Result<Store> {
+    let (config, profile) = match load_config_and_profile(matches)? {
+        Some((config, profile)) => (Some(config), profile),
+        None                    => return Err(err_msg("No configuration file found")),
+    };
+
+    let rtp = get_rtp_match(matches)?;
+    Store::new_read_only(get_storepath(matches, &rtp, profile.as_ref()), &config)
+}
+
+fn get_storepath(matches: &ArgMatches, rtp: &PathBuf, profile: Option<&Profile>) -> PathBuf {
+    matches.value_of("storepath")
+        .map(PathBuf::from)
+        .or_else(|| profile.and_then(|p| p.storepath(rtp)))
+        .unwrap_or_else(|| {
+            let mut spath = rtp.clone();
+            spath.push("store");
+            spath
+        })
+}
+
+fn load_config_and_profile(matches: &ArgMatches) -> Result<Option<(Value, Option<Profile>)>> {
+    let rtp = get_rtp_match(matches)?;
+
+    let configpath = matches.value_of("config")
+                            .map_or_else(|| rtp.clone(), PathBuf::from);
+
+    debug!("Config path = {:?}", configpath);
+
+    match fetch_config(&configpath)? {
+        None             => Ok(None),
+        Some(mut config) => {
+            // The profile is applied first, so that the overrides from the commandline win
+            let profile = load_profile(matches, Some(&mut config))?;
+
+            if let Err(e) = override_config(&mut config, get_override_specs(matches)) {
+                error!("Could not apply config overrides");
+                trace_error(&e);
+            }
+
+            Ok(Some((config, profile)))
+        },
+    }
+}
+
+/// Select the profile from the commandline or the environment and apply it to `config`
+fn load_profile(matches: &ArgMatches, config: Option<&mut Value>) -> Result<Option<Profile>> {
+    let name = match selected_profile_name(matches)? {
+        Some(name) => name,
+        None       => return Ok(None),
+    };
+
+    let config  = config.ok_or_else(|| format_err!("Cannot use profile '{}' without configuration", name))?;
+    let profile = Profile::from_config(config, &name)?;
+    profile.apply(config);
+    Ok(Some(profile))
+}
+
 fn get_override_specs(matches: &ArgMatches) -> Vec<String> {
     matches
         .values_of("config-override")
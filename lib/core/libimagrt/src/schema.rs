@@ -0,0 +1,41 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! The configuration keys of the runtime
+//!
+//! The schema types are defined in `libimagstore::schema` and re-exported here.
+
+pub use libimagstore::schema::ConfigKey;
+pub use libimagstore::schema::ConfigSchema;
+pub use libimagstore::schema::Problem;
+pub use libimagstore::schema::ValueKind;
+
+/// Register the keys which are read by the runtime
+///
+/// The keys of the logger are registered with `logger::register_config_schema()`.
+pub fn register_config_schema(schema: &mut ConfigSchema) {
+    use self::ValueKind::*;
+
+    schema
+        .register(ConfigKey::new("rt.editor", String, "The editor command, if $EDITOR is not set"))
+        .register(ConfigKey::new("rt.progressbar_style", String, "The style of progress bars"))
+        .register(ConfigKey::new("rt.progressticker_chars", String, "The characters of progress tickers"))
+        .register_table("imag.aliases", "Aliases for the imag subcommands")
+        .register_table("profiles", "Named profiles, see `imag profile`");
+}
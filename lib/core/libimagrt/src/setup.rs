@@ -18,7 +18,12 @@
 //
 
 use clap::App;
+use clap::ArgMatches;
+use toml::Value;
 
+use crate::completion::{completion_request, print_completions};
+use crate::logger::ImagLogger;
+use crate::plugin::{plugin_info_request, PluginInfo};
 use crate::runtime::Runtime;
 
 pub type Name          = &'static str;
@@ -29,13 +34,34 @@ pub type About         = &'static str;
 ///
 /// exit()s the program if the runtime couldn't be build, prints error with println!() before
 /// exiting
+///
+/// If the program was called with `completion::COMPLETE_FLAG` or `plugin::PLUGIN_INFO_FLAG` as
+/// first argument, the completion candidates or the plugin info are printed and the program
+/// exit()s as well.
 pub fn generate_runtime_setup<'a, B>(name: Name, version: Version<'a>, about: About, builder: B)
     -> Runtime<'a>
     where B: FnOnce(App<'a, 'a>) -> App<'a, 'a>
+{
+    generate_runtime_setup_with_config(name, version, about, &[], builder)
+}
+
+/// Like `generate_runtime_setup()`, for commands which read configuration sections besides the
+/// ones of the runtime and the store
+///
+/// The `config_sections` are reported in the plugin info of the command.
+pub fn generate_runtime_setup_with_config<'a, B>(name: Name,
+                                                 version: Version<'a>,
+                                                 about: About,
+                                                 config_sections: &[&str],
+                                                 builder: B)
+    -> Runtime<'a>
+    where B: FnOnce(App<'a, 'a>) -> App<'a, 'a>
 {
     use std::process::exit;
     use libimagerror::trace::trace_error_dbg;
 
+    let builder = handle_requests(name, version, about, config_sections, builder);
+
     Runtime::new(builder(Runtime::get_default_cli_builder(name, version, about)))
         .unwrap_or_else(|e| {
             eprintln!("Could not set up Runtime");
@@ -44,3 +70,66 @@ pub fn generate_runtime_setup<'a, B>(name: Name, version: Version<'a>, about: Ab
             exit(1);
         })
 }
+
+/// Helper for commands which work on the configuration only and do not need a `Runtime`
+///
+/// Like `generate_runtime_setup()`, but the store is not opened, so this works even if the
+/// configuration of the store is broken. Returns the commandline matches and the configuration
+/// (see `runtime::load_config()`), `None` if there is no configuration file. Logging is set up,
+/// with the env-logger if the logging configuration is broken.
+///
+/// exit()s the program if the configuration cannot be loaded.
+pub fn generate_config_setup<'a, B>(name: Name, version: Version<'a>, about: About, builder: B)
+    -> (ArgMatches<'a>, Option<Value>)
+    where B: FnOnce(App<'a, 'a>) -> App<'a, 'a>
+{
+    use std::process::exit;
+    use libimagerror::trace::trace_error;
+
+    let builder = handle_requests(name, version, about, &[], builder);
+
+    let matches = builder(Runtime::get_default_cli_builder(name, version, about)).get_matches();
+    match crate::runtime::load_config(&matches) {
+        Ok(config) => {
+            // The imag logger needs a valid logging configuration, which is what the command might
+            // be about to fix
+            if ImagLogger::new(&matches, config.as_ref()).is_ok() {
+                Runtime::_init_logger(&matches, config.as_ref());
+            } else {
+                let _ = env_logger::try_init();
+            }
+            (matches, config)
+        },
+        Err(e) => {
+            trace_error(&e);
+            exit(1)
+        },
+    }
+}
+
+/// Print the completion candidates or the plugin info and exit, if the program was called for that
+///
+/// Returns the `builder` otherwise.
+fn handle_requests<'a, B>(name: Name, version: Version<'a>, about: About, config_sections: &[&str], builder: B)
+    -> B
+    where B: FnOnce(App<'a, 'a>) -> App<'a, 'a>
+{
+    use std::process::exit;
+
+    if let Some(words) = completion_request() {
+        let app = builder(Runtime::get_default_cli_builder(name, version, about));
+        print_completions(&app, Runtime::get_default_cli_builder(name, version, about), &words);
+        exit(0);
+    }
+
+    if plugin_info_request() {
+        let app = builder(Runtime::get_default_cli_builder(name, version, about));
+        let code = match PluginInfo::from_app(&app, config_sections).print() {
+            Ok(()) => 0,
+            Err(_) => 1,
+        };
+        exit(code);
+    }
+
+    builder
+}
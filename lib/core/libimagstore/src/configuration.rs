@@ -17,6 +17,8 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
+use std::time::Duration;
+
 use toml::Value;
 
 use failure::Fallible as Result;
@@ -25,6 +27,10 @@ use failure::Error;
 
 use libimagerror::errors::ErrorMsg as EM;
 
+use crate::file_abstraction::BackendKind;
+
+const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_millis(5000);
+
 /// Checks whether the store configuration has a key "implicit-create" which maps to a boolean
 /// value. If that key is present, the boolean is returned, otherwise false is returned.
 pub fn config_implicit_store_create_allowed(config: &Option<Value>) -> Result<bool> {
@@ -43,6 +49,107 @@ pub fn config_implicit_store_create_allowed(config: &Option<Value>) -> Result<bo
     }
 }
 
+/// Checks whether the store configuration has a key "store.index.enabled" which maps to a boolean
+/// value. If that key is present, the boolean is returned, otherwise false is returned.
+pub fn config_index_enabled(config: &Option<Value>) -> Result<bool> {
+    use toml_query::read::TomlValueReadTypeExt;
+
+    let key = "store.index.enabled";
+
+    if let Some(ref t) = *config {
+        t.read_bool(key)
+            .context(format_err!("Error reading header '{}' in configuration", key))
+            .map_err(Error::from)
+            .context(EM::TomlQueryError)
+            .map_err(Error::from)
+            .map(|b| b.unwrap_or(false))
+    } else {
+        Ok(false)
+    }
+}
+
+/// Checks whether the store configuration has a key "store.history.enabled" which maps to a
+/// boolean value. If that key is present, the boolean is returned, otherwise false is returned.
+pub fn config_history_enabled(config: &Option<Value>) -> Result<bool> {
+    use toml_query::read::TomlValueReadTypeExt;
+
+    let key = "store.history.enabled";
+
+    if let Some(ref t) = *config {
+        t.read_bool(key)
+            .context(format_err!("Error reading header '{}' in configuration", key))
+            .map_err(Error::from)
+            .context(EM::TomlQueryError)
+            .map_err(Error::from)
+            .map(|b| b.unwrap_or(false))
+    } else {
+        Ok(false)
+    }
+}
+
+/// Checks whether the store configuration has a key "store.encryption.enabled" which maps to a
+/// boolean value. If that key is present, the boolean is returned, otherwise false is returned.
+///
+/// The other encryption settings are read by the `file_abstraction::crypt` module.
+pub fn config_encryption_enabled(config: &Option<Value>) -> Result<bool> {
+    use toml_query::read::TomlValueReadTypeExt;
+
+    let key = "store.encryption.enabled";
+
+    if let Some(ref t) = *config {
+        t.read_bool(key)
+            .context(format_err!("Error reading header '{}' in configuration", key))
+            .map_err(Error::from)
+            .context(EM::TomlQueryError)
+            .map_err(Error::from)
+            .map(|b| b.unwrap_or(false))
+    } else {
+        Ok(false)
+    }
+}
+
+/// Reads "store.locking.timeout", the number of milliseconds to wait for an entry which is locked
+/// by another process. Defaults to 5 seconds.
+pub fn config_lock_timeout(config: &Option<Value>) -> Result<Duration> {
+    use toml_query::read::TomlValueReadTypeExt;
+
+    let key = "store.locking.timeout";
+
+    if let Some(ref t) = *config {
+        t.read_int(key)
+            .context(format_err!("Error reading header '{}' in configuration", key))
+            .map_err(Error::from)
+            .context(EM::TomlQueryError)?
+            .map(|ms| if ms < 0 {
+                Err(format_err!("Config key '{}' must not be negative", key))
+            } else {
+                Ok(Duration::from_millis(ms as u64))
+            })
+            .unwrap_or(Ok(DEFAULT_LOCK_TIMEOUT))
+    } else {
+        Ok(DEFAULT_LOCK_TIMEOUT)
+    }
+}
+
+/// Reads the backend which is selected with "store.backend". If the key is not present, the
+/// filesystem backend is returned.
+pub fn config_backend(config: &Option<Value>) -> Result<BackendKind> {
+    use toml_query::read::TomlValueReadTypeExt;
+
+    let key = "store.backend";
+
+    if let Some(ref t) = *config {
+        t.read_string(key)
+            .context(format_err!("Error reading header '{}' in configuration", key))
+            .map_err(Error::from)
+            .context(EM::TomlQueryError)?
+            .map(|name| BackendKind::from_name(&name))
+            .unwrap_or(Ok(BackendKind::Filesystem))
+    } else {
+        Ok(BackendKind::Filesystem)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use toml::de::from_str as toml_from_str;
@@ -79,5 +186,75 @@ mod tests {
         assert!(config_implicit_store_create_allowed(&Some(config)).unwrap());
     }
 
-}
+    #[test]
+    fn test_index_enabled_toml_empty() {
+        let config = toml_from_str("").unwrap();
+        assert!(!config_index_enabled(&Some(config)).unwrap());
+    }
+
+    #[test]
+    fn test_index_enabled_toml_true() {
+        let config = toml_from_str(r#"
+        [store.index]
+            enabled = true
+        "#).unwrap();
+
+        assert!(config_index_enabled(&Some(config)).unwrap());
+    }
+
+    #[test]
+    fn test_history_enabled() {
+        let config = toml_from_str(r#"
+        [store.history]
+            enabled = true
+        "#).unwrap();
+
+        assert!(config_history_enabled(&Some(config)).unwrap());
+        assert!(!config_history_enabled(&None).unwrap());
+    }
+
+    #[test]
+    fn test_lock_timeout() {
+        use std::time::Duration;
+
+        let config = toml_from_str(r#"
+        [store.locking]
+            timeout = 250
+        "#).unwrap();
+
+        assert_eq!(config_lock_timeout(&Some(config)).unwrap(), Duration::from_millis(250));
+        assert_eq!(config_lock_timeout(&None).unwrap(), Duration::from_millis(5000));
+    }
+
+    #[test]
+    fn test_backend_default() {
+        use crate::file_abstraction::BackendKind;
+
+        let config = toml_from_str("").unwrap();
+        assert_eq!(config_backend(&Some(config)).unwrap(), BackendKind::Filesystem);
+        assert_eq!(config_backend(&None).unwrap(), BackendKind::Filesystem);
+    }
 
+    #[test]
+    fn test_backend_sqlite() {
+        use crate::file_abstraction::BackendKind;
+
+        let config = toml_from_str(r#"
+        [store]
+            backend = "sqlite"
+        "#).unwrap();
+
+        assert_eq!(config_backend(&Some(config)).unwrap(), BackendKind::Sqlite);
+    }
+
+    #[test]
+    fn test_backend_unknown() {
+        let config = toml_from_str(r#"
+        [store]
+            backend = "floppy"
+        "#).unwrap();
+
+        assert!(config_backend(&Some(config)).is_err());
+    }
+
+}
@@ -0,0 +1,244 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Notifications about changes of store entries
+//!
+//! `Store::subscribe()` returns a channel which receives a `StoreEvent` for every entry the store
+//! object writes, deletes or moves.
+//!
+//! Changes made by other processes (other imag commands, an editor, git, ...) are not seen by
+//! the store object. With the `watch` feature, `Store::watch_external()` watches the store
+//! directory with inotify (or whatever the platform offers) and sends these changes to the
+//! subscribers as well. This only works with the filesystem backend.
+
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::mpsc::Sender;
+
+use serde::Serialize;
+use serde::Serializer;
+use serde::ser::SerializeMap;
+
+use crate::storeid::StoreId;
+
+/// Something happened to an entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoreEvent {
+    Created(StoreId),
+    Updated(StoreId),
+    Deleted(StoreId),
+    Moved { from: StoreId, to: StoreId },
+}
+
+impl StoreEvent {
+
+    /// The name of the event, as used in the serialized form
+    pub fn name(&self) -> &'static str {
+        match *self {
+            StoreEvent::Created(_)   => "created",
+            StoreEvent::Updated(_)   => "updated",
+            StoreEvent::Deleted(_)   => "deleted",
+            StoreEvent::Moved { .. } => "moved",
+        }
+    }
+
+    /// The id of the entry the event is about, the new id for moves
+    pub fn id(&self) -> &StoreId {
+        match *self {
+            StoreEvent::Created(ref id)       |
+            StoreEvent::Updated(ref id)       |
+            StoreEvent::Deleted(ref id)       |
+            StoreEvent::Moved { to: ref id, .. } => id,
+        }
+    }
+
+}
+
+/// Serializes to `{"event": "updated", "id": "foo/bar"}` or
+/// `{"event": "moved", "from": "foo/bar", "to": "foo/baz"}`
+impl Serialize for StoreEvent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(match *self {
+            StoreEvent::Moved { .. } => 3,
+            _                        => 2,
+        }))?;
+
+        map.serialize_entry("event", self.name())?;
+        match *self {
+            StoreEvent::Moved { ref from, ref to } => {
+                map.serialize_entry("from", &from.local_display_string())?;
+                map.serialize_entry("to", &to.local_display_string())?;
+            },
+            _ => map.serialize_entry("id", &self.id().local_display_string())?,
+        }
+        map.end()
+    }
+}
+
+/// The subscribers of a store
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Subscribers(Arc<Mutex<Vec<Sender<StoreEvent>>>>);
+
+impl Subscribers {
+
+    pub(crate) fn add(&self, sender: Sender<StoreEvent>) {
+        if let Ok(mut subscribers) = self.0.lock() {
+            subscribers.push(sender);
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.lock().map(|s| s.is_empty()).unwrap_or(true)
+    }
+
+    /// Send the event to all subscribers, forgetting the ones which hung up
+    pub(crate) fn emit(&self, event: StoreEvent) {
+        trace!("Store event: {:?}", event);
+        if let Ok(mut subscribers) = self.0.lock() {
+            subscribers.retain(|s| s.send(event.clone()).is_ok());
+        }
+    }
+
+}
+
+#[cfg(feature = "watch")]
+pub use self::watch::ExternalWatcher;
+
+#[cfg(feature = "watch")]
+mod watch {
+    use std::path::PathBuf;
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    use notify::DebouncedEvent;
+    use notify::RecommendedWatcher;
+    use notify::RecursiveMode;
+    use notify::Watcher;
+    use failure::Fallible as Result;
+    use failure::ResultExt;
+
+    use super::StoreEvent;
+    use super::Subscribers;
+    use crate::storeid::StoreId;
+
+    /// Map a path inside the store to a StoreId, `None` for internal files (hidden paths)
+    pub(super) fn storeid_for_path(storepath: &PathBuf, path: &PathBuf) -> Option<StoreId> {
+        let local = path.strip_prefix(storepath).ok()?;
+        let hidden = local
+            .components()
+            .any(|c| c.as_os_str().to_str().map(|s| s.starts_with('.')).unwrap_or(true));
+
+        if hidden || local.as_os_str().is_empty() {
+            None
+        } else {
+            StoreId::new(local.to_path_buf()).ok()
+        }
+    }
+
+    /// Watches the store directory for changes from other processes, stops watching on drop
+    pub struct ExternalWatcher {
+        _watcher: RecommendedWatcher,
+    }
+
+    impl ExternalWatcher {
+
+        /// Watch `storepath` and send the changes to `subscribers`
+        ///
+        /// Filesystem events are collected for `delay` before they are sent, so that an editor
+        /// saving a file results in one event.
+        pub(crate) fn new(storepath: PathBuf, subscribers: Subscribers, delay: Duration)
+            -> Result<ExternalWatcher>
+        {
+            let (tx, rx)    = channel();
+            let mut watcher = ::notify::watcher(tx, delay)
+                .context(format_err!("Failed to create watcher for {}", storepath.display()))?;
+
+            watcher
+                .watch(&storepath, RecursiveMode::Recursive)
+                .context(format_err!("Failed to watch {}", storepath.display()))?;
+
+            // The thread ends when the watcher is dropped, as this closes the channel
+            let _ = ::std::thread::spawn(move || {
+                for event in rx {
+                    if let Some(event) = translate(&storepath, event) {
+                        subscribers.emit(event);
+                    }
+                }
+                debug!("Stopped watching {}", storepath.display());
+            });
+
+            Ok(ExternalWatcher { _watcher: watcher })
+        }
+
+    }
+
+    fn translate(storepath: &PathBuf, event: DebouncedEvent) -> Option<StoreEvent> {
+        let id = |path: &PathBuf| storeid_for_path(storepath, path);
+
+        match event {
+            DebouncedEvent::Create(ref path) if path.is_file() => id(path).map(StoreEvent::Created),
+            DebouncedEvent::Write(ref path)                    => id(path).map(StoreEvent::Updated),
+            DebouncedEvent::Remove(ref path)                   => id(path).map(StoreEvent::Deleted),
+            DebouncedEvent::Rename(ref from, ref to)           => match (id(from), id(to)) {
+                (Some(from), Some(to)) => Some(StoreEvent::Moved { from, to }),
+                (None, Some(to))       => Some(StoreEvent::Updated(to)), // editors write via temp files
+                (Some(from), None)     => Some(StoreEvent::Deleted(from)),
+                (None, None)           => None,
+            },
+            DebouncedEvent::Error(e, path) => {
+                warn!("Error while watching the store ({:?}): {}", path, e);
+                None
+            },
+            _ => None,
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_event_json() {
+        let a = StoreId::new(PathBuf::from("a/b")).unwrap();
+        let c = StoreId::new(PathBuf::from("c")).unwrap();
+
+        let json = ::serde_json::to_string(&StoreEvent::Updated(a.clone())).unwrap();
+        assert_eq!(json, r#"{"event":"updated","id":"a/b"}"#);
+
+        let json = ::serde_json::to_string(&StoreEvent::Moved { from: a, to: c }).unwrap();
+        assert_eq!(json, r#"{"event":"moved","from":"a/b","to":"c"}"#);
+    }
+
+    #[test]
+    #[cfg(feature = "watch")]
+    fn test_storeid_for_path() {
+        let store = PathBuf::from("/store");
+        let id    = |p: &str| watch::storeid_for_path(&store, &PathBuf::from(p));
+
+        assert_eq!(id("/store/a/b"), Some(StoreId::new(PathBuf::from("a/b")).unwrap()));
+        assert_eq!(id("/store/.history/a/b.log"), None);
+        assert_eq!(id("/store/a/.b.swp"), None);
+        assert_eq!(id("/store"), None);
+        assert_eq!(id("/elsewhere/a"), None);
+    }
+}
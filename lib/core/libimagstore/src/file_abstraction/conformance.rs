@@ -0,0 +1,285 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Conformance checks for `FileAbstraction` implementations
+//!
+//! Each check gets a fresh, empty backend and the store path it operates on and fails with an
+//! error describing the violated rule of the contract (see the `file_abstraction` module).
+//!
+//! A backend implementation can run all checks from a test:
+//!
+//! ```ignore
+//! #[test]
+//! fn test_backend_conformance() {
+//!     check_backend(|| {
+//!         let backend = Arc::new(MyBackend::new()) as Arc<FileAbstraction>;
+//!         Ok((PathBuf::from("/"), backend))
+//!     }).unwrap();
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use failure::Fallible as Result;
+use failure::ResultExt;
+use failure::err_msg;
+
+use super::Drain;
+use super::FileAbstraction;
+use crate::store::Entry;
+use crate::store::Store;
+use crate::storeid::StoreId;
+use crate::storeid::StoreIdWithBase;
+
+/// A conformance check, gets the store path and a fresh backend
+pub type Check = fn(&PathBuf, Arc<FileAbstraction>) -> Result<()>;
+
+/// All checks, by name
+pub fn checks() -> Vec<(&'static str, Check)> {
+    vec![
+        ("write_read"        , check_write_read),
+        ("read_missing"      , check_read_missing),
+        ("remove"            , check_remove),
+        ("copy"              , check_copy),
+        ("rename"            , check_rename),
+        ("create_dir_all"    , check_create_dir_all),
+        ("pathes_recursively", check_pathes_recursively),
+        ("fill"              , check_fill),
+        ("store"             , check_store),
+    ]
+}
+
+/// Run all checks
+///
+/// `new_backend` is called once per check and must return the store path and a new, empty
+/// backend which is not shared (the `fill` check needs mutable access to it).
+pub fn check_backend<F>(mut new_backend: F) -> Result<()>
+    where F: FnMut() -> Result<(PathBuf, Arc<FileAbstraction>)>
+{
+    for (name, check) in checks() {
+        debug!("Running backend conformance check: {}", name);
+        let (storepath, backend) = new_backend()?;
+        check(&storepath, backend).context(format_err!("Backend conformance check failed: {}", name))?;
+    }
+
+    Ok(())
+}
+
+/// Written entries can be read, overwritten and read again
+pub fn check_write_read(storepath: &PathBuf, backend: Arc<FileAbstraction>) -> Result<()> {
+    let entry = new_entry("check/write_read", "first")?;
+    let path  = write(storepath, &backend, &entry)?;
+
+    ensure!(backend.exists(&path)?, "Written file does not exist");
+    ensure!(backend.is_file(&path)?, "Written file is not a file");
+    ensure!(read(storepath, &backend, &path)? == Some(entry), "Read entry differs from written entry");
+
+    let entry = new_entry("check/write_read", "second")?;
+    let _     = write(storepath, &backend, &entry)?;
+    ensure!(read(storepath, &backend, &path)? == Some(entry), "Overwritten entry was not read back");
+    Ok(())
+}
+
+/// Reading a file which does not exist yields `None`
+pub fn check_read_missing(storepath: &PathBuf, backend: Arc<FileAbstraction>) -> Result<()> {
+    let path = storepath.join("check/missing");
+
+    ensure!(!backend.exists(&path)?, "Missing file exists");
+    ensure!(!backend.is_file(&path)?, "Missing file is a file");
+    ensure!(read(storepath, &backend, &path)?.is_none(), "Reading a missing file yields an entry");
+    Ok(())
+}
+
+/// Removed files are gone, removing a missing file fails
+pub fn check_remove(storepath: &PathBuf, backend: Arc<FileAbstraction>) -> Result<()> {
+    let path = write(storepath, &backend, &new_entry("check/remove", "content")?)?;
+
+    backend.remove_file(&path)?;
+    ensure!(!backend.exists(&path)?, "Removed file still exists");
+    ensure!(read(storepath, &backend, &path)?.is_none(), "Removed file can still be read");
+    ensure!(backend.remove_file(&path).is_err(), "Removing a missing file does not fail");
+    Ok(())
+}
+
+/// Copies have the same content, copying a missing file fails
+pub fn check_copy(storepath: &PathBuf, backend: Arc<FileAbstraction>) -> Result<()> {
+    let from = write(storepath, &backend, &new_entry("check/copy_from", "content")?)?;
+    let to   = storepath.join("check/copy_to");
+
+    backend.copy(&from, &to)?;
+    ensure!(backend.exists(&from)?, "Source of copy is gone");
+    ensure!(backend.exists(&to)?, "Target of copy does not exist");
+
+    let copied = read(storepath, &backend, &to)?.ok_or_else(|| err_msg("Copied file cannot be read"))?;
+    ensure!(copied.get_content() == "content", "Copied file has different content");
+
+    let missing = storepath.join("check/missing");
+    ensure!(backend.copy(&missing, &to).is_err(), "Copying a missing file does not fail");
+    Ok(())
+}
+
+/// Renamed files are moved, renaming a missing file fails
+pub fn check_rename(storepath: &PathBuf, backend: Arc<FileAbstraction>) -> Result<()> {
+    let from = write(storepath, &backend, &new_entry("check/rename_from", "content")?)?;
+    let to   = storepath.join("check/rename_to");
+
+    backend.rename(&from, &to)?;
+    ensure!(!backend.exists(&from)?, "Source of rename still exists");
+    ensure!(backend.exists(&to)?, "Target of rename does not exist");
+
+    let renamed = read(storepath, &backend, &to)?.ok_or_else(|| err_msg("Renamed file cannot be read"))?;
+    ensure!(renamed.get_content() == "content", "Renamed file has different content");
+
+    ensure!(backend.rename(&from, &to).is_err(), "Renaming a missing file does not fail");
+    Ok(())
+}
+
+/// Creating directories is idempotent, directories are not files
+pub fn check_create_dir_all(storepath: &PathBuf, backend: Arc<FileAbstraction>) -> Result<()> {
+    let path = storepath.join("check/dir/subdir");
+
+    backend.create_dir_all(&path)?;
+    backend.create_dir_all(&path).context("Creating an existing directory failed")?;
+    ensure!(!backend.is_file(&path)?, "Directory is a file");
+    Ok(())
+}
+
+/// All entries are found, hidden paths are skipped, collections are respected
+pub fn check_pathes_recursively(storepath: &PathBuf, backend: Arc<FileAbstraction>) -> Result<()> {
+    for name in &["a/1", "a/2", "b/1", ".hidden/1"] {
+        let _ = write(storepath, &backend, &new_entry(name, "content")?)?;
+    }
+
+    let ids = |collection: Option<&str>| -> Result<Vec<String>> {
+        let mut iter = backend.pathes_recursively(storepath.clone(), storepath, backend.clone())?;
+        if let Some(c) = collection {
+            iter = iter.in_collection(c)?;
+        }
+
+        let mut ids = iter
+            .map(|id| id.and_then(|id| id.without_base().to_str()))
+            .collect::<Result<Vec<_>>>()?;
+        ids.sort();
+        Ok(ids)
+    };
+
+    ensure!(ids(None)? == vec!["a/1", "a/2", "b/1"], "Wrong pathes: {:?}", ids(None)?);
+    ensure!(ids(Some("a"))? == vec!["a/1", "a/2"], "Wrong pathes in collection: {:?}", ids(Some("a"))?);
+    Ok(())
+}
+
+/// Filled entries can be read, drained entries exist
+pub fn check_fill(storepath: &PathBuf, mut backend: Arc<FileAbstraction>) -> Result<()> {
+    let entry = new_entry("check/fill", "content")?;
+    let path  = storepath.join(entry.get_location().local());
+
+    {
+        let mut hm = HashMap::new();
+        hm.insert(path.clone(), entry.clone());
+
+        Arc::get_mut(&mut backend)
+            .ok_or_else(|| err_msg("Backend is shared"))?
+            .fill(Drain::new(hm))?;
+    }
+
+    ensure!(read(storepath, &backend, &path)? == Some(entry), "Filled entry cannot be read");
+
+    for (path, entry) in backend.drain()?.iter() {
+        ensure!(read(storepath, &backend, &path)? == Some(entry), "Drained entry cannot be read: {}", path.display());
+    }
+
+    Ok(())
+}
+
+/// A `Store` can be built on the backend and used
+///
+/// The store path must exist on the filesystem.
+pub fn check_store(storepath: &PathBuf, backend: Arc<FileAbstraction>) -> Result<()> {
+    let store = Store::new_with_backend(storepath.clone(), &None, backend)?;
+    let id    = StoreId::new(PathBuf::from("check/store"))?;
+    let moved = StoreId::new(PathBuf::from("check/moved"))?;
+
+    {
+        let mut entry = store.create(id.clone())?;
+        entry.get_content_mut().push_str("content");
+    }
+
+    ensure!(store.get_copy(id.clone())?.get_content() == "content", "Entry was not written");
+    ensure!(store.entries()?.count() == 1, "Wrong number of entries");
+
+    store.move_by_id(id.clone(), moved.clone())?;
+    ensure!(store.get(id)?.is_none(), "Moved entry still exists");
+    ensure!(store.get_copy(moved.clone())?.get_content() == "content", "Moved entry was not written");
+
+    store.delete(moved.clone())?;
+    ensure!(store.get(moved)?.is_none(), "Deleted entry still exists");
+    Ok(())
+}
+
+fn new_entry(name: &str, content: &str) -> Result<Entry> {
+    let mut entry = Entry::new(StoreId::new(PathBuf::from(name))?);
+    entry.get_content_mut().push_str(content);
+    Ok(entry)
+}
+
+fn write(storepath: &PathBuf, backend: &Arc<FileAbstraction>, entry: &Entry) -> Result<PathBuf> {
+    let path = storepath.join(entry.get_location().local());
+    backend.new_instance(path.clone()).write_file_content(entry)?;
+    Ok(path)
+}
+
+fn read(storepath: &PathBuf, backend: &Arc<FileAbstraction>, path: &PathBuf) -> Result<Option<Entry>> {
+    let id = StoreIdWithBase::from_full_path(storepath, path.clone())?;
+    backend.new_instance(path.clone()).get_file_content(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::file_abstraction::FileAbstraction;
+    use crate::file_abstraction::fs::FSFileAbstraction;
+    use crate::file_abstraction::inmemory::InMemoryFileAbstraction;
+
+    #[test]
+    fn test_inmemory_backend_conformance() {
+        check_backend(|| {
+            let backend = Arc::new(InMemoryFileAbstraction::default()) as Arc<FileAbstraction>;
+            Ok((PathBuf::from("/"), backend))
+        }).unwrap();
+    }
+
+    #[test]
+    fn test_fs_backend_conformance() {
+        let mut dirs = vec![];
+        check_backend(|| {
+            let dir     = TempDir::new("imag-backend-conformance")?;
+            let path    = dir.path().to_path_buf();
+            let backend = Arc::new(FSFileAbstraction::default()) as Arc<FileAbstraction>;
+            dirs.push(dir);
+            Ok((path, backend))
+        }).unwrap();
+    }
+}
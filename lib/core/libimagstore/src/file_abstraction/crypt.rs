@@ -0,0 +1,801 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Encryption of entries at rest
+//!
+//! With the `encryption` feature, the store can encrypt the entries of configured collections
+//! before they are handed to the backend:
+//!
+//! ```toml
+//! [store.encryption]
+//! enabled        = true
+//! collections    = [ "diary/*", "contact/*" ]
+//! encrypt-header = false
+//! keyfile        = "/home/user/.imag-store.key"
+//! ```
+//!
+//! `collections` are glob patterns which are matched against the ids of the entries. The key is
+//! derived (with Argon2id) from the contents of `keyfile` or, if no keyfile is configured, from
+//! the passphrase in the `IMAG_STORE_PASSPHRASE` environment variable. The salt of the key lives
+//! in the `.encryption.toml` file in the store, together with a check value which detects a wrong
+//! passphrase when the store is opened.
+//!
+//! Entries are encrypted with XChaCha20-Poly1305. If `encrypt-header` is set, the whole entry is
+//! encrypted and the backend only sees `imag.version`, otherwise the header stays readable (so
+//! that it can be queried and indexed) and only the content is encrypted. Either way, the header
+//! of the stored entry gets an `imag.encryption` table and the content is the base64 encoded
+//! ciphertext.
+//!
+//! `CryptFileAbstraction` wraps the backend of the store and does the work: encrypted entries are
+//! decrypted in `FileAbstractionInstance::get_file_content()`, no matter whether they are in one
+//! of the configured collections (so entries can be removed from the set of encrypted
+//! collections), entries in the configured collections are encrypted in
+//! `FileAbstractionInstance::write_file_content()`.
+//!
+//! `Store::rekey()` (`imag store rekey`) re-writes all entries with a new key. This also encrypts
+//! entries which were written before their collection was configured to be encrypted. The
+//! parameters of the new key are written to `.encryption-rekey.toml` before any entry is
+//! re-written and replace `.encryption.toml` when all entries are done. While re-keying, entries
+//! are read with the new or the old key, so an interrupted re-keying is finished by running it
+//! again with the same new key.
+//!
+//! Entries of the encrypted collections are encrypted in the transaction journal as well. A
+//! persistent store refuses to use the index or the history together with encryption, as they
+//! keep the entries in plain text.
+
+use std::env;
+use std::fmt::Debug;
+use std::fmt::Error as FmtError;
+use std::fmt::Formatter;
+use std::fs::OpenOptions;
+use std::io::Read;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::RwLock;
+
+use chacha20poly1305::XChaCha20Poly1305;
+use chacha20poly1305::XNonce;
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::aead::AeadCore;
+use chacha20poly1305::aead::KeyInit;
+use chacha20poly1305::aead::OsRng;
+use chacha20poly1305::aead::rand_core::RngCore;
+use glob::Pattern;
+use toml::Value;
+use toml_query::insert::TomlValueInsertExt;
+use toml_query::read::TomlValueReadExt;
+use toml_query::read::TomlValueReadTypeExt;
+use failure::Fallible as Result;
+use failure::ResultExt;
+use failure::Error;
+
+use libimagerror::errors::ErrorMsg as EM;
+
+use super::Drain;
+use super::FileAbstraction;
+use super::FileAbstractionInstance;
+use super::iter::PathIterator;
+use crate::store::Entry;
+use crate::storeid::StoreId;
+use crate::storeid::StoreIdWithBase;
+
+/// The name of the file in the store which holds the salt of the key
+pub const PARAMS_FILE_NAME: &str = ".encryption.toml";
+
+/// The name of the file in the store which holds the salt of the new key while re-keying
+pub const REKEY_PARAMS_FILE_NAME: &str = ".encryption-rekey.toml";
+
+/// The environment variable the passphrase is read from if no keyfile is configured
+pub const PASSPHRASE_ENV_VAR: &str = "IMAG_STORE_PASSPHRASE";
+
+const CIPHER_NAME: &str = "xchacha20poly1305";
+const CHECK_TEXT: &[u8] = b"imag";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const KDF_MEMORY_COST: u32 = 19456; // KiB
+const KDF_TIME_COST: u32 = 2;
+
+/// Where the key of the store is derived from
+pub enum Secret {
+    Passphrase(String),
+    Keyfile(PathBuf),
+}
+
+impl Secret {
+
+    /// Read `store.encryption.keyfile`, fall back to the passphrase in `IMAG_STORE_PASSPHRASE`
+    pub fn from_config(config: &Option<Value>) -> Result<Secret> {
+        let keyfile = match *config {
+            Some(ref config) => config.read_string("store.encryption.keyfile").context(EM::TomlQueryError)?,
+            None             => None,
+        };
+
+        match keyfile {
+            Some(path) => Ok(Secret::Keyfile(PathBuf::from(path))),
+            None       => env::var(PASSPHRASE_ENV_VAR)
+                .map(Secret::Passphrase)
+                .map_err(|_| format_err!("No key for the encrypted store: set 'store.encryption.keyfile' or ${}",
+                                         PASSPHRASE_ENV_VAR)),
+        }
+    }
+
+    fn bytes(&self) -> Result<Vec<u8>> {
+        match *self {
+            Secret::Passphrase(ref pw) => Ok(pw.as_bytes().to_vec()),
+            Secret::Keyfile(ref path)  => {
+                let mut buf = vec![];
+                let _ = OpenOptions::new()
+                    .read(true)
+                    .open(path)
+                    .and_then(|mut file| file.read_to_end(&mut buf))
+                    .context(format_err!("Cannot read keyfile: {}", path.display()))?;
+                Ok(buf)
+            },
+        }
+    }
+
+}
+
+impl Debug for Secret {
+    fn fmt(&self, fmt: &mut Formatter) -> ::std::result::Result<(), FmtError> {
+        match *self {
+            Secret::Passphrase(_)     => write!(fmt, "Secret::Passphrase(..)"),
+            Secret::Keyfile(ref path) => write!(fmt, "Secret::Keyfile({})", path.display()),
+        }
+    }
+}
+
+/// The contents of the `.encryption.toml` file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct KeyParams {
+    salt: String,
+    memory_cost: u32,
+    time_cost: u32,
+
+    /// `CHECK_TEXT`, encrypted with the key
+    check: String,
+}
+
+struct Key {
+    params: KeyParams,
+    cipher: XChaCha20Poly1305,
+}
+
+impl Key {
+
+    /// Derive a new key with a new salt
+    fn generate(secret: &Secret) -> Result<Key> {
+        let mut salt = [0; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+
+        let mut params = KeyParams {
+            salt: ::base64::encode(&salt),
+            memory_cost: KDF_MEMORY_COST,
+            time_cost: KDF_TIME_COST,
+            check: String::new(),
+        };
+
+        let cipher   = derive_cipher(secret, &params)?;
+        let mut key  = Key { params: params.clone(), cipher };
+        params.check = key.encrypt(CHECK_TEXT)?;
+        key.params   = params;
+        Ok(key)
+    }
+
+    /// Derive the key described by `params`, fails if `secret` is not the right one
+    fn from_params(secret: &Secret, params: KeyParams) -> Result<Key> {
+        let cipher = derive_cipher(secret, &params)?;
+        let key    = Key { params, cipher };
+
+        match key.decrypt(&key.params.check) {
+            Ok(ref check) if check.as_slice() == CHECK_TEXT => Ok(key),
+            _ => Err(format_err!("Wrong passphrase or keyfile for the encrypted store")),
+        }
+    }
+
+    /// Encrypt with a random nonce, returns the base64 encoded nonce and ciphertext
+    fn encrypt(&self, plain: &[u8]) -> Result<String> {
+        let nonce      = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plain)
+            .map_err(|_| format_err!("Encryption failed"))?;
+
+        let mut buf = nonce.to_vec();
+        buf.extend(ciphertext);
+        Ok(::base64::encode(&buf))
+    }
+
+    fn decrypt(&self, text: &str) -> Result<Vec<u8>> {
+        let buf = ::base64::decode(text.trim()).context(format_err!("Encrypted data is not valid base64"))?;
+        if buf.len() < NONCE_LEN {
+            return Err(format_err!("Encrypted data is too short"))
+        }
+
+        let (nonce, ciphertext) = buf.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| format_err!("Decryption failed, wrong key or corrupted data"))
+    }
+
+}
+
+impl Debug for Key {
+    fn fmt(&self, fmt: &mut Formatter) -> ::std::result::Result<(), FmtError> {
+        write!(fmt, "Key {{ params: {:?} }}", self.params)
+    }
+}
+
+fn derive_cipher(secret: &Secret, params: &KeyParams) -> Result<XChaCha20Poly1305> {
+    let salt   = ::base64::decode(&params.salt).context(format_err!("Invalid salt in {}", PARAMS_FILE_NAME))?;
+    let config = ::argon2::Config {
+        variant: ::argon2::Variant::Argon2id,
+        mem_cost: params.memory_cost,
+        time_cost: params.time_cost,
+        hash_length: 32,
+        ..::argon2::Config::default()
+    };
+
+    let key = ::argon2::hash_raw(&secret.bytes()?, &salt, &config)
+        .context(format_err!("Key derivation failed"))?;
+
+    XChaCha20Poly1305::new_from_slice(&key).map_err(|_| format_err!("Key derivation failed"))
+}
+
+/// The encryption settings and the key of a store
+#[derive(Debug)]
+pub struct Encryption {
+    collections: Vec<Pattern>,
+    encrypt_header: bool,
+
+    /// Where the key parameters are stored, `None` for non-persistent backends
+    params_path: Option<PathBuf>,
+    key: RwLock<Key>,
+
+    /// The previous key while re-keying, for the entries which were not re-written yet
+    old_key: RwLock<Option<Key>>,
+}
+
+impl Encryption {
+
+    /// Read the "store.encryption" settings, `None` if encryption is not enabled
+    ///
+    /// Loads the key parameters from the store if they exist, otherwise a new key is generated and
+    /// (if `persistent`) its parameters are written to the store.
+    pub(crate) fn from_config(storepath: &PathBuf, persistent: bool, config: &Option<Value>)
+        -> Result<Option<Encryption>>
+    {
+        let config = match *config {
+            Some(ref config) => config,
+            None             => return Ok(None),
+        };
+
+        if !config.read_bool("store.encryption.enabled").context(EM::TomlQueryError)?.unwrap_or(false) {
+            return Ok(None)
+        }
+
+        let collections = match config.read("store.encryption.collections").context(EM::TomlQueryError)? {
+            Some(&Value::Array(ref patterns)) => patterns
+                .iter()
+                .map(|p| p
+                     .as_str()
+                     .ok_or_else(|| format_err!("Config key 'store.encryption.collections' must be an array of strings"))
+                     .and_then(|p| Pattern::new(p).context(format_err!("Invalid pattern: {}", p)).map_err(Error::from)))
+                .collect::<Result<Vec<_>>>()?,
+            Some(_) => return Err(format_err!("Config key 'store.encryption.collections' must be an array of strings")),
+            None    => vec![],
+        };
+
+        let encrypt_header = config
+            .read_bool("store.encryption.encrypt-header")
+            .context(EM::TomlQueryError)?
+            .unwrap_or(false);
+
+        let secret      = Secret::from_config(&Some(config.clone()))?;
+        let params_path = if persistent { Some(storepath.join(PARAMS_FILE_NAME)) } else { None };
+        let key         = match params_path {
+            Some(ref path) if path.exists() => Key::from_params(&secret, read_params(path)?)?,
+            _                               => Key::generate(&secret)?,
+        };
+
+        let encryption = Encryption {
+            collections,
+            encrypt_header,
+            params_path,
+            key: RwLock::new(key),
+            old_key: RwLock::new(None),
+        };
+
+        if encryption.params_path.as_ref().map(|p| !p.exists()).unwrap_or(false) {
+            encryption.save_params()?;
+        }
+
+        if encryption.rekey_params_path().map(|p| p.exists()).unwrap_or(false) {
+            warn!("Re-keying the store was interrupted, run it again with the same new key to finish it");
+        }
+
+        Ok(Some(encryption))
+    }
+
+    /// Whether the entry is in one of the encrypted collections
+    pub fn encrypts(&self, id: &StoreId) -> bool {
+        let id = id.local_display_string();
+        self.collections.iter().any(|p| p.matches(&id))
+    }
+
+    fn rekey_params_path(&self) -> Option<PathBuf> {
+        self.params_path.as_ref().map(|p| p.with_file_name(REKEY_PARAMS_FILE_NAME))
+    }
+
+    /// Start re-keying: entries are written with a new key derived from `secret`, and read with
+    /// the new or the old key
+    ///
+    /// The parameters of the new key are written to the store before the key is used. If an
+    /// earlier re-keying was interrupted, its key is used again, so `secret` must be the same.
+    pub(crate) fn begin_rekey(&self, secret: &Secret) -> Result<()> {
+        let path = self.rekey_params_path();
+        let new  = match path {
+            Some(ref path) if path.exists() => {
+                info!("Resuming interrupted re-keying");
+                Key::from_params(secret, read_params(path)?)
+                    .context(format_err!("An interrupted re-keying used another new key"))?
+            },
+            _ => Key::generate(secret)?,
+        };
+
+        if let Some(ref path) = path {
+            write_params(path, &new.params)?;
+        }
+
+        let mut key     = self.key.write().map_err(|_| Error::from(EM::LockError))?;
+        let mut old_key = self.old_key.write().map_err(|_| Error::from(EM::LockError))?;
+        *old_key = Some(::std::mem::replace(&mut *key, new));
+        Ok(())
+    }
+
+    /// Finish re-keying after all entries were written with the new key
+    ///
+    /// The parameters of the new key replace the ones of the old key in the store.
+    pub(crate) fn finish_rekey(&self) -> Result<()> {
+        if let (Some(rekey_path), Some(path)) = (self.rekey_params_path(), self.params_path.as_ref()) {
+            ::std::fs::rename(&rekey_path, path)
+                .context(format_err!("Cannot write {}", path.display()))?;
+        }
+
+        let mut old_key = self.old_key.write().map_err(|_| Error::from(EM::LockError))?;
+        *old_key = None;
+        Ok(())
+    }
+
+    /// Write the key parameters to the store, if the backend is persistent
+    pub(crate) fn save_params(&self) -> Result<()> {
+        match self.params_path {
+            Some(ref path) => {
+                let key = self.key.read().map_err(|_| Error::from(EM::LockError))?;
+                write_params(path, &key.params)
+            },
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn encrypt_entry(&self, entry: &Entry) -> Result<Entry> {
+        let key = self.key.read().map_err(|_| Error::from(EM::LockError))?;
+
+        let (mut encrypted, plain, part) = if self.encrypt_header {
+            (Entry::new(entry.get_location().clone()), entry.to_str()?, "entry")
+        } else {
+            (entry.clone(), entry.get_content().to_string(), "content")
+        };
+
+        let mut marker = ::toml::map::Map::new();
+        marker.insert(String::from("cipher"), Value::String(String::from(CIPHER_NAME)));
+        marker.insert(String::from("part"), Value::String(String::from(part)));
+
+        let _ = encrypted
+            .get_header_mut()
+            .insert("imag.encryption", Value::Table(marker))
+            .context(EM::EntryHeaderWriteError)?;
+        *encrypted.get_content_mut() = key.encrypt(plain.as_bytes())?;
+
+        Ok(encrypted)
+    }
+
+    pub(crate) fn decrypt_entry(&self, id: StoreId, mut entry: Entry) -> Result<Entry> {
+        let part = entry
+            .get_header()
+            .read_string("imag.encryption.part")
+            .context(EM::EntryHeaderReadError)?
+            .ok_or_else(|| format_err!("Encrypted entry has no 'imag.encryption.part'"))?;
+
+        let cipher = entry.get_header().read_string("imag.encryption.cipher").context(EM::EntryHeaderReadError)?;
+        if cipher.as_ref().map(|c| c != CIPHER_NAME).unwrap_or(true) {
+            return Err(format_err!("Unsupported cipher: {:?}", cipher))
+        }
+
+        let plain = {
+            let key     = self.key.read().map_err(|_| Error::from(EM::LockError))?;
+            let old_key = self.old_key.read().map_err(|_| Error::from(EM::LockError))?;
+            let plain   = match (key.decrypt(entry.get_content()), old_key.as_ref()) {
+                (Ok(plain), _)          => plain,
+                (Err(_), Some(old_key)) => old_key.decrypt(entry.get_content())?,
+                (Err(e), None)          => return Err(e),
+            };
+
+            String::from_utf8(plain).context(EM::UTF8Error)?
+        };
+
+        match part.as_ref() {
+            "entry"   => Entry::from_str(id, &plain),
+            "content" => {
+                if let Some(imag) = entry.get_header_mut().get_mut("imag").and_then(Value::as_table_mut) {
+                    let _ = imag.remove("encryption");
+                }
+                *entry.get_content_mut() = plain;
+                Ok(entry)
+            },
+            other => Err(format_err!("Unknown encrypted part of entry: {}", other)),
+        }
+    }
+
+}
+
+fn read_params(path: &PathBuf) -> Result<KeyParams> {
+    let mut text = String::new();
+    let _ = OpenOptions::new()
+        .read(true)
+        .open(path)
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .context(format_err!("Cannot read {}", path.display()))?;
+
+    ::toml::de::from_str(&text)
+        .context(format_err!("Invalid {}", path.display()))
+        .map_err(Error::from)
+}
+
+/// Write key parameters to `path`, atomically
+fn write_params(path: &PathBuf, params: &KeyParams) -> Result<()> {
+    let text = ::toml::ser::to_string(params).context(format_err!("Cannot serialize key parameters"))?;
+    let tmp  = path.with_extension("toml.new");
+
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&tmp)
+        .and_then(|mut file| file.write_all(text.as_bytes()))
+        .and_then(|_| ::std::fs::rename(&tmp, path))
+        .context(format_err!("Cannot write {}", path.display()))
+        .map_err(Error::from)
+}
+
+/// Whether the entry, as it is stored in the backend, is encrypted
+pub fn is_encrypted(entry: &Entry) -> bool {
+    entry.get_header().read("imag.encryption").map(|v| v.is_some()).unwrap_or(false)
+}
+
+/// A backend which encrypts the entries before they are written to another backend
+#[derive(Debug)]
+pub struct CryptFileAbstraction {
+    inner: Arc<FileAbstraction>,
+    encryption: Arc<Encryption>,
+}
+
+impl CryptFileAbstraction {
+
+    pub fn new(inner: Arc<FileAbstraction>, encryption: Arc<Encryption>) -> CryptFileAbstraction {
+        CryptFileAbstraction { inner, encryption }
+    }
+
+}
+
+impl FileAbstraction for CryptFileAbstraction {
+
+    fn remove_file(&self, path: &PathBuf) -> Result<()> {
+        self.inner.remove_file(path)
+    }
+
+    fn copy(&self, from: &PathBuf, to: &PathBuf) -> Result<()> {
+        self.inner.copy(from, to)
+    }
+
+    fn rename(&self, from: &PathBuf, to: &PathBuf) -> Result<()> {
+        self.inner.rename(from, to)
+    }
+
+    fn create_dir_all(&self, path: &PathBuf) -> Result<()> {
+        self.inner.create_dir_all(path)
+    }
+
+    fn exists(&self, path: &PathBuf) -> Result<bool> {
+        self.inner.exists(path)
+    }
+
+    fn is_file(&self, path: &PathBuf) -> Result<bool> {
+        self.inner.is_file(path)
+    }
+
+    fn new_instance(&self, p: PathBuf) -> Box<FileAbstractionInstance> {
+        Box::new(CryptFileAbstractionInstance {
+            inner: self.inner.new_instance(p),
+            encryption: self.encryption.clone(),
+        })
+    }
+
+    /// Drains the inner backend, with the entries decrypted
+    fn drain(&self) -> Result<Drain> {
+        let mut drain = self.inner.drain()?;
+        drain
+            .iter()
+            .map(|(path, entry)| {
+                let entry = if is_encrypted(&entry) {
+                    let id = entry.get_location().clone();
+                    self.encryption.decrypt_entry(id, entry)?
+                } else {
+                    entry
+                };
+                Ok((path, entry))
+            })
+            .collect::<Result<_>>()
+            .map(Drain::new)
+    }
+
+    fn fill(&mut self, mut d: Drain) -> Result<()> {
+        let encryption = self.encryption.clone();
+        let drain = d
+            .iter()
+            .map(|(path, entry)| if encryption.encrypts(entry.get_location()) {
+                encryption.encrypt_entry(&entry).map(|e| (path, e))
+            } else {
+                Ok((path, entry))
+            })
+            .collect::<Result<_>>()
+            .map(Drain::new)?;
+
+        Arc::get_mut(&mut self.inner)
+            .ok_or_else(|| format_err!("Backend is shared"))?
+            .fill(drain)
+    }
+
+    fn pathes_recursively<'a>(&self, basepath: PathBuf, storepath: &'a PathBuf, backend: Arc<FileAbstraction>) -> Result<PathIterator<'a>> {
+        self.inner.pathes_recursively(basepath, storepath, backend)
+    }
+
+    fn is_persistent(&self) -> bool {
+        self.inner.is_persistent()
+    }
+}
+
+#[derive(Debug)]
+pub struct CryptFileAbstractionInstance {
+    inner: Box<FileAbstractionInstance>,
+    encryption: Arc<Encryption>,
+}
+
+impl FileAbstractionInstance for CryptFileAbstractionInstance {
+
+    fn get_file_content<'a>(&mut self, id: StoreIdWithBase<'a>) -> Result<Option<Entry>> {
+        match self.inner.get_file_content(id.clone())? {
+            Some(ref entry) if !is_encrypted(entry) => Ok(Some(entry.clone())),
+            Some(entry) => self
+                .encryption
+                .decrypt_entry(entry.get_location().clone(), entry)
+                .context(format_err!("Cannot decrypt entry"))
+                .map_err(Error::from)
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn write_file_content(&mut self, buf: &Entry) -> Result<()> {
+        if self.encryption.encrypts(buf.get_location()) {
+            let encrypted = self.encryption.encrypt_entry(buf)?;
+            self.inner.write_file_content(&encrypted)
+        } else {
+            self.inner.write_file_content(buf)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::file_abstraction::conformance::check_backend;
+    use crate::file_abstraction::inmemory::InMemoryFileAbstraction;
+    use crate::store::Store;
+
+    fn config(dir: &TempDir, keyfile: &str, encrypt_header: bool) -> Option<Value> {
+        let path = dir.path().join(keyfile);
+        if !path.exists() {
+            ::std::fs::write(&path, keyfile).unwrap();
+        }
+
+        let config = format!(r#"
+        [store]
+            implicit-create = true
+        [store.encryption]
+            enabled        = true
+            collections    = ["secret/*"]
+            encrypt-header = {}
+            keyfile        = "{}"
+        "#, encrypt_header, path.display());
+
+        Some(::toml::de::from_str(&config).unwrap())
+    }
+
+    fn encryption(dir: &TempDir, encrypt_header: bool) -> Arc<Encryption> {
+        let storepath = dir.path().to_path_buf();
+        Arc::new(Encryption::from_config(&storepath, false, &config(dir, "key", encrypt_header)).unwrap().unwrap())
+    }
+
+    #[test]
+    fn test_crypt_backend_conformance() {
+        let dir        = TempDir::new("imag-crypt-conformance").unwrap();
+        let encryption = encryption(&dir, true);
+
+        check_backend(|| {
+            let inner   = Arc::new(InMemoryFileAbstraction::default());
+            let backend = Arc::new(CryptFileAbstraction::new(inner, encryption.clone())) as Arc<FileAbstraction>;
+            Ok((PathBuf::from("/"), backend))
+        }).unwrap();
+    }
+
+    #[test]
+    fn test_encrypted_entries() {
+        let dir = TempDir::new("imag-crypt-entries").unwrap();
+
+        for &encrypt_header in [false, true].iter() {
+            let inner   = Arc::new(InMemoryFileAbstraction::default());
+            let backend = CryptFileAbstraction::new(inner.clone(), encryption(&dir, encrypt_header));
+            let base    = PathBuf::from("/");
+
+            for name in ["secret/diary", "public"].iter() {
+                let id        = StoreId::new(PathBuf::from(name)).unwrap();
+                let path      = base.join(name);
+                let mut entry = Entry::new(id.clone());
+                entry.get_content_mut().push_str("Dear diary");
+                let _ = entry.get_header_mut().insert("diary.mood", Value::String(String::from("fine"))).unwrap();
+
+                backend.new_instance(path.clone()).write_file_content(&entry).unwrap();
+
+                let stored = inner.new_instance(path.clone()).get_file_content(id.clone().with_base(&base)).unwrap().unwrap();
+                let read   = backend.new_instance(path).get_file_content(id.with_base(&base)).unwrap().unwrap();
+                assert_eq!(read, entry);
+
+                if *name == "public" {
+                    assert!(!is_encrypted(&stored));
+                    assert_eq!(stored, entry);
+                } else {
+                    assert!(is_encrypted(&stored));
+                    assert!(!stored.get_content().contains("Dear diary"));
+                    assert_eq!(stored.get_header().read("diary.mood").unwrap().is_some(), !encrypt_header);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_store_rekey() {
+        let dir   = TempDir::new("imag-crypt-rekey").unwrap();
+        let store = dir.path().join("store");
+
+        {
+            let store = Store::new(store.clone(), &config(&dir, "old", false)).unwrap();
+            let mut entry = store.create(PathBuf::from("secret/entry")).unwrap();
+            entry.get_content_mut().push_str("content");
+        }
+
+        assert!(Store::new(store.clone(), &config(&dir, "new", false)).is_err());
+
+        {
+            let store = Store::new(store.clone(), &config(&dir, "old", false)).unwrap();
+            let new   = Secret::Keyfile(dir.path().join("new"));
+            assert_eq!(store.rekey(&new).unwrap(), 1);
+        }
+
+        assert!(Store::new(store.clone(), &config(&dir, "old", false)).is_err());
+        let store = Store::new(store.clone(), &config(&dir, "new", false)).unwrap();
+        assert_eq!(store.get_copy(PathBuf::from("secret/entry")).unwrap().get_content(), "content");
+    }
+
+    #[test]
+    fn test_store_rekey_interrupted() {
+        let dir   = TempDir::new("imag-crypt-rekey-interrupted").unwrap();
+        let store = dir.path().join("store");
+        let names = ["secret/a", "secret/b", "secret/c"];
+
+        {
+            let store = Store::new(store.clone(), &config(&dir, "old", false)).unwrap();
+            for name in names.iter() {
+                let mut entry = store.create(PathBuf::from(name)).unwrap();
+                entry.get_content_mut().push_str(name);
+            }
+        }
+
+        {
+            let store = Store::new(store.clone(), &config(&dir, "old", false)).unwrap();
+            let new   = Secret::Keyfile(dir.path().join("new"));
+            ::std::fs::write(dir.path().join("new"), "new").unwrap();
+            assert!(store.rekey_entries(&new, Some(1)).is_err());
+        }
+
+        assert!(store.join(REKEY_PARAMS_FILE_NAME).exists());
+
+        {
+            let store = Store::new(store.clone(), &config(&dir, "old", false)).unwrap();
+            let other = Secret::Keyfile(dir.path().join("other"));
+            ::std::fs::write(dir.path().join("other"), "other").unwrap();
+            assert!(store.rekey(&other).is_err());
+
+            let new = Secret::Keyfile(dir.path().join("new"));
+            assert_eq!(store.rekey(&new).unwrap(), names.len());
+        }
+
+        assert!(!store.join(REKEY_PARAMS_FILE_NAME).exists());
+        let store = Store::new(store.clone(), &config(&dir, "new", false)).unwrap();
+        for name in names.iter() {
+            assert_eq!(store.get_copy(PathBuf::from(name)).unwrap().get_content(), *name);
+        }
+    }
+
+    #[test]
+    fn test_journal_is_encrypted() {
+        use crate::transaction::Operation;
+
+        let dir   = TempDir::new("imag-crypt-journal").unwrap();
+        let store = Store::new(dir.path().join("store"), &config(&dir, "key", false)).unwrap();
+
+        for &(name, encrypted) in [("secret/entry", true), ("public", false)].iter() {
+            let mut entry = Entry::new(StoreId::new(PathBuf::from(name)).unwrap());
+            entry.get_content_mut().push_str("Dear diary");
+            let op = Operation::Update(entry);
+
+            let sealed = store.seal_operation(&op).unwrap();
+            match sealed {
+                Operation::Update(ref e) => {
+                    assert_eq!(is_encrypted(e), encrypted);
+                    assert_eq!(e.get_content().contains("Dear diary"), !encrypted);
+                },
+                _ => panic!("Not an update: {:?}", sealed),
+            }
+
+            assert_eq!(store.unseal_operation(sealed).unwrap(), op);
+        }
+    }
+
+    #[test]
+    fn test_encryption_refuses_index_and_history() {
+        let dir = TempDir::new("imag-crypt-index").unwrap();
+
+        for table in ["index", "history"].iter() {
+            let mut config = config(&dir, "key", false).unwrap();
+            let _ = config.insert(&format!("store.{}.enabled", table), Value::Boolean(true)).unwrap();
+
+            let config = Some(config);
+            assert!(Store::new(dir.path().join("store"), &config).is_err());
+            assert!(Store::new_inmemory(dir.path().join("store"), &config).is_ok());
+        }
+    }
+}
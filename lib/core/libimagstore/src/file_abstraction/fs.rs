@@ -33,6 +33,7 @@ use crate::file_abstraction::iter::PathIterator;
 use crate::file_abstraction::iter::PathIterBuilder;
 
 use walkdir::WalkDir;
+use walkdir::DirEntry;
 use failure::ResultExt;
 use failure::Fallible as Result;
 use failure::Error;
@@ -176,6 +177,7 @@ impl PathIterBuilder for WalkDirPathIterBuilder {
             .min_depth(1)
             .max_open(100)
             .into_iter()
+            .filter_entry(|e| e.depth() == 0 || !is_hidden(e))
             .filter(|r| match r {
                 Err(_) => true,
                 Ok(path) => path.file_type().is_file(),
@@ -201,6 +203,16 @@ impl PathIterBuilder for WalkDirPathIterBuilder {
     }
 }
 
+/// Files and directories starting with a dot are not part of the store
+///
+/// These are used for store-internal data (like the index) or by other tools (like git).
+fn is_hidden(e: &DirEntry) -> bool {
+    e.file_name()
+        .to_str()
+        .map(|s| s.starts_with('.'))
+        .unwrap_or(false)
+}
+
 fn open_file<A: AsRef<Path>>(p: A) -> ::std::io::Result<Option<File>> {
     match OpenOptions::new().write(true).read(true).open(p) {
         Err(e) => match e.kind() {
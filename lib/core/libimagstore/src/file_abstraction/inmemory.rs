@@ -187,7 +187,7 @@ impl FileAbstraction for InMemoryFileAbstraction {
         Ok(())
     }
 
-    fn pathes_recursively<'a>(&self, _basepath: PathBuf, storepath: &'a PathBuf, backend: Arc<FileAbstraction>) -> Result<PathIterator<'a>> {
+    fn pathes_recursively<'a>(&self, basepath: PathBuf, storepath: &'a PathBuf, backend: Arc<FileAbstraction>) -> Result<PathIterator<'a>> {
         trace!("Building PathIterator object (inmemory implementation)");
         let keys : Vec<PathBuf> = self
             .backend()
@@ -195,16 +195,38 @@ impl FileAbstraction for InMemoryFileAbstraction {
             .map_err(|_| EM::LockError)?
             .get_mut()
             .keys()
+            .filter(|p| !is_hidden(p, &basepath))
             .map(PathBuf::from)
             .map(Ok)
             .collect::<Result<_>>()?; // we have to collect() because of the lock() above.
 
-        Ok(PathIterator::new(Box::new(InMemPathIterBuilder(keys)), storepath, backend))
+        Ok(PathIterator::new(Box::new(InMemPathIterBuilder(keys, basepath)), storepath, backend))
+    }
+
+    fn is_persistent(&self) -> bool {
+        false
     }
 }
 
+/// Check whether a component of `path` below `basepath` starts with a dot
+fn is_hidden(path: &PathBuf, basepath: &PathBuf) -> bool {
+    path.strip_prefix(basepath)
+        .unwrap_or(path)
+        .components()
+        .any(|c| c.as_os_str().to_str().map(|s| s.starts_with('.')).unwrap_or(false))
+}
+
 #[derive(Debug)]
-pub struct InMemPathIterBuilder(Vec<PathBuf>);
+pub struct InMemPathIterBuilder(Vec<PathBuf>, PathBuf);
+
+impl InMemPathIterBuilder {
+
+    /// Create a builder for iterators over `pathes`, which all are below `basepath`
+    pub fn new(pathes: Vec<PathBuf>, basepath: PathBuf) -> InMemPathIterBuilder {
+        InMemPathIterBuilder(pathes, basepath)
+    }
+
+}
 
 impl PathIterBuilder for InMemPathIterBuilder {
     fn build_iter(&self) -> Box<Iterator<Item = Result<PathBuf>>> {
@@ -213,7 +235,9 @@ impl PathIterBuilder for InMemPathIterBuilder {
 
     fn in_collection(&mut self, c: &str) -> Result<()> {
         debug!("Altering PathIterBuilder path with: {:?}", c);
-        self.0.retain(|p| p.starts_with(c));
+        self.1.push(c);
+        let basepath = &self.1;
+        self.0.retain(|p| p.starts_with(basepath));
         debug!(" -> path : {:?}", self.0);
         Ok(())
     }
@@ -27,8 +27,12 @@ use crate::storeid::StoreIdWithBase;
 use crate::file_abstraction::FileAbstraction;
 
 /// See documentation for PathIterator
-pub(crate) trait PathIterBuilder : Debug {
+pub trait PathIterBuilder : Debug {
+
+    /// Build a new iterator over the paths
     fn build_iter(&self) -> Box<Iterator<Item = Result<PathBuf>>>;
+
+    /// Restrict the paths the built iterators yield to the collection `c`
     fn in_collection(&mut self, c: &str) -> Result<()>;
 }
 
@@ -46,7 +50,7 @@ pub(crate) trait PathIterBuilder : Debug {
 ///
 /// This means quite a few allocations down the road, as the PathIterator itself is not generic, but
 /// this seems to be the best way to implement this.
-pub(crate) struct PathIterator<'a> {
+pub struct PathIterator<'a> {
     iter_builder: Box<PathIterBuilder>,
     iter:         Box<Iterator<Item = Result<PathBuf>>>,
     storepath:    &'a PathBuf,
@@ -17,6 +17,32 @@
 // Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
 //
 
+//! Backends for the store
+//!
+//! The store does not access the filesystem directly, but through an implementation of the
+//! `FileAbstraction` trait. Two backends are shipped with libimagstore: `fs::FSFileAbstraction`,
+//! which is used by `Store::new()`, and `inmemory::InMemoryFileAbstraction`, which is used by
+//! `Store::new_inmemory()`. Other backends can be passed to `Store::new_with_backend()`.
+//!
+//! # Contract
+//!
+//! All paths passed to a backend are absolute, they are the store path joined with the path of
+//! the `StoreId`. A backend must implement the following semantics:
+//!
+//! * A path "exists" if an entry was written to it (via a `FileAbstractionInstance`) and it was
+//!   not removed or renamed since. Directories may exist as well, but they are never files.
+//! * Reading a path which does not exist is not an error, it yields `None`.
+//! * `remove_file()`, `copy()` and `rename()` fail if the source path does not exist. `copy()` and
+//!   `rename()` overwrite the target.
+//! * `create_dir_all()` must succeed for paths which already exist.
+//! * `pathes_recursively()` yields every path an entry lives at below the base path, in no
+//!   particular order. Paths with a component starting with a dot are not entries and must be
+//!   skipped, the store keeps its internal data there.
+//! * `fill()` writes all entries of a `Drain` into the backend. `drain()` returns the entries
+//!   the backend holds in memory, a backend which writes to disk may return an empty `Drain`.
+//!
+//! The `conformance` module contains checks for these rules which every backend should pass.
+
 use std::path::PathBuf;
 use std::fmt::Debug;
 use std::collections::HashMap;
@@ -27,41 +53,168 @@ use failure::Fallible as Result;
 use crate::store::Entry;
 use crate::storeid::StoreIdWithBase;
 
+pub mod conformance;
+#[cfg(feature = "encryption")]
+pub mod crypt;
 pub mod fs;
 pub mod inmemory;
 pub mod iter;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
 
 use self::iter::PathIterator;
 
 /// An abstraction trait over filesystem actions
-pub(crate) trait FileAbstraction : Debug {
+///
+/// See the module documentation for the contract an implementation has to fulfill.
+pub trait FileAbstraction : Debug {
+
+    /// Remove the file at `path`, fail if it does not exist
     fn remove_file(&self, path: &PathBuf) -> Result<()>;
+
+    /// Copy the file at `from` to `to`, fail if `from` does not exist
     fn copy(&self, from: &PathBuf, to: &PathBuf) -> Result<()>;
+
+    /// Move the file at `from` to `to`, fail if `from` does not exist
     fn rename(&self, from: &PathBuf, to: &PathBuf) -> Result<()>;
+
+    /// Create a directory and all its parents, if the backend has a notion of directories
     fn create_dir_all(&self, _: &PathBuf) -> Result<()>;
 
+    /// Check whether a file or a directory exists at the path
     fn exists(&self, _: &PathBuf) -> Result<bool>;
+
+    /// Check whether a file exists at the path
     fn is_file(&self, _: &PathBuf) -> Result<bool>;
 
+    /// Get a handle for reading and writing the file at `p`
+    ///
+    /// The file does not need to exist.
     fn new_instance(&self, p: PathBuf) -> Box<FileAbstractionInstance>;
 
+    /// Get all entries the backend holds in memory
     fn drain(&self) -> Result<Drain>;
+
+    /// Write all entries from the `Drain` to the backend
     fn fill<'a>(&'a mut self, d: Drain) -> Result<()>;
 
+    /// Get an iterator over all files below `basepath`
+    ///
+    /// `storepath` is the path of the store, `backend` is the backend itself, which the iterator
+    /// uses to check whether a path is a file.
     fn pathes_recursively<'a>(&self, basepath: PathBuf, storepath: &'a PathBuf, backend: Arc<FileAbstraction>) -> Result<PathIterator<'a>>;
+
+    /// Whether the backend keeps its data in the store directory on the filesystem
+    ///
+    /// If this returns `true`, the store keeps its index and its transaction journal in hidden
+    /// directories inside the store path. Otherwise, the index lives in memory and transactions
+    /// are not journaled.
+    fn is_persistent(&self) -> bool {
+        true
+    }
 }
 
 /// An abstraction trait over actions on files
-pub(crate) trait FileAbstractionInstance : Debug {
+pub trait FileAbstractionInstance : Debug {
 
     /// Get the contents of the FileAbstractionInstance, as Entry object.
     ///
     /// The `StoreIdWithBase` is passed because the backend does not know where the Entry lives, but the
     /// Entry type itself must be constructed with the id.
+    ///
+    /// Returns `None` if the file does not exist.
     fn get_file_content<'a>(&mut self, id: StoreIdWithBase<'a>) -> Result<Option<Entry>>;
+
+    /// Write the entry to the file, creating it if it does not exist
     fn write_file_content(&mut self, buf: &Entry) -> Result<()>;
 }
 
+/// The backends which can be selected with `store.backend` in the configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    /// One file per entry, see `fs::FSFileAbstraction`
+    Filesystem,
+
+    /// One SQLite database for all entries, see `sqlite::SqliteFileAbstraction`
+    Sqlite,
+}
+
+impl BackendKind {
+
+    pub fn from_name(name: &str) -> Result<BackendKind> {
+        match name {
+            "filesystem" => Ok(BackendKind::Filesystem),
+            "sqlite"     => Ok(BackendKind::Sqlite),
+            other        => Err(format_err!("Unknown store backend: '{}'", other)),
+        }
+    }
+
+    /// Get the backend which is selected with `store.backend` in the configuration
+    ///
+    /// Defaults to `BackendKind::Filesystem` if the key is not set.
+    pub fn from_config(config: &Option<::toml::Value>) -> Result<BackendKind> {
+        crate::configuration::config_backend(config)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            BackendKind::Filesystem => "filesystem",
+            BackendKind::Sqlite     => "sqlite",
+        }
+    }
+
+    /// Create a backend of this kind for the store at `location`
+    pub fn create_backend(&self, location: &PathBuf) -> Result<Arc<FileAbstraction>> {
+        match *self {
+            BackendKind::Filesystem => Ok(Arc::new(fs::FSFileAbstraction::default())),
+
+            #[cfg(feature = "sqlite")]
+            BackendKind::Sqlite => Ok(Arc::new(sqlite::SqliteFileAbstraction::new(location.clone()))),
+
+            #[cfg(not(feature = "sqlite"))]
+            BackendKind::Sqlite => Err(format_err!("Store backend not compiled in: '{}' ({})",
+                                                   self.name(), location.display())),
+        }
+    }
+
+}
+
+/// Copy all entries of the store at `storepath` from one backend to another
+///
+/// Entries which exist in `to` already are overwritten. If `remove` is true, the entries are
+/// removed from `from` after they were copied. Returns the number of copied entries.
+pub fn copy_entries(storepath: &PathBuf,
+                    from: Arc<FileAbstraction>,
+                    to: Arc<FileAbstraction>,
+                    remove: bool)
+    -> Result<usize>
+{
+    let ids = from
+        .pathes_recursively(storepath.clone(), storepath, from.clone())?
+        .collect::<Result<Vec<StoreIdWithBase>>>()?;
+
+    for id in ids.iter() {
+        let path = id.clone().into_pathbuf()?;
+        debug!("Copying entry: {}", path.display());
+
+        let entry = from
+            .new_instance(path.clone())
+            .get_file_content(id.clone())?
+            .ok_or_else(|| format_err!("Entry vanished while copying: {}", path.display()))?;
+
+        to.new_instance(path).write_file_content(&entry)?;
+    }
+
+    if remove {
+        for id in ids.iter() {
+            from.remove_file(&id.clone().into_pathbuf()?)?;
+        }
+    }
+
+    Ok(ids.len())
+}
+
+/// All entries of a backend, by their path
 pub struct Drain(HashMap<PathBuf, Entry>);
 
 impl Drain {
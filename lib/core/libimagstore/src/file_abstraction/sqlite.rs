@@ -0,0 +1,353 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! A backend which keeps all entries in one SQLite database
+//!
+//! The database lives in the store directory (see `SQLITE_DB_NAME`). Entries are stored with their
+//! path relative to the store, so the store directory can be moved.
+
+use std::fmt::{Debug, Formatter, Error as FmtError};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use rusqlite::Connection;
+use rusqlite::OptionalExtension;
+use rusqlite::NO_PARAMS;
+use toml::Value;
+
+use libimagerror::errors::ErrorMsg as EM;
+
+use failure::Fallible as Result;
+use failure::ResultExt;
+use failure::Error;
+
+use super::FileAbstraction;
+use super::FileAbstractionInstance;
+use super::Drain;
+use crate::store::Entry;
+use crate::storeid::StoreIdWithBase;
+use crate::file_abstraction::iter::PathIterator;
+use crate::file_abstraction::inmemory::InMemPathIterBuilder;
+
+/// The name of the database file inside the store directory
+pub const SQLITE_DB_NAME: &str = ".store.sqlite";
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS entries (
+        path    TEXT PRIMARY KEY NOT NULL,
+        header  TEXT NOT NULL,
+        content TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS directories (
+        path TEXT PRIMARY KEY NOT NULL
+    );
+";
+
+/// The database connection, opened when it is used for the first time
+struct Database {
+    storepath: PathBuf,
+    conn:      Mutex<Option<Connection>>,
+}
+
+impl Debug for Database {
+    fn fmt(&self, fmt: &mut Formatter) -> ::std::result::Result<(), FmtError> {
+        write!(fmt, "Database({})", self.storepath.join(SQLITE_DB_NAME).display())
+    }
+}
+
+impl Database {
+
+    fn with_connection<T, F>(&self, f: F) -> Result<T>
+        where F: FnOnce(&mut Connection) -> Result<T>
+    {
+        let mut conn = self.conn.lock().map_err(|_| Error::from(EM::LockError))?;
+
+        if conn.is_none() {
+            let path = self.storepath.join(SQLITE_DB_NAME);
+            debug!("Opening database {}", path.display());
+
+            let c = Connection::open(&path)
+                .context(format_err!("Failed to open database {}", path.display()))?;
+            c.execute_batch(SCHEMA).context("Failed to create database schema")?;
+            *conn = Some(c);
+        }
+
+        f(conn.as_mut().unwrap()) // unwrap safe by above
+    }
+
+    /// The path as stored in the database: relative to the store, with '/' as separator
+    fn key(&self, path: &PathBuf) -> Result<String> {
+        path.strip_prefix(&self.storepath)
+            .unwrap_or(path)
+            .components()
+            .map(|c| c.as_os_str().to_str().map(String::from).ok_or_else(|| Error::from(EM::ConversionError)))
+            .collect::<Result<Vec<String>>>()
+            .map(|v| v.join("/"))
+    }
+
+    fn entry_exists(&self, key: &str) -> Result<bool> {
+        self.with_connection(|conn| {
+            conn.query_row("SELECT 1 FROM entries WHERE path = ?1", params![key], |_| Ok(()))
+                .optional()
+                .map(|o| o.is_some())
+                .map_err(Error::from)
+        })
+    }
+
+}
+
+/// `FileAbstractionInstance` for the SQLite backend
+#[derive(Debug)]
+pub struct SqliteFileAbstractionInstance {
+    db:  Arc<Database>,
+    key: String,
+}
+
+impl FileAbstractionInstance for SqliteFileAbstractionInstance {
+
+    fn get_file_content<'a>(&mut self, id: StoreIdWithBase<'a>) -> Result<Option<Entry>> {
+        debug!("Getting from database: {:?}", self);
+        let key = &self.key;
+        let row = self.db.with_connection(|conn| {
+            conn.query_row("SELECT header, content FROM entries WHERE path = ?1",
+                           params![key],
+                           |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+                .optional()
+                .map_err(Error::from)
+        })?;
+
+        match row {
+            None => Ok(None),
+            Some((header, content)) => {
+                let header = ::toml::de::from_str::<Value>(&header)
+                    .context(format_err!("Failed to parse header of '{}'", key))
+                    .context(EM::TomlDeserError)?;
+
+                let mut entry = Entry::new(id.without_base());
+                *entry.get_header_mut()  = header;
+                *entry.get_content_mut() = content;
+                Ok(Some(entry))
+            },
+        }
+    }
+
+    fn write_file_content(&mut self, buf: &Entry) -> Result<()> {
+        debug!("Writing to database: {:?}", self);
+        let key    = &self.key;
+        let header = ::toml::ser::to_string(buf.get_header())
+            .context(format_err!("Failed to serialize header of '{}'", key))
+            .context(EM::EntryHeaderError)?;
+
+        self.db.with_connection(|conn| {
+            conn.execute("INSERT OR REPLACE INTO entries (path, header, content) VALUES (?1, ?2, ?3)",
+                         params![key, header, buf.get_content()])
+                .context(EM::FileNotWritten)
+                .map_err(Error::from)
+                .map(|_| ())
+        })
+    }
+}
+
+/// A backend which keeps all entries in a SQLite database in the store directory
+#[derive(Debug)]
+pub struct SqliteFileAbstraction {
+    db: Arc<Database>,
+}
+
+impl SqliteFileAbstraction {
+
+    /// Create the backend for the store at `storepath`
+    ///
+    /// The database is opened (and created, if it does not exist) when it is used for the first
+    /// time.
+    pub fn new(storepath: PathBuf) -> SqliteFileAbstraction {
+        SqliteFileAbstraction {
+            db: Arc::new(Database { storepath, conn: Mutex::new(None) }),
+        }
+    }
+
+}
+
+impl FileAbstraction for SqliteFileAbstraction {
+
+    fn remove_file(&self, path: &PathBuf) -> Result<()> {
+        debug!("Removing: {:?}", path);
+        let key = self.db.key(path)?;
+        let n   = self.db.with_connection(|conn| {
+            conn.execute("DELETE FROM entries WHERE path = ?1", params![key])
+                .context(EM::FileNotRemoved)
+                .map_err(Error::from)
+        })?;
+
+        if n == 0 {
+            Err(Error::from(EM::FileNotFound))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn copy(&self, from: &PathBuf, to: &PathBuf) -> Result<()> {
+        debug!("Copying : {:?} -> {:?}", from, to);
+        let from = self.db.key(from)?;
+        let to   = self.db.key(to)?;
+        let n    = self.db.with_connection(|conn| {
+            conn.execute("INSERT OR REPLACE INTO entries (path, header, content)
+                              SELECT ?2, header, content FROM entries WHERE path = ?1",
+                         params![from, to])
+                .context(EM::FileNotCopied)
+                .map_err(Error::from)
+        })?;
+
+        if n == 0 {
+            Err(Error::from(EM::FileNotFound))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn rename(&self, from: &PathBuf, to: &PathBuf) -> Result<()> {
+        debug!("Renaming: {:?} -> {:?}", from, to);
+        let from = self.db.key(from)?;
+        let to   = self.db.key(to)?;
+        let n    = self.db.with_connection(|conn| {
+            conn.execute("UPDATE OR REPLACE entries SET path = ?2 WHERE path = ?1", params![from, to])
+                .context(EM::FileNotRenamed)
+                .map_err(Error::from)
+        })?;
+
+        if n == 0 {
+            Err(Error::from(EM::FileNotFound))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn create_dir_all(&self, path: &PathBuf) -> Result<()> {
+        let key = self.db.key(path)?;
+        if key.is_empty() {
+            return Ok(())
+        }
+
+        self.db.with_connection(|conn| {
+            let tx = conn.transaction()?;
+            let mut dir = String::new();
+            for component in key.split('/') {
+                if !dir.is_empty() {
+                    dir.push('/');
+                }
+                dir.push_str(component);
+                tx.execute("INSERT OR IGNORE INTO directories (path) VALUES (?1)", params![dir])
+                    .context(EM::DirNotCreated)?;
+            }
+            tx.commit().context(EM::DirNotCreated).map_err(Error::from)
+        })
+    }
+
+    fn exists(&self, path: &PathBuf) -> Result<bool> {
+        let key = self.db.key(path)?;
+        if key.is_empty() || self.db.entry_exists(&key)? {
+            return Ok(true)
+        }
+
+        let prefix = format!("{}/", key);
+        self.db.with_connection(|conn| {
+            conn.query_row("SELECT 1 FROM directories WHERE path = ?1
+                            UNION ALL
+                            SELECT 1 FROM entries WHERE substr(path, 1, length(?2)) = ?2",
+                           params![key, prefix],
+                           |_| Ok(()))
+                .optional()
+                .map(|o| o.is_some())
+                .map_err(Error::from)
+        })
+    }
+
+    fn is_file(&self, path: &PathBuf) -> Result<bool> {
+        let key = self.db.key(path)?;
+        self.db.entry_exists(&key)
+    }
+
+    fn new_instance(&self, p: PathBuf) -> Box<FileAbstractionInstance> {
+        // The key cannot be computed for paths which are not UTF-8. Such paths are not valid
+        // StoreIds anyways, so we use the lossy representation here.
+        let key = self.db.key(&p).unwrap_or_else(|_| p.to_string_lossy().into_owned());
+        Box::new(SqliteFileAbstractionInstance { db: self.db.clone(), key })
+    }
+
+    /// We return nothing from the database here, as it is persistent.
+    fn drain(&self) -> Result<Drain> {
+        Ok(Drain::empty())
+    }
+
+    fn fill<'a>(&'a mut self, mut d: Drain) -> Result<()> {
+        d.iter().fold(Ok(()), |acc, (path, element)| {
+            acc.and_then(|_| self.new_instance(path).write_file_content(&element))
+        })
+    }
+
+    fn pathes_recursively<'a>(&self,
+                              basepath: PathBuf,
+                              storepath: &'a PathBuf,
+                              backend: Arc<FileAbstraction>)
+        -> Result<PathIterator<'a>>
+    {
+        trace!("Building PathIterator object (sqlite implementation)");
+        let keys = self.db.with_connection(|conn| {
+            let mut stmt = conn.prepare("SELECT path FROM entries ORDER BY path")?;
+            let keys     = stmt
+                .query_map(NO_PARAMS, |row| row.get::<_, String>(0))?
+                .collect::<::std::result::Result<Vec<String>, _>>()?;
+            Ok(keys)
+        })?;
+
+        let pathes = keys
+            .into_iter()
+            .filter(|k| !k.split('/').any(|c| c.starts_with('.')))
+            .map(|k| self.db.storepath.join(k))
+            .filter(|p| p.starts_with(&basepath))
+            .collect();
+
+        Ok(PathIterator::new(Box::new(InMemPathIterBuilder::new(pathes, basepath)), storepath, backend))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tempdir::TempDir;
+
+    use super::SqliteFileAbstraction;
+    use crate::file_abstraction::FileAbstraction;
+    use crate::file_abstraction::conformance::check_backend;
+
+    #[test]
+    fn test_sqlite_backend_conformance() {
+        let mut dirs = vec![];
+        check_backend(|| {
+            let dir     = TempDir::new("imag-sqlite-conformance")?;
+            let path    = dir.path().to_path_buf();
+            let backend = Arc::new(SqliteFileAbstraction::new(path.clone())) as Arc<FileAbstraction>;
+            dirs.push(dir);
+            Ok((path, backend))
+        }).unwrap();
+    }
+}
@@ -0,0 +1,665 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Append-only change log of the versions of store entries
+//!
+//! The history is optional and has to be enabled in the configuration:
+//!
+//! ```toml
+//! [store.history]
+//! enabled = true
+//! ```
+//!
+//! If enabled, the `Store` appends a revision to the log of an entry whenever the entry is
+//! written with changed contents, deleted or moved. The log of an entry lives in
+//! `<store>/.history/<id>.log` and contains one JSON object per line, each holding the line
+//! diff of the whole entry (header and content) against the previous revision.
+//!
+//! If an entry without a log is changed, its version from before the change is recorded as
+//! revision 0 first, so the change can always be undone.
+//!
+//! Every 32nd revision is a snapshot, which holds the whole text instead of the diff against the
+//! previous revision. To get the text of a revision, only the revisions since the last snapshot
+//! before it have to be applied.
+//!
+//! Logs are never rewritten. When an entry is moved, its log is moved with it (unless the new id
+//! has a log already, in which case the old log stays where it is).
+
+use std::fs::OpenOptions;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::path::PathBuf;
+
+use chrono::Local;
+use failure::Fallible as Result;
+use failure::ResultExt;
+use failure::Error;
+use serde_json;
+
+use libimagerror::errors::ErrorMsg as EM;
+
+use crate::storeid::StoreId;
+
+/// The name of the directory inside the store where the change logs live
+pub const HISTORY_DIR_NAME: &str = ".history";
+
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// Every `SNAPSHOT_INTERVAL`th revision is recorded as snapshot
+const SNAPSHOT_INTERVAL: usize = 32;
+
+/// What happened to an entry in a revision
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RevisionKind {
+    /// The entry was written
+    Write,
+
+    /// The entry was deleted, the text of the revision is empty
+    Delete,
+
+    /// The entry was moved here from another id
+    Move,
+}
+
+impl RevisionKind {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            RevisionKind::Write  => "write",
+            RevisionKind::Delete => "delete",
+            RevisionKind::Move   => "move",
+        }
+    }
+}
+
+/// One step of the diff between two revisions, in lines
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Edit {
+    Keep(usize),
+    Delete(usize),
+    Insert(Vec<String>),
+}
+
+/// One line of the change log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Record {
+    rev: usize,
+    time: String,
+    kind: RevisionKind,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    from: Option<String>,
+
+    /// Whether the edits apply to the empty text rather than the previous revision
+    #[serde(default, skip_serializing_if = "is_false")]
+    snapshot: bool,
+
+    edits: Vec<Edit>,
+}
+
+impl Record {
+
+    /// A record of revision `rev`, diffed against the text `last` of the previous revision unless
+    /// it is a snapshot
+    fn new(rev: usize, kind: RevisionKind, from: Option<String>, last: &str, text: &str) -> Record {
+        let snapshot = rev > 0 && rev % SNAPSHOT_INTERVAL == 0;
+        let edits    = edits(if snapshot { "" } else { last }, text);
+        Record { rev, time: now(), kind, from, snapshot, edits }
+    }
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+/// A revision of an entry, as listed by `History::revisions()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Revision {
+    number: usize,
+    time: String,
+    kind: RevisionKind,
+    moved_from: Option<String>,
+}
+
+impl Revision {
+
+    /// The number of the revision, counted from 0 for every id
+    pub fn number(&self) -> usize {
+        self.number
+    }
+
+    /// The local time the revision was recorded at, formatted as `%Y-%m-%dT%H:%M:%S`
+    pub fn time(&self) -> &str {
+        &self.time
+    }
+
+    pub fn kind(&self) -> RevisionKind {
+        self.kind
+    }
+
+    /// The id the entry was moved from, for `RevisionKind::Move` revisions
+    pub fn moved_from(&self) -> Option<&str> {
+        self.moved_from.as_ref().map(String::as_str)
+    }
+}
+
+/// A line of the output of `diff_lines()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineDiff {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+/// The change logs of the store at a path
+#[derive(Debug)]
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+
+    pub(crate) fn new(storepath: &PathBuf) -> History {
+        History { path: storepath.join(HISTORY_DIR_NAME) }
+    }
+
+    /// Whether there is a change log for `id`
+    pub fn is_tracked(&self, id: &StoreId) -> Result<bool> {
+        self.log_path(id).map(|p| p.exists())
+    }
+
+    /// All revisions of `id`, oldest first
+    ///
+    /// Returns an empty list if there is no change log for `id`.
+    pub fn revisions(&self, id: &StoreId) -> Result<Vec<Revision>> {
+        self.read_log(id).map(|records| {
+            records
+                .into_iter()
+                .map(|r| Revision {
+                    number: r.rev,
+                    time: r.time,
+                    kind: r.kind,
+                    moved_from: r.from,
+                })
+                .collect()
+        })
+    }
+
+    /// The full text (header and content, as written to the store) of `id` at revision `rev`
+    ///
+    /// The text of a `RevisionKind::Delete` revision is empty.
+    pub fn text_at(&self, id: &StoreId, rev: usize) -> Result<String> {
+        let records = self.read_log(id)?;
+        if rev >= records.len() {
+            return Err(format_err!("No revision {} of '{}'", rev, id));
+        }
+
+        reconstruct(&records[..=rev])
+    }
+
+    /// Append a revision to the log of `id`
+    ///
+    /// `prior` is the text of the entry before the change, it is recorded as revision 0 if there
+    /// is no log for `id` yet. Nothing is recorded for writes which do not change the text.
+    pub(crate) fn record(&self,
+                         id: &StoreId,
+                         kind: RevisionKind,
+                         text: &str,
+                         from: Option<&StoreId>,
+                         prior: Option<String>)
+        -> Result<()>
+    {
+        let records = self.read_log(id)?;
+        let mut next = records.len();
+        let mut last = reconstruct(&records)?;
+
+        if records.is_empty() {
+            if let Some(prior) = prior {
+                trace!("Recording prior version of {}", id);
+                self.append(id, &Record::new(next, RevisionKind::Write, None, &last, &prior))?;
+                next += 1;
+                last  = prior;
+            }
+        }
+
+        if kind == RevisionKind::Write && next > 0 && last == text {
+            trace!("{} unchanged, not recording a revision", id);
+            return Ok(())
+        }
+
+        debug!("Recording revision {} of {}", next, id);
+        let from = from.map(|f| f.local_display_string());
+        self.append(id, &Record::new(next, kind, from, &last, text))
+    }
+
+    /// Move the log of `old` to `new` and record the move
+    ///
+    /// `text` is the text of the moved entry.
+    pub(crate) fn record_move(&self, old: &StoreId, new: &StoreId, text: &str) -> Result<()> {
+        let old_log = self.log_path(old)?;
+        let new_log = self.log_path(new)?;
+
+        if old_log.exists() && !new_log.exists() {
+            if let Some(parent) = new_log.parent() {
+                ::std::fs::create_dir_all(parent).context(EM::DirNotCreated)?;
+            }
+            ::std::fs::rename(&old_log, &new_log).context(EM::FileNotRenamed)?;
+        }
+
+        self.record(new, RevisionKind::Move, text, Some(old), Some(text.to_string()))
+    }
+
+    fn read_log(&self, id: &StoreId) -> Result<Vec<Record>> {
+        let path = self.log_path(id)?;
+        if !path.exists() {
+            return Ok(vec![])
+        }
+
+        let file = OpenOptions::new().read(true).open(&path).context(EM::FileNotFound)?;
+        BufReader::new(file)
+            .lines()
+            .filter(|line| line.as_ref().map(|l| !l.is_empty()).unwrap_or(true))
+            .map(|line| {
+                let line = line.context(EM::IO)?;
+                serde_json::from_str(&line)
+                    .context(format_err!("Corrupt change log: {}", path.display()))
+                    .map_err(Error::from)
+            })
+            .collect()
+    }
+
+    fn append(&self, id: &StoreId, record: &Record) -> Result<()> {
+        let path = self.log_path(id)?;
+        if let Some(parent) = path.parent() {
+            ::std::fs::create_dir_all(parent).context(EM::DirNotCreated)?;
+        }
+
+        let mut line = serde_json::to_string(record).context(EM::IO)?;
+        line.push('\n');
+
+        OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&path)
+            .and_then(|mut f| f.write_all(line.as_bytes()).and_then(|_| f.sync_data()))
+            .context(EM::FileNotWritten)
+            .map_err(Error::from)
+    }
+
+    /// `<store>/.history/<id>.log`
+    fn log_path(&self, id: &StoreId) -> Result<PathBuf> {
+        let mut path = self.path.join(id.local());
+        let name     = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .map(|n| format!("{}.log", n))
+            .ok_or_else(|| Error::from(EM::ConversionError))?;
+
+        path.set_file_name(name);
+        Ok(path)
+    }
+
+}
+
+/// Compute the line diff between two texts
+pub fn diff_lines(old: &str, new: &str) -> Vec<LineDiff> {
+    let old_lines = split(old);
+    let mut old_pos = 0;
+    let mut result  = vec![];
+
+    for edit in edits(old, new) {
+        match edit {
+            Edit::Keep(n) => {
+                result.extend(old_lines[old_pos..old_pos + n].iter().map(|l| LineDiff::Same(l.to_string())));
+                old_pos += n;
+            },
+            Edit::Delete(n) => {
+                result.extend(old_lines[old_pos..old_pos + n].iter().map(|l| LineDiff::Removed(l.to_string())));
+                old_pos += n;
+            },
+            Edit::Insert(lines) => {
+                result.extend(lines.into_iter().map(LineDiff::Added));
+            },
+        }
+    }
+
+    result
+}
+
+fn now() -> String {
+    Local::now().format(TIME_FORMAT).to_string()
+}
+
+fn split(text: &str) -> Vec<&str> {
+    if text.is_empty() {
+        vec![]
+    } else {
+        text.split('\n').collect()
+    }
+}
+
+/// Apply the edits of the records since the last snapshot to the empty text
+fn reconstruct(records: &[Record]) -> Result<String> {
+    let start = records.iter().rposition(|r| r.snapshot).unwrap_or(0);
+    records[start..].iter().fold(Ok(String::new()), |text, record| {
+        text.and_then(|text| apply(&text, &record.edits))
+            .context(format_err!("Corrupt change log, cannot apply revision {}", record.rev))
+            .map_err(Error::from)
+    })
+}
+
+fn apply(text: &str, edits: &[Edit]) -> Result<String> {
+    let old     = split(text);
+    let mut pos = 0;
+    let mut out = vec![];
+
+    for edit in edits {
+        match *edit {
+            Edit::Keep(n) | Edit::Delete(n) if pos + n > old.len() => {
+                return Err(format_err!("Edit exceeds text: {} + {} > {}", pos, n, old.len()))
+            },
+            Edit::Keep(n) => {
+                out.extend(old[pos..pos + n].iter().map(|l| l.to_string()));
+                pos += n;
+            },
+            Edit::Delete(n) => pos += n,
+            Edit::Insert(ref lines) => out.extend(lines.iter().cloned()),
+        }
+    }
+
+    if pos != old.len() {
+        return Err(format_err!("Edits do not cover text: {} != {}", pos, old.len()))
+    }
+
+    Ok(out.join("\n"))
+}
+
+/// The edits which transform `old` into `new`
+fn edits(old: &str, new: &str) -> Vec<Edit> {
+    let mut result = vec![];
+    diff_into(&split(old), &split(new), &mut result);
+    result
+}
+
+fn push_edit(out: &mut Vec<Edit>, edit: Edit) {
+    match (out.last_mut(), edit) {
+        (_, Edit::Keep(0)) | (_, Edit::Delete(0))       => {},
+        (_, Edit::Insert(ref ms)) if ms.is_empty()      => {},
+        (Some(Edit::Keep(n)), Edit::Keep(m))            => *n += m,
+        (Some(Edit::Delete(n)), Edit::Delete(m))        => *n += m,
+        (Some(Edit::Insert(ls)), Edit::Insert(mut ms))  => ls.append(&mut ms),
+        (_, edit)                                       => out.push(edit),
+    }
+}
+
+/// Append the edits which transform `a` into `b` to `out`
+///
+/// This is the linear space variant of the diff algorithm by Eugene W. Myers ("An O(ND)
+/// Difference Algorithm and Its Variations", 1986): The common prefix and suffix are stripped,
+/// then the middle snake of the shortest edit script is searched and both halves are diffed
+/// recursively. Needs O(N + M) memory and O((N + M) * D) time for D differing lines.
+fn diff_into(a: &[&str], b: &[&str], out: &mut Vec<Edit>) {
+    let prefix = a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    push_edit(out, Edit::Keep(prefix));
+
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+
+    if a_mid.is_empty() || b_mid.is_empty() {
+        push_edit(out, Edit::Delete(a_mid.len()));
+        push_edit(out, Edit::Insert(b_mid.iter().map(|l| l.to_string()).collect()));
+    } else {
+        let (x, y, u, v) = middle_snake(a_mid, b_mid);
+
+        if (x, y) == (0, 0) && (u, v) == (a_mid.len(), b_mid.len()) || (x, y, u, v) == (0, 0, 0, 0) {
+            // Cannot happen for texts without common prefix and suffix, but do not recurse forever
+            push_edit(out, Edit::Delete(a_mid.len()));
+            push_edit(out, Edit::Insert(b_mid.iter().map(|l| l.to_string()).collect()));
+        } else {
+            diff_into(&a_mid[..x], &b_mid[..y], out);
+            push_edit(out, Edit::Keep(u - x));
+            diff_into(&a_mid[u..], &b_mid[v..], out);
+        }
+    }
+
+    push_edit(out, Edit::Keep(suffix));
+}
+
+/// Find the middle snake of the shortest edit script from `a` to `b`
+///
+/// Returns the start `(x, y)` and the end `(u, v)` of the snake, which is a (possibly empty) run
+/// of equal lines `a[x..u] == b[y..v]` on the shortest edit script.
+fn middle_snake(a: &[&str], b: &[&str]) -> (usize, usize, usize, usize) {
+    let n      = a.len() as isize;
+    let m      = b.len() as isize;
+    let delta  = n - m;
+    let odd    = delta % 2 != 0;
+    let max    = (n + m + 1) / 2 + 1;
+    let offset = max;
+
+    // The furthest x reached on each diagonal k, forward from (0, 0) and backward from (n, m).
+    // The backward search works on the reversed texts, its diagonal c is diagonal delta - c of
+    // the forward search.
+    let mut forward  = vec![0isize; (2 * max + 1) as usize];
+    let mut backward = vec![0isize; (2 * max + 1) as usize];
+
+    for d in 0..max {
+        let mut k = -d;
+        while k <= d {
+            let i = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && forward[i - 1] < forward[i + 1]) {
+                forward[i + 1]
+            } else {
+                forward[i - 1] + 1
+            };
+            let mut y = x - k;
+            let (x0, y0) = (x, y);
+
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[i] = x;
+
+            let c = delta - k;
+            if odd && c > -d && c < d && forward[i] + backward[(c + offset) as usize] >= n {
+                return (x0 as usize, y0 as usize, x as usize, y as usize)
+            }
+
+            k += 2;
+        }
+
+        let mut c = -d;
+        while c <= d {
+            let j = (c + offset) as usize;
+            let mut x = if c == -d || (c != d && backward[j - 1] < backward[j + 1]) {
+                backward[j + 1]
+            } else {
+                backward[j - 1] + 1
+            };
+            let mut y = x - c;
+            let (x0, y0) = (x, y);
+
+            while x < n && y < m && a[(n - x - 1) as usize] == b[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[j] = x;
+
+            let k = delta - c;
+            if !odd && k >= -d && k <= d && forward[(k + offset) as usize] + backward[j] >= n {
+                return ((n - x) as usize, (m - y) as usize, (n - x0) as usize, (m - y0) as usize)
+            }
+
+            c += 2;
+        }
+    }
+
+    (0, 0, 0, 0) // not reached, the search paths always meet
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_edits_roundtrip() {
+        let texts = [
+            "",
+            "a",
+            "a\nb\nc",
+            "a\nx\nc\n",
+            "---\n[imag]\nversion = \"0.10.0\"\n---\nfoo\nbar",
+            "---\n[imag]\nversion = \"0.10.0\"\n[x]\ny = 1\n---\nbar\nbaz\n",
+        ];
+
+        for old in texts.iter() {
+            for new in texts.iter() {
+                assert_eq!(apply(old, &edits(old, new)).unwrap(), *new, "{:?} -> {:?}", old, new);
+            }
+        }
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        let diff = diff_lines("a\nb\nc", "a\nx\nc");
+        assert_eq!(diff, vec![
+            LineDiff::Same(String::from("a")),
+            LineDiff::Removed(String::from("b")),
+            LineDiff::Added(String::from("x")),
+            LineDiff::Same(String::from("c")),
+        ]);
+    }
+
+    #[test]
+    fn test_edits_are_minimal() {
+        fn lcs_len(a: &[&str], b: &[&str]) -> usize {
+            let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+            for i in (0..a.len()).rev() {
+                for j in (0..b.len()).rev() {
+                    lcs[i][j] = if a[i] == b[j] {
+                        lcs[i + 1][j + 1] + 1
+                    } else {
+                        ::std::cmp::max(lcs[i + 1][j], lcs[i][j + 1])
+                    };
+                }
+            }
+            lcs[0][0]
+        }
+
+        // Texts of the lines "a", "b" and "c", from a simple pseudo random sequence
+        let mut seed = 42u32;
+        let mut text = |len: usize| {
+            (0..len)
+                .map(|_| {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    ["a", "b", "c"][(seed >> 16) as usize % 3]
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        for round in 0..200 {
+            let old = text(round % 13);
+            let new = text(round % 7 + 1);
+            let es  = edits(&old, &new);
+            assert_eq!(apply(&old, &es).unwrap(), new);
+
+            let changed = es.iter().map(|e| match *e {
+                Edit::Keep(_)          => 0,
+                Edit::Delete(n)        => n,
+                Edit::Insert(ref ls)   => ls.len(),
+            }).sum::<usize>();
+
+            let (a, b) = (split(&old), split(&new));
+            assert_eq!(changed, a.len() + b.len() - 2 * lcs_len(&a, &b), "{:?} -> {:?}", old, new);
+        }
+    }
+
+    #[test]
+    fn test_record_and_reconstruct() {
+        let dir     = TempDir::new("imag-history-test").unwrap();
+        let history = History::new(&dir.path().to_path_buf());
+        let id      = StoreId::new(PathBuf::from("foo/bar")).unwrap();
+        let moved   = StoreId::new(PathBuf::from("foo/baz")).unwrap();
+
+        assert!(!history.is_tracked(&id).unwrap());
+        assert!(history.revisions(&id).unwrap().is_empty());
+
+        history.record(&id, RevisionKind::Write, "second", None, Some(String::from("first"))).unwrap();
+        history.record(&id, RevisionKind::Write, "second", None, None).unwrap(); // unchanged
+        history.record(&id, RevisionKind::Write, "third\nline", None, None).unwrap();
+
+        assert!(dir.path().join(".history/foo/bar.log").exists());
+        let kinds = history.revisions(&id).unwrap().into_iter().map(|r| r.kind()).collect::<Vec<_>>();
+        assert_eq!(kinds, vec![RevisionKind::Write, RevisionKind::Write, RevisionKind::Write]);
+        assert_eq!(history.text_at(&id, 0).unwrap(), "first");
+        assert_eq!(history.text_at(&id, 1).unwrap(), "second");
+        assert_eq!(history.text_at(&id, 2).unwrap(), "third\nline");
+        assert!(history.text_at(&id, 3).is_err());
+
+        history.record_move(&id, &moved, "third\nline").unwrap();
+        assert!(!history.is_tracked(&id).unwrap());
+        let revs = history.revisions(&moved).unwrap();
+        assert_eq!(revs.len(), 4);
+        assert_eq!(revs[3].kind(), RevisionKind::Move);
+        assert_eq!(revs[3].moved_from(), Some("foo/bar"));
+        assert_eq!(history.text_at(&moved, 3).unwrap(), "third\nline");
+
+        history.record(&moved, RevisionKind::Delete, "", None, None).unwrap();
+        assert_eq!(history.text_at(&moved, 4).unwrap(), "");
+        assert_eq!(history.text_at(&moved, 0).unwrap(), "first");
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let dir     = TempDir::new("imag-history-test").unwrap();
+        let history = History::new(&dir.path().to_path_buf());
+        let id      = StoreId::new(PathBuf::from("snap")).unwrap();
+        let text    = |i: usize| format!("head\nline {}\ntail", i);
+
+        for i in 0..70 {
+            history.record(&id, RevisionKind::Write, &text(i), None, None).unwrap();
+        }
+
+        let snapshots = history
+            .read_log(&id)
+            .unwrap()
+            .into_iter()
+            .filter(|r| r.snapshot)
+            .map(|r| r.rev)
+            .collect::<Vec<_>>();
+        assert_eq!(snapshots, vec![32, 64]);
+
+        for i in 0..70 {
+            assert_eq!(history.text_at(&id, i).unwrap(), text(i));
+        }
+    }
+}
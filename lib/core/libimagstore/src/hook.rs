@@ -0,0 +1,354 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Hooks which are run before and after store operations
+//!
+//! Hooks run before (pre) and after (post) an entry is created, updated, deleted or moved. A hook
+//! is either an external command from the configuration or a closure registered with
+//! `Store::register_hook()`:
+//!
+//! ```toml
+//! [store.hooks]
+//! pre-update  = [ "imag-check-headers" ]
+//! post-update = [ "imag git commit -q -a -m 'Update entry'" ]
+//! ```
+//!
+//! Commands are run with `sh -c`. They get the entry on stdin and the following environment
+//! variables:
+//!
+//! * `IMAG_HOOK`: the position of the hook, for example "pre-update"
+//! * `IMAG_HOOK_ID`: the id of the entry
+//! * `IMAG_HOOK_NEW_ID`: the new id of the entry, for moves
+//! * `IMAG_STORE`: the path of the store
+//!
+//! If a pre-hook fails (a command exits with a non-zero code or a closure returns an error), the
+//! operation is aborted with an error. Closures registered as pre-create or pre-update hooks may
+//! modify the entry before it is written, for example to add tags. Failing post-hooks are logged,
+//! as the operation already happened.
+//!
+//! The pre-create hooks run when a new entry is borrowed with `Store::create()` or
+//! `Store::retrieve()`, so these calls fail if a hook refuses the entry. The post-create hooks
+//! run when the entry is written for the first time.
+//!
+//! Closures run before commands. Pre-hooks are not run again when a transaction journal is
+//! replayed.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::RwLock;
+
+use toml::Value;
+use toml_query::read::TomlValueReadExt;
+use failure::Fallible as Result;
+use failure::ResultExt;
+use failure::Error;
+
+use libimagerror::errors::ErrorMsg as EM;
+
+use crate::store::Entry;
+use crate::storeid::StoreId;
+
+/// When a hook is run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HookPosition {
+    PreCreate,
+    PostCreate,
+    PreUpdate,
+    PostUpdate,
+    PreDelete,
+    PostDelete,
+    PreMove,
+    PostMove,
+}
+
+impl HookPosition {
+
+    /// All positions, in the order of the operations
+    pub fn all() -> &'static [HookPosition] {
+        &[
+            HookPosition::PreCreate,
+            HookPosition::PostCreate,
+            HookPosition::PreUpdate,
+            HookPosition::PostUpdate,
+            HookPosition::PreDelete,
+            HookPosition::PostDelete,
+            HookPosition::PreMove,
+            HookPosition::PostMove,
+        ]
+    }
+
+    /// The name of the position, as used in the configuration
+    pub fn name(&self) -> &'static str {
+        match *self {
+            HookPosition::PreCreate  => "pre-create",
+            HookPosition::PostCreate => "post-create",
+            HookPosition::PreUpdate  => "pre-update",
+            HookPosition::PostUpdate => "post-update",
+            HookPosition::PreDelete  => "pre-delete",
+            HookPosition::PostDelete => "post-delete",
+            HookPosition::PreMove    => "pre-move",
+            HookPosition::PostMove   => "post-move",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<HookPosition> {
+        HookPosition::all().iter().find(|p| p.name() == name).cloned()
+    }
+
+    pub fn is_pre(&self) -> bool {
+        match *self {
+            HookPosition::PreCreate |
+            HookPosition::PreUpdate |
+            HookPosition::PreDelete |
+            HookPosition::PreMove   => true,
+            _                       => false,
+        }
+    }
+
+}
+
+/// What a hook is run for
+#[derive(Debug, Clone, Copy)]
+pub struct HookEvent<'a> {
+    position: HookPosition,
+    id: &'a StoreId,
+    new_id: Option<&'a StoreId>,
+}
+
+impl<'a> HookEvent<'a> {
+
+    pub(crate) fn new(position: HookPosition, id: &'a StoreId, new_id: Option<&'a StoreId>) -> HookEvent<'a> {
+        HookEvent { position, id, new_id }
+    }
+
+    pub fn position(&self) -> HookPosition {
+        self.position
+    }
+
+    /// The id of the entry, the old id for moves
+    pub fn id(&self) -> &StoreId {
+        self.id
+    }
+
+    /// The new id of the entry, for moves
+    pub fn new_id(&self) -> Option<&StoreId> {
+        self.new_id
+    }
+
+}
+
+/// A hook closure
+///
+/// Changes to the entry are only written for pre-create and pre-update hooks.
+pub type HookFn = Box<Fn(&HookEvent, &mut Entry) -> Result<()> + Send + Sync>;
+
+/// The hooks of a store
+#[derive(Default)]
+pub(crate) struct Hooks {
+    commands: BTreeMap<HookPosition, Vec<String>>,
+    closures: RwLock<Vec<(HookPosition, HookFn)>>,
+}
+
+impl Hooks {
+
+    /// Read the hook commands from "store.hooks"
+    pub(crate) fn from_config(config: &Option<Value>) -> Result<Hooks> {
+        let mut hooks = Hooks::default();
+
+        let table = match *config {
+            Some(ref config) => config.read("store.hooks").context(EM::TomlQueryError)?,
+            None             => None,
+        };
+
+        let table = match table {
+            Some(&Value::Table(ref t)) => t,
+            Some(_)                    => return Err(format_err!("Config key 'store.hooks' must be a table")),
+            None                       => return Ok(hooks),
+        };
+
+        for (name, commands) in table {
+            let position = HookPosition::from_name(name)
+                .ok_or_else(|| format_err!("Unknown hook position in 'store.hooks': {}", name))?;
+
+            let commands = commands
+                .as_array()
+                .and_then(|cmds| cmds.iter().map(|c| c.as_str().map(String::from)).collect::<Option<Vec<_>>>())
+                .ok_or_else(|| format_err!("Config key 'store.hooks.{}' must be an array of strings", name))?;
+
+            hooks.commands.insert(position, commands);
+        }
+
+        Ok(hooks)
+    }
+
+    pub(crate) fn add(&self, position: HookPosition, hook: HookFn) -> Result<()> {
+        self.closures
+            .write()
+            .map_err(|_| Error::from(EM::LockError))
+            .map(|mut closures| closures.push((position, hook)))
+    }
+
+    /// Whether any hook is registered for `position`
+    pub(crate) fn has(&self, position: HookPosition) -> bool {
+        self.commands.get(&position).map(|c| !c.is_empty()).unwrap_or(false) ||
+            self.closures
+                .read()
+                .map(|closures| closures.iter().any(|&(p, _)| p == position))
+                .unwrap_or(false)
+    }
+
+    /// Run all hooks for the position of `event`
+    ///
+    /// Stops at the first failing hook.
+    pub(crate) fn run(&self, storepath: &PathBuf, event: &HookEvent, entry: &mut Entry) -> Result<()> {
+        {
+            let closures = self.closures.read().map_err(|_| Error::from(EM::LockError))?;
+            for &(_, ref hook) in closures.iter().filter(|&&(p, _)| p == event.position) {
+                hook(event, entry)
+                    .context(format_err!("{} hook failed for '{}'", event.position.name(), event.id))?;
+            }
+        }
+
+        if let Some(commands) = self.commands.get(&event.position) {
+            for command in commands {
+                run_command(command, storepath, event, entry)
+                    .context(format_err!("{} hook '{}' failed for '{}'", event.position.name(), command, event.id))?;
+            }
+        }
+
+        Ok(())
+    }
+
+}
+
+fn run_command(command: &str, storepath: &PathBuf, event: &HookEvent, entry: &Entry) -> Result<()> {
+    debug!("Running {} hook: {}", event.position.name(), command);
+
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(command)
+        .env("IMAG_HOOK", event.position.name())
+        .env("IMAG_HOOK_ID", event.id.local_display_string())
+        .env("IMAG_STORE", storepath)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    if let Some(new_id) = event.new_id {
+        cmd.env("IMAG_HOOK_NEW_ID", new_id.local_display_string());
+    }
+
+    let mut child = cmd.spawn().context(EM::ExternalProcessError)?;
+    let text      = entry.to_str()?;
+    let mut stdin = child.stdin.take().ok_or_else(|| Error::from(EM::ExternalProcessError))?;
+
+    // The entry is written from another thread, because a hook which writes much output before
+    // reading its input would block on its full stdout pipe while we block on its stdin pipe
+    let writer = ::std::thread::spawn(move || stdin.write_all(text.as_bytes()));
+
+    let output = child.wait_with_output().context(EM::ExternalProcessError)?;
+
+    match writer.join() {
+        Ok(Ok(())) => {},
+        // The hook does not have to read the entry, so a broken pipe is fine here
+        Ok(Err(ref e)) if e.kind() == ::std::io::ErrorKind::BrokenPipe => {},
+        Ok(Err(e)) => return Err(e).context(EM::ExternalProcessError).map_err(Error::from),
+        Err(_)     => return Err(format_err!("Writing the entry to the hook failed")),
+    }
+
+    trace!("Hook stdout: {}", String::from_utf8_lossy(&output.stdout));
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format_err!("Hook exited with {}: {}",
+                        output.status,
+                        String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_hooks_from_config() {
+        let config = ::toml::de::from_str(r#"
+        [store.hooks]
+            pre-update = ["true", "false"]
+        "#).unwrap();
+
+        let hooks = Hooks::from_config(&Some(config)).unwrap();
+        assert!(hooks.has(HookPosition::PreUpdate));
+        assert!(!hooks.has(HookPosition::PostUpdate));
+
+        let invalid = ::toml::de::from_str(r#"
+        [store.hooks]
+            pre-nothing = ["true"]
+        "#).unwrap();
+        assert!(Hooks::from_config(&Some(invalid)).is_err());
+    }
+
+    #[test]
+    fn test_command_hooks() {
+        let config = ::toml::de::from_str(r#"
+        [store.hooks]
+            pre-update  = ["grep -q content && test \"$IMAG_HOOK_ID\" = test"]
+            pre-delete  = ["echo no >&2; exit 1"]
+        "#).unwrap();
+
+        let hooks     = Hooks::from_config(&Some(config)).unwrap();
+        let id        = StoreId::new(PathBuf::from("test")).unwrap();
+        let storepath = PathBuf::from("/");
+        let mut entry = Entry::new(id.clone());
+
+        let event = HookEvent::new(HookPosition::PreUpdate, &id, None);
+        assert!(hooks.run(&storepath, &event, &mut entry).is_err());
+
+        entry.get_content_mut().push_str("content");
+        assert!(hooks.run(&storepath, &event, &mut entry).is_ok());
+
+        let event = HookEvent::new(HookPosition::PreDelete, &id, None);
+        let err   = hooks.run(&storepath, &event, &mut entry).unwrap_err();
+        assert!(format!("{}", err.find_root_cause()).contains("no"));
+    }
+
+    #[test]
+    fn test_hook_writing_before_reading() {
+        // More than fits into a pipe, in both directions
+        let config = ::toml::de::from_str(r#"
+        [store.hooks]
+            pre-update  = ["head -c 200000 /dev/zero; cat >/dev/null"]
+        "#).unwrap();
+
+        let hooks     = Hooks::from_config(&Some(config)).unwrap();
+        let id        = StoreId::new(PathBuf::from("test")).unwrap();
+        let storepath = PathBuf::from("/");
+        let mut entry = Entry::new(id.clone());
+        entry.get_content_mut().push_str(&"a".repeat(200000));
+
+        let event = HookEvent::new(HookPosition::PreUpdate, &id, None);
+        assert!(hooks.run(&storepath, &event, &mut entry).is_ok());
+    }
+}
@@ -0,0 +1,650 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Persistent index over the content terms and header fields of all store entries
+//!
+//! The index is optional and has to be enabled in the configuration:
+//!
+//! ```toml
+//! [store.index]
+//! enabled = true
+//! ```
+//!
+//! If enabled, the `Store` keeps the index up to date on every `Store::update()`,
+//! `Store::delete()`, `Store::move_by_id()` and when a `FileLockEntry` is dropped. The index is
+//! written to `<store>/.index/index.json` when the `Store` object is dropped.
+//!
+//! While a `Store` with an index is alive, a "dirty" marker file is present in the index
+//! directory. If the marker is found while loading the index (because a process crashed before it
+//! could write the index back), the index is considered stale and rebuilt from the store.
+//!
+//! Changes done to the store with external tools (an editor, `git checkout`, ...) are detected
+//! by a fingerprint of the store directory (the number of files and directories and the newest
+//! modification time), which is saved with the index and compared when the index is loaded.
+//! `Store::rebuild_index()` (`imag store index rebuild`) rebuilds the index by hand.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::UNIX_EPOCH;
+
+use toml::Value;
+use failure::Fallible as Result;
+use failure::ResultExt;
+use failure::Error;
+use walkdir::WalkDir;
+
+use libimagerror::errors::ErrorMsg as EM;
+
+use crate::store::Entry;
+use crate::storeid::StoreId;
+
+/// The version of the on-disk format of the index
+///
+/// If the version found on disk differs from this one, the index is rebuilt.
+const INDEX_FORMAT_VERSION: u32 = 1;
+
+/// The name of the directory inside the store where the index lives
+pub const INDEX_DIR_NAME: &str = ".index";
+
+const INDEX_FILE_NAME: &str = "index.json";
+const DIRTY_MARKER_NAME: &str = "dirty";
+
+/// What the index knows about one entry
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IndexedEntry {
+    /// The normalized (lowercased) words of the entry content
+    terms: BTreeSet<String>,
+
+    /// The header of the entry, flattened to "a.b.c" -> value
+    header: BTreeMap<String, Value>,
+}
+
+impl IndexedEntry {
+
+    /// Build the index information for an entry
+    pub fn from_entry(entry: &Entry) -> IndexedEntry {
+        let terms = tokenize(entry.get_content()).collect();
+
+        let mut header = BTreeMap::new();
+        flatten_header(None, entry.get_header(), &mut header);
+
+        IndexedEntry { terms, header }
+    }
+
+    pub fn terms(&self) -> &BTreeSet<String> {
+        &self.terms
+    }
+
+    pub fn header(&self) -> &BTreeMap<String, Value> {
+        &self.header
+    }
+}
+
+/// A summary of the store directory, to notice changes which were done without updating the index
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct StoreFingerprint {
+    /// The number of files and directories in the store, hidden ones excluded
+    files: u64,
+
+    /// The newest modification time of these, as seconds and nanoseconds since the epoch
+    newest_secs: u64,
+    newest_nanos: u32,
+}
+
+impl StoreFingerprint {
+
+    fn of(storepath: &Path) -> Result<StoreFingerprint> {
+        let mut fp = StoreFingerprint::default();
+
+        let iter = WalkDir::new(storepath)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|e| !e.file_name().to_str().map(|s| s.starts_with('.')).unwrap_or(false));
+
+        for dir_entry in iter {
+            let mtime = dir_entry
+                .context(EM::IO)?
+                .metadata()
+                .context(EM::IO)?
+                .modified()
+                .context(EM::IO)?
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+
+            fp.files += 1;
+            if (mtime.as_secs(), mtime.subsec_nanos()) > (fp.newest_secs, fp.newest_nanos) {
+                fp.newest_secs  = mtime.as_secs();
+                fp.newest_nanos = mtime.subsec_nanos();
+            }
+        }
+
+        Ok(fp)
+    }
+}
+
+/// The on-disk representation of the index
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct IndexData {
+    version: u32,
+
+    /// The store as it was when the index was written. Missing in indexes written by older
+    /// versions, which are considered stale therefor.
+    #[serde(default)]
+    fingerprint: Option<StoreFingerprint>,
+
+    entries: BTreeMap<String, IndexedEntry>,
+}
+
+impl IndexData {
+
+    fn into_entries(self) -> Result<BTreeMap<StoreId, IndexedEntry>> {
+        self.entries
+            .into_iter()
+            .map(|(id, ie)| StoreId::new(PathBuf::from(id)).map(|id| (id, ie)))
+            .collect()
+    }
+}
+
+/// The in-memory index
+///
+/// Holds all indexed entries as well as an inverted term index, which is not persisted but
+/// recomputed on load.
+#[derive(Debug, Default)]
+struct IndexInner {
+    entries: BTreeMap<StoreId, IndexedEntry>,
+    terms: BTreeMap<String, BTreeSet<StoreId>>,
+
+    /// Entries which were changed by this process, to be merged into the on-disk index on save
+    changed: BTreeSet<StoreId>,
+
+    /// Entries which were removed by this process, to be merged into the on-disk index on save
+    removed: BTreeSet<StoreId>,
+
+    /// Whether the index was completely rebuilt in this process
+    rebuilt: bool,
+}
+
+impl IndexInner {
+
+    fn from_entries(entries: BTreeMap<StoreId, IndexedEntry>) -> IndexInner {
+        let mut inner = IndexInner::default();
+        for (id, ie) in entries {
+            inner.insert(id, ie);
+        }
+        inner
+    }
+
+    fn insert(&mut self, id: StoreId, ie: IndexedEntry) {
+        self.remove(&id);
+        for term in ie.terms.iter() {
+            self.terms.entry(term.clone()).or_insert_with(BTreeSet::new).insert(id.clone());
+        }
+        self.entries.insert(id, ie);
+    }
+
+    fn remove(&mut self, id: &StoreId) -> Option<IndexedEntry> {
+        let old = self.entries.remove(id)?;
+        for term in old.terms.iter() {
+            let now_empty = self.terms
+                .get_mut(term)
+                .map(|ids| { ids.remove(id); ids.is_empty() })
+                .unwrap_or(false);
+
+            if now_empty {
+                let _ = self.terms.remove(term);
+            }
+        }
+        Some(old)
+    }
+}
+
+/// The index of a store
+///
+/// Use `Store::index()` to get the index of a store, if there is one.
+#[derive(Debug)]
+pub struct StoreIndex {
+    /// Where the index is persisted. `None` if the index lives in memory only.
+    path: Option<PathBuf>,
+
+    /// The store the index is persisted for. `None` if the index lives in memory only.
+    storepath: Option<PathBuf>,
+    inner: RwLock<IndexInner>,
+}
+
+impl StoreIndex {
+
+    /// Create an index which is not persisted
+    pub(crate) fn inmemory() -> StoreIndex {
+        StoreIndex {
+            path: None,
+            storepath: None,
+            inner: RwLock::new(IndexInner::default()),
+        }
+    }
+
+    /// Load the index from the store at `storepath`
+    ///
+    /// Returns the index and whether it is stale. A stale index has to be rebuilt by the caller.
+    pub(crate) fn load(storepath: &PathBuf) -> Result<(StoreIndex, bool)> {
+        let mut dir = storepath.clone();
+        dir.push(INDEX_DIR_NAME);
+
+        if !dir.exists() {
+            ::std::fs::create_dir_all(&dir).context(EM::DirNotCreated)?;
+        }
+
+        let path      = dir.join(INDEX_FILE_NAME);
+        let marker    = dir.join(DIRTY_MARKER_NAME);
+        let was_dirty = marker.exists();
+
+        let (entries, stale) = match read_index_file(&path)? {
+            None => {
+                debug!("No index found at {}", path.display());
+                (BTreeMap::new(), true)
+            },
+            Some(ref data) if data.version != INDEX_FORMAT_VERSION => {
+                debug!("Index format version {} found, expected {}", data.version, INDEX_FORMAT_VERSION);
+                (BTreeMap::new(), true)
+            },
+            Some(data) => {
+                let changed = data.fingerprint != Some(StoreFingerprint::of(storepath)?);
+                if was_dirty {
+                    debug!("Index was not written back properly, considering it stale");
+                } else if changed {
+                    debug!("Store was changed without updating the index, considering it stale");
+                }
+                (data.into_entries()?, was_dirty || changed)
+            },
+        };
+
+        let _ = File::create(&marker).context(EM::FileNotCreated)?;
+
+        let index = StoreIndex {
+            path: Some(path),
+            storepath: Some(storepath.clone()),
+            inner: RwLock::new(IndexInner::from_entries(entries)),
+        };
+
+        Ok((index, stale))
+    }
+
+    /// Whether the index is persisted to disk
+    pub fn is_persistent(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Add or replace the index information for an entry
+    pub(crate) fn update(&self, entry: &Entry) -> Result<()> {
+        let id = entry.get_location().clone();
+        trace!("Indexing {}", id);
+        let mut inner = self.inner.write().map_err(|_| Error::from(EM::LockError))?;
+        inner.insert(id.clone(), IndexedEntry::from_entry(entry));
+        inner.removed.remove(&id);
+        inner.changed.insert(id);
+        Ok(())
+    }
+
+    /// Remove the index information for an entry
+    pub(crate) fn remove(&self, id: &StoreId) -> Result<()> {
+        trace!("Removing {} from index", id);
+        let mut inner = self.inner.write().map_err(|_| Error::from(EM::LockError))?;
+        let _ = inner.remove(id);
+        inner.changed.remove(id);
+        inner.removed.insert(id.clone());
+        Ok(())
+    }
+
+    /// Move the index information for an entry from `old` to `new`
+    pub(crate) fn rename(&self, old: &StoreId, new: &StoreId) -> Result<()> {
+        trace!("Moving {} -> {} in index", old, new);
+        let mut inner = self.inner.write().map_err(|_| Error::from(EM::LockError))?;
+        if let Some(ie) = inner.remove(old) {
+            inner.insert(new.clone(), ie);
+        }
+        inner.changed.remove(old);
+        inner.removed.insert(old.clone());
+        inner.removed.remove(new);
+        inner.changed.insert(new.clone());
+        Ok(())
+    }
+
+    /// Replace the complete index with the passed entries
+    pub(crate) fn replace_all<I>(&self, entries: I) -> Result<()>
+        where I: Iterator<Item = (StoreId, IndexedEntry)>
+    {
+        let mut inner = self.inner.write().map_err(|_| Error::from(EM::LockError))?;
+        *inner = IndexInner::from_entries(entries.collect());
+        inner.rebuilt = true;
+        Ok(())
+    }
+
+    /// Write the index to disk
+    ///
+    /// If the index was not rebuilt by this process, the changes of this process are merged into
+    /// the index which is on disk, so that concurrent processes do not overwrite each others
+    /// changes.
+    ///
+    /// Does nothing if the index is not persistent.
+    pub(crate) fn save(&self) -> Result<()> {
+        let path = match self.path {
+            Some(ref p) => p,
+            None        => return Ok(()),
+        };
+
+        let inner = self.inner.read().map_err(|_| Error::from(EM::LockError))?;
+
+        let entries = if inner.rebuilt {
+            inner.entries.clone()
+        } else {
+            let mut on_disk = read_index_file(path)?
+                .filter(|data| data.version == INDEX_FORMAT_VERSION)
+                .map(IndexData::into_entries)
+                .unwrap_or_else(|| Ok(BTreeMap::new()))?;
+
+            for id in inner.removed.iter() {
+                let _ = on_disk.remove(id);
+            }
+
+            for id in inner.changed.iter() {
+                if let Some(ie) = inner.entries.get(id) {
+                    let _ = on_disk.insert(id.clone(), ie.clone());
+                }
+            }
+
+            on_disk
+        };
+
+        debug!("Writing index with {} entries to {}", entries.len(), path.display());
+        let entries = entries
+            .into_iter()
+            .map(|(id, ie)| id.to_str().map(|id| (id, ie)))
+            .collect::<Result<_>>()?;
+        let fingerprint = match self.storepath {
+            Some(ref storepath) => Some(StoreFingerprint::of(storepath)?),
+            None                => None,
+        };
+        let data = IndexData { version: INDEX_FORMAT_VERSION, fingerprint, entries };
+
+        let tmp = path.with_extension("json.tmp");
+        {
+            let file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&tmp)
+                .context(EM::FileNotCreated)?;
+
+            ::serde_json::to_writer(file, &data)
+                .context(EM::FileNotWritten)?;
+        }
+
+        ::std::fs::rename(&tmp, path).context(EM::FileNotRenamed)?;
+
+        if let Some(marker) = path.parent().map(|p| p.join(DIRTY_MARKER_NAME)) {
+            if marker.exists() {
+                ::std::fs::remove_file(marker).context(EM::FileNotRemoved)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the number of indexed entries
+    pub fn len(&self) -> Result<usize> {
+        self.inner
+            .read()
+            .map_err(|_| Error::from(EM::LockError))
+            .map(|inner| inner.entries.len())
+    }
+
+    /// Check whether the index is empty
+    pub fn is_empty(&self) -> Result<bool> {
+        self.len().map(|l| l == 0)
+    }
+
+    /// Get the ids of all indexed entries
+    pub fn ids(&self) -> Result<Vec<StoreId>> {
+        self.inner
+            .read()
+            .map_err(|_| Error::from(EM::LockError))
+            .map(|inner| inner.entries.keys().cloned().collect())
+    }
+
+    /// Get the index information of one entry
+    pub fn get(&self, id: &StoreId) -> Result<Option<IndexedEntry>> {
+        self.inner
+            .read()
+            .map_err(|_| Error::from(EM::LockError))
+            .map(|inner| inner.entries.get(id).cloned())
+    }
+
+    /// Get the ids of all entries which contain the word `term`
+    ///
+    /// The term is normalized the same way the content is normalized when indexing, so the lookup
+    /// is case-insensitive.
+    pub fn ids_with_term(&self, term: &str) -> Result<Vec<StoreId>> {
+        let term = term.to_lowercase();
+        self.inner
+            .read()
+            .map_err(|_| Error::from(EM::LockError))
+            .map(|inner| {
+                inner.terms
+                    .get(&term)
+                    .map(|ids| ids.iter().cloned().collect())
+                    .unwrap_or_else(Vec::new)
+            })
+    }
+
+    /// Get the ids of all entries which contain all of the passed words
+    pub fn ids_with_all_terms<S: AsRef<str>>(&self, terms: &[S]) -> Result<Vec<StoreId>> {
+        let inner = self.inner.read().map_err(|_| Error::from(EM::LockError))?;
+        let mut result : Option<BTreeSet<StoreId>> = None;
+
+        for term in terms {
+            let ids = inner.terms
+                .get(&term.as_ref().to_lowercase())
+                .cloned()
+                .unwrap_or_else(BTreeSet::new);
+
+            result = Some(match result {
+                None      => ids,
+                Some(acc) => acc.intersection(&ids).cloned().collect(),
+            });
+        }
+
+        Ok(result.map(|r| r.into_iter().collect()).unwrap_or_else(Vec::new))
+    }
+
+    /// Get the ids of all entries which contain a word which contains `part`
+    ///
+    /// This can be used to find candidates for a substring search in the content of the entries:
+    /// If the content of an entry contains `part` (and `part` contains only word characters), one
+    /// of the words of the content contains `part` as well. The lookup is case-insensitive.
+    pub fn ids_with_term_containing(&self, part: &str) -> Result<Vec<StoreId>> {
+        let part = part.to_lowercase();
+        self.inner
+            .read()
+            .map_err(|_| Error::from(EM::LockError))
+            .map(|inner| {
+                inner.terms
+                    .iter()
+                    .filter(|(term, _)| term.contains(&part))
+                    .flat_map(|(_, ids)| ids.iter().cloned())
+                    .collect::<BTreeSet<StoreId>>()
+                    .into_iter()
+                    .collect()
+            })
+    }
+
+    /// Get the ids of all entries which have a header field at `path`
+    ///
+    /// The path is specified in the "a.b.c" form and may point to a table as well.
+    pub fn ids_with_header(&self, path: &str) -> Result<Vec<StoreId>> {
+        let prefix = format!("{}.", path);
+        self.filter_entries(|ie| {
+            ie.header.contains_key(path) || ie.header.keys().any(|k| k.starts_with(&prefix))
+        })
+    }
+
+    /// Get the ids of all entries which have a header field at `path` for which `pred` holds
+    ///
+    /// Only leaf values (values which are not tables) can be matched, as only those are indexed.
+    pub fn ids_where_header<F>(&self, path: &str, pred: F) -> Result<Vec<StoreId>>
+        where F: Fn(&Value) -> bool
+    {
+        self.filter_entries(|ie| ie.header.get(path).map(|v| pred(v)).unwrap_or(false))
+    }
+
+    fn filter_entries<F>(&self, f: F) -> Result<Vec<StoreId>>
+        where F: Fn(&IndexedEntry) -> bool
+    {
+        self.inner
+            .read()
+            .map_err(|_| Error::from(EM::LockError))
+            .map(|inner| {
+                inner.entries
+                    .iter()
+                    .filter(|(_, ie)| f(ie))
+                    .map(|(id, _)| id.clone())
+                    .collect()
+            })
+    }
+}
+
+/// Split a text into normalized words, as they are indexed
+pub fn tokenize<'a>(text: &'a str) -> impl Iterator<Item = String> + 'a {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .map(str::to_lowercase)
+}
+
+fn flatten_header(prefix: Option<&str>, value: &Value, out: &mut BTreeMap<String, Value>) {
+    match *value {
+        Value::Table(ref tab) => for (k, v) in tab.iter() {
+            let key = match prefix {
+                Some(p) => format!("{}.{}", p, k),
+                None    => k.clone(),
+            };
+            flatten_header(Some(&key), v, out);
+        },
+        ref other => if let Some(p) = prefix {
+            let _ = out.insert(String::from(p), other.clone());
+        },
+    }
+}
+
+fn read_index_file(path: &PathBuf) -> Result<Option<IndexData>> {
+    if !path.exists() {
+        return Ok(None)
+    }
+
+    let file = File::open(path).context(EM::IO)?;
+    match ::serde_json::from_reader(file) {
+        Ok(data) => Ok(Some(data)),
+        Err(e)   => {
+            warn!("Index at {} could not be read, ignoring it: {}", path.display(), e);
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use toml::Value;
+
+    use super::*;
+    use crate::store::Entry;
+    use crate::storeid::StoreId;
+
+    fn entry(name: &str, content: &str) -> Entry {
+        let id = StoreId::new(PathBuf::from(name)).unwrap();
+        let mut entry = Entry::new(id);
+        *entry.get_content_mut() = String::from(content);
+        entry
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let terms : Vec<String> = tokenize("Hello, World! hello-again").collect();
+        assert_eq!(terms, vec!["hello", "world", "hello", "again"]);
+    }
+
+    #[test]
+    fn test_index_terms() {
+        let index = StoreIndex::inmemory();
+        index.update(&entry("a", "Foo bar")).unwrap();
+        index.update(&entry("b", "bar baz")).unwrap();
+
+        assert_eq!(index.ids_with_term("FOO").unwrap().len(), 1);
+        assert_eq!(index.ids_with_term("bar").unwrap().len(), 2);
+        assert_eq!(index.ids_with_all_terms(&["bar", "baz"]).unwrap().len(), 1);
+        assert_eq!(index.ids_with_term_containing("ba").unwrap().len(), 2);
+        assert!(index.ids_with_term("nope").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_index_update_replaces_terms() {
+        let index = StoreIndex::inmemory();
+        index.update(&entry("a", "foo")).unwrap();
+        index.update(&entry("a", "bar")).unwrap();
+
+        assert!(index.ids_with_term("foo").unwrap().is_empty());
+        assert_eq!(index.ids_with_term("bar").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_index_remove_and_rename() {
+        let index = StoreIndex::inmemory();
+        let a = StoreId::new(PathBuf::from("a")).unwrap();
+        let b = StoreId::new(PathBuf::from("b")).unwrap();
+
+        index.update(&entry("a", "foo")).unwrap();
+        index.rename(&a, &b).unwrap();
+        assert_eq!(index.ids_with_term("foo").unwrap(), vec![b.clone()]);
+
+        index.remove(&b).unwrap();
+        assert!(index.is_empty().unwrap());
+    }
+
+    #[test]
+    fn test_index_header() {
+        let index = StoreIndex::inmemory();
+        let mut e = entry("a", "");
+        {
+            let hdr = e.get_header_mut().as_table_mut().unwrap();
+            let mut sub = ::toml::map::Map::new();
+            sub.insert(String::from("b"), Value::Integer(1));
+            hdr.insert(String::from("x"), Value::Table(sub));
+        }
+        index.update(&e).unwrap();
+        index.update(&entry("b", "")).unwrap();
+
+        assert_eq!(index.ids_with_header("imag.version").unwrap().len(), 2);
+        assert_eq!(index.ids_with_header("x").unwrap().len(), 1);
+        assert_eq!(index.ids_with_header("x.b").unwrap().len(), 1);
+        assert!(index.ids_where_header("x.b", |v| *v == Value::Integer(2)).unwrap().is_empty());
+    }
+}
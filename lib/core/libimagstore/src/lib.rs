@@ -45,9 +45,18 @@ extern crate toml;
 extern crate semver;
 extern crate walkdir;
 #[macro_use] extern crate is_match;
+extern crate serde;
+#[macro_use] extern crate serde_derive;
 extern crate serde_json;
 #[macro_use] extern crate failure;
 extern crate toml_query;
+extern crate fs2;
+extern crate chrono;
+#[cfg(feature = "watch")] extern crate notify;
+#[cfg(feature = "encryption")] extern crate chacha20poly1305;
+#[cfg(feature = "encryption")] extern crate argon2;
+#[cfg(feature = "encryption")] extern crate base64;
+#[cfg(feature = "sqlite")] #[macro_use] extern crate rusqlite;
 
 extern crate libimagerror;
 extern crate libimagutil;
@@ -57,6 +66,14 @@ extern crate libimagutil;
 pub mod storeid;
 pub mod iter;
 pub mod store;
+pub mod index;
+pub mod lock;
+pub mod history;
+pub mod migration;
+pub mod event;
+pub mod hook;
+pub mod transaction;
+pub mod schema;
 mod configuration;
-mod file_abstraction;
+pub mod file_abstraction;
 
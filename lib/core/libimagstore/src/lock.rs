@@ -0,0 +1,219 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Advisory locks on entries, shared between processes
+//!
+//! If libimagstore is compiled with the `fs-locking` feature, the store takes an exclusive lock
+//! (`flock()`) on every entry which is borrowed via `Store::create()`, `Store::retrieve()` or
+//! `Store::get()`. The lock is released when the `FileLockEntry` is dropped. Deleting and moving
+//! entries takes the lock as well.
+//!
+//! If the entry is locked by another process, the store waits for it to be released, at most for
+//! `store.locking.timeout` milliseconds. After that, an `ErrorMsg::EntryLocked` error is returned.
+//!
+//! The lock files live in the `.locks` directory inside the store. They are not removed when the
+//! lock is released, because another process might wait for the very same file.
+
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+
+use fs2::FileExt;
+use failure::Fallible as Result;
+use failure::ResultExt;
+use failure::Error;
+
+use libimagerror::errors::ErrorMsg as EM;
+
+use crate::storeid::StoreId;
+
+/// The name of the directory inside the store where the lock files live
+pub const LOCKS_DIR_NAME: &str = ".locks";
+
+/// How long to wait before trying to take a contended lock again
+const RETRY_INTERVAL: Duration = Duration::from_millis(20);
+
+/// An exclusive lock on an entry, released on drop
+#[derive(Debug)]
+pub(crate) struct EntryLock {
+    id:   StoreId,
+    file: File,
+}
+
+impl EntryLock {
+
+    /// Lock the entry `id` of the store at `storepath`
+    ///
+    /// Waits at most `timeout` for another process to release the lock.
+    pub(crate) fn acquire(storepath: &PathBuf, id: &StoreId, timeout: Duration) -> Result<EntryLock> {
+        let path = lock_file_path(storepath, id)?;
+        trace!("Locking {} via {}", id, path.display());
+
+        if let Some(parent) = path.parent() {
+            ::std::fs::create_dir_all(parent).context(EM::DirNotCreated)?;
+        }
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&path)
+            .context(EM::FileNotCreated)?;
+
+        let start = Instant::now();
+        loop {
+            match file.try_lock_exclusive() {
+                Ok(()) => {
+                    trace!("Locked {}", id);
+                    return Ok(EntryLock { id: id.clone(), file })
+                },
+
+                Err(ref e) if e.raw_os_error() == ::fs2::lock_contended_error().raw_os_error() => {
+                    let elapsed = start.elapsed();
+                    if elapsed >= timeout {
+                        debug!("Waiting for lock on {} timed out", id);
+                        return Err(Error::from(EM::EntryLocked(id.local_display_string())))
+                    }
+
+                    ::std::thread::sleep(::std::cmp::min(RETRY_INTERVAL, timeout - elapsed));
+                },
+
+                Err(e) => return Err(e).context(EM::IO).map_err(Error::from),
+            }
+        }
+    }
+
+}
+
+impl Drop for EntryLock {
+
+    fn drop(&mut self) {
+        trace!("Unlocking {}", self.id);
+        if let Err(e) = self.file.unlock() {
+            // The lock is released when the file is closed anyways
+            debug!("Failed to unlock {}: {:?}", self.id, e);
+        }
+    }
+
+}
+
+/// `<store>/.locks/<id>.lock`
+fn lock_file_path(storepath: &PathBuf, id: &StoreId) -> Result<PathBuf> {
+    let mut path = storepath.join(LOCKS_DIR_NAME).join(id.local());
+    let name     = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(|n| format!("{}.lock", n))
+        .ok_or_else(|| Error::from(EM::ConversionError))?;
+
+    path.set_file_name(name);
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::process::Command;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use tempdir::TempDir;
+
+    use libimagerror::errors::ErrorMsg as EM;
+
+    use super::EntryLock;
+    use crate::storeid::StoreId;
+
+    const CHILD_ENV: &str = "IMAG_LOCK_TEST_CHILD_DIR";
+
+    fn is_entry_locked(e: &::failure::Error) -> bool {
+        match e.downcast_ref::<EM>() {
+            Some(EM::EntryLocked(_)) => true,
+            _                        => false,
+        }
+    }
+
+    fn wait_for(path: &PathBuf) {
+        let start = Instant::now();
+        while !path.exists() {
+            assert!(start.elapsed() < Duration::from_secs(30), "Timeout waiting for {}", path.display());
+            ::std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_lock_is_exclusive() {
+        let dir  = TempDir::new("imag-lock-test").unwrap();
+        let path = dir.path().to_path_buf();
+        let id   = StoreId::new(PathBuf::from("foo/bar")).unwrap();
+        let zero = Duration::from_millis(0);
+
+        let lock = EntryLock::acquire(&path, &id, zero).unwrap();
+        assert!(path.join(".locks/foo/bar.lock").exists());
+
+        let err = EntryLock::acquire(&path, &id, Duration::from_millis(50)).unwrap_err();
+        assert!(is_entry_locked(&err));
+
+        let other = StoreId::new(PathBuf::from("foo/baz")).unwrap();
+        assert!(EntryLock::acquire(&path, &other, zero).is_ok());
+
+        drop(lock);
+        assert!(EntryLock::acquire(&path, &id, zero).is_ok());
+    }
+
+    /// Helper for `test_lock_between_processes()`, does nothing if not called from there
+    #[test]
+    fn test_lock_child_process_helper() {
+        let path = match ::std::env::var(CHILD_ENV) {
+            Ok(p)  => PathBuf::from(p),
+            Err(_) => return,
+        };
+
+        let id   = StoreId::new(PathBuf::from("entry")).unwrap();
+        let lock = EntryLock::acquire(&path, &id, Duration::from_millis(0)).unwrap();
+        ::std::fs::write(path.join("locked"), "").unwrap();
+        wait_for(&path.join("release"));
+        drop(lock);
+    }
+
+    #[test]
+    fn test_lock_between_processes() {
+        let dir  = TempDir::new("imag-lock-test").unwrap();
+        let path = dir.path().to_path_buf();
+        let id   = StoreId::new(PathBuf::from("entry")).unwrap();
+
+        let mut child = Command::new(::std::env::current_exe().unwrap())
+            .arg("--exact")
+            .arg("lock::tests::test_lock_child_process_helper")
+            .env(CHILD_ENV, &path)
+            .spawn()
+            .unwrap();
+
+        wait_for(&path.join("locked"));
+
+        let err = EntryLock::acquire(&path, &id, Duration::from_millis(100)).unwrap_err();
+        assert!(is_entry_locked(&err));
+
+        ::std::fs::write(path.join("release"), "").unwrap();
+        assert!(EntryLock::acquire(&path, &id, Duration::from_secs(30)).is_ok());
+        assert!(child.wait().unwrap().success());
+    }
+}
@@ -0,0 +1,345 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Migration of entries written by older versions of imag
+//!
+//! Every entry carries the version of imag which wrote it in `imag.version`. If a library changes
+//! the layout of the header parts it owns, it provides a `Migration` which upgrades entries to the
+//! new layout. A migration is keyed by the version which introduced the new layout and is applied
+//! to all entries with an older `imag.version`.
+//!
+//! Libraries offer a function which registers their migrations in a `MigrationRegistry`:
+//!
+//! ```ignore
+//! pub fn register_migrations(registry: &mut MigrationRegistry) -> Result<()> {
+//!     registry.register(Migration::new("mylib-rename-foo", "0.11.0", rename_foo)?);
+//!     Ok(())
+//! }
+//! ```
+//!
+//! `MigrationRegistry::migrate_store()` (`imag store migrate`) then runs the migrations in the
+//! order of their versions on all entries and sets `imag.version` to the current version.
+
+use semver::Version;
+use toml::Value;
+use toml_query::insert::TomlValueInsertExt;
+use toml_query::read::TomlValueReadTypeExt;
+use failure::Fallible as Result;
+use failure::ResultExt;
+use failure::Error;
+
+use libimagerror::errors::ErrorMsg as EM;
+
+use crate::store::Entry;
+use crate::store::Store;
+use crate::storeid::StoreId;
+
+/// The function which upgrades an entry
+pub type MigrationFn = fn(&mut Entry) -> Result<()>;
+
+/// One upgrade step for entries written before `version`
+pub struct Migration {
+    name: &'static str,
+    version: Version,
+    migrate: MigrationFn,
+}
+
+impl Migration {
+
+    /// Create a migration, `version` has to be a semver version string
+    pub fn new(name: &'static str, version: &str, migrate: MigrationFn) -> Result<Migration> {
+        let version = Version::parse(version)
+            .context(format_err!("Invalid version for migration '{}': {}", name, version))?;
+
+        Ok(Migration { name, version, migrate })
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The version which introduced the layout this migration upgrades to
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+
+}
+
+/// What `MigrationRegistry::migrate_store()` did (or would have done)
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    migrated: Vec<(StoreId, Vec<&'static str>)>,
+    up_to_date: usize,
+    failed: Vec<(StoreId, Error)>,
+}
+
+impl MigrationReport {
+
+    /// The migrated entries with the names of the migrations which were applied to them
+    ///
+    /// Entries which only got a new `imag.version` are listed without migrations.
+    pub fn migrated(&self) -> &[(StoreId, Vec<&'static str>)] {
+        &self.migrated
+    }
+
+    /// The number of entries which were up to date already
+    pub fn up_to_date(&self) -> usize {
+        self.up_to_date
+    }
+
+    /// The entries which could not be migrated
+    pub fn failed(&self) -> &[(StoreId, Error)] {
+        &self.failed
+    }
+
+}
+
+/// The set of all known migrations
+#[derive(Default)]
+pub struct MigrationRegistry {
+    migrations: Vec<Migration>,
+    current: Option<Version>,
+}
+
+impl MigrationRegistry {
+
+    /// Create an empty registry which migrates to the version of libimagstore
+    pub fn new() -> MigrationRegistry {
+        MigrationRegistry::default()
+    }
+
+    /// Create an empty registry which migrates to `current` instead of the version of
+    /// libimagstore
+    pub fn with_current_version(current: Version) -> MigrationRegistry {
+        MigrationRegistry { migrations: vec![], current: Some(current) }
+    }
+
+    /// Add a migration
+    ///
+    /// Migrations with the same version are run in the order they were registered in.
+    pub fn register(&mut self, migration: Migration) -> &mut Self {
+        let pos = self
+            .migrations
+            .iter()
+            .position(|m| m.version > migration.version)
+            .unwrap_or_else(|| self.migrations.len());
+
+        self.migrations.insert(pos, migration);
+        self
+    }
+
+    /// All migrations, ordered by version
+    pub fn migrations(&self) -> &[Migration] {
+        &self.migrations
+    }
+
+    /// The version entries are migrated to
+    pub fn current_version(&self) -> Version {
+        self.current
+            .clone()
+            .unwrap_or_else(|| Version::parse(env!("CARGO_PKG_VERSION")).unwrap()) // our own version is valid
+    }
+
+    /// The migrations which have to be applied to `entry`, in order
+    ///
+    /// Fails if the entry has no valid `imag.version` or was written by a newer version.
+    pub fn pending<'a>(&'a self, entry: &Entry) -> Result<Vec<&'a Migration>> {
+        let version = entry_version(entry)?;
+        if version > self.current_version() {
+            return Err(format_err!("Entry '{}' was written by a newer version of imag: {}",
+                                   entry.get_location(), version))
+        }
+
+        Ok(self.migrations.iter().filter(|m| version < m.version).collect())
+    }
+
+    /// Apply all pending migrations to `entry` and set its `imag.version` to the current version
+    ///
+    /// Returns the names of the applied migrations, or `None` if the entry is up to date.
+    pub fn migrate_entry(&self, entry: &mut Entry) -> Result<Option<Vec<&'static str>>> {
+        let current = self.current_version();
+        if entry_version(entry)? == current {
+            return Ok(None)
+        }
+
+        let mut applied = vec![];
+        for migration in self.pending(entry)? {
+            debug!("Applying migration '{}' to {}", migration.name, entry.get_location());
+            (migration.migrate)(entry)
+                .context(format_err!("Migration '{}' failed for '{}'", migration.name, entry.get_location()))?;
+            applied.push(migration.name);
+        }
+
+        let _ = entry
+            .get_header_mut()
+            .insert("imag.version", Value::String(current.to_string()))
+            .context(EM::EntryHeaderWriteError)?;
+
+        Ok(Some(applied))
+    }
+
+    /// Migrate all entries of the store
+    ///
+    /// If `dry_run` is set, the entries are not written, but the report tells what would have been
+    /// done. Entries which fail to migrate are reported and do not stop the migration of the
+    /// others.
+    pub fn migrate_store(&self, store: &Store, dry_run: bool) -> Result<MigrationReport> {
+        let mut report = MigrationReport::default();
+
+        for id in store.entries()? {
+            let id = id?;
+
+            // Migrate a copy, so that a failing migration does not leave a half-migrated entry
+            let result = store.get_copy(id.clone()).and_then(|mut copy| {
+                match self.migrate_entry(&mut copy)? {
+                    Some(applied) => {
+                        if !dry_run {
+                            let mut entry = store.retrieve(id.clone())?;
+                            *entry.get_header_mut()  = copy.get_header().clone();
+                            *entry.get_content_mut() = copy.get_content().to_string();
+                        }
+                        Ok(Some(applied))
+                    },
+                    None => Ok(None),
+                }
+            });
+
+            match result {
+                Ok(Some(applied)) => report.migrated.push((id, applied)),
+                Ok(None)          => report.up_to_date += 1,
+                Err(e)            => report.failed.push((id, e)),
+            }
+        }
+
+        Ok(report)
+    }
+
+}
+
+fn entry_version(entry: &Entry) -> Result<Version> {
+    let version = entry
+        .get_header()
+        .read_string("imag.version")
+        .context(EM::EntryHeaderReadError)?
+        .ok_or_else(|| format_err!("Entry '{}' has no imag.version", entry.get_location()))?;
+
+    Version::parse(&version)
+        .context(format_err!("Entry '{}' has an invalid imag.version: {}", entry.get_location(), version))
+        .map_err(Error::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use semver::Version;
+    use toml::Value;
+    use toml_query::insert::TomlValueInsertExt;
+    use toml_query::read::TomlValueReadTypeExt;
+
+    use failure::Fallible as Result;
+
+    use super::*;
+    use crate::store::Entry;
+    use crate::store::Store;
+    use crate::storeid::StoreId;
+
+    fn entry_with_version(name: &str, version: &str) -> Entry {
+        let mut entry = Entry::new(StoreId::new(PathBuf::from(name)).unwrap());
+        let _ = entry
+            .get_header_mut()
+            .insert("imag.version", Value::String(String::from(version)))
+            .unwrap();
+        entry
+    }
+
+    fn add_a(entry: &mut Entry) -> Result<()> {
+        entry.get_content_mut().push_str("a");
+        Ok(())
+    }
+
+    fn add_b(entry: &mut Entry) -> Result<()> {
+        entry.get_content_mut().push_str("b");
+        Ok(())
+    }
+
+    fn registry() -> MigrationRegistry {
+        let mut registry = MigrationRegistry::with_current_version(Version::parse("0.3.0").unwrap());
+        registry
+            .register(Migration::new("b", "0.3.0", add_b).unwrap())
+            .register(Migration::new("a", "0.2.0", add_a).unwrap());
+        registry
+    }
+
+    #[test]
+    fn test_migrations_are_ordered_by_version() {
+        let names = registry().migrations().iter().map(|m| m.name()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["a", "b"]);
+        assert!(Migration::new("invalid", "not a version", add_a).is_err());
+    }
+
+    #[test]
+    fn test_migrate_entry() {
+        let registry = registry();
+
+        let mut old = entry_with_version("old", "0.1.0");
+        assert_eq!(registry.migrate_entry(&mut old).unwrap(), Some(vec!["a", "b"]));
+        assert_eq!(old.get_content(), "ab");
+        assert_eq!(old.get_header().read_string("imag.version").unwrap(), Some(String::from("0.3.0")));
+
+        let mut newer = entry_with_version("newer", "0.2.0");
+        assert_eq!(registry.migrate_entry(&mut newer).unwrap(), Some(vec!["b"]));
+        assert_eq!(newer.get_content(), "b");
+
+        let mut current = entry_with_version("current", "0.3.0");
+        assert_eq!(registry.migrate_entry(&mut current).unwrap(), None);
+
+        let mut future = entry_with_version("future", "1.0.0");
+        assert!(registry.migrate_entry(&mut future).is_err());
+    }
+
+    #[test]
+    fn test_migrate_store_dry_run() {
+        let store    = Store::new_inmemory(PathBuf::from("/"), &None).unwrap();
+        let registry = registry();
+
+        {
+            let mut entry = store.create(PathBuf::from("old")).unwrap();
+            *entry.get_header_mut() = entry_with_version("old", "0.1.0").get_header().clone();
+        }
+        {
+            let mut entry = store.create(PathBuf::from("current")).unwrap();
+            *entry.get_header_mut() = entry_with_version("current", "0.3.0").get_header().clone();
+        }
+
+        let report = registry.migrate_store(&store, true).unwrap();
+        assert_eq!(report.migrated().len(), 1);
+        assert_eq!(report.up_to_date(), 1);
+        assert!(report.failed().is_empty());
+        assert_eq!(store.get_copy(PathBuf::from("old")).unwrap().get_content(), "");
+
+        let report = registry.migrate_store(&store, false).unwrap();
+        assert_eq!(report.migrated().len(), 1);
+        assert_eq!(store.get_copy(PathBuf::from("old")).unwrap().get_content(), "ab");
+
+        let report = registry.migrate_store(&store, false).unwrap();
+        assert!(report.migrated().is_empty());
+        assert_eq!(report.up_to_date(), 2);
+    }
+}
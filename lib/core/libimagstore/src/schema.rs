@@ -0,0 +1,406 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! A registry of the known configuration keys
+//!
+//! Libraries and binaries declare the keys they read from the configuration, with their types,
+//! defaults and a short description, in a `ConfigSchema`. The schema is used to validate a
+//! configuration, so that typos and values of the wrong type are found before a command fails
+//! because of them, and to print the default configuration.
+//!
+//! Tables with arbitrary keys, like `imag.aliases`, are registered with
+//! `ConfigSchema::register_table()`. Their contents are not validated.
+//!
+//! The schema lives in the store, so that every library can register its keys. The keys of the
+//! store itself are registered with `register_config_schema()`.
+
+use std::collections::BTreeMap;
+use std::collections::btree_map::Values;
+use std::fmt::{Display, Formatter, Error as FmtError};
+
+use toml::Value;
+use toml_query::insert::TomlValueInsertExt;
+use failure::Fallible as Result;
+use failure::ResultExt;
+
+use libimagerror::errors::ErrorMsg as EM;
+
+use crate::hook::HookPosition;
+
+/// The type of a configuration value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Datetime,
+    Array,
+    Table,
+}
+
+impl ValueKind {
+
+    pub fn of(value: &Value) -> ValueKind {
+        match *value {
+            Value::String(_)   => ValueKind::String,
+            Value::Integer(_)  => ValueKind::Integer,
+            Value::Float(_)    => ValueKind::Float,
+            Value::Boolean(_)  => ValueKind::Boolean,
+            Value::Datetime(_) => ValueKind::Datetime,
+            Value::Array(_)    => ValueKind::Array,
+            Value::Table(_)    => ValueKind::Table,
+        }
+    }
+
+    /// Parse a value of this kind from the commandline
+    ///
+    /// Strings are taken as they are, everything else is parsed as TOML value, for example
+    /// `[ "a", "b" ]` for an array. Returns `None` if `s` is not a value of this kind.
+    pub fn parse(&self, s: &str) -> Option<Value> {
+        if *self == ValueKind::String {
+            return Some(Value::String(String::from(s)))
+        }
+
+        ::toml::de::from_str::<Value>(&format!("value = {}", s))
+            .ok()
+            .and_then(|mut table| table.as_table_mut().and_then(|t| t.remove("value")))
+            .filter(|value| ValueKind::of(value) == *self)
+    }
+
+}
+
+impl Display for ValueKind {
+    fn fmt(&self, fmt: &mut Formatter) -> ::std::result::Result<(), FmtError> {
+        let s = match *self {
+            ValueKind::String   => "string",
+            ValueKind::Integer  => "integer",
+            ValueKind::Float    => "float",
+            ValueKind::Boolean  => "boolean",
+            ValueKind::Datetime => "datetime",
+            ValueKind::Array    => "array",
+            ValueKind::Table    => "table",
+        };
+        write!(fmt, "{}", s)
+    }
+}
+
+/// A key in the configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigKey {
+    path: String,
+    kind: ValueKind,
+    default: Option<Value>,
+    description: &'static str,
+}
+
+impl ConfigKey {
+
+    pub fn new<P: Into<String>>(path: P, kind: ValueKind, description: &'static str) -> ConfigKey {
+        ConfigKey {
+            path: path.into(),
+            kind,
+            default: None,
+            description,
+        }
+    }
+
+    /// The value which is used if the key is not in the configuration
+    pub fn with_default<V: Into<Value>>(mut self, default: V) -> ConfigKey {
+        self.default = Some(default.into());
+        self
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn kind(&self) -> ValueKind {
+        self.kind
+    }
+
+    pub fn default(&self) -> Option<&Value> {
+        self.default.as_ref()
+    }
+
+    pub fn description(&self) -> &'static str {
+        self.description
+    }
+
+}
+
+/// Something which is wrong with a configuration
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// The key is not known, with the most similar known key
+    UnknownKey { path: String, suggestion: Option<String> },
+
+    /// The value of the key has the wrong type
+    WrongType { path: String, expected: ValueKind, found: ValueKind },
+}
+
+impl Display for Problem {
+    fn fmt(&self, fmt: &mut Formatter) -> ::std::result::Result<(), FmtError> {
+        match *self {
+            Problem::UnknownKey { ref path, suggestion: Some(ref s) } => {
+                write!(fmt, "Unknown key '{}', did you mean '{}'?", path, s)
+            },
+            Problem::UnknownKey { ref path, suggestion: None } => {
+                write!(fmt, "Unknown key '{}'", path)
+            },
+            Problem::WrongType { ref path, ref expected, ref found } => {
+                write!(fmt, "Key '{}' should be a {}, but is a {}", path, expected, found)
+            },
+        }
+    }
+}
+
+/// The set of all known configuration keys
+#[derive(Debug, Default)]
+pub struct ConfigSchema {
+    keys: BTreeMap<String, ConfigKey>,
+    tables: BTreeMap<String, &'static str>,
+}
+
+impl ConfigSchema {
+
+    pub fn new() -> ConfigSchema {
+        ConfigSchema::default()
+    }
+
+    pub fn register(&mut self, key: ConfigKey) -> &mut Self {
+        self.keys.insert(key.path.clone(), key);
+        self
+    }
+
+    /// Register a table which may contain arbitrary keys
+    pub fn register_table<P: Into<String>>(&mut self, path: P, description: &'static str) -> &mut Self {
+        self.tables.insert(path.into(), description);
+        self
+    }
+
+    /// Get the key at `path`
+    pub fn key(&self, path: &str) -> Option<&ConfigKey> {
+        self.keys.get(path)
+    }
+
+    pub fn keys(&self) -> Values<String, ConfigKey> {
+        self.keys.values()
+    }
+
+    /// Get the description of the table with arbitrary keys `path` is in, if any
+    pub fn table(&self, path: &str) -> Option<&'static str> {
+        self.tables
+            .iter()
+            .find(|&(table, _)| path == table || path.starts_with(&format!("{}.", table)))
+            .map(|(_, description)| *description)
+    }
+
+    /// Find the unknown keys and the values of the wrong type in `config`
+    pub fn validate(&self, config: &Value) -> Vec<Problem> {
+        let mut problems = vec![];
+
+        if let Some(table) = config.as_table() {
+            for (key, value) in table {
+                self.validate_value(key.clone(), value, &mut problems);
+            }
+        }
+
+        problems
+    }
+
+    fn validate_value(&self, path: String, value: &Value, problems: &mut Vec<Problem>) {
+        let found = ValueKind::of(value);
+
+        if self.tables.contains_key(&path) {
+            if found != ValueKind::Table {
+                problems.push(Problem::WrongType { path, expected: ValueKind::Table, found });
+            }
+        } else if let Some(key) = self.keys.get(&path) {
+            if found != key.kind {
+                problems.push(Problem::WrongType { path, expected: key.kind, found });
+            }
+        } else if self.is_parent(&path) {
+            match value.as_table() {
+                Some(table) => for (key, value) in table {
+                    self.validate_value(format!("{}.{}", path, key), value, problems);
+                },
+                None => problems.push(Problem::WrongType { path, expected: ValueKind::Table, found }),
+            }
+        } else {
+            let suggestion = self.suggest(&path);
+            problems.push(Problem::UnknownKey { path, suggestion });
+        }
+    }
+
+    /// Whether there are known keys below `path`
+    fn is_parent(&self, path: &str) -> bool {
+        let prefix = format!("{}.", path);
+        self.keys.keys().chain(self.tables.keys()).any(|k| k.starts_with(&prefix))
+    }
+
+    /// The known key which is most similar to `path`, if it is similar enough
+    fn suggest(&self, path: &str) -> Option<String> {
+        self.keys
+            .keys()
+            .chain(self.tables.keys())
+            .map(|k| (edit_distance(path, k), k))
+            .filter(|&(distance, _)| distance <= path.len() / 3)
+            .min()
+            .map(|(_, k)| k.clone())
+    }
+
+    /// Build a configuration which contains the default values of all keys which have one
+    pub fn defaults(&self) -> Result<Value> {
+        let mut config = Value::Table(Default::default());
+
+        for key in self.keys.values() {
+            if let Some(ref default) = key.default {
+                let _ = config.insert(&key.path, default.clone()).context(EM::TomlQueryError)?;
+            }
+        }
+
+        Ok(config)
+    }
+
+}
+
+/// The Levenshtein distance of `a` and `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                previous
+            } else {
+                1 + previous.min(current).min(row[j])
+            };
+            previous = current;
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Register the keys which are read by the store
+pub fn register_config_schema(schema: &mut ConfigSchema) {
+    use self::ValueKind::*;
+
+    schema
+        .register(ConfigKey::new("store.implicit-create", Boolean, "Create the store directory if it does not exist")
+                  .with_default(false))
+        .register(ConfigKey::new("store.backend", String, "The store backend: 'filesystem' or 'sqlite'")
+                  .with_default("filesystem"))
+        .register(ConfigKey::new("store.index.enabled", Boolean, "Keep an index over content and header of all entries")
+                  .with_default(false))
+        .register(ConfigKey::new("store.locking.timeout", Integer, "Milliseconds to wait for an entry which is locked by another process")
+                  .with_default(5000))
+        .register(ConfigKey::new("store.history.enabled", Boolean, "Keep a change log of all entries")
+                  .with_default(false))
+        .register(ConfigKey::new("store.encryption.enabled", Boolean, "Encrypt entries at rest")
+                  .with_default(false))
+        .register(ConfigKey::new("store.encryption.collections", Array, "Glob patterns of the collections which are encrypted"))
+        .register(ConfigKey::new("store.encryption.encrypt-header", Boolean, "Encrypt the header as well as the content")
+                  .with_default(false))
+        .register(ConfigKey::new("store.encryption.keyfile", String, "Derive the key from this file instead of a passphrase"));
+
+    for position in HookPosition::all() {
+        let path = format!("store.hooks.{}", position.name());
+        let _    = schema.register(ConfigKey::new(path, Array, "Commands which are run for this store operation"));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use toml::Value;
+    use toml_query::read::TomlValueReadExt;
+
+    use super::*;
+
+    fn schema() -> ConfigSchema {
+        let mut schema = ConfigSchema::new();
+        schema
+            .register(ConfigKey::new("store.implicit-create", ValueKind::Boolean, "").with_default(false))
+            .register(ConfigKey::new("store.backend", ValueKind::String, "").with_default("filesystem"))
+            .register(ConfigKey::new("rt.editor", ValueKind::String, ""))
+            .register_table("imag.aliases", "");
+        schema
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = ::toml::de::from_str::<Value>(r#"
+            [rt]
+            editor = 1
+
+            [store]
+            implict-create = true
+            backend = "filesystem"
+
+            [imag.aliases]
+            store = [ "s" ]
+
+            [foo]
+            bar = 1
+        "#).unwrap();
+
+        let problems = schema().validate(&config);
+
+        assert_eq!(problems, vec![
+            Problem::UnknownKey {
+                path: String::from("foo"),
+                suggestion: None,
+            },
+            Problem::WrongType {
+                path: String::from("rt.editor"),
+                expected: ValueKind::String,
+                found: ValueKind::Integer,
+            },
+            Problem::UnknownKey {
+                path: String::from("store.implict-create"),
+                suggestion: Some(String::from("store.implicit-create")),
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_defaults() {
+        let defaults = schema().defaults().unwrap();
+
+        assert_eq!(defaults.read("store.implicit-create").unwrap(), Some(&Value::Boolean(false)));
+        assert_eq!(defaults.read("store.backend").unwrap(), Some(&Value::String("filesystem".into())));
+        assert!(defaults.read("rt.editor").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(ValueKind::String.parse("a b"), Some(Value::String("a b".into())));
+        assert_eq!(ValueKind::Integer.parse("5"), Some(Value::Integer(5)));
+        assert_eq!(ValueKind::Integer.parse("five"), None);
+        assert_eq!(ValueKind::Boolean.parse("true"), Some(Value::Boolean(true)));
+        assert_eq!(ValueKind::Array.parse("[ \"a\" ]"), Some(Value::Array(vec![Value::String("a".into())])));
+    }
+
+}
@@ -29,6 +29,9 @@ use std::ops::DerefMut;
 use std::fmt::Formatter;
 use std::fmt::Debug;
 use std::fmt::Error as FMTError;
+use std::time::Duration;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::channel;
 
 use libimagerror::errors::ErrorMsg as EM;
 
@@ -42,10 +45,28 @@ use failure::Error;
 
 use crate::storeid::{IntoStoreId, StoreId};
 use crate::iter::Entries;
+use crate::index::StoreIndex;
+use crate::index::IndexedEntry;
+use crate::lock::EntryLock;
+use crate::history::History;
+use crate::history::RevisionKind;
+use crate::event::StoreEvent;
+use crate::event::Subscribers;
+use crate::hook::Hooks;
+use crate::hook::HookEvent;
+use crate::hook::HookPosition;
+use crate::transaction::Transaction;
+use crate::transaction::Operation;
+use crate::file_abstraction::BackendKind;
 use crate::file_abstraction::FileAbstraction;
 use crate::file_abstraction::FileAbstractionInstance;
-use crate::file_abstraction::fs::FSFileAbstraction;
 use crate::file_abstraction::inmemory::InMemoryFileAbstraction;
+#[cfg(feature = "encryption")]
+use crate::file_abstraction::crypt::CryptFileAbstraction;
+#[cfg(feature = "encryption")]
+use crate::file_abstraction::crypt::Encryption;
+#[cfg(feature = "encryption")]
+use crate::file_abstraction::crypt::Secret;
 
 use libimagutil::debug_result::*;
 
@@ -64,6 +85,16 @@ struct StoreEntry {
     store_base: PathBuf, // small sacrefice over lifetimes on the Store type
     file: Box<FileAbstractionInstance>,
     status: StoreEntryStatus,
+
+    /// The lock on the entry, held while it is borrowed (with the `fs-locking` feature only)
+    lock: Option<EntryLock>,
+
+    /// The content of the borrowed entry which the backend already holds
+    ///
+    /// Writing a borrowed entry with this content is skipped, so that borrowing an entry without
+    /// modifying it does not write it or run hooks. `None` if the entry must be written, for
+    /// example because it does not exist in the backend yet.
+    clean: Option<Entry>,
 }
 
 impl StoreEntry {
@@ -71,18 +102,13 @@ impl StoreEntry {
     fn new(store_base: PathBuf, id: StoreId, backend: &Arc<FileAbstraction>) -> Result<StoreEntry> {
         let pb = id.clone().with_base(&store_base).into_pathbuf()?;
 
-        #[cfg(feature = "fs-lock")]
-        {
-            open_file(pb.clone())
-                .and_then(|f| f.lock_exclusive())
-                .with_context(|| EM::IO)?;
-        }
-
         Ok(StoreEntry {
             id,
             store_base,
             file: backend.new_instance(pb),
             status: StoreEntryStatus::Present,
+            lock: None,
+            clean: None,
         })
     }
 
@@ -93,16 +119,26 @@ impl StoreEntry {
     }
 
     fn get_entry(&mut self) -> Result<Entry> {
+        self.load_entry().map(|entry| entry.unwrap_or_else(|| Entry::new(self.id.clone())))
+    }
+
+    /// Like `StoreEntry::get_entry()`, but `None` if the entry does not exist in the backend
+    fn load_entry(&mut self) -> Result<Option<Entry>> {
         if !self.is_borrowed() {
-            match self.file.get_file_content(self.id.clone().with_base(&self.store_base))? {
-                Some(file) => Ok(file),
-                None       => Ok(Entry::new(self.id.clone()))
-            }
+            self.file.get_file_content(self.id.clone().with_base(&self.store_base))
         } else {
             Err(format_err!("EntryAlreadyBorrowed: {}", self.id))
         }
     }
 
+    /// Give the entry back after it was borrowed
+    fn release(&mut self) {
+        debug!("Modifying presence of {} -> Present", self.id);
+        self.status = StoreEntryStatus::Present;
+        self.lock   = None;
+        self.clean  = None;
+    }
+
     fn write_entry(&mut self, entry: &Entry) -> Result<()> {
         if self.is_borrowed() {
             assert_eq!(self.id, entry.location);
@@ -116,17 +152,6 @@ impl StoreEntry {
     }
 }
 
-#[cfg(feature = "fs-lock")]
-impl Drop for StoreEntry {
-
-    fn drop(self) {
-        self.get_entry()
-            .and_then(|entry| open_file(entry.get_location().clone()))
-            .and_then(|f| f.unlock())
-    }
-
-}
-
 
 /// The Store itself, through this object one can interact with IMAG's entries
 pub struct Store {
@@ -145,6 +170,39 @@ pub struct Store {
     ///
     /// This provides the filesystem-operation functions (or pretends to)
     backend: Arc<FileAbstraction>,
+
+    /// The index over the entries, if enabled
+    index: Option<StoreIndex>,
+
+    /// The directory where transaction journals are written to
+    ///
+    /// `None` if the backend is not persistent, transactions are not journaled in this case.
+    journal: Option<PathBuf>,
+
+    /// How long to wait for entries which are locked by another process
+    ///
+    /// `None` if entries are not locked, see the `lock` module.
+    lock_timeout: Option<Duration>,
+
+    /// The change logs of the entries, if enabled
+    ///
+    /// Always `None` if the backend is not persistent.
+    history: Option<History>,
+
+    /// The receivers of `StoreEvent`s, see `Store::subscribe()`
+    subscribers: Subscribers,
+
+    /// The hooks which are run before and after operations
+    hooks: Hooks,
+
+    /// The encryption settings and key, if entries are encrypted
+    ///
+    /// The backend is wrapped in a `CryptFileAbstraction` in this case.
+    #[cfg(feature = "encryption")]
+    encryption: Option<Arc<Encryption>>,
+
+    /// Whether the store was opened with `Store::new_read_only()`
+    read_only: bool,
 }
 
 impl Store {
@@ -160,15 +218,31 @@ impl Store {
     ///
     /// If the path exists and is a file, the operation is aborted as well, an error is returned.
     ///
+    /// The backend is selected with `store.backend` in the store_config, the filesystem backend is
+    /// used if it is not set.
+    ///
     /// # Return values
     ///
     /// - On success: Store object
     ///
     pub fn new(location: PathBuf, store_config: &Option<Value>) -> Result<Store> {
-        let backend = Arc::new(FSFileAbstraction::default());
+        let backend = BackendKind::from_config(store_config)?.create_backend(&location)?;
         Store::new_with_backend(location, store_config, backend)
     }
 
+    /// Open the Store in `location` for reading only
+    ///
+    /// Unlike `Store::new()`, this does not create the store directory, does not replay pending
+    /// transaction journals and does not load, rebuild or write back the index. Entries can be
+    /// listed and read with `Store::entries()` and `Store::get_copy()`, all operations which
+    /// would modify the store fail.
+    ///
+    /// This is meant for cheap lookups, like the shell completion.
+    pub fn new_read_only(location: PathBuf, store_config: &Option<Value>) -> Result<Store> {
+        let backend = BackendKind::from_config(store_config)?.create_backend(&location)?;
+        Store::open(location, store_config, backend, true)
+    }
+
     /// Create the store with an in-memory filesystem
     ///
     /// # Usage
@@ -183,14 +257,26 @@ impl Store {
     /// Create a Store object as descripbed in `Store::new()` documentation, but with an alternative
     /// backend implementation.
     ///
-    /// Do not use directly, only for testing purposes.
-    pub(crate) fn new_with_backend(location: PathBuf,
-                        store_config: &Option<Value>,
-                        backend: Arc<FileAbstraction>) -> Result<Store> {
+    /// See the `file_abstraction` module for what a backend has to implement.
+    pub fn new_with_backend(location: PathBuf,
+                            store_config: &Option<Value>,
+                            backend: Arc<FileAbstraction>) -> Result<Store> {
+        Store::open(location, store_config, backend, false)
+    }
+
+    fn open(location: PathBuf,
+            store_config: &Option<Value>,
+            backend: Arc<FileAbstraction>,
+            read_only: bool) -> Result<Store> {
         use crate::configuration::*;
 
-        debug!("Building new Store object");
+        debug!("Building new Store object with backend: {:?}", backend);
+        let persistent = backend.is_persistent();
         if !location.exists() {
+            if read_only {
+                return Err(format_err!("StorePathMissing: {}", location.display()))
+            }
+
             if !config_implicit_store_create_allowed(store_config)? {
                 return Err(format_err!("CreateStoreDirDenied"))
                     .context(EM::FileError)
@@ -207,12 +293,76 @@ impl Store {
             return Err(format_err!("StorePathExists: {}", location.display()));
         }
 
+        if persistent
+            && config_encryption_enabled(store_config)?
+            && (config_index_enabled(store_config)? || config_history_enabled(store_config)?)
+        {
+            return Err(format_err!("The index and the history cannot be used with an encrypted store, \
+                                    they would keep the entries in plain text"))
+        }
+
+        #[cfg(feature = "encryption")]
+        let (backend, encryption) = match Encryption::from_config(&location, persistent, store_config)? {
+            Some(encryption) => {
+                let encryption = Arc::new(encryption);
+                let backend    = Arc::new(CryptFileAbstraction::new(backend, encryption.clone()));
+                (backend as Arc<FileAbstraction>, Some(encryption))
+            },
+            None => (backend, None),
+        };
+
+        #[cfg(not(feature = "encryption"))]
+        {
+            if config_encryption_enabled(store_config)? {
+                return Err(format_err!("Store encryption is enabled, but not compiled in"))
+            }
+        }
+
+        let (index, index_stale) = if read_only || !config_index_enabled(store_config)? {
+            (None, false)
+        } else if persistent {
+            let (index, stale) = StoreIndex::load(&location)
+                .context(format_err!("IndexLoadError: {}", location.display()))?;
+            (Some(index), stale)
+        } else {
+            (Some(StoreIndex::inmemory()), false)
+        };
+
         let store = Store {
             location: location.clone(),
             entries: Arc::new(RwLock::new(HashMap::new())),
             backend: backend,
+            index,
+            journal: if persistent && !read_only {
+                Some(location.join(crate::transaction::JOURNAL_DIR_NAME))
+            } else {
+                None
+            },
+            lock_timeout: if cfg!(feature = "fs-locking") && persistent {
+                Some(config_lock_timeout(store_config)?)
+            } else {
+                None
+            },
+            history: if persistent && !read_only && config_history_enabled(store_config)? {
+                Some(History::new(&location))
+            } else {
+                None
+            },
+            subscribers: Subscribers::default(),
+            hooks: Hooks::from_config(store_config)?,
+            #[cfg(feature = "encryption")]
+            encryption,
+            read_only,
         };
 
+        crate::transaction::replay_journals(&store)
+            .context(format_err!("JournalReplayError: {}", location.display()))?;
+
+        if index_stale {
+            info!("Index is stale, rebuilding");
+            store.rebuild_index()?;
+        }
+
         debug!("Store building succeeded");
         debug!("------------------------");
         debug!("{:?}", store);
@@ -229,6 +379,7 @@ impl Store {
     ///
     pub fn create<'a, S: IntoStoreId>(&'a self, id: S) -> Result<FileLockEntry<'a>> {
         let id = id.into_storeid()?;
+        self.check_writable()?;
 
         debug!("Creating id: '{}'", id);
 
@@ -239,6 +390,8 @@ impl Store {
             return Err(format_err!("EntryAlreadyExists: {}", id));
         }
 
+        let entry = self.run_pre_create_hooks(&id)?;
+
         {
             let mut hsmap = self
                 .entries
@@ -252,17 +405,24 @@ impl Store {
                            .context(format_err!("CreateCallError: {}", id))
                            .map_err(Error::from)
             }
+            let lock = self.lock_entry(&id)?;
+            if lock.is_some() && self.backend.exists(&id.clone().with_base(self.path()).into_pathbuf()?)? {
+                debug!("Entry was created by another process: {:?}", id);
+                return Err(format_err!("EntryAlreadyExists: {}", id));
+            }
+
             hsmap.insert(id.clone(), {
                 debug!("Creating: '{}'", id);
                 let mut se = StoreEntry::new(self.path().clone(), id.clone(), &self.backend)?;
                 se.status = StoreEntryStatus::Borrowed;
+                se.lock   = lock;
                 se
             });
         }
 
         debug!("Constructing FileLockEntry: '{}'", id);
 
-        Ok(FileLockEntry::new(self, Entry::new(id)))
+        Ok(FileLockEntry::new(self, entry))
     }
 
     /// Borrow a given Entry. When the `FileLockEntry` is either `update`d or
@@ -277,7 +437,15 @@ impl Store {
     ///
     pub fn retrieve<'a, S: IntoStoreId>(&'a self, id: S) -> Result<FileLockEntry<'a>> {
         let id = id.into_storeid()?;
+        self.check_writable()?;
         debug!("Retrieving id: '{}'", id);
+
+        let created = if self.hooks.has(HookPosition::PreCreate) && !self.exists(id.clone())? {
+            Some(self.run_pre_create_hooks(&id)?)
+        } else {
+            None
+        };
+
         let entry = self
             .entries
             .write()
@@ -285,9 +453,22 @@ impl Store {
             .and_then(|mut es| {
                 let new_se = StoreEntry::new(self.path().clone(), id.clone(), &self.backend)?;
                 let se = es.entry(id.clone()).or_insert(new_se);
-                let entry = se.get_entry();
+                let lock = if se.is_borrowed() { None } else { self.lock_entry(&id)? };
+                let entry = match created {
+                    Some(entry) if !se.is_borrowed() => entry,
+                    _                                => match se.load_entry()? {
+                        Some(entry) => {
+                            se.clean = Some(entry.clone());
+                            entry
+                        },
+                        None => Entry::new(id.clone()),
+                    },
+                };
                 se.status = StoreEntryStatus::Borrowed;
-                entry
+                if lock.is_some() {
+                    se.lock = lock;
+                }
+                Ok(entry)
             })
             .context(format_err!("RetrieveCallError: {}", id))?;
 
@@ -344,30 +525,127 @@ impl Store {
     /// it is not public.
     ///
     fn _update<'a>(&'a self, entry: &mut FileLockEntry<'a>, modify_presence: bool) -> Result<()> {
-        let mut hsmap = self.entries.write()
-            .map_err(|_| Error::from(EM::LockError))?;
+        if let Some(hooked) = self.write_borrowed(&entry.entry, modify_presence, true)? {
+            entry.entry = hooked;
+        }
+        Ok(())
+    }
 
-        let se = hsmap.get_mut(&entry.location).ok_or_else(|| {
-            EM::EntryNotFound(entry.location.local_display_string())
-        })?;
+    /// Internal method to write an entry which is currently borrowed to the filesystem store
+    ///
+    /// Returns the entry as it was written if a pre-hook changed it. The pre-create hooks of a new
+    /// entry already ran when it was borrowed, so only the pre-update hooks are run here.
+    fn write_borrowed(&self, entry: &Entry, modify_presence: bool, run_pre_hooks: bool)
+        -> Result<Option<Entry>>
+    {
+        {
+            let mut hsmap = self.entries.write().map_err(|_| Error::from(EM::LockError))?;
+            if let Some(se) = hsmap.get_mut(&entry.location) {
+                if se.is_borrowed() && se.clean.as_ref() == Some(entry) {
+                    debug!("Entry unchanged, not writing: {}", entry.get_location());
+                    if modify_presence {
+                        se.release();
+                    }
+                    return Ok(None)
+                }
+            }
+        }
+
+        let needs_existence = !self.subscribers.is_empty()
+            || self.hooks.has(HookPosition::PreUpdate)
+            || self.hooks.has(HookPosition::PostUpdate)
+            || self.hooks.has(HookPosition::PostCreate);
+
+        let existed = if needs_existence {
+            self.backend.exists(&entry.location.clone().with_base(self.path()).into_pathbuf()?)?
+        } else {
+            true // not needed
+        };
 
-        assert!(se.is_borrowed(), "Tried to update a non borrowed entry.");
+        let post = if existed { HookPosition::PostUpdate } else { HookPosition::PostCreate };
 
-        debug!("Verifying Entry");
-        entry.entry.verify()?;
+        let hooked = if existed && run_pre_hooks && self.hooks.has(HookPosition::PreUpdate) {
+            let mut copy = entry.clone();
+            let event    = HookEvent::new(HookPosition::PreUpdate, &entry.location, None);
+            if let Err(e) = self.hooks.run(self.path(), &event, &mut copy) {
+                if modify_presence {
+                    self.release_borrowed(&entry.location, existed)?;
+                }
+                return Err(e)
+            }
+
+            if copy != *entry { Some(copy) } else { None }
+        } else {
+            None
+        };
+        let entry = hooked.as_ref().unwrap_or(entry);
+
+        {
+            let mut hsmap = self.entries.write()
+                .map_err(|_| Error::from(EM::LockError))?;
+
+            let se = hsmap.get_mut(&entry.location).ok_or_else(|| {
+                EM::EntryNotFound(entry.location.local_display_string())
+            })?;
+
+            assert!(se.is_borrowed(), "Tried to update a non borrowed entry.");
+
+            debug!("Verifying Entry");
+            entry.verify()?;
+
+            self.write_borrowed_entry(se, entry, existed, modify_presence)?;
+        }
+
+        self.run_post_hooks(post, &entry.location, None, Some(entry.clone()));
+        Ok(hooked)
+    }
+
+    fn write_borrowed_entry(&self, se: &mut StoreEntry, entry: &Entry, existed: bool, modify_presence: bool)
+        -> Result<()>
+    {
+        let prior = self.prior_version(&entry.location)?;
 
         debug!("Writing Entry");
-        se.write_entry(&entry.entry)?;
+        se.write_entry(entry)?;
         trace!("Entry written");
+
+        self.subscribers.emit(if existed {
+            StoreEvent::Updated(entry.location.clone())
+        } else {
+            StoreEvent::Created(entry.location.clone())
+        });
+
+        if let Some(ref history) = self.history {
+            history.record(&entry.location, RevisionKind::Write, &entry.to_str()?, None, prior)?;
+        }
+
+        if let Some(ref index) = self.index {
+            index.update(entry)?;
+        }
+
         if modify_presence {
-            debug!("Modifying presence of {} -> Present", entry.get_location());
-            se.status = StoreEntryStatus::Present;
+            se.release();
+        } else {
+            se.clean = Some(entry.clone());
         }
 
         trace!("Entry updated successfully");
         Ok(())
     }
 
+    /// Give back a borrowed entry without writing it
+    ///
+    /// Entries which do not exist in the backend are removed from the cache.
+    fn release_borrowed(&self, id: &StoreId, existed: bool) -> Result<()> {
+        let mut hsmap = self.entries.write().map_err(|_| Error::from(EM::LockError))?;
+        if !existed {
+            let _ = hsmap.remove(id);
+        } else if let Some(se) = hsmap.get_mut(id) {
+            se.release();
+        }
+        Ok(())
+    }
+
     /// Flush the store internal cache
     ///
     /// This is helpful if a lot of entries are beeing read/written, because the store holds the
@@ -442,7 +720,21 @@ impl Store {
     /// On success: ()
     ///
     pub fn delete<S: IntoStoreId>(&self, id: S) -> Result<()> {
-        let id = id.into_storeid()?;
+        self._delete(id.into_storeid()?, true)
+    }
+
+    fn _delete(&self, id: StoreId, run_pre_hooks: bool) -> Result<()> {
+        self.check_writable()?;
+
+        if run_pre_hooks {
+            self.run_pre_hooks(HookPosition::PreDelete, &id, None)?;
+        }
+
+        let deleted = if self.hooks.has(HookPosition::PostDelete) {
+            self.entry_on_backend(&id)?
+        } else {
+            None
+        };
 
         debug!("Deleting id: '{}'", id);
 
@@ -491,12 +783,27 @@ impl Store {
         }
 
         debug!("Seems like {:?} is on the FS", pb);
+        let _lock = self.lock_entry(&id).context(format_err!("DeleteCallError: {}", id))?;
+        let prior = self.prior_version(&id)?;
         let _ = self
             .backend
             .remove_file(&pb)
             .context(EM::FileError)
             .context(format_err!("DeleteCallError: {}", id))?;
 
+        if let Some(ref history) = self.history {
+            history.record(&id, RevisionKind::Delete, "", None, prior)?;
+        }
+
+        self.subscribers.emit(StoreEvent::Deleted(id.clone()));
+        if deleted.is_some() {
+            self.run_post_hooks(HookPosition::PostDelete, &id, None, deleted);
+        }
+
+        if let Some(ref index) = self.index {
+            index.remove(&id)?;
+        }
+
         debug!("Deleted");
         Ok(())
     }
@@ -542,8 +849,34 @@ impl Store {
                 Ok(())
             })
             .context(EM::FileError)
-            .context(format_err!("MoveCallError: {} -> {}", old_id, new_id))
-            .map_err(Error::from)
+            .context(format_err!("MoveCallError: {} -> {}", old_id, new_id))?;
+
+        self.subscribers.emit(if remove_old {
+            StoreEvent::Moved { from: old_id.clone(), to: new_id.clone() }
+        } else {
+            StoreEvent::Created(new_id.clone())
+        });
+
+        if let Some(ref history) = self.history {
+            let text = entry.entry.to_str()?;
+            history.record(&new_id, RevisionKind::Write, &text, None, None)?;
+
+            if remove_old {
+                history.record(&old_id, RevisionKind::Delete, "", None, Some(text))?;
+            }
+        }
+
+        if let Some(ref index) = self.index {
+            let mut copy = entry.entry.clone();
+            copy.location = new_id;
+            index.update(&copy)?;
+
+            if remove_old {
+                index.remove(&old_id)?;
+            }
+        }
+
+        Ok(())
     }
 
     /// Move an entry without loading
@@ -580,8 +913,18 @@ impl Store {
     /// So the link is _partly dangling_, so to say.
     ///
     pub fn move_by_id(&self, old_id: StoreId, new_id: StoreId) -> Result<()> {
+        self._move_by_id(old_id, new_id, true)
+    }
+
+    fn _move_by_id(&self, old_id: StoreId, new_id: StoreId, run_pre_hooks: bool) -> Result<()> {
+        self.check_writable()?;
+
         debug!("Moving '{}' to '{}'", old_id, new_id);
 
+        if run_pre_hooks {
+            self.run_pre_hooks(HookPosition::PreMove, &old_id, Some(&new_id))?;
+        }
+
         {
             let mut hsmap = self.entries.write()
                 .map_err(|_| Error::from(EM::LockError))?;
@@ -600,6 +943,9 @@ impl Store {
 
             debug!("Old id is not yet borrowed");
 
+            let _old_lock = self.lock_entry(&old_id)?;
+            let _new_lock = self.lock_entry(&new_id)?;
+
             let old_id_pb = old_id.clone().with_base(self.path()).into_pathbuf()?;
             let new_id_pb = new_id.clone().with_base(self.path()).into_pathbuf()?;
 
@@ -619,6 +965,17 @@ impl Store {
 
             debug!("Rename worked on filesystem");
 
+            if let Some(ref history) = self.history {
+                let text = self.version_on_backend(&new_id)?.unwrap_or_default();
+                history.record_move(&old_id, &new_id, &text)?;
+            }
+
+            self.subscribers.emit(StoreEvent::Moved { from: old_id.clone(), to: new_id.clone() });
+
+            if let Some(ref index) = self.index {
+                index.rename(&old_id, &new_id)?;
+            }
+
             // assert enforced through check hsmap.contains_key(&new_id) above.
             // Should therefor never fail
             assert!(hsmap
@@ -630,9 +987,108 @@ impl Store {
         }
 
         debug!("Moved");
+        self.run_post_hooks(HookPosition::PostMove, &old_id, Some(&new_id), None);
         Ok(())
     }
 
+    /// Register a closure which is run before or after store operations
+    ///
+    /// See the `hook` module.
+    pub fn register_hook<F>(&self, position: HookPosition, hook: F) -> Result<()>
+        where F: Fn(&HookEvent, &mut Entry) -> Result<()> + Send + Sync + 'static
+    {
+        self.hooks.add(position, Box::new(hook))
+    }
+
+    /// Run the pre-create hooks for the new entry `id`, returning the entry as the hooks left it
+    fn run_pre_create_hooks(&self, id: &StoreId) -> Result<Entry> {
+        let mut entry = Entry::new(id.clone());
+        if self.hooks.has(HookPosition::PreCreate) {
+            let event = HookEvent::new(HookPosition::PreCreate, id, None);
+            self.hooks.run(self.path(), &event, &mut entry)?;
+        }
+        Ok(entry)
+    }
+
+    /// Run the pre-hooks of an operation on an existing entry
+    ///
+    /// Does nothing if the entry does not exist, the operation fails in this case anyways.
+    fn run_pre_hooks(&self, position: HookPosition, id: &StoreId, new_id: Option<&StoreId>) -> Result<()> {
+        if !self.hooks.has(position) {
+            return Ok(())
+        }
+
+        match self.entry_on_backend(id)? {
+            Some(mut entry) => self.hooks.run(self.path(), &HookEvent::new(position, id, new_id), &mut entry),
+            None            => Ok(()),
+        }
+    }
+
+    /// Run the post-hooks of an operation, failures are only logged
+    ///
+    /// If `entry` is `None`, the entry is loaded from the backend (with the new id for moves).
+    fn run_post_hooks(&self, position: HookPosition, id: &StoreId, new_id: Option<&StoreId>, entry: Option<Entry>) {
+        if !self.hooks.has(position) {
+            return
+        }
+
+        let entry = match entry {
+            Some(entry) => Ok(Some(entry)),
+            None        => self.entry_on_backend(new_id.unwrap_or(id)),
+        };
+
+        let result = entry.and_then(|entry| match entry {
+            Some(mut entry) => self.hooks.run(self.path(), &HookEvent::new(position, id, new_id), &mut entry),
+            None            => Ok(()),
+        });
+
+        if let Err(e) = result {
+            warn!("{} hooks failed for '{}'", position.name(), id);
+            ::libimagerror::trace::trace_error(&e);
+        }
+    }
+
+    /// The version of `id` in the backend, if the history is enabled and `id` has no change log
+    /// yet
+    ///
+    /// Has to be called before the entry is changed, it is recorded as the first revision then.
+    fn prior_version(&self, id: &StoreId) -> Result<Option<String>> {
+        match self.history {
+            Some(ref history) if !history.is_tracked(id)? => self.version_on_backend(id),
+            _                                              => Ok(None),
+        }
+    }
+
+    /// The text of `id` as it is in the backend, bypassing the cache
+    fn version_on_backend(&self, id: &StoreId) -> Result<Option<String>> {
+        self.entry_on_backend(id)?.map(|entry| entry.to_str()).transpose()
+    }
+
+    /// `id` as it is in the backend, bypassing the cache
+    fn entry_on_backend(&self, id: &StoreId) -> Result<Option<Entry>> {
+        let pb = id.clone().with_base(self.path()).into_pathbuf()?;
+        self.backend
+            .new_instance(pb)
+            .get_file_content(id.clone().with_base(self.path()))
+    }
+
+    /// Fail if the store was opened with `Store::new_read_only()`
+    fn check_writable(&self) -> Result<()> {
+        if self.read_only {
+            Err(format_err!("StoreReadOnly: {}", self.location.display()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Lock the entry against other processes, if locking is enabled
+    fn lock_entry(&self, id: &StoreId) -> Result<Option<EntryLock>> {
+        match self.lock_timeout {
+            Some(timeout) => EntryLock::acquire(self.path(), id, timeout).map(Some),
+            None          => Ok(None),
+        }
+    }
+
     /// Get _all_ entries in the store (by id as iterator)
     pub fn entries<'a>(&'a self) -> Result<Entries<'a>> {
         trace!("Building 'Entries' iterator");
@@ -661,6 +1117,308 @@ impl Store {
         &self.location
     }
 
+    /// Start a new transaction
+    ///
+    /// See the documentation of the `transaction` module.
+    pub fn transaction<'a>(&'a self) -> Transaction<'a> {
+        Transaction::new(self)
+    }
+
+    pub(crate) fn journal_path(&self) -> Option<&PathBuf> {
+        self.journal.as_ref()
+    }
+
+    /// The operation as it is written to the journal
+    ///
+    /// Entries of the encrypted collections are encrypted, so the journal does not keep them in
+    /// plain text.
+    pub(crate) fn seal_operation(&self, op: &Operation) -> Result<Operation> {
+        #[cfg(feature = "encryption")]
+        {
+            if let (Some(encryption), &Operation::Update(ref entry)) = (self.encryption.as_ref(), op) {
+                if encryption.encrypts(entry.get_location()) {
+                    return encryption.encrypt_entry(entry).map(Operation::Update)
+                }
+            }
+        }
+
+        Ok(op.clone())
+    }
+
+    /// The operation as it was before `Store::seal_operation()`, for replaying the journal
+    pub(crate) fn unseal_operation(&self, op: Operation) -> Result<Operation> {
+        #[cfg(feature = "encryption")]
+        {
+            if let (Some(encryption), &Operation::Update(ref entry)) = (self.encryption.as_ref(), &op) {
+                if crate::file_abstraction::crypt::is_encrypted(entry) {
+                    let id = entry.get_location().clone();
+                    return encryption.decrypt_entry(id, entry.clone()).map(Operation::Update)
+                }
+            }
+        }
+
+        Ok(op)
+    }
+
+    /// Check whether an operation of a transaction can be applied
+    ///
+    /// Entries which are updated must be borrowed, entries which are deleted or moved must exist
+    /// and must not be borrowed.
+    pub(crate) fn check_operation(&self, op: &Operation) -> Result<()> {
+        self.check_writable()?;
+
+        let is_borrowed = |id: &StoreId| -> Result<bool> {
+            self.entries
+                .read()
+                .map_err(|_| Error::from(EM::LockError))
+                .map(|hsmap| hsmap.get(id).map(StoreEntry::is_borrowed).unwrap_or(false))
+        };
+
+        match *op {
+            Operation::Update(ref entry) => if !is_borrowed(entry.get_location())? {
+                return Err(format_err!("Entry is not borrowed: {}", entry.get_location()))
+            },
+            Operation::Delete(ref id) | Operation::Move(ref id, _) => {
+                if is_borrowed(id)? {
+                    return Err(format_err!("Entry already borrowed: {}", id))
+                }
+
+                if !self.exists(id.clone())? {
+                    return Err(format_err!("Entry does not exist: {}", id))
+                }
+            },
+        }
+
+        if let Operation::Move(_, ref to) = *op {
+            if is_borrowed(to)? || self.exists(to.clone())? {
+                return Err(format_err!("Entry exists already: {}", to))
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run the pre-hooks for an operation of a transaction which is being committed
+    ///
+    /// This happens before the journal is written, so that a failing hook aborts the whole
+    /// transaction. Hooks may alter the entries which are written.
+    pub(crate) fn run_operation_pre_hooks(&self, op: &mut Operation) -> Result<()> {
+        match *op {
+            Operation::Update(ref mut entry) => {
+                // The pre-create hooks of new entries already ran when they were borrowed
+                if self.hooks.has(HookPosition::PreUpdate) {
+                    let id   = entry.get_location().clone();
+                    let path = id.clone().with_base(self.path()).into_pathbuf()?;
+                    if self.backend.exists(&path)? {
+                        let event = HookEvent::new(HookPosition::PreUpdate, &id, None);
+                        self.hooks.run(self.path(), &event, entry)?;
+                    }
+                }
+
+                Ok(())
+            },
+            Operation::Delete(ref id)         => self.run_pre_hooks(HookPosition::PreDelete, id, None),
+            Operation::Move(ref from, ref to) => self.run_pre_hooks(HookPosition::PreMove, from, Some(to)),
+        }
+    }
+
+    /// Apply an operation of a transaction which is being committed
+    ///
+    /// The pre-hooks were already run by `Store::run_operation_pre_hooks()`.
+    pub(crate) fn apply_operation(&self, op: &Operation) -> Result<()> {
+        match *op {
+            Operation::Update(ref entry)      => self.write_borrowed(entry, false, false).map(|_| ()),
+            Operation::Delete(ref id)         => self._delete(id.clone(), false),
+            Operation::Move(ref from, ref to) => self._move_by_id(from.clone(), to.clone(), false),
+        }
+    }
+
+    /// Remember that the borrowed entry `entry` has nothing to write anymore
+    ///
+    /// Used for the entries of a committed transaction: the transaction already wrote them, maybe
+    /// altered by a pre-update hook, so dropping the `FileLockEntry` must not write them again.
+    pub(crate) fn mark_clean(&self, entry: &Entry) -> Result<()> {
+        let mut hsmap = self.entries.write().map_err(|_| Error::from(EM::LockError))?;
+        if let Some(se) = hsmap.get_mut(entry.get_location()) {
+            if se.is_borrowed() {
+                se.clean = Some(entry.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply an operation from a journal which was left over
+    ///
+    /// The entries are written without parsing the old version, as it might be broken.
+    pub(crate) fn replay_operation(&self, op: Operation) -> Result<()> {
+        match op {
+            Operation::Update(entry) => {
+                debug!("Replaying update of {}", entry.get_location());
+                let pb = entry.get_location().clone().with_base(self.path()).into_pathbuf()?;
+                self.backend.new_instance(pb).write_file_content(&entry)?;
+
+                if let Some(ref index) = self.index {
+                    index.update(&entry)?;
+                }
+
+                Ok(())
+            },
+            Operation::Delete(id) => if self.exists(id.clone())? {
+                debug!("Replaying delete of {}", id);
+                self._delete(id, false)
+            } else {
+                Ok(())
+            },
+            Operation::Move(from, to) => match (self.exists(from.clone())?, self.exists(to.clone())?) {
+                (true, false) => {
+                    debug!("Replaying move of {} to {}", from, to);
+                    self._move_by_id(from, to, false)
+                },
+                (true, true) => {
+                    warn!("Not replaying move of {} to {}, both exist", from, to);
+                    Ok(())
+                },
+                (false, _) => Ok(()), // already moved
+            },
+        }
+    }
+
+    /// Receive a `StoreEvent` for every entry this store object creates, updates, deletes or
+    /// moves
+    ///
+    /// See the `event` module.
+    pub fn subscribe(&self) -> Receiver<StoreEvent> {
+        let (tx, rx) = channel();
+        self.subscribers.add(tx);
+        rx
+    }
+
+    /// Send changes of the store directory made by other processes to the subscribers, until the
+    /// returned watcher is dropped
+    ///
+    /// Only useful with the filesystem backend. `delay` is the time filesystem events are
+    /// collected before they are sent, so that an editor saving a file results in one event.
+    #[cfg(feature = "watch")]
+    pub fn watch_external(&self, delay: Duration) -> Result<crate::event::ExternalWatcher> {
+        crate::event::ExternalWatcher::new(self.location.clone(), self.subscribers.clone(), delay)
+    }
+
+    /// Get the change logs of the store, if the history is enabled
+    pub fn history(&self) -> Option<&History> {
+        self.history.as_ref()
+    }
+
+    /// Get the index of the store, if the index is enabled
+    pub fn index(&self) -> Option<&StoreIndex> {
+        self.index.as_ref()
+    }
+
+    /// Rebuild the index from all entries in the store
+    ///
+    /// Does nothing if the index is not enabled.
+    pub fn rebuild_index(&self) -> Result<()> {
+        let index = match self.index {
+            Some(ref index) => index,
+            None            => return Ok(()),
+        };
+
+        debug!("Rebuilding index");
+        let entries = self
+            .entries()?
+            .map(|id| {
+                let id = id?;
+                self.get_copy(id.clone()).map(|e| (id, IndexedEntry::from_entry(&e)))
+            })
+            .collect::<Result<Vec<_>>>()
+            .context(err_msg("IndexRebuildError"))?;
+
+        index.replace_all(entries.into_iter())?;
+        debug!("Index rebuilt");
+        Ok(())
+    }
+
+    /// Re-write all entries with a new key derived from `secret`
+    ///
+    /// All entries are read before anything is written. Entries are (re-)encrypted or decrypted
+    /// according to the configured collections. Returns the number of re-written entries.
+    ///
+    /// The parameters of the new key are written to the store first and the old key is kept until
+    /// all entries are re-written. If the process dies in between, calling this again with the
+    /// same `secret` finishes the re-keying.
+    #[cfg(feature = "encryption")]
+    pub fn rekey(&self, secret: &Secret) -> Result<usize> {
+        self.rekey_entries(secret, None)
+    }
+
+    /// Re-key, but stop with an error after re-writing `limit` entries, if it is set
+    ///
+    /// The limit is used to test interrupted re-keying.
+    #[cfg(feature = "encryption")]
+    pub(crate) fn rekey_entries(&self, secret: &Secret, limit: Option<usize>) -> Result<usize> {
+        self.check_writable()?;
+        let encryption = self
+            .encryption
+            .as_ref()
+            .ok_or_else(|| format_err!("Encryption is not enabled for this store"))?;
+
+        let borrowed = self
+            .entries
+            .read()
+            .map_err(|_| Error::from(EM::LockError))?
+            .values()
+            .any(StoreEntry::is_borrowed);
+        if borrowed {
+            return Err(Error::from(EM::IdLocked)).context(err_msg("RekeyError")).map_err(Error::from)
+        }
+
+        encryption.begin_rekey(secret)?;
+
+        let entries = self
+            .backend
+            .pathes_recursively(self.path().clone(), self.path(), self.backend.clone())?
+            .map(|id| {
+                let id   = id?;
+                let path = id.clone().into_pathbuf()?;
+                self.backend
+                    .new_instance(path.clone())
+                    .get_file_content(id)?
+                    .map(|entry| (path.clone(), entry))
+                    .ok_or_else(|| format_err!("Entry vanished: {}", path.display()))
+            })
+            .collect::<Result<Vec<_>>>()
+            .context(err_msg("RekeyError"))?;
+
+        for (i, &(ref path, ref entry)) in entries.iter().enumerate() {
+            if limit.map(|limit| i >= limit).unwrap_or(false) {
+                return Err(format_err!("Re-keying stopped after {} entries", i))
+            }
+
+            debug!("Re-writing {}", path.display());
+            self.backend.new_instance(path.clone()).write_file_content(entry)?;
+        }
+
+        encryption.finish_rekey()?;
+        Ok(entries.len())
+    }
+
+}
+
+impl Drop for Store {
+
+    /// Writes the index back to disk, if there is one
+    ///
+    /// Errors are ignored, as there is nothing we can do about them here. The index is rebuilt
+    /// the next time the store is opened in that case.
+    fn drop(&mut self) {
+        if let Some(ref index) = self.index {
+            if let Err(e) = index.save() {
+                use libimagerror::trace::trace_error_dbg;
+                trace!("Error happened in Store::drop() while writing the index");
+                trace_error_dbg(&e);
+            }
+        }
+    }
+
 }
 
 impl Debug for Store {
@@ -1236,5 +1994,411 @@ mod store_tests {
         }
     }
 
+    #[test]
+    fn test_store_transaction_commit() {
+        use crate::storeid::StoreId;
+        setup_logging();
+
+        let store = get_store();
+        let a     = StoreId::new(PathBuf::from("tx-a")).unwrap();
+        let b     = StoreId::new(PathBuf::from("tx-b")).unwrap();
+        let c     = StoreId::new(PathBuf::from("tx-c")).unwrap();
+        let d     = StoreId::new(PathBuf::from("tx-d")).unwrap();
+
+        { let _ = store.create(c.clone()).unwrap(); }
+        { let _ = store.create(d.clone()).unwrap(); }
+
+        {
+            let mut ea = store.create(a.clone()).unwrap();
+            let mut eb = store.create(b.clone()).unwrap();
+            *ea.get_content_mut() = String::from("a");
+            *eb.get_content_mut() = String::from("b");
+
+            let mut tx = store.transaction();
+            tx.update(&ea).unwrap();
+            tx.update(&eb).unwrap();
+            tx.delete(c.clone()).unwrap();
+            tx.move_by_id(d.clone(), StoreId::new(PathBuf::from("tx-e")).unwrap()).unwrap();
+            tx.commit().unwrap();
+
+            // Written by the transaction already, while still borrowed
+            let backend_a = store.backend
+                .new_instance(a.clone().with_base(store.path()).into_pathbuf().unwrap())
+                .get_file_content(a.clone().with_base(store.path()))
+                .unwrap()
+                .unwrap();
+            assert_eq!(backend_a.get_content(), "a");
+        }
+
+        assert!(store.get(c).unwrap().is_none());
+        assert!(store.get(d).unwrap().is_none());
+        assert!(store.get(PathBuf::from("tx-e")).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_store_transaction_update_requires_borrow() {
+        use crate::storeid::StoreId;
+        use crate::store::Entry;
+        setup_logging();
+
+        let store = get_store();
+        let entry = Entry::new(StoreId::new(PathBuf::from("tx-not-borrowed")).unwrap());
+
+        let mut tx = store.transaction();
+        tx.update(&entry).unwrap();
+        assert!(tx.commit().is_err());
+    }
+
+    fn get_store_with_index() -> Store {
+        use crate::file_abstraction::inmemory::InMemoryFileAbstraction;
+        let config = ::toml::de::from_str(r#"
+        [store.index]
+            enabled = true
+        "#).unwrap();
+        let backend = Arc::new(InMemoryFileAbstraction::default());
+        Store::new_with_backend(PathBuf::from("/"), &Some(config), backend).unwrap()
+    }
+
+    #[test]
+    fn test_store_index_follows_store_operations() {
+        use crate::storeid::StoreId;
+        setup_logging();
+
+        let store = get_store_with_index();
+        let id    = StoreId::new(PathBuf::from("test-index")).unwrap();
+        let id_mv = StoreId::new(PathBuf::from("test-index-moved")).unwrap();
+
+        {
+            let mut entry = store.create(id.clone()).unwrap();
+            *entry.get_content_mut() = String::from("Some indexed text");
+        }
+
+        let index = store.index().unwrap();
+        assert_eq!(index.ids_with_term("indexed").unwrap(), vec![id.clone()]);
+        assert_eq!(index.ids_with_header("imag.version").unwrap(), vec![id.clone()]);
+
+        store.move_by_id(id.clone(), id_mv.clone()).unwrap();
+        assert_eq!(index.ids_with_term("indexed").unwrap(), vec![id_mv.clone()]);
+
+        store.delete(id_mv).unwrap();
+        assert!(index.ids_with_term("indexed").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_store_index_rebuild() {
+        use crate::storeid::StoreId;
+        setup_logging();
+
+        let store = get_store_with_index();
+        for n in 1..10 {
+            let id = StoreId::new(PathBuf::from(format!("test-{}", n))).unwrap();
+            let mut entry = store.create(id).unwrap();
+            *entry.get_content_mut() = format!("entry number{}", n);
+        }
+
+        store.rebuild_index().unwrap();
+        let index = store.index().unwrap();
+        assert_eq!(index.len().unwrap(), 9);
+        assert_eq!(index.ids_with_term("entry").unwrap().len(), 9);
+        assert_eq!(index.ids_with_term("number3").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_store_index_notices_external_changes() {
+        use tempdir::TempDir;
+        setup_logging();
+
+        let dir    = TempDir::new("imag-store-index-test").unwrap();
+        let config : Option<::toml::Value> = Some(::toml::de::from_str(r#"
+        [store.index]
+            enabled = true
+        "#).unwrap());
+        let write_entry = |name: &str, content: &str| {
+            let text = format!("---\n[imag]\nversion = \"{}\"\n---\n{}", env!("CARGO_PKG_VERSION"), content);
+            ::std::fs::write(dir.path().join(name), text).unwrap();
+        };
+
+        {
+            let store = Store::new(dir.path().to_path_buf(), &config).unwrap();
+            let mut entry = store.create(PathBuf::from("a")).unwrap();
+            *entry.get_content_mut() = String::from("alpha");
+        }
+
+        write_entry("b", "beta");
+        {
+            let store = Store::new(dir.path().to_path_buf(), &config).unwrap();
+            let index = store.index().unwrap();
+            assert_eq!(index.len().unwrap(), 2);
+            assert_eq!(index.ids_with_term("beta").unwrap().len(), 1);
+        }
+
+        write_entry("a", "gamma");
+        {
+            let store = Store::new(dir.path().to_path_buf(), &config).unwrap();
+            let index = store.index().unwrap();
+            assert!(index.ids_with_term("alpha").unwrap().is_empty());
+            assert_eq!(index.ids_with_term("gamma").unwrap().len(), 1);
+        }
+
+        ::std::fs::remove_file(dir.path().join("b")).unwrap();
+        {
+            let store = Store::new(dir.path().to_path_buf(), &config).unwrap();
+            assert_eq!(store.index().unwrap().len().unwrap(), 1);
+        }
+    }
+
+    #[test]
+    fn test_store_read_only() {
+        use tempdir::TempDir;
+        use crate::storeid::StoreId;
+        setup_logging();
+
+        let dir    = TempDir::new("imag-store-read-only-test").unwrap();
+        let config : Option<::toml::Value> = Some(::toml::de::from_str(r#"
+        [store.index]
+            enabled = true
+        "#).unwrap());
+
+        assert!(Store::new_read_only(dir.path().join("missing"), &config).is_err());
+        assert!(!dir.path().join("missing").exists());
+
+        {
+            let store = Store::new(dir.path().to_path_buf(), &config).unwrap();
+            let mut entry = store.create(PathBuf::from("a")).unwrap();
+            *entry.get_content_mut() = String::from("alpha");
+        }
+        ::std::fs::remove_dir_all(dir.path().join(crate::index::INDEX_DIR_NAME)).unwrap();
+
+        let store = Store::new_read_only(dir.path().to_path_buf(), &config).unwrap();
+        let ids   = store.entries().unwrap().into_storeid_iter().collect::<Vec<_>>();
+        assert_eq!(ids.len(), 1);
+        assert_eq!(store.get_copy(PathBuf::from("a")).unwrap().get_content(), "alpha");
+
+        assert!(store.index().is_none());
+        assert!(store.create(PathBuf::from("b")).is_err());
+        assert!(store.retrieve(PathBuf::from("a")).is_err());
+        assert!(store.delete(PathBuf::from("a")).is_err());
+        assert!(store.move_by_id(StoreId::new(PathBuf::from("a")).unwrap(),
+                                 StoreId::new(PathBuf::from("b")).unwrap()).is_err());
+        drop(store);
+
+        assert!(!dir.path().join(crate::index::INDEX_DIR_NAME).exists());
+        assert!(dir.path().join("a").exists());
+    }
+
+    #[test]
+    fn test_store_history_follows_store_operations() {
+        use tempdir::TempDir;
+        use crate::history::RevisionKind;
+        use crate::storeid::StoreId;
+        use super::Entry;
+        setup_logging();
+
+        let dir    = TempDir::new("imag-store-history-test").unwrap();
+        let config = ::toml::de::from_str(r#"
+        [store.history]
+            enabled = true
+        "#).unwrap();
+        let store  = Store::new(dir.path().to_path_buf(), &Some(config)).unwrap();
+        let id     = StoreId::new(PathBuf::from("history")).unwrap();
+        let moved  = StoreId::new(PathBuf::from("moved")).unwrap();
+
+        {
+            let mut entry = store.create(id.clone()).unwrap();
+            *entry.get_content_mut() = String::from("first");
+        }
+        {
+            let mut entry = store.retrieve(id.clone()).unwrap();
+            *entry.get_content_mut() = String::from("second");
+        }
+        let _ = store.retrieve(id.clone()).unwrap(); // unchanged, not recorded
+
+        store.move_by_id(id.clone(), moved.clone()).unwrap();
+        store.delete(moved.clone()).unwrap();
+
+        let history = store.history().unwrap();
+        let kinds   = history
+            .revisions(&moved)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.kind())
+            .collect::<Vec<_>>();
+
+        assert!(history.revisions(&id).unwrap().is_empty());
+        assert_eq!(kinds, vec![
+            RevisionKind::Write,
+            RevisionKind::Write,
+            RevisionKind::Move,
+            RevisionKind::Delete,
+        ]);
+
+        let text = history.text_at(&moved, 0).unwrap();
+        assert_eq!(Entry::from_str(moved.clone(), &text).unwrap().get_content(), "first");
+        let text = history.text_at(&moved, 1).unwrap();
+        assert_eq!(Entry::from_str(moved.clone(), &text).unwrap().get_content(), "second");
+        assert_eq!(history.text_at(&moved, 3).unwrap(), "");
+    }
+
+    #[test]
+    fn test_store_events() {
+        use crate::event::StoreEvent;
+        use crate::storeid::StoreId;
+        setup_logging();
+
+        let store = get_store();
+        let rx    = store.subscribe();
+        let id    = StoreId::new(PathBuf::from("test-event")).unwrap();
+        let moved = StoreId::new(PathBuf::from("test-event-moved")).unwrap();
+
+        let _ = store.create(id.clone()).unwrap();
+        let _ = store.retrieve(id.clone()).unwrap(); // unchanged, not written
+        {
+            let mut entry = store.retrieve(id.clone()).unwrap();
+            *entry.get_content_mut() = String::from("changed");
+        }
+        store.move_by_id(id.clone(), moved.clone()).unwrap();
+        store.delete(moved.clone()).unwrap();
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![
+            StoreEvent::Created(id.clone()),
+            StoreEvent::Updated(id.clone()),
+            StoreEvent::Moved { from: id, to: moved.clone() },
+            StoreEvent::Deleted(moved),
+        ]);
+
+        drop(rx);
+        let _ = store.create(PathBuf::from("test-event-after-hangup")).unwrap();
+        assert!(store.subscribers.is_empty());
+    }
+
+    #[test]
+    fn test_store_hooks() {
+        use crate::hook::HookPosition;
+        use crate::storeid::StoreId;
+        setup_logging();
+
+        let store = get_store();
+        store.register_hook(HookPosition::PreUpdate, |_, entry| {
+            entry.get_content_mut().push_str("hooked");
+            Ok(())
+        }).unwrap();
+        store.register_hook(HookPosition::PreDelete, |_, _| Err(format_err!("Not deleting"))).unwrap();
+        store.register_hook(HookPosition::PreCreate, |event, _| {
+            if event.id().local_display_string() == "test-hook-refused" {
+                Err(format_err!("Not creating"))
+            } else {
+                Ok(())
+            }
+        }).unwrap();
+
+        let _ = store.create(PathBuf::from("test-hook")).unwrap();
+        {
+            let mut entry = store.retrieve(PathBuf::from("test-hook")).unwrap();
+            *entry.get_content_mut() = String::from("content ");
+        }
+        assert_eq!(store.get_copy(PathBuf::from("test-hook")).unwrap().get_content(), "content hooked");
+
+        assert!(store.delete(PathBuf::from("test-hook")).is_err());
+        assert!(store.exists(StoreId::new(PathBuf::from("test-hook")).unwrap()).unwrap());
+
+        assert!(store.create(PathBuf::from("test-hook-refused")).is_err());
+        assert!(store.retrieve(PathBuf::from("test-hook-refused")).is_err());
+        assert!(!store.exists(StoreId::new(PathBuf::from("test-hook-refused")).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_unchanged_entry_is_not_written() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use crate::hook::HookPosition;
+        setup_logging();
+
+        let store  = get_store();
+        let _      = store.create(PathBuf::from("unchanged")).unwrap();
+        let events = store.subscribe();
+        let calls  = Arc::new(AtomicUsize::new(0));
+        for position in &[HookPosition::PreUpdate, HookPosition::PostUpdate] {
+            let calls = calls.clone();
+            store.register_hook(*position, move |_, _| {
+                let _ = calls.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }).unwrap();
+        }
+
+        {
+            let _ = store.retrieve(PathBuf::from("unchanged")).unwrap();
+        }
+        {
+            let mut entry = store.retrieve(PathBuf::from("unchanged")).unwrap();
+            store.update(&mut entry).unwrap();
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        assert!(events.try_recv().is_err());
+
+        {
+            let mut entry = store.retrieve(PathBuf::from("unchanged")).unwrap();
+            *entry.get_content_mut() = String::from("changed");
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(events.try_recv().is_ok());
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    fn test_store_watch_external() {
+        use std::time::Duration;
+        use tempdir::TempDir;
+        use crate::event::StoreEvent;
+        use crate::storeid::StoreId;
+        setup_logging();
+
+        let dir     = TempDir::new("imag-store-watch-test").unwrap();
+        let store   = Store::new(dir.path().to_path_buf(), &None).unwrap();
+        let rx      = store.subscribe();
+        let watcher = store.watch_external(Duration::from_millis(50)).unwrap();
+
+        ::std::fs::write(dir.path().join("external"), "---\n[imag]\nversion = \"0.10.0\"\n---\n").unwrap();
+        ::std::fs::write(dir.path().join(".hidden"), "").unwrap();
+
+        let event = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(event, StoreEvent::Created(StoreId::new(PathBuf::from("external")).unwrap()));
+
+        drop(watcher);
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+    }
+
+    #[cfg(feature = "fs-locking")]
+    #[test]
+    fn test_store_entry_locked_by_other_store() {
+        use tempdir::TempDir;
+        use libimagerror::errors::ErrorMsg as EM;
+
+        let dir    = TempDir::new("imag-store-lock-test").unwrap();
+        let path   = dir.path().to_path_buf();
+        let config = ::toml::de::from_str(r#"
+        [store.locking]
+            timeout = 50
+        "#).unwrap();
+        let config = Some(config);
+
+        // Two store objects lock like two processes would, as flock() locks are per open file
+        let store1 = Store::new(path.clone(), &config).unwrap();
+        let store2 = Store::new(path.clone(), &config).unwrap();
+
+        {
+            let _entry = store1.retrieve(PathBuf::from("locked")).unwrap();
+
+            let err = store2.retrieve(PathBuf::from("locked")).unwrap_err();
+            match err.find_root_cause().downcast_ref::<EM>() {
+                Some(EM::EntryLocked(_)) => {},
+                other => panic!("Expected EntryLocked error, got {:?}", other),
+            }
+
+            assert!(store2.delete(PathBuf::from("locked")).is_err());
+            assert!(store2.retrieve(PathBuf::from("other")).is_ok());
+        }
+
+        assert!(store2.retrieve(PathBuf::from("locked")).is_ok());
+    }
+
 }
 
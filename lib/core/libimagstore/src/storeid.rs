@@ -152,21 +152,20 @@ impl IntoStoreId for PathBuf {
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
-pub(crate) struct StoreIdWithBase<'a>(&'a PathBuf, PathBuf);
+pub struct StoreIdWithBase<'a>(&'a PathBuf, PathBuf);
 
 impl<'a> StoreIdWithBase<'a> {
-    #[cfg(test)]
-    pub(crate) fn new(base: &'a PathBuf, path: PathBuf) -> Self {
+    pub fn new(base: &'a PathBuf, path: PathBuf) -> Self {
         StoreIdWithBase(base, path)
     }
 
-    pub(crate) fn without_base(self) -> StoreId {
+    pub fn without_base(self) -> StoreId {
         StoreId(self.1)
     }
 
     /// Transform the StoreId object into a PathBuf, error if the base of the StoreId is not
     /// specified.
-    pub(crate) fn into_pathbuf(self) -> Result<PathBuf> {
+    pub fn into_pathbuf(self) -> Result<PathBuf> {
         let mut base = self.0.clone();
         base.push(self.1);
         Ok(base)
@@ -176,7 +175,7 @@ impl<'a> StoreIdWithBase<'a> {
     ///
     /// Automatically creates a StoreId object which has a `base` set to `store_part` if stripping
     /// the `store_part` from the `full_path` succeeded.
-    pub(crate) fn from_full_path<D>(store_part: &'a PathBuf, full_path: D) -> Result<StoreIdWithBase<'a>>
+    pub fn from_full_path<D>(store_part: &'a PathBuf, full_path: D) -> Result<StoreIdWithBase<'a>>
         where D: Deref<Target = Path>
     {
         trace!("Creating StoreIdWithBase object from full path = {} with store_part = {}",
@@ -0,0 +1,489 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Transactional batch writes
+//!
+//! A `Transaction` collects several modifications of the store (writing entries, deleting and
+//! moving them) and applies them all at once when it is committed.
+//!
+//! Before the modifications are applied, they are written to a journal file in the `.journal`
+//! directory inside the store. The journal file is removed after all modifications were applied.
+//! If the process dies while applying the modifications, the journal file is still there the next
+//! time the store is opened with `Store::new()` and the modifications are applied again. Thus,
+//! either all or none of the modifications end up in the store. Entries of encrypted collections
+//! are encrypted in the journal.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let mut a = store.retrieve(id_a)?;
+//! let mut b = store.retrieve(id_b)?;
+//! a.add_link(&mut b)?;
+//!
+//! let mut tx = store.transaction();
+//! tx.update(&a)?;
+//! tx.update(&b)?;
+//! tx.commit()?;
+//! ```
+//!
+//! Committing marks the staged entries as written, so dropping their `FileLockEntry` afterwards
+//! does not write them again (and does not undo changes made by pre-update hooks), unless they
+//! were modified after they were staged.
+
+use std::fs::OpenOptions;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use failure::Fallible as Result;
+use failure::ResultExt;
+use failure::Error;
+
+use libimagerror::errors::ErrorMsg as EM;
+
+use crate::store::Entry;
+use crate::store::Store;
+use crate::storeid::StoreId;
+
+/// The name of the directory inside the store where the journal lives
+pub const JOURNAL_DIR_NAME: &str = ".journal";
+
+const JOURNAL_EXTENSION: &str = "journal";
+
+/// One modification of the store
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Operation {
+    /// Write the entry
+    Update(Entry),
+
+    /// Delete the entry with the id
+    Delete(StoreId),
+
+    /// Move the entry from the first to the second id
+    Move(StoreId, StoreId),
+}
+
+/// The on-disk representation of an `Operation`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum JournalOperation {
+    Update { id: String, entry: String },
+    Delete { id: String },
+    Move { from: String, to: String },
+}
+
+impl JournalOperation {
+
+    fn from_operation(op: &Operation) -> Result<JournalOperation> {
+        Ok(match *op {
+            Operation::Update(ref e) => JournalOperation::Update {
+                id: e.get_location().to_str()?,
+                entry: e.to_str()?,
+            },
+            Operation::Delete(ref id) => JournalOperation::Delete { id: id.to_str()? },
+            Operation::Move(ref from, ref to) => JournalOperation::Move {
+                from: from.to_str()?,
+                to: to.to_str()?,
+            },
+        })
+    }
+
+    fn into_operation(self) -> Result<Operation> {
+        let id = |s: String| StoreId::new(PathBuf::from(s));
+        Ok(match self {
+            JournalOperation::Update { id: i, entry } => Operation::Update(Entry::from_str(id(i)?, &entry)?),
+            JournalOperation::Delete { id: i }        => Operation::Delete(id(i)?),
+            JournalOperation::Move { from, to }       => Operation::Move(id(from)?, id(to)?),
+        })
+    }
+}
+
+/// A set of modifications to the store which are applied atomically
+///
+/// Use `Store::transaction()` to get one. If the transaction is dropped without calling
+/// `Transaction::commit()`, nothing happens.
+#[derive(Debug)]
+pub struct Transaction<'a> {
+    store: &'a Store,
+    operations: Vec<Operation>,
+}
+
+impl<'a> Transaction<'a> {
+
+    pub(crate) fn new(store: &'a Store) -> Transaction<'a> {
+        Transaction { store, operations: vec![] }
+    }
+
+    /// Stage writing an entry
+    ///
+    /// The current state of the entry is recorded, later modifications of the entry object are not
+    /// part of the transaction. The entry must be borrowed from the store (as `FileLockEntry`)
+    /// until the transaction is committed.
+    pub fn update(&mut self, entry: &Entry) -> Result<()> {
+        debug!("Staging update of '{}'", entry.get_location());
+        entry.verify()?;
+        self.operations.push(Operation::Update(entry.clone()));
+        Ok(())
+    }
+
+    /// Stage deleting an entry
+    ///
+    /// The entry must not be borrowed when the transaction is committed.
+    pub fn delete(&mut self, id: StoreId) -> Result<()> {
+        debug!("Staging delete of '{}'", id);
+        self.operations.push(Operation::Delete(id));
+        Ok(())
+    }
+
+    /// Stage moving an entry, see `Store::move_by_id()`
+    ///
+    /// The entry must not be borrowed when the transaction is committed.
+    pub fn move_by_id(&mut self, old_id: StoreId, new_id: StoreId) -> Result<()> {
+        debug!("Staging move of '{}' to '{}'", old_id, new_id);
+        self.operations.push(Operation::Move(old_id, new_id));
+        Ok(())
+    }
+
+    /// Check whether there are no staged modifications
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Apply all staged modifications
+    ///
+    /// Before anything is written, all modifications are checked (see `Transaction::update()`,
+    /// `Transaction::delete()` and `Transaction::move_by_id()`). If a check fails, nothing is
+    /// modified and an error is returned.
+    ///
+    /// If an error occurs while applying the modifications and the store is persistent, the
+    /// remaining modifications are applied the next time the store is opened.
+    pub fn commit(mut self) -> Result<()> {
+        if self.operations.is_empty() {
+            return Ok(())
+        }
+
+        debug!("Committing transaction with {} operations", self.operations.len());
+        for op in self.operations.iter() {
+            self.store.check_operation(op).context(err_msg_for(op))?;
+        }
+
+        // The entries as they were staged, before hooks altered them. The `FileLockEntry`s still
+        // hold this content
+        let staged = self
+            .operations
+            .iter()
+            .filter_map(|op| match *op {
+                Operation::Update(ref entry) => Some(entry.clone()),
+                _                            => None,
+            })
+            .collect::<Vec<_>>();
+
+        for op in self.operations.iter_mut() {
+            self.store.run_operation_pre_hooks(op).context(err_msg_for(op))?;
+        }
+
+        let journal = match self.store.journal_path() {
+            Some(dir) => {
+                let sealed = self
+                    .operations
+                    .iter()
+                    .map(|op| self.store.seal_operation(op))
+                    .collect::<Result<Vec<_>>>()?;
+                Some(write_journal(dir, &sealed)?)
+            },
+            None => None,
+        };
+
+        for op in self.operations.iter() {
+            self.store.apply_operation(op)
+                .context(err_msg_for(op))
+                .context(format_err!("TransactionCommitError"))?;
+        }
+
+        if let Some(journal) = journal {
+            trace!("Removing journal {}", journal.display());
+            ::std::fs::remove_file(&journal).context(EM::FileNotRemoved)?;
+        }
+
+        for entry in staged.iter() {
+            self.store.mark_clean(entry)?;
+        }
+
+        debug!("Transaction committed");
+        Ok(())
+    }
+}
+
+fn err_msg_for(op: &Operation) -> Error {
+    match *op {
+        Operation::Update(ref e)          => format_err!("Failed to write '{}'", e.get_location()),
+        Operation::Delete(ref id)         => format_err!("Failed to delete '{}'", id),
+        Operation::Move(ref from, ref to) => format_err!("Failed to move '{}' to '{}'", from, to),
+    }
+}
+
+/// Write the operations to a new journal file in `dir`
+///
+/// The journal is written to a temporary file which is then renamed, so a journal file is always
+/// complete.
+fn write_journal(dir: &PathBuf, ops: &[Operation]) -> Result<PathBuf> {
+    use std::io::Write;
+
+    if !dir.exists() {
+        ::std::fs::create_dir_all(dir).context(EM::DirNotCreated)?;
+    }
+
+    let ops = ops
+        .iter()
+        .map(JournalOperation::from_operation)
+        .collect::<Result<Vec<_>>>()?;
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let name    = format!("{:032}-{}", nanos, ::std::process::id());
+    let path    = dir.join(&name).with_extension(JOURNAL_EXTENSION);
+    let tmppath = dir.join(&name).with_extension("tmp");
+
+    trace!("Writing journal {}", path.display());
+    {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tmppath)
+            .context(EM::FileNotCreated)?;
+
+        ::serde_json::to_writer(&mut file, &ops).context(EM::FileNotWritten)?;
+        file.flush().context(EM::FileNotWritten)?;
+        file.sync_all().context(EM::FileNotWritten)?;
+    }
+
+    ::std::fs::rename(&tmppath, &path).context(EM::FileNotRenamed)?;
+    Ok(path)
+}
+
+/// Find all complete journal files in `dir`, in the order they were written
+fn journal_files(dir: &PathBuf) -> Result<Vec<PathBuf>> {
+    let mut files = ::std::fs::read_dir(dir)
+        .context(EM::IO)?
+        .map(|de| de.map(|de| de.path()).context(EM::IO).map_err(Error::from))
+        .collect::<Result<Vec<_>>>()?;
+
+    files.retain(|p| p.extension().map(|e| e == JOURNAL_EXTENSION).unwrap_or(false));
+    files.sort();
+    Ok(files)
+}
+
+fn read_journal(path: &PathBuf) -> Result<Vec<Operation>> {
+    let file = ::std::fs::File::open(path).context(EM::IO)?;
+    let ops : Vec<JournalOperation> = ::serde_json::from_reader(file)
+        .context(format_err!("Failed to parse journal: {}", path.display()))?;
+
+    ops.into_iter().map(JournalOperation::into_operation).collect()
+}
+
+/// Check whether the entry written by the update at `ops[i]` is gone because a later operation of
+/// the same journal, which was already applied, moved or deleted it
+fn update_superseded(store: &Store, ops: &[Operation], i: usize) -> Result<bool> {
+    let mut current = match ops[i] {
+        Operation::Update(ref entry) => entry.get_location().clone(),
+        _                            => return Ok(false),
+    };
+
+    if store.exists(current.clone())? {
+        return Ok(false)
+    }
+
+    let mut moved = false;
+    for op in ops[i + 1..].iter() {
+        match *op {
+            Operation::Move(ref from, ref to) if *from == current => {
+                current = to.clone();
+                moved   = true;
+            },
+            Operation::Delete(ref id) if *id == current => return Ok(true),
+            _ => {},
+        }
+    }
+
+    Ok(moved && store.exists(current)?)
+}
+
+/// Replay all journals which are left over from transactions which were not completely applied
+///
+/// Replaying is idempotent: Entries are written again, unless a later operation of the journal
+/// already moved or deleted them. Entries which are already deleted or moved are skipped.
+pub(crate) fn replay_journals(store: &Store) -> Result<()> {
+    let dir = match store.journal_path() {
+        Some(dir) => dir,
+        None      => return Ok(()),
+    };
+
+    if !dir.exists() {
+        return Ok(())
+    }
+
+    for journal in journal_files(dir)? {
+        info!("Replaying journal {}", journal.display());
+        let ops = read_journal(&journal)?
+            .into_iter()
+            .map(|op| store.unseal_operation(op))
+            .collect::<Result<Vec<_>>>()?;
+        for i in 0..ops.len() {
+            if update_superseded(store, &ops, i)? {
+                debug!("Skipping replay of {:?}, it was moved or deleted later", ops[i]);
+                continue
+            }
+
+            store.replay_operation(ops[i].clone())
+                .context(format_err!("Failed to replay journal {}", journal.display()))?;
+        }
+
+        ::std::fs::remove_file(&journal).context(EM::FileNotRemoved)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::store::Entry;
+    use crate::storeid::StoreId;
+
+    #[test]
+    fn test_journal_operation_roundtrip() {
+        let id = StoreId::new(PathBuf::from("test")).unwrap();
+        let mut entry = Entry::new(id.clone());
+        *entry.get_content_mut() = String::from("content");
+
+        let ops = vec![
+            Operation::Update(entry),
+            Operation::Delete(id.clone()),
+            Operation::Move(id.clone(), StoreId::new(PathBuf::from("other")).unwrap()),
+        ];
+
+        for op in ops {
+            let jop  = JournalOperation::from_operation(&op).unwrap();
+            let json = ::serde_json::to_string(&jop).unwrap();
+            let back = ::serde_json::from_str::<JournalOperation>(&json).unwrap();
+            assert_eq!(op, back.into_operation().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_journal_is_replayed_on_store_open() {
+        use tempdir::TempDir;
+        use crate::store::Store;
+
+        let dir   = TempDir::new("imag-journal-test").unwrap();
+        let path  = dir.path().to_path_buf();
+        let id    = StoreId::new(PathBuf::from("replayed")).unwrap();
+        let mut entry = Entry::new(id.clone());
+        *entry.get_content_mut() = String::from("from journal");
+
+        // A broken entry, as if the process died while writing it
+        ::std::fs::write(path.join("replayed"), "---\n[imag").unwrap();
+
+        let journal_dir = path.join(JOURNAL_DIR_NAME);
+        let journal     = write_journal(&journal_dir, &[Operation::Update(entry)]).unwrap();
+        assert!(journal.exists());
+
+        let store = Store::new(path.clone(), &None).unwrap();
+        assert!(!journal.exists());
+        assert_eq!(store.get_copy(id).unwrap().get_content(), "from journal");
+    }
+
+    #[test]
+    fn test_replaying_applied_journal_changes_nothing() {
+        use tempdir::TempDir;
+        use crate::store::Store;
+
+        let dir   = TempDir::new("imag-journal-test").unwrap();
+        let path  = dir.path().to_path_buf();
+        let a     = StoreId::new(PathBuf::from("a")).unwrap();
+        let b     = StoreId::new(PathBuf::from("b")).unwrap();
+        let gone  = StoreId::new(PathBuf::from("gone")).unwrap();
+
+        let ops = {
+            let store = Store::new(path.clone(), &None).unwrap();
+            let _     = store.create(gone.clone()).unwrap();
+            let entry = {
+                let mut entry = store.create(a.clone()).unwrap();
+                *entry.get_content_mut() = String::from("moved");
+                (*entry).clone()
+            };
+
+            store.move_by_id(a.clone(), b.clone()).unwrap();
+            store.delete(gone.clone()).unwrap();
+
+            vec![
+                Operation::Update(entry),
+                Operation::Move(a.clone(), b.clone()),
+                Operation::Delete(gone.clone()),
+            ]
+        };
+
+        // As if the process died after applying the operations, but before removing the journal
+        let journal = write_journal(&path.join(JOURNAL_DIR_NAME), &ops).unwrap();
+
+        let store = Store::new(path.clone(), &None).unwrap();
+        assert!(!journal.exists());
+        assert!(!store.exists(a).unwrap());
+        assert!(!store.exists(gone).unwrap());
+        assert_eq!(store.get_copy(b).unwrap().get_content(), "moved");
+    }
+
+    #[test]
+    fn test_committed_entries_are_not_written_again() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use crate::hook::HookPosition;
+        use crate::store::Store;
+
+        let store = Store::new_inmemory(PathBuf::from("/"), &None).unwrap();
+        let id    = StoreId::new(PathBuf::from("hooked")).unwrap();
+        let _     = store.create(id.clone()).unwrap();
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        {
+            let calls = calls.clone();
+            store.register_hook(HookPosition::PreUpdate, move |_, entry| {
+                let _ = calls.fetch_add(1, Ordering::SeqCst);
+                entry.get_content_mut().push_str(" hooked");
+                Ok(())
+            }).unwrap();
+        }
+
+        {
+            let mut entry = store.retrieve(id.clone()).unwrap();
+            *entry.get_content_mut() = String::from("staged");
+
+            let mut tx = store.transaction();
+            tx.update(&entry).unwrap();
+            tx.commit().unwrap();
+        }
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(store.get_copy(id).unwrap().get_content(), "staged hooked");
+    }
+}
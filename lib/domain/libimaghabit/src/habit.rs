@@ -55,6 +55,9 @@ pub trait HabitTemplate : Sized {
     ///
     /// It uses `Store::retrieve()` underneath. So if there is already an instance for the day
     /// passed, this will simply return the instance.
+    ///
+    /// The instance and the template are written in one transaction, so the template must be
+    /// borrowed from `store`.
     fn create_instance_with_date<'a>(&mut self, store: &'a Store, date: &NaiveDate)
         -> Result<FileLockEntry<'a>>;
 
@@ -104,7 +107,7 @@ impl HabitTemplate for Entry {
 
         store.create(id)
             .map_err(From::from)
-            .and_then(|entry| postprocess_instance(entry, name, date, self))
+            .and_then(|entry| postprocess_instance(store, entry, name, date, self))
     }
 
     fn create_instance_today<'a>(&mut self, store: &'a Store) -> Result<FileLockEntry<'a>> {
@@ -118,7 +121,7 @@ impl HabitTemplate for Entry {
 
         store.retrieve(id)
             .map_err(From::from)
-            .and_then(|entry| postprocess_instance(entry, name, date, self))
+            .and_then(|entry| postprocess_instance(store, entry, name, date, self))
     }
 
     fn retrieve_instance_today<'a>(&mut self, store: &'a Store) -> Result<FileLockEntry<'a>> {
@@ -393,7 +396,10 @@ pub mod builder {
 
 }
 
-fn postprocess_instance<'a>(mut entry: FileLockEntry<'a>,
+/// Set the headers of the instance and link it to the template, which must be borrowed from
+/// `store`, as both entries are written in one transaction
+fn postprocess_instance<'a>(store: &'a Store,
+                            mut entry: FileLockEntry<'a>,
                             name: String,
                             date: String,
                             template: &mut Entry)
@@ -408,6 +414,11 @@ fn postprocess_instance<'a>(mut entry: FileLockEntry<'a>,
 
     entry.add_link(template)?;
 
+    let mut tx = store.transaction();
+    tx.update(&entry)?;
+    tx.update(template)?;
+    tx.commit()?;
+
     Ok(entry)
 }
 
@@ -78,7 +78,7 @@ impl<'a, 'b> Wiki<'a, 'b> {
             .ok_or_else(|| err_msg("Missing index page"))?;
         let mut entry = self.0.create(sid)?;
 
-        entry.add_link(&mut index).map(|_| entry)
+        self.link_to_index(&mut entry, &mut index).map(|_| entry)
     }
 
     pub fn retrieve_entry<EN: AsRef<str>>(&self, entry_name: EN) -> Result<FileLockEntry<'a>> {
@@ -89,7 +89,17 @@ impl<'a, 'b> Wiki<'a, 'b> {
             .ok_or_else(|| err_msg("Missing index page"))?;
         let mut entry = self.0.retrieve(sid)?;
 
-        entry.add_link(&mut index).map(|_| entry)
+        self.link_to_index(&mut entry, &mut index).map(|_| entry)
+    }
+
+    /// Link the entry to the index page, writing both in one transaction
+    fn link_to_index(&self, entry: &mut FileLockEntry<'a>, index: &mut FileLockEntry<'a>) -> Result<()> {
+        entry.add_link(index)?;
+
+        let mut tx = self.0.transaction();
+        tx.update(entry)?;
+        tx.update(index)?;
+        tx.commit()
     }
 
     pub fn all_ids(&self) -> Result<Entries<'a>> {
@@ -47,6 +47,8 @@ provide_kindflag_path!(IsAnnotation, "annotation.is_annotation");
 impl Annotateable for Entry {
 
     /// Annotate an entry, returns the new entry which is used to annotate
+    ///
+    /// The entry must be borrowed from `store`, as both entries are written in one transaction.
     fn annotate<'a>(&mut self, store: &'a Store) -> Result<FileLockEntry<'a>> {
         let ann_name = Uuid::new_v4().to_hyphenated().to_string();
         debug!("Creating annotation with name = {}", ann_name);
@@ -67,14 +69,28 @@ impl Annotateable for Entry {
                     .map_err(Error::from)
                     .map(|_| anno)
             })
+            .and_then(|anno| {
+                let mut tx = store.transaction();
+                tx.update(&anno)?;
+                tx.update(self)?;
+                tx.commit().context(err_msg("Failed to write annotation"))?;
+                Ok(anno)
+            })
     }
 
     // Removes the annotation `ann_name` from the current entry.
     // Fails if there's no such annotation entry or if the link to that annotation entry does not
-    // exist.
+    // exist. Both entries are written in one transaction, so the entry must be borrowed from
+    // `store`.
     fn denotate<'a>(&mut self, store: &'a Store, ann_name: &str) -> Result<Option<FileLockEntry<'a>>> {
         if let Some(mut annotation) = store.get(crate::module_path::new_id(ann_name)?)? {
             let _ = self.remove_link(&mut annotation)?;
+
+            let mut tx = store.transaction();
+            tx.update(self)?;
+            tx.update(&annotation)?;
+            tx.commit().context(err_msg("Failed to write denotation"))?;
+
             Ok(Some(annotation))
         } else {
             // error: annotation does not exist
@@ -0,0 +1,150 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use std::cmp::Ordering;
+
+use semver::Version;
+
+use libimagstore::store::Entry;
+
+use crate::builtin::header::field_path::FieldPath;
+use crate::builtin::header::field_predicate::FieldPredicate;
+use crate::builtin::header::field_predicate::Predicate;
+use filters::failable::filter::FailableFilter;
+
+use failure::Fallible as Result;
+use failure::Error;
+
+use toml::Value;
+
+/// How a header field is compared to a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+
+    fn accepts(&self, ord: Ordering) -> bool {
+        match *self {
+            Comparison::Equal          => ord == Ordering::Equal,
+            Comparison::Less           => ord == Ordering::Less,
+            Comparison::LessOrEqual    => ord != Ordering::Greater,
+            Comparison::Greater        => ord == Ordering::Greater,
+            Comparison::GreaterOrEqual => ord != Ordering::Less,
+        }
+    }
+
+}
+
+/// Compare two header values
+///
+/// Integers and floats are compared numerically. Strings are compared as versions if both of them
+/// look like one ("0.9" is read as "0.9.0"), otherwise lexicographically, which also orders ISO
+/// 8601 dates correctly. All other combinations cannot be compared.
+pub fn compare_values(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (&Value::Integer(a), &Value::Integer(b)) => Some(a.cmp(&b)),
+        (&Value::Integer(a), &Value::Float(b))   => (a as f64).partial_cmp(&b),
+        (&Value::Float(a),   &Value::Integer(b)) => a.partial_cmp(&(b as f64)),
+        (&Value::Float(a),   &Value::Float(b))   => a.partial_cmp(&b),
+        (&Value::String(ref a), &Value::String(ref b)) => match (parse_version(a), parse_version(b)) {
+            (Some(a), Some(b)) => Some(a.cmp(&b)),
+            _                  => Some(a.cmp(b)),
+        },
+        _ => None,
+    }
+}
+
+fn parse_version(s: &str) -> Option<Version> {
+    let dots = s.matches('.').count();
+    if dots < 2 {
+        Version::parse(&format!("{}{}", s, ".0".repeat(2 - dots))).ok()
+    } else {
+        Version::parse(s).ok()
+    }
+}
+
+struct ComparePred {
+    comparison: Comparison,
+    value: Value,
+}
+
+impl Predicate for ComparePred {
+
+    fn evaluate(&self, v: &Value) -> bool {
+        match compare_values(v, &self.value) {
+            Some(ord) => self.comparison.accepts(ord),
+            // Values which cannot be ordered, like booleans, can still be equal
+            None      => self.comparison == Comparison::Equal && *v == self.value,
+        }
+    }
+
+}
+
+/// Check whether a certain header field in an entry compares to a value as requested
+///
+/// `FieldCompare::new(path, Comparison::Greater, value)` matches entries where the field at `path`
+/// is greater than `value`. See `compare_values()` for which values can be compared.
+pub struct FieldCompare {
+    filter: FieldPredicate<ComparePred>,
+}
+
+impl FieldCompare {
+
+    pub fn new(path: FieldPath, comparison: Comparison, value: Value) -> FieldCompare {
+        FieldCompare {
+            filter: FieldPredicate::new(path, Box::new(ComparePred { comparison, value })),
+        }
+    }
+
+}
+
+impl FailableFilter<Entry> for FieldCompare {
+    type Error = Error;
+
+    fn filter(&self, e: &Entry) -> Result<bool> {
+        self.filter.filter(e)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use toml::Value;
+
+    use super::compare_values;
+
+    #[test]
+    fn test_compare_values() {
+        let s = |s: &str| Value::String(String::from(s));
+
+        assert_eq!(compare_values(&Value::Integer(2), &Value::Float(1.5)), Some(Ordering::Greater));
+        assert_eq!(compare_values(&s("0.10.0"), &s("0.9")), Some(Ordering::Greater));
+        assert_eq!(compare_values(&s("0.9.0"), &s("0.9")), Some(Ordering::Equal));
+        assert_eq!(compare_values(&s("2019-01-02"), &s("2019-01-10")), Some(Ordering::Less));
+        assert_eq!(compare_values(&s("1"), &Value::Integer(1)), None);
+    }
+}
@@ -57,7 +57,10 @@ impl Predicate for EqGt {
 
 }
 
-/// Check whether certain header field in a entry is equal to a value
+/// Check whether `expected_value` is greater than a certain header field in an entry
+///
+/// Note that this matches entries where the field is less than `expected_value`. Only integers and
+/// floats are compared, use `FieldCompare` for other comparisons.
 pub struct FieldGt {
     filter: FieldPredicate<EqGt>,
 }
@@ -81,3 +84,32 @@ impl FailableFilter<Entry> for FieldGt {
 
 }
 
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use toml::Value;
+    use toml_query::insert::TomlValueInsertExt;
+    use filters::failable::filter::FailableFilter;
+
+    use libimagstore::store::Entry;
+    use libimagstore::storeid::StoreId;
+
+    use super::FieldGt;
+    use crate::builtin::header::field_path::FieldPath;
+
+    fn matches(field: Value, expected: Value) -> bool {
+        let mut entry = Entry::new(StoreId::new(PathBuf::from("test")).unwrap());
+        let _ = entry.get_header_mut().insert("a", field).unwrap();
+        FieldGt::new(FieldPath::from("a"), expected).filter(&entry).unwrap()
+    }
+
+    #[test]
+    fn test_field_gt() {
+        assert!(matches(Value::Integer(3), Value::Integer(5)));
+        assert!(matches(Value::Float(4.5), Value::Integer(5)));
+        assert!(!matches(Value::Integer(7), Value::Integer(5)));
+        assert!(!matches(Value::Integer(5), Value::Integer(5)));
+        assert!(!matches(Value::String(String::from("a")), Value::String(String::from("b"))));
+    }
+}
@@ -57,7 +57,10 @@ impl Predicate for EqLt {
 
 }
 
-/// Check whether certain header field in a entry is equal to a value
+/// Check whether `expected_value` is less than a certain header field in an entry
+///
+/// Note that this matches entries where the field is greater than `expected_value`. Only integers and
+/// floats are compared, use `FieldCompare` for other comparisons.
 pub struct FieldLt {
     filter: FieldPredicate<EqLt>,
 }
@@ -81,3 +84,32 @@ impl FailableFilter<Entry> for FieldLt {
 
 }
 
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use toml::Value;
+    use toml_query::insert::TomlValueInsertExt;
+    use filters::failable::filter::FailableFilter;
+
+    use libimagstore::store::Entry;
+    use libimagstore::storeid::StoreId;
+
+    use super::FieldLt;
+    use crate::builtin::header::field_path::FieldPath;
+
+    fn matches(field: Value, expected: Value) -> bool {
+        let mut entry = Entry::new(StoreId::new(PathBuf::from("test")).unwrap());
+        let _ = entry.get_header_mut().insert("a", field).unwrap();
+        FieldLt::new(FieldPath::from("a"), expected).filter(&entry).unwrap()
+    }
+
+    #[test]
+    fn test_field_lt() {
+        assert!(matches(Value::Integer(7), Value::Integer(5)));
+        assert!(matches(Value::Float(5.5), Value::Integer(5)));
+        assert!(!matches(Value::Integer(3), Value::Integer(5)));
+        assert!(!matches(Value::Integer(5), Value::Integer(5)));
+        assert!(!matches(Value::String(String::from("a")), Value::String(String::from("b"))));
+    }
+}
@@ -56,3 +56,4 @@ pub mod builtin;
 // their functionality
 
 pub mod tags;
+pub mod query;
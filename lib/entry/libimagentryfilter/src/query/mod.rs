@@ -0,0 +1,255 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! A query language for selecting entries
+//!
+//! Queries are compiled to a tree of the builtin filters of this library, so that commandline
+//! tools can offer a textual way to select entries:
+//!
+//! ```text
+//! header.imag.version > "0.9" and tag:work and not content ~ /TODO/
+//! ```
+//!
+//! Predicates are combined with `and`, `or`, `not` and parentheses. The available predicates are:
+//!
+//! * `header.<path>`: the header field exists
+//! * `header.<path> is <type>`: the header field has the type (string, integer, float, boolean,
+//!   array or table)
+//! * `header.<path> <op> <value>` with `==`, `!=`, `<`, `<=`, `>`, `>=`: compares the header
+//!   field. Values are strings (`"..."` or a bare word), integers, floats or booleans. Strings
+//!   which look like versions are compared as versions and integers and floats are compared
+//!   numerically, by `==` and `!=` as well.
+//! * `header.<path> ~ /regex/`: the header field is a string matching the regex
+//! * `tag:<tag>`: the entry has the tag
+//! * `content ~ /regex/`: the content matches the regex
+//! * `content.length <op> <n>`: compares the length of the content
+//!
+//! A missing header field never matches a comparison, but `!=` matches it.
+
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+use regex::Regex;
+use filters::filter::Filter;
+use filters::failable::filter::FailableFilter;
+use failure::Fallible as Result;
+use failure::ResultExt;
+use failure::Error;
+
+use libimagstore::store::Entry;
+use libimagentrytag::tag::is_tag_str;
+
+use crate::builtin::bool_filter::BoolFilter;
+use crate::builtin::content::grep::ContentGrep;
+use crate::builtin::header::field_compare::Comparison;
+use crate::builtin::header::field_compare::FieldCompare;
+use crate::builtin::header::field_exists::FieldExists;
+use crate::builtin::header::field_grep::FieldGrep;
+use crate::builtin::header::field_istype::FieldIsType;
+use crate::builtin::header::field_istype::Type;
+use crate::tags::HasTag;
+
+pub mod parser;
+
+use self::parser::Expr;
+use self::parser::Op;
+
+type BoxedFilter = Box<FailableFilter<Entry, Error = Error>>;
+
+/// A compiled query, use it with `FailableFilter::filter()`
+pub struct Query {
+    source: String,
+    filter: BoxedFilter,
+}
+
+impl Query {
+
+    /// Parse and compile a query
+    pub fn parse(query: &str) -> Result<Query> {
+        let expr = parser::parse(query)?;
+        Query::from_expr(query, expr)
+    }
+
+    /// Compile an already parsed query, `source` is only used for displaying the query
+    pub fn from_expr(source: &str, expr: Expr) -> Result<Query> {
+        Ok(Query {
+            source: String::from(source),
+            filter: compile(expr)?,
+        })
+    }
+
+    /// The query as it was written
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+}
+
+impl FromStr for Query {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Query> {
+        Query::parse(s)
+    }
+}
+
+impl Debug for Query {
+    fn fmt(&self, fmt: &mut Formatter) -> ::std::fmt::Result {
+        write!(fmt, "Query({:?})", self.source)
+    }
+}
+
+impl FailableFilter<Entry> for Query {
+    type Error = Error;
+
+    fn filter(&self, e: &Entry) -> Result<bool> {
+        self.filter.filter(e)
+    }
+}
+
+fn infallible<F: Filter<Entry> + 'static>(f: F) -> BoxedFilter {
+    Box::new(move |e: &Entry| -> Result<bool> { Ok(f.filter(e)) })
+}
+
+fn regex(s: &str) -> Result<Regex> {
+    Regex::new(s).context(format_err!("Invalid regex in query: {}", s)).map_err(Error::from)
+}
+
+fn compile(expr: Expr) -> Result<BoxedFilter> {
+    Ok(match expr {
+        Expr::And(a, b) => {
+            let (a, b) = (compile(*a)?, compile(*b)?);
+            Box::new(move |e: &Entry| -> Result<bool> { Ok(a.filter(e)? && b.filter(e)?) })
+        },
+        Expr::Or(a, b) => {
+            let (a, b) = (compile(*a)?, compile(*b)?);
+            Box::new(move |e: &Entry| -> Result<bool> { Ok(a.filter(e)? || b.filter(e)?) })
+        },
+        Expr::Not(a) => {
+            let a = compile(*a)?;
+            Box::new(move |e: &Entry| -> Result<bool> { a.filter(e).map(|b| !b) })
+        },
+        Expr::Bool(b) => infallible(BoolFilter::new(b)),
+        Expr::HeaderExists(path) => Box::new(FieldExists::new(path)),
+        Expr::HeaderIsType(path, ty) => {
+            let ty = match ty.as_ref() {
+                "string"  => Type::String,
+                "integer" => Type::Integer,
+                "float"   => Type::Float,
+                "boolean" => Type::Boolean,
+                "array"   => Type::Array,
+                "table"   => Type::Table,
+                other     => return Err(format_err!("Unknown type in query: {}", other)),
+            };
+            Box::new(FieldIsType::new(path, ty))
+        },
+        Expr::HeaderCompare(path, Op::Ne, value) => compile(Expr::Not(Box::new(Expr::HeaderCompare(path, Op::Eq, value))))?,
+        Expr::HeaderCompare(path, op, value) => {
+            let comparison = match op {
+                Op::Eq => Comparison::Equal,
+                Op::Lt => Comparison::Less,
+                Op::Le => Comparison::LessOrEqual,
+                Op::Gt => Comparison::Greater,
+                _      => Comparison::GreaterOrEqual,
+            };
+            Box::new(FieldCompare::new(path, comparison, value))
+        },
+        Expr::HeaderMatches(path, re) => Box::new(FieldGrep::new(path, regex(&re)?)),
+        Expr::HasTag(tag) => {
+            let _ = is_tag_str(&tag).context(format_err!("Invalid tag in query: {}", tag))?;
+            infallible(HasTag::new(tag))
+        },
+        Expr::ContentMatches(re) => infallible(ContentGrep::new(regex(&re)?)?),
+        Expr::ContentLength(op, len) => infallible(move |e: &Entry| {
+            let ord = e.get_content().len().cmp(&len);
+            match op {
+                Op::Eq => ord == Ordering::Equal,
+                Op::Ne => ord != Ordering::Equal,
+                Op::Lt => ord == Ordering::Less,
+                Op::Le => ord != Ordering::Greater,
+                Op::Gt => ord == Ordering::Greater,
+                Op::Ge => ord != Ordering::Less,
+            }
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use toml::Value;
+    use toml_query::insert::TomlValueInsertExt;
+    use filters::failable::filter::FailableFilter;
+
+    use libimagstore::store::Entry;
+    use libimagstore::storeid::StoreId;
+    use libimagentrytag::tagable::Tagable;
+
+    use super::Query;
+
+    fn entry() -> Entry {
+        let mut entry = Entry::new(StoreId::new(PathBuf::from("test")).unwrap());
+        let _ = entry.get_header_mut().insert("imag.version", Value::String(String::from("0.10.0"))).unwrap();
+        let _ = entry.get_header_mut().insert("count", Value::Integer(3)).unwrap();
+        entry.add_tag(String::from("work")).unwrap();
+        entry.get_content_mut().push_str("Some TODO here");
+        entry
+    }
+
+    fn matches(query: &str) -> bool {
+        query.parse::<Query>().unwrap().filter(&entry()).unwrap()
+    }
+
+    #[test]
+    fn test_query() {
+        assert!(matches(r#"header.imag.version > "0.9" and tag:work and content ~ /TODO/"#));
+        assert!(!matches(r#"header.imag.version > "0.9" and tag:work and not content ~ /TODO/"#));
+        assert!(matches("header.count >= 3 and header.count < 4 and header.count is integer"));
+        assert!(matches("tag:home or (header.count == 3 and not header.missing)"));
+        assert!(matches("header.missing != 3 and not header.missing > 0"));
+        assert!(matches("content.length == 14 and header.imag.version ~ /^0\\.10/"));
+        assert!(!matches("tag:home or false"));
+    }
+
+    #[test]
+    fn test_equality_is_version_and_number_aware() {
+        let mut entry = entry();
+        let _ = entry.get_header_mut().insert("imag.version", Value::String(String::from("0.9.0"))).unwrap();
+        let _ = entry.get_header_mut().insert("one", Value::Integer(1)).unwrap();
+        let _ = entry.get_header_mut().insert("flag", Value::Boolean(true)).unwrap();
+
+        let matches = |query: &str| query.parse::<Query>().unwrap().filter(&entry).unwrap();
+
+        assert!(matches(r#"header.imag.version == "0.9""#));
+        assert!(!matches(r#"header.imag.version != "0.9""#));
+        assert!(matches("header.one == 1.0"));
+        assert!(matches("header.one != 1.5"));
+        assert!(matches("header.flag == true and header.flag != false"));
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        assert!(Query::parse("content ~ /(/").is_err());
+        assert!(Query::parse("tag:Not-Valid").is_err());
+        assert!(Query::parse("header.a is thing").is_err());
+    }
+}
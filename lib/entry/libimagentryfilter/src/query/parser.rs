@@ -0,0 +1,385 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Parser for the query language
+//!
+//! The grammar, from the loosest to the tightest binding:
+//!
+//! ```text
+//! query     := and ("or" and)*
+//! and       := not ("and" not)*
+//! not       := "not" not | "(" query ")" | "true" | "false" | predicate
+//! predicate := "header." PATH                       # the field exists
+//!            | "header." PATH "is" TYPE             # string, integer, float, boolean, array, table
+//!            | "header." PATH OP VALUE
+//!            | "header." PATH "~" REGEX
+//!            | "tag:" TAG
+//!            | "content" "~" REGEX
+//!            | "content.length" OP INTEGER
+//! OP        := "==" | "!=" | "<" | "<=" | ">" | ">="
+//! VALUE     := "string" | INTEGER | FLOAT | true | false | bare-word
+//! REGEX     := /regex/ | "regex"
+//! ```
+
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use toml::Value;
+use failure::Fallible as Result;
+
+/// A comparison operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// The parsed form of a query
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Bool(bool),
+    HeaderExists(String),
+    HeaderIsType(String, String),
+    HeaderCompare(String, Op, Value),
+    HeaderMatches(String, String),
+    HasTag(String),
+    ContentMatches(String),
+    ContentLength(Op, usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Op(Op),
+    Match,
+    Str(String),
+    Regex(String),
+    Integer(i64),
+    Float(f64),
+    Word(String),
+}
+
+/// Parse a query string
+pub fn parse(query: &str) -> Result<Expr> {
+    let tokens = tokenize(query)?;
+    let mut parser = Parser { tokens: &tokens, pos: 0, len: query.len() };
+    let expr = parser.parse_or()?;
+
+    match parser.peek() {
+        None              => Ok(expr),
+        Some((pos, tok))  => Err(error(pos, format!("Unexpected {:?}", tok))),
+    }
+}
+
+fn error(pos: usize, msg: String) -> ::failure::Error {
+    format_err!("Invalid query at position {}: {}", pos, msg)
+}
+
+fn is_word_char(c: char) -> bool {
+    !c.is_whitespace() && !"()=!<>~\"".contains(c)
+}
+
+fn tokenize(query: &str) -> Result<Vec<(usize, Token)>> {
+    let mut tokens = vec![];
+    let mut chars  = query.char_indices().peekable();
+
+    while let Some(&(pos, c)) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                let _ = chars.next();
+                continue
+            },
+            '(' => { let _ = chars.next(); Token::LParen },
+            ')' => { let _ = chars.next(); Token::RParen },
+            '~' => { let _ = chars.next(); Token::Match },
+            '=' | '!' | '<' | '>' => {
+                let _ = chars.next();
+                let eq = chars.peek().map(|&(_, c)| c == '=').unwrap_or(false);
+                if eq {
+                    let _ = chars.next();
+                }
+
+                Token::Op(match (c, eq) {
+                    ('=', _)     => Op::Eq,
+                    ('!', true)  => Op::Ne,
+                    ('<', false) => Op::Lt,
+                    ('<', true)  => Op::Le,
+                    ('>', false) => Op::Gt,
+                    ('>', true)  => Op::Ge,
+                    _            => return Err(error(pos, String::from("Expected '!='"))),
+                })
+            },
+            '"' | '/' => {
+                let _ = chars.next();
+                let s = read_delimited(&mut chars, c)
+                    .ok_or_else(|| error(pos, format!("Missing closing {}", c)))?;
+
+                if c == '"' { Token::Str(s) } else { Token::Regex(s) }
+            },
+            _ => {
+                let mut word = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if !is_word_char(c) {
+                        break
+                    }
+                    word.push(c);
+                    let _ = chars.next();
+                }
+
+                // "nan", "inf" and the like are words, not floats
+                if let Ok(i) = word.parse::<i64>() {
+                    Token::Integer(i)
+                } else if let Some(f) = word.parse::<f64>().ok().filter(|f| f.is_finite()) {
+                    Token::Float(f)
+                } else {
+                    Token::Word(word)
+                }
+            },
+        };
+
+        tokens.push((pos, token));
+    }
+
+    Ok(tokens)
+}
+
+/// Read until the unescaped `delim`, a backslash escapes the delimiter
+///
+/// Other escapes are kept, so that regexes like `/\d+/` work as expected.
+fn read_delimited(chars: &mut Peekable<CharIndices>, delim: char) -> Option<String> {
+    let mut s = String::new();
+    while let Some((_, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some((_, n)) if n == delim => s.push(n),
+                Some((_, n))               => { s.push('\\'); s.push(n); },
+                None                       => return None,
+            },
+            c if c == delim => return Some(s),
+            c               => s.push(c),
+        }
+    }
+    None
+}
+
+struct Parser<'a> {
+    tokens: &'a [(usize, Token)],
+    pos: usize,
+    len: usize,
+}
+
+impl<'a> Parser<'a> {
+
+    fn peek(&self) -> Option<(usize, &'a Token)> {
+        self.tokens.get(self.pos).map(|&(pos, ref tok)| (pos, tok))
+    }
+
+    fn next(&mut self, expected: &str) -> Result<(usize, &'a Token)> {
+        let len = self.len;
+        let tok = self.peek().ok_or_else(|| error(len, format!("Expected {}, found end of query", expected)))?;
+        self.pos += 1;
+        Ok(tok)
+    }
+
+    fn next_is_word(&self, word: &str) -> bool {
+        match self.peek() {
+            Some((_, &Token::Word(ref w))) => w == word,
+            _                              => false,
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_and()?;
+        while self.next_is_word("or") {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_not()?;
+        while self.next_is_word("and") {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr> {
+        match self.next("a predicate")? {
+            (_, &Token::Word(ref w)) if w == "not" => Ok(Expr::Not(Box::new(self.parse_not()?))),
+            (_, &Token::LParen) => {
+                let expr = self.parse_or()?;
+                match self.next("')'")? {
+                    (_, &Token::RParen) => Ok(expr),
+                    (pos, tok)          => Err(error(pos, format!("Expected ')', found {:?}", tok))),
+                }
+            },
+            (pos, &Token::Word(ref w)) => self.parse_predicate(pos, w),
+            (pos, tok)                 => Err(error(pos, format!("Expected a predicate, found {:?}", tok))),
+        }
+    }
+
+    fn parse_predicate(&mut self, pos: usize, word: &str) -> Result<Expr> {
+        if word == "true" || word == "false" {
+            return Ok(Expr::Bool(word == "true"))
+        }
+
+        if word.starts_with("tag:") {
+            return Ok(Expr::HasTag(String::from(&word[4..])))
+        }
+
+        if word == "content" {
+            return self.parse_regex().map(Expr::ContentMatches)
+        }
+
+        if word == "content.length" {
+            let op = self.parse_op()?;
+            return match self.next("a length")? {
+                (_, &Token::Integer(i)) if i >= 0 => Ok(Expr::ContentLength(op, i as usize)),
+                (pos, tok) => Err(error(pos, format!("Expected a length, found {:?}", tok))),
+            }
+        }
+
+        if word.starts_with("header.") && word.len() > "header.".len() {
+            let path = String::from(&word["header.".len()..]);
+            return match self.peek() {
+                Some((_, &Token::Match)) => self.parse_regex().map(|r| Expr::HeaderMatches(path, r)),
+                Some((_, &Token::Op(_))) => {
+                    let op    = self.parse_op()?;
+                    let value = self.parse_value()?;
+                    Ok(Expr::HeaderCompare(path, op, value))
+                },
+                Some((_, &Token::Word(ref w))) if w == "is" => {
+                    self.pos += 1;
+                    match self.next("a type")? {
+                        (_, &Token::Word(ref ty)) => Ok(Expr::HeaderIsType(path, ty.clone())),
+                        (pos, tok) => Err(error(pos, format!("Expected a type, found {:?}", tok))),
+                    }
+                },
+                _ => Ok(Expr::HeaderExists(path)),
+            }
+        }
+
+        Err(error(pos, format!("Unknown predicate '{}', expected 'header.<path>', 'tag:<tag>', 'content' or 'content.length'", word)))
+    }
+
+    fn parse_op(&mut self) -> Result<Op> {
+        match self.next("an operator")? {
+            (_, &Token::Op(op)) => Ok(op),
+            (pos, tok)          => Err(error(pos, format!("Expected an operator, found {:?}", tok))),
+        }
+    }
+
+    fn parse_regex(&mut self) -> Result<String> {
+        match self.next("'~'")? {
+            (_, &Token::Match) => {},
+            (pos, tok)         => return Err(error(pos, format!("Expected '~', found {:?}", tok))),
+        }
+
+        match self.next("a regex")? {
+            (_, &Token::Regex(ref r)) | (_, &Token::Str(ref r)) => Ok(r.clone()),
+            (pos, tok) => Err(error(pos, format!("Expected a regex, found {:?}", tok))),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value> {
+        match self.next("a value")? {
+            (_, &Token::Str(ref s))   => Ok(Value::String(s.clone())),
+            (_, &Token::Integer(i))   => Ok(Value::Integer(i)),
+            (_, &Token::Float(f))     => Ok(Value::Float(f)),
+            (_, &Token::Word(ref w)) if w == "true"  => Ok(Value::Boolean(true)),
+            (_, &Token::Word(ref w)) if w == "false" => Ok(Value::Boolean(false)),
+            (pos, &Token::Word(ref w)) if w == "and" || w == "or" || w == "not" => {
+                Err(error(pos, format!("Expected a value, found '{}'", w)))
+            },
+            (_, &Token::Word(ref w))  => Ok(Value::String(w.clone())),
+            (pos, tok) => Err(error(pos, format!("Expected a value, found {:?}", tok))),
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use toml::Value;
+
+    use super::*;
+
+    fn header(path: &str, op: Op, value: Value) -> Expr {
+        Expr::HeaderCompare(String::from(path), op, value)
+    }
+
+    #[test]
+    fn test_precedence() {
+        let expr = parse(r#"header.imag.version > "0.9" and tag:work or not content ~ /TODO/"#).unwrap();
+        let expected = Expr::Or(
+            Box::new(Expr::And(
+                Box::new(header("imag.version", Op::Gt, Value::String(String::from("0.9")))),
+                Box::new(Expr::HasTag(String::from("work"))),
+            )),
+            Box::new(Expr::Not(Box::new(Expr::ContentMatches(String::from("TODO"))))),
+        );
+        assert_eq!(expr, expected);
+
+        let expr = parse("not (header.a or header.b)").unwrap();
+        let expected = Expr::Not(Box::new(Expr::Or(
+            Box::new(Expr::HeaderExists(String::from("a"))),
+            Box::new(Expr::HeaderExists(String::from("b"))),
+        )));
+        assert_eq!(expr, expected);
+    }
+
+    #[test]
+    fn test_values_and_operators() {
+        assert_eq!(parse("header.a>=1").unwrap(), header("a", Op::Ge, Value::Integer(1)));
+        assert_eq!(parse("header.a != 1.5").unwrap(), header("a", Op::Ne, Value::Float(1.5)));
+        assert_eq!(parse("header.a == true").unwrap(), header("a", Op::Eq, Value::Boolean(true)));
+        assert_eq!(parse("header.a = foo").unwrap(), header("a", Op::Eq, Value::String(String::from("foo"))));
+        assert_eq!(parse("header.a = nan").unwrap(), header("a", Op::Eq, Value::String(String::from("nan"))));
+        assert_eq!(parse("header.a = inf").unwrap(), header("a", Op::Eq, Value::String(String::from("inf"))));
+        assert_eq!(parse(r#"header.a ~ "a\"b""#).unwrap(),
+                   Expr::HeaderMatches(String::from("a"), String::from("a\"b")));
+        assert_eq!(parse(r"content ~ /\d+\/x/").unwrap(), Expr::ContentMatches(String::from(r"\d+/x")));
+        assert_eq!(parse("content.length < 10").unwrap(), Expr::ContentLength(Op::Lt, 10));
+        assert_eq!(parse("header.a is string").unwrap(),
+                   Expr::HeaderIsType(String::from("a"), String::from("string")));
+    }
+
+    #[test]
+    fn test_errors() {
+        let msg = |q: &str| format!("{}", parse(q).unwrap_err());
+
+        assert_eq!(msg("header.a and"), "Invalid query at position 12: Expected a predicate, found end of query");
+        assert!(msg("(tag:a").contains("Expected ')'"));
+        assert!(msg("foo").contains("Unknown predicate 'foo'"));
+        assert!(msg("content ~ /abc").contains("Missing closing /"));
+        assert!(msg("header.a == and").contains("Expected a value"));
+        assert!(msg("tag:a tag:b").contains("position 6"));
+    }
+}
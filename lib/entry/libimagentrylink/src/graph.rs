@@ -0,0 +1,504 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! The link graph of a store
+//!
+//! `LinkGraph` collects the links of all entries in a store into one graph, which can be exported
+//! (Graphviz DOT, GraphML, JSON) and analyzed (shortest paths, connected components, orphans,
+//! hubs).
+//!
+//! Internal links become undirected edges, directional links become directed edges which carry
+//! the labels of the link. Nodes are identified by the string representation of their StoreId.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
+
+use failure::Fallible as Result;
+use failure::ResultExt;
+use failure::Error;
+
+use libimagstore::store::Store;
+use libimagstore::store::Entry;
+use libimagerror::errors::ErrorMsg as EM;
+
+use crate::linkable::Linkable;
+use crate::label::LinkDirection;
+
+/// The formats a `LinkGraph` can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    GraphMl,
+    Json,
+}
+
+impl GraphFormat {
+    pub fn names() -> &'static [&'static str] {
+        &["dot", "graphml", "json"]
+    }
+
+    pub fn from_name(name: &str) -> Option<GraphFormat> {
+        match name {
+            "dot"     => Some(GraphFormat::Dot),
+            "graphml" => Some(GraphFormat::GraphMl),
+            "json"    => Some(GraphFormat::Json),
+            _         => None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    id: String,
+
+    /// The URL, if the node is an external link entry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+
+    /// Whether the node is an entry in the store or only the target of a (dead) link
+    exists: bool,
+}
+
+impl Node {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_ref().map(String::as_str)
+    }
+
+    pub fn exists(&self) -> bool {
+        self.exists
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    source: String,
+    target: String,
+    directed: bool,
+    labels: Vec<String>,
+}
+
+impl Edge {
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    pub fn is_directed(&self) -> bool {
+        self.directed
+    }
+
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct LinkGraph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+
+    #[serde(skip)]
+    adjacency: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl LinkGraph {
+
+    /// Build the link graph of all entries in `store`
+    ///
+    /// `url_of` is called for each entry and can return the URL the entry stands for, if it is an
+    /// external link entry. Pass `|_| Ok(None)` if URLs are not of interest.
+    pub fn build<F>(store: &Store, url_of: F) -> Result<LinkGraph>
+        where F: Fn(&Entry) -> Result<Option<String>>
+    {
+        let mut nodes = BTreeMap::new();
+        let mut edges : BTreeMap<(String, String, bool), BTreeSet<String>> = BTreeMap::new();
+
+        // Copies are enough here, the graph only reads the links of each entry
+        for id in store.entries()? {
+            let entry = store.get_copy(id?)?;
+            let id    = entry.get_location().to_str().context(EM::ConversionError)?;
+            debug!("Adding to link graph: {}", id);
+
+            for link in entry.unidirectional_links()? {
+                let other = link.to_str()?;
+                let key   = if id <= other { (id.clone(), other, false) } else { (other, id.clone(), false) };
+                let _     = edges.entry(key).or_default();
+            }
+
+            for link in entry.directional_links_to()? {
+                let _ = edges.entry((id.clone(), link.to_str()?, true)).or_default();
+            }
+
+            for link in entry.directional_links_from()? {
+                let _ = edges.entry((link.to_str()?, id.clone(), true)).or_default();
+            }
+
+            for label in entry.labeled_links()? {
+                let other = String::from(label.target_str());
+                let key   = match label.direction() {
+                    LinkDirection::To   => (id.clone(), other, true),
+                    LinkDirection::From => (other, id.clone(), true),
+                };
+                let _ = edges.entry(key).or_default().insert(String::from(label.label()));
+            }
+
+            let url = url_of(&entry)?;
+            let _   = nodes.insert(id.clone(), Node { id, url, exists: true });
+        }
+
+        let mut graph = LinkGraph::default();
+
+        for ((source, target, directed), labels) in edges {
+            for id in [&source, &target].iter() {
+                if !nodes.contains_key(*id) {
+                    debug!("Link target not in store: {}", id);
+                    let _ = nodes.insert((*id).clone(), Node { id: (*id).clone(), url: None, exists: false });
+                }
+            }
+
+            graph.adjacency.entry(source.clone()).or_default().insert(target.clone());
+            graph.adjacency.entry(target.clone()).or_default().insert(source.clone());
+            graph.edges.push(Edge { source, target, directed, labels: labels.into_iter().collect() });
+        }
+
+        graph.nodes = nodes.into_iter().map(|(_, n)| n).collect();
+        Ok(graph)
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    pub fn node(&self, id: &str) -> Option<&Node> {
+        self.nodes.iter().find(|n| n.id == id)
+    }
+
+    /// The number of distinct entries `id` is linked with, regardless of the link direction
+    pub fn degree(&self, id: &str) -> usize {
+        self.adjacency.get(id).map(BTreeSet::len).unwrap_or(0)
+    }
+
+    /// The shortest chain of links from `from` to `to`, including both ends
+    ///
+    /// If `follow_direction` is set, directed edges are only followed from their source to their
+    /// target. Returns `None` if there is no such chain.
+    pub fn shortest_path(&self, from: &str, to: &str, follow_direction: bool) -> Option<Vec<String>> {
+        if self.node(from).is_none() || self.node(to).is_none() {
+            return None
+        }
+
+        let mut neighbours : BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for edge in self.edges.iter() {
+            neighbours.entry(&edge.source).or_default().insert(&edge.target);
+            if !(follow_direction && edge.directed) {
+                neighbours.entry(&edge.target).or_default().insert(&edge.source);
+            }
+        }
+
+        let mut predecessors : BTreeMap<&str, &str> = BTreeMap::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(from);
+
+        while let Some(current) = queue.pop_front() {
+            if current == to {
+                let mut path = vec![String::from(to)];
+                let mut current = to;
+                while let Some(prev) = predecessors.get(current) {
+                    path.push(String::from(*prev));
+                    current = prev;
+                }
+                path.reverse();
+                return Some(path)
+            }
+
+            for next in neighbours.get(current).into_iter().flat_map(|n| n.iter()) {
+                if *next != from && !predecessors.contains_key(next) {
+                    let _ = predecessors.insert(next, current);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// The connected components of the graph, ignoring link directions, largest first
+    pub fn connected_components(&self) -> Vec<Vec<String>> {
+        let mut seen : BTreeSet<&str> = BTreeSet::new();
+        let mut components = vec![];
+
+        for node in self.nodes.iter() {
+            if seen.contains(node.id.as_str()) {
+                continue
+            }
+
+            let mut component = vec![];
+            let mut stack     = vec![node.id.as_str()];
+            let _             = seen.insert(&node.id);
+
+            while let Some(current) = stack.pop() {
+                component.push(String::from(current));
+                for next in self.adjacency.get(current).into_iter().flat_map(|n| n.iter()) {
+                    if seen.insert(next) {
+                        stack.push(next);
+                    }
+                }
+            }
+
+            component.sort();
+            components.push(component);
+        }
+
+        components.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        components
+    }
+
+    /// All nodes without any links
+    pub fn orphans(&self) -> Vec<&Node> {
+        self.nodes.iter().filter(|n| self.degree(&n.id) == 0).collect()
+    }
+
+    /// The `n` nodes with the most links, with their degree, most linked first
+    pub fn hubs(&self, n: usize) -> Vec<(&Node, usize)> {
+        let mut hubs = self.nodes
+            .iter()
+            .map(|node| (node, self.degree(&node.id)))
+            .filter(|&(_, degree)| degree > 0)
+            .collect::<Vec<_>>();
+
+        hubs.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.id.cmp(&b.0.id)));
+        hubs.truncate(n);
+        hubs
+    }
+
+    pub fn export(&self, format: GraphFormat) -> Result<String> {
+        match format {
+            GraphFormat::Dot     => Ok(self.to_dot()),
+            GraphFormat::GraphMl => Ok(self.to_graphml()),
+            GraphFormat::Json    => ::serde_json::to_string_pretty(self)
+                .context(EM::FormatError)
+                .map_err(Error::from),
+        }
+    }
+
+    /// Render the graph in the Graphviz DOT language
+    ///
+    /// Undirected edges are rendered without arrowheads, so the whole graph can be rendered as
+    /// digraph.
+    pub fn to_dot(&self) -> String {
+        let mut s = String::from("digraph links {\n");
+
+        for node in self.nodes.iter() {
+            let mut attrs = vec![format!("label=\"{}\"", dot_escape(node.url.as_ref().unwrap_or(&node.id)))];
+            if node.url.is_some() {
+                attrs.push(String::from("shape=box"));
+            }
+            if !node.exists {
+                attrs.push(String::from("style=dashed"));
+            }
+            s.push_str(&format!("    \"{}\" [{}];\n", dot_escape(&node.id), attrs.join(", ")));
+        }
+
+        for edge in self.edges.iter() {
+            let mut attrs = vec![];
+            if !edge.directed {
+                attrs.push(String::from("dir=none"));
+            }
+            if !edge.labels.is_empty() {
+                attrs.push(format!("label=\"{}\"", dot_escape(&edge.labels.join(", "))));
+            }
+
+            let attrs = if attrs.is_empty() { String::new() } else { format!(" [{}]", attrs.join(", ")) };
+            s.push_str(&format!("    \"{}\" -> \"{}\"{};\n", dot_escape(&edge.source), dot_escape(&edge.target), attrs));
+        }
+
+        s.push_str("}\n");
+        s
+    }
+
+    /// Render the graph as GraphML document
+    pub fn to_graphml(&self) -> String {
+        let mut s = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"url\" for=\"node\" attr.name=\"url\" attr.type=\"string\"/>\n",
+            "  <key id=\"exists\" for=\"node\" attr.name=\"exists\" attr.type=\"boolean\"/>\n",
+            "  <key id=\"labels\" for=\"edge\" attr.name=\"labels\" attr.type=\"string\"/>\n",
+            "  <graph id=\"links\" edgedefault=\"undirected\">\n",
+        ));
+
+        for node in self.nodes.iter() {
+            s.push_str(&format!("    <node id=\"{}\">\n", xml_escape(&node.id)));
+            if let Some(ref url) = node.url {
+                s.push_str(&format!("      <data key=\"url\">{}</data>\n", xml_escape(url)));
+            }
+            s.push_str(&format!("      <data key=\"exists\">{}</data>\n", node.exists));
+            s.push_str("    </node>\n");
+        }
+
+        for edge in self.edges.iter() {
+            s.push_str(&format!("    <edge source=\"{}\" target=\"{}\" directed=\"{}\"",
+                                xml_escape(&edge.source),
+                                xml_escape(&edge.target),
+                                edge.directed));
+            if edge.labels.is_empty() {
+                s.push_str("/>\n");
+            } else {
+                s.push_str(">\n");
+                s.push_str(&format!("      <data key=\"labels\">{}</data>\n", xml_escape(&edge.labels.join(", "))));
+                s.push_str("    </edge>\n");
+            }
+        }
+
+        s.push_str("  </graph>\n</graphml>\n");
+        s
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+pub trait StoreLinkGraphExt {
+    /// Build the link graph of the store, without resolving URLs
+    fn link_graph(&self) -> Result<LinkGraph>;
+}
+
+impl StoreLinkGraphExt for Store {
+    fn link_graph(&self) -> Result<LinkGraph> {
+        LinkGraph::build(self, |_| Ok(None))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use libimagstore::store::Store;
+
+    use super::*;
+
+    fn setup_logging() {
+        let _ = ::env_logger::try_init();
+    }
+
+    fn get_store() -> Store {
+        Store::new_inmemory(PathBuf::from("/"), &None).unwrap()
+    }
+
+    // a -- b -> c    d    e -- f
+    fn get_graph() -> LinkGraph {
+        let store = get_store();
+        {
+            let mut a = store.create(PathBuf::from("a")).unwrap();
+            let mut b = store.create(PathBuf::from("b")).unwrap();
+            let mut c = store.create(PathBuf::from("c")).unwrap();
+            let _     = store.create(PathBuf::from("d")).unwrap();
+            let mut e = store.create(PathBuf::from("e")).unwrap();
+            let mut f = store.create(PathBuf::from("f")).unwrap();
+
+            a.add_link(&mut b).unwrap();
+            b.add_labeled_link_to(&mut c, "blocks", None).unwrap();
+            e.add_link(&mut f).unwrap();
+        }
+
+        store.link_graph().unwrap()
+    }
+
+    #[test]
+    fn test_graph_edges() {
+        setup_logging();
+        let graph = get_graph();
+
+        assert_eq!(graph.nodes().len(), 6);
+        assert_eq!(graph.edges().len(), 3);
+
+        let directed = graph.edges().iter().find(|e| e.is_directed()).unwrap();
+        assert_eq!(directed.source(), "b");
+        assert_eq!(directed.target(), "c");
+        assert_eq!(directed.labels(), &[String::from("blocks")]);
+    }
+
+    #[test]
+    fn test_shortest_path() {
+        setup_logging();
+        let graph = get_graph();
+
+        let path = graph.shortest_path("a", "c", true).unwrap();
+        assert_eq!(path, vec!["a", "b", "c"]);
+        assert!(graph.shortest_path("c", "a", true).is_none());
+        assert_eq!(graph.shortest_path("c", "a", false).unwrap(), vec!["c", "b", "a"]);
+        assert!(graph.shortest_path("a", "f", false).is_none());
+        assert!(graph.shortest_path("a", "x", false).is_none());
+    }
+
+    #[test]
+    fn test_analysis() {
+        setup_logging();
+        let graph = get_graph();
+
+        let components = graph.connected_components();
+        assert_eq!(components, vec![vec!["a", "b", "c"], vec!["e", "f"], vec!["d"]]);
+
+        let orphans = graph.orphans().into_iter().map(Node::id).collect::<Vec<_>>();
+        assert_eq!(orphans, vec!["d"]);
+
+        let hubs = graph.hubs(1);
+        assert_eq!(hubs.len(), 1);
+        assert_eq!(hubs[0].0.id(), "b");
+        assert_eq!(hubs[0].1, 2);
+    }
+
+    #[test]
+    fn test_export() {
+        setup_logging();
+        let graph = get_graph();
+
+        let dot = graph.export(GraphFormat::Dot).unwrap();
+        assert!(dot.contains("\"b\" -> \"c\" [label=\"blocks\"];"));
+        assert!(dot.contains("\"a\" -> \"b\" [dir=none];"));
+
+        let graphml = graph.export(GraphFormat::GraphMl).unwrap();
+        assert!(graphml.contains("<edge source=\"e\" target=\"f\" directed=\"false\"/>"));
+
+        let json : ::serde_json::Value = ::serde_json::from_str(&graph.export(GraphFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["nodes"].as_array().unwrap().len(), 6);
+        assert_eq!(json["edges"][1]["labels"][0], "blocks");
+    }
+}
@@ -0,0 +1,157 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+
+//! Labeled links
+//!
+//! A label names the relation a directional link expresses, for example "blocks", "parent-of" or
+//! "cites", and can carry some metadata: when the link was made and why.
+//!
+//! Labels are stored in the `links.labels` array of both linked entries. The link itself is still
+//! stored in `links.to` and `links.from`, so code which does not know about labels still sees it.
+
+use std::path::PathBuf;
+
+use chrono::NaiveDateTime;
+use failure::Fallible as Result;
+use failure::ResultExt;
+use failure::Error;
+
+use libimagstore::storeid::StoreId;
+
+use crate::link::Link;
+
+/// The format the creation time of a labeled link is stored in
+pub const DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkDirection {
+    /// The link points from the entry to the target
+    To,
+
+    /// The link points from the target to the entry
+    From,
+}
+
+impl LinkDirection {
+    pub fn reversed(self) -> Self {
+        match self {
+            LinkDirection::To   => LinkDirection::From,
+            LinkDirection::From => LinkDirection::To,
+        }
+    }
+}
+
+/// A labeled link, as stored in the header of one of the linked entries
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LabeledLink {
+    target: String,
+    label: String,
+    direction: LinkDirection,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+impl LabeledLink {
+
+    /// Create a new labeled link to `target`, made now
+    pub(crate) fn new(target: String, label: &str, direction: LinkDirection, reason: Option<&str>) -> Self {
+        LabeledLink {
+            target,
+            label: String::from(label),
+            direction,
+            created: Some(::chrono::Local::now().naive_local().format(DATETIME_FORMAT).to_string()),
+            reason: reason.map(String::from),
+        }
+    }
+
+    /// The same link, as seen from the other end (the entry at `source`)
+    pub(crate) fn mirrored(&self, source: String) -> Self {
+        LabeledLink {
+            target: source,
+            label: self.label.clone(),
+            direction: self.direction.reversed(),
+            created: self.created.clone(),
+            reason: self.reason.clone(),
+        }
+    }
+
+    /// Check whether this is the label `label` on the link to `target` in `direction`
+    pub(crate) fn is(&self, target: &str, direction: LinkDirection, label: &str) -> bool {
+        self.is_on(target, direction) && self.label == label
+    }
+
+    /// Check whether this is a label on the link to `target` in `direction`
+    pub(crate) fn is_on(&self, target: &str, direction: LinkDirection) -> bool {
+        self.target == target && self.direction == direction
+    }
+
+    pub(crate) fn target_str(&self) -> &str {
+        &self.target
+    }
+
+    pub(crate) fn set_target(&mut self, target: String) {
+        self.target = target;
+    }
+
+    /// The other end of the link
+    pub fn target(&self) -> Result<StoreId> {
+        StoreId::new(PathBuf::from(&self.target))
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn direction(&self) -> LinkDirection {
+        self.direction
+    }
+
+    /// When the link was made, if known
+    pub fn created(&self) -> Result<Option<NaiveDateTime>> {
+        self.created
+            .as_ref()
+            .map(|s| {
+                NaiveDateTime::parse_from_str(s, DATETIME_FORMAT)
+                    .context(format_err!("Failed to parse link creation time: {}", s))
+                    .map_err(Error::from)
+            })
+            .transpose()
+    }
+
+    /// Why the link was made, if known
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_ref().map(String::as_str)
+    }
+
+    /// Get the link as `Link::LinkTo` or `Link::LinkFrom`, depending on its direction
+    pub fn to_link(&self) -> Result<Link> {
+        let link = self.target()?;
+        Ok(match self.direction {
+            LinkDirection::To   => Link::LinkTo { link },
+            LinkDirection::From => Link::LinkFrom { link },
+        })
+    }
+
+}
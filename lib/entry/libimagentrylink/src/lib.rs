@@ -44,8 +44,10 @@ extern crate toml_query;
 extern crate url;
 extern crate sha1;
 extern crate hex;
+extern crate chrono;
 extern crate serde;
 #[macro_use] extern crate serde_derive;
+extern crate serde_json;
 #[macro_use] extern crate failure;
 #[macro_use] extern crate is_match;
 
@@ -58,8 +60,12 @@ extern crate libimagutil;
 
 module_entry_path_mod!("links");
 
+pub mod graph;
 pub mod iter;
+pub mod label;
 pub mod linkable;
 pub mod link;
+pub mod migration;
 pub mod storecheck;
+pub mod transfer;
 
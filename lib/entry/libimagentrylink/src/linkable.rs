@@ -32,6 +32,8 @@ use failure::err_msg;
 
 use crate::iter::LinkIter;
 use crate::link::Link;
+use crate::label::LabeledLink;
+use crate::label::LinkDirection;
 
 pub trait Linkable {
 
@@ -54,21 +56,52 @@ pub trait Linkable {
     fn remove_link(&mut self, link: &mut Entry) -> Result<()>;
 
     /// Remove _all_ internal links
+    ///
+    /// All modified entries are written in one store transaction, so either all or none of the
+    /// links are removed. The implementor object must be borrowed from the store.
     fn unlink(&mut self, store: &Store) -> Result<()>;
 
     /// Add a directional link: self -> otehr
     fn add_link_to(&mut self, other: &mut Entry) -> Result<()>;
 
     /// Remove a directional link: self -> otehr
+    ///
+    /// The labels of the link are removed as well.
     fn remove_link_to(&mut self, other: &mut Entry) -> Result<()>;
 
+    /// Add a directional link self -> other with the label `label`
+    ///
+    /// The label is stored in both entries, together with the time the link was made and the
+    /// optional `reason`. A link can have several labels. Adding a label which is already on the
+    /// link replaces it.
+    fn add_labeled_link_to(&mut self, other: &mut Entry, label: &str, reason: Option<&str>) -> Result<()>;
+
+    /// Remove the label `label` from the directional link self -> other
+    ///
+    /// If no other label is left on the link, the link itself is removed as well.
+    fn remove_labeled_link_to(&mut self, other: &mut Entry, label: &str) -> Result<()>;
+
+    /// Get all labels on outgoing and incoming links
+    fn labeled_links(&self) -> Result<Vec<LabeledLink>>;
+
+    /// Get all outgoing (`Link::LinkTo`) and incoming (`Link::LinkFrom`) links with the label
+    /// `label`
+    fn links_with_label(&self, label: &str) -> Result<LinkIter>;
+
+    /// Let all links of the implementor object which point to `old` point to `new`
+    ///
+    /// This only alters the implementor object. It is meant to be called on the entries linked to
+    /// an entry which gets moved from `old` to `new`.
+    fn relocate_link(&mut self, old: &StoreId, new: &StoreId) -> Result<()>;
+
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-struct LinkPartial {
-    internal: Option<Vec<String>>,
-    from: Option<Vec<String>>,
-    to: Option<Vec<String>>,
+pub(crate) struct LinkPartial {
+    pub(crate) internal: Option<Vec<String>>,
+    pub(crate) from: Option<Vec<String>>,
+    pub(crate) to: Option<Vec<String>>,
+    pub(crate) labels: Option<Vec<LabeledLink>>,
 }
 
 impl Default for LinkPartial {
@@ -77,6 +110,7 @@ impl Default for LinkPartial {
             internal: None,
             from: None,
             to: None,
+            labels: None,
         }
     }
 }
@@ -217,13 +251,22 @@ impl Linkable for Entry {
 
     fn unlink(&mut self, store: &Store) -> Result<()> {
         debug!("Unlinking {:?}", self);
+        let mut tx       = store.transaction();
+        let mut unlinked = vec![]; // keep the entries borrowed until the transaction is committed
+
         for id in self.links()?.map(|l| l.get_store_id().clone()) {
             match store.get(id).context("Failed to get entry")? {
-                Some(mut entry) => self.remove_link(&mut entry)?,
+                Some(mut entry) => {
+                    self.remove_link(&mut entry)?;
+                    tx.update(&entry)?;
+                    unlinked.push(entry);
+                },
                 None            => return Err(err_msg("Link target does not exist")),
             }
         }
 
+        tx.update(self)?;
+        tx.commit().context("Failed to commit unlinking")?;
         Ok(())
     }
 
@@ -269,11 +312,131 @@ impl Linkable for Entry {
             left.to = Some(left_to);
             right.from = Some(right_from);
 
+            if let Some(labels) = left.labels.as_mut() {
+                labels.retain(|l| !l.is_on(&right_location, LinkDirection::To));
+            }
+            if let Some(labels) = right.labels.as_mut() {
+                labels.retain(|l| !l.is_on(&left_location, LinkDirection::From));
+            }
+
+            trace!("Finished: ({:?}, {:?})", left, right);
+            Ok((left, right))
+        })
+    }
+
+    fn add_labeled_link_to(&mut self, other: &mut Entry, label: &str, reason: Option<&str>) -> Result<()> {
+        debug!("Adding labeled link '{}': {:?}", label, other);
+        let left_location  = self.get_location().to_str()?;
+        let right_location = other.get_location().to_str()?;
+        let left_label     = LabeledLink::new(right_location.clone(), label, LinkDirection::To, reason);
+        let right_label    = left_label.mirrored(left_location.clone());
+
+        alter_linking(self, other, |mut left, mut right| {
+            push_unique(&mut left.to, right_location);
+            push_unique(&mut right.from, left_location);
+            set_label(&mut left.labels, left_label);
+            set_label(&mut right.labels, right_label);
+
+            trace!("Finished: ({:?}, {:?})", left, right);
+            Ok((left, right))
+        })
+    }
+
+    fn remove_labeled_link_to(&mut self, other: &mut Entry, label: &str) -> Result<()> {
+        debug!("Removing labeled link '{}': {:?}", label, other);
+        let left_location  = self.get_location().to_str()?;
+        let right_location = other.get_location().to_str()?;
+
+        alter_linking(self, other, |mut left, mut right| {
+            let mut left_labels = left.labels.unwrap_or_else(|| vec![]);
+            left_labels.retain(|l| !l.is(&right_location, LinkDirection::To, label));
+
+            let mut right_labels = right.labels.unwrap_or_else(|| vec![]);
+            right_labels.retain(|l| !l.is(&left_location, LinkDirection::From, label));
+
+            // the link goes away with its last label
+            if !left_labels.iter().any(|l| l.is_on(&right_location, LinkDirection::To)) {
+                if let Some(to) = left.to.as_mut() {
+                    to.retain(|l| *l != right_location);
+                }
+                if let Some(from) = right.from.as_mut() {
+                    from.retain(|l| *l != left_location);
+                }
+            }
+
+            left.labels  = Some(left_labels);
+            right.labels = Some(right_labels);
+
             trace!("Finished: ({:?}, {:?})", left, right);
             Ok((left, right))
         })
     }
 
+    fn labeled_links(&self) -> Result<Vec<LabeledLink>> {
+        debug!("Getting labeled links");
+        Ok(self.get_header()
+            .read_partial::<LinkPartial>()?
+            .unwrap_or_else(Default::default)
+            .labels
+            .unwrap_or_else(|| vec![]))
+    }
+
+    fn links_with_label(&self, label: &str) -> Result<LinkIter> {
+        debug!("Getting links with label '{}'", label);
+        self.labeled_links()?
+            .into_iter()
+            .filter(|l| l.label() == label)
+            .map(|l| l.to_link())
+            .collect::<Result<Vec<Link>>>()
+            .map(LinkIter::new)
+    }
+
+    fn relocate_link(&mut self, old: &StoreId, new: &StoreId) -> Result<()> {
+        debug!("Relocating links of {:?}: {} -> {}", self.get_location(), old, new);
+        let old = old.to_str()?;
+        let new = new.to_str()?;
+
+        let relocate = |links: Option<Vec<String>>| -> Option<Vec<String>> {
+            links.map(|links| {
+                let mut links = links
+                    .into_iter()
+                    .map(|l| if l == old { new.clone() } else { l })
+                    .collect::<Vec<_>>();
+                links.sort_unstable();
+                links.dedup();
+                links
+            })
+        };
+
+        let partial = match self.get_header().read_partial::<LinkPartial>()? {
+            Some(partial) => partial,
+            None          => return Ok(()),
+        };
+
+        let labels = partial.labels.map(|labels| {
+            labels
+                .into_iter()
+                .map(|mut l| {
+                    if l.target_str() == old {
+                        l.set_target(new.clone());
+                    }
+                    l
+                })
+                .collect()
+        });
+
+        let partial = LinkPartial {
+            internal: relocate(partial.internal),
+            from:     relocate(partial.from),
+            to:       relocate(partial.to),
+            labels,
+        };
+
+        trace!("Partial after relocating: {:?}", partial);
+        self.get_header_mut().insert_serialized("links", partial)?;
+        Ok(())
+    }
+
 }
 
 fn link_string_iter_to_link_iter<I>(iter: I) -> Result<LinkIter>
@@ -286,6 +449,21 @@ fn link_string_iter_to_link_iter<I>(iter: I) -> Result<LinkIter>
         .map(LinkIter::new)
 }
 
+/// Push `link` to `links`, if it is not already in there
+fn push_unique(links: &mut Option<Vec<String>>, link: String) {
+    let links = links.get_or_insert_with(Vec::new);
+    if !links.contains(&link) {
+        links.push(link);
+    }
+}
+
+/// Add `label` to `labels`, replacing the same label on the same link
+pub(crate) fn set_label(labels: &mut Option<Vec<LabeledLink>>, label: LabeledLink) {
+    let labels = labels.get_or_insert_with(Vec::new);
+    labels.retain(|l| !l.is(label.target_str(), label.direction(), label.label()));
+    labels.push(label);
+}
+
 fn alter_linking<F>(left: &mut Entry, right: &mut Entry, f: F) -> Result<()>
     where F: FnOnce(LinkPartial, LinkPartial) -> Result<(LinkPartial, LinkPartial)>
 {
@@ -582,5 +760,137 @@ mod test {
         }
 
     }
+    #[test]
+    fn test_unlink() {
+        setup_logging();
+        let store = get_store();
+
+        let mut e1 = store.retrieve(PathBuf::from("1")).unwrap();
+        {
+            let mut e2 = store.retrieve(PathBuf::from("2")).unwrap();
+            let mut e3 = store.retrieve(PathBuf::from("3")).unwrap();
+            assert!(e1.add_link(&mut e2).is_ok());
+            assert!(e1.add_link(&mut e3).is_ok());
+        }
+
+        assert!(e1.unlink(&store).is_ok());
+
+        assert_eq!(e1.links().unwrap().collect::<Vec<_>>().len(), 0);
+        let e2 = store.get(PathBuf::from("2")).unwrap().unwrap();
+        let e3 = store.get(PathBuf::from("3")).unwrap().unwrap();
+        assert_eq!(e2.links().unwrap().collect::<Vec<_>>().len(), 0);
+        assert_eq!(e3.links().unwrap().collect::<Vec<_>>().len(), 0);
+    }
+
+    #[test]
+    fn test_relocate_link() {
+        use libimagstore::storeid::StoreId;
+
+        setup_logging();
+        let store = get_store();
+
+        let mut e1 = store.retrieve(PathBuf::from("1")).unwrap();
+        let mut e2 = store.retrieve(PathBuf::from("2")).unwrap();
+        let mut e3 = store.retrieve(PathBuf::from("3")).unwrap();
+
+        assert!(e1.add_link(&mut e2).is_ok());
+        assert!(e3.add_link_to(&mut e2).is_ok());
+
+        let old = StoreId::new(PathBuf::from("2")).unwrap();
+        let new = StoreId::new(PathBuf::from("moved")).unwrap();
+
+        assert!(e1.relocate_link(&old, &new).is_ok());
+        assert!(e3.relocate_link(&old, &new).is_ok());
+
+        let links = |iter: crate::iter::LinkIter| -> Vec<String> {
+            iter.map(|l| l.to_str().unwrap()).collect()
+        };
+
+        assert_eq!(links(e1.unidirectional_links().unwrap()), vec!["moved"]);
+        assert_eq!(links(e3.directional_links_to().unwrap()), vec!["moved"]);
+    }
+
+    #[test]
+    fn test_labeled_link() {
+        use crate::label::LinkDirection;
+        use crate::link::Link;
+
+        setup_logging();
+        let store = get_store();
+
+        let mut e1 = store.retrieve(PathBuf::from("1")).unwrap();
+        let mut e2 = store.retrieve(PathBuf::from("2")).unwrap();
+
+        assert!(e1.labeled_links().unwrap().is_empty());
+        assert!(e1.add_labeled_link_to(&mut e2, "blocks", Some("needs 2 first")).is_ok());
+
+        let to = e1.links_with_label("blocks").unwrap().collect::<Vec<_>>();
+        assert_eq!(to, vec![Link::LinkTo { link: e2.get_location().clone() }]);
+
+        let from = e2.links_with_label("blocks").unwrap().collect::<Vec<_>>();
+        assert_eq!(from, vec![Link::LinkFrom { link: e1.get_location().clone() }]);
+
+        assert!(e1.links_with_label("cites").unwrap().next().is_none());
+
+        let labels = e2.labeled_links().unwrap();
+        assert_eq!(labels.len(), 1);
+        assert_eq!(labels[0].label(), "blocks");
+        assert_eq!(labels[0].direction(), LinkDirection::From);
+        assert_eq!(labels[0].reason(), Some("needs 2 first"));
+        assert!(labels[0].created().unwrap().is_some());
+
+        // labeled links are plain directional links for code which does not know about labels
+        assert_eq!(e1.directional_links_to().unwrap().count(), 1);
+        assert_eq!(e2.directional_links_from().unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_remove_labeled_link() {
+        setup_logging();
+        let store = get_store();
+
+        let mut e1 = store.retrieve(PathBuf::from("1")).unwrap();
+        let mut e2 = store.retrieve(PathBuf::from("2")).unwrap();
+
+        assert!(e1.add_labeled_link_to(&mut e2, "blocks", None).is_ok());
+        assert!(e1.add_labeled_link_to(&mut e2, "cites", None).is_ok());
+        assert!(e1.add_labeled_link_to(&mut e2, "cites", None).is_ok()); // replaces the label
+
+        assert_eq!(e1.labeled_links().unwrap().len(), 2);
+        assert_eq!(e2.labeled_links().unwrap().len(), 2);
+        assert_eq!(e1.directional_links_to().unwrap().count(), 1);
+
+        assert!(e1.remove_labeled_link_to(&mut e2, "blocks").is_ok());
+        assert_eq!(e1.labeled_links().unwrap().len(), 1);
+        assert_eq!(e2.labeled_links().unwrap().len(), 1);
+        assert_eq!(e1.directional_links_to().unwrap().count(), 1);
+
+        assert!(e1.remove_labeled_link_to(&mut e2, "cites").is_ok());
+        assert!(e1.labeled_links().unwrap().is_empty());
+        assert!(e2.labeled_links().unwrap().is_empty());
+        assert_eq!(e1.directional_links_to().unwrap().count(), 0);
+        assert_eq!(e2.directional_links_from().unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_remove_link_to_removes_labels() {
+        setup_logging();
+        let store = get_store();
+
+        let mut e1 = store.retrieve(PathBuf::from("1")).unwrap();
+        let mut e2 = store.retrieve(PathBuf::from("2")).unwrap();
+        let mut e3 = store.retrieve(PathBuf::from("3")).unwrap();
+
+        assert!(e1.add_labeled_link_to(&mut e2, "blocks", None).is_ok());
+        assert!(e1.add_labeled_link_to(&mut e3, "blocks", None).is_ok());
+        assert!(e1.remove_link_to(&mut e2).is_ok());
+
+        let targets = e1.links_with_label("blocks")
+            .unwrap()
+            .map(|l| l.to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(targets, vec!["3"]);
+        assert!(e2.labeled_links().unwrap().is_empty());
+    }
 
 }
@@ -0,0 +1,119 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Migrations for the link layout in the entry header
+//!
+//! Before 0.4.0, links were stored in `imag.links`, which is reserved for the store. Since then,
+//! they live in `links.internal`.
+//!
+//! The labels of links (`links.labels`) need no migration, as they are optional.
+
+use toml::Value;
+use toml_query::insert::TomlValueInsertExt;
+use toml_query::read::TomlValueReadExt;
+use failure::Fallible as Result;
+use failure::ResultExt;
+use failure::Error;
+
+use libimagstore::store::Entry;
+use libimagstore::migration::Migration;
+use libimagstore::migration::MigrationRegistry;
+use libimagerror::errors::ErrorMsg as EM;
+
+/// Register the migrations of libimagentrylink
+pub fn register_migrations(registry: &mut MigrationRegistry) -> Result<()> {
+    registry.register(Migration::new("links-out-of-imag-section", "0.4.0", move_links_out_of_imag_section)?);
+    Ok(())
+}
+
+/// Move the links from `imag.links` to `links.internal`, merging them with links already there
+fn move_links_out_of_imag_section(entry: &mut Entry) -> Result<()> {
+    // toml-query refuses to delete non-empty arrays, so we remove the key from the table
+    let old = entry
+        .get_header_mut()
+        .get_mut("imag")
+        .and_then(Value::as_table_mut)
+        .and_then(|imag| imag.remove("links"));
+
+    let old = match old {
+        Some(Value::Array(links)) => links,
+        Some(_)                   => return Err(Error::from(EM::EntryHeaderTypeError2("imag.links", "array"))),
+        None                      => return Ok(()),
+    };
+
+    let mut links = match entry.get_header().read("links.internal").context(EM::EntryHeaderReadError)? {
+        Some(&Value::Array(ref links)) => links.clone(),
+        Some(_)                        => return Err(Error::from(EM::EntryHeaderTypeError2("links.internal", "array"))),
+        None                           => vec![],
+    };
+
+    for link in old {
+        if !link.is_str() {
+            return Err(Error::from(EM::EntryHeaderTypeError2("imag.links", "array of strings")))
+        }
+
+        if !links.contains(&link) {
+            links.push(link);
+        }
+    }
+
+    debug!("Moving {} links of {} to links.internal", links.len(), entry.get_location());
+    let _ = entry
+        .get_header_mut()
+        .insert("links.internal", Value::Array(links))
+        .context(EM::EntryHeaderWriteError)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use toml::Value;
+    use toml_query::read::TomlValueReadExt;
+
+    use libimagstore::store::Entry;
+    use libimagstore::migration::MigrationRegistry;
+
+    use super::register_migrations;
+
+    #[test]
+    fn test_links_are_moved_out_of_imag_section() {
+        let text = r#"---
+[imag]
+version = "0.3.0"
+links = ["a", "b"]
+
+[links]
+internal = ["b", "c"]
+---
+"#;
+        let mut entry    = Entry::from_str(PathBuf::from("test"), text).unwrap();
+        let mut registry = MigrationRegistry::new();
+        register_migrations(&mut registry).unwrap();
+
+        let applied = registry.migrate_entry(&mut entry).unwrap();
+        assert_eq!(applied, Some(vec!["links-out-of-imag-section"]));
+        assert!(entry.get_header().read("imag.links").unwrap().is_none());
+
+        let links = entry.get_header().read("links.internal").unwrap().cloned().unwrap();
+        let links = links.as_array().unwrap().iter().map(Value::as_str).collect::<Vec<_>>();
+        assert_eq!(links, vec![Some("b"), Some("c"), Some("a")]);
+    }
+}
@@ -0,0 +1,499 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! Copying entries from one store into another
+//!
+//! Entries keep their ids when they are transferred. Their headers (and with them tags, refs and
+//! other metadata) are copied as they are, except for the links: Links to entries which are
+//! neither transferred nor present in the target store are dropped, links to entries which are
+//! already in the target store get their counterpart in the target entry.
+
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter, Error as FmtError};
+
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+
+use toml::Value;
+use toml_query::read::TomlValueReadExt;
+use toml_query::insert::TomlValueInsertExt;
+use sha1::{Sha1, Digest};
+use failure::Fallible as Result;
+
+use crate::linkable::Linkable;
+use crate::linkable::LinkPartial;
+use crate::linkable::set_label;
+use crate::label::LabeledLink;
+
+/// Options for `transfer()`
+#[derive(Debug, Clone, Default)]
+pub struct TransferOptions {
+    with_links: bool,
+    overwrite: bool,
+}
+
+impl TransferOptions {
+
+    /// Also transfer all entries which are reachable via links from the passed entries
+    pub fn with_links(mut self, with_links: bool) -> Self {
+        self.with_links = with_links;
+        self
+    }
+
+    /// Replace entries in the target store which differ from the transferred ones
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+}
+
+/// Why an entry was not transferred
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conflict {
+    /// The entry exists in the target store with another content. Contains the sha1 hashes of the
+    /// source and the target content.
+    ContentDiffers { source: String, target: String },
+
+    /// The entry exists in the target store with the same content but another header
+    HeaderDiffers,
+}
+
+impl Display for Conflict {
+    fn fmt(&self, fmt: &mut Formatter) -> ::std::result::Result<(), FmtError> {
+        match *self {
+            Conflict::ContentDiffers { ref source, ref target } => {
+                write!(fmt, "exists with different content (sha1 {} != {})", source, target)
+            },
+            Conflict::HeaderDiffers => write!(fmt, "exists with different header"),
+        }
+    }
+}
+
+/// What `transfer()` did
+#[derive(Debug, Default)]
+pub struct TransferReport {
+    copied: Vec<StoreId>,
+    overwritten: Vec<StoreId>,
+    identical: Vec<StoreId>,
+    conflicts: Vec<(StoreId, Conflict)>,
+    dropped_links: Vec<(StoreId, StoreId)>,
+}
+
+impl TransferReport {
+
+    /// The entries which did not exist in the target store
+    pub fn copied(&self) -> &[StoreId] {
+        &self.copied
+    }
+
+    /// The entries which replaced a different entry in the target store
+    pub fn overwritten(&self) -> &[StoreId] {
+        &self.overwritten
+    }
+
+    /// The entries which existed in the target store already and were left untouched
+    pub fn identical(&self) -> &[StoreId] {
+        &self.identical
+    }
+
+    /// The entries which were not transferred because the target store has a different entry
+    /// with the same id
+    pub fn conflicts(&self) -> &[(StoreId, Conflict)] {
+        &self.conflicts
+    }
+
+    /// The links which were removed from transferred entries, as `(entry, link target)`, because
+    /// the link target does not exist in the target store
+    pub fn dropped_links(&self) -> &[(StoreId, StoreId)] {
+        &self.dropped_links
+    }
+
+}
+
+/// The link header fields, each with the field the counterpart of a link lives in
+const LINK_FIELDS : &[(&str, &str)] = &[
+    ("links.internal", "links.internal"),
+    ("links.to",       "links.from"),
+    ("links.from",     "links.to"),
+];
+
+/// Copy the entries `ids` from `source` into `target`
+///
+/// Entries which exist in `target` already are only replaced if `options` say so, otherwise they
+/// are reported as conflicts (or as identical, if they do not differ).
+pub fn transfer<I>(source: &Store, target: &Store, ids: I, options: &TransferOptions)
+    -> Result<TransferReport>
+    where I: IntoIterator<Item = StoreId>
+{
+    let ids = if options.with_links {
+        link_closure(source, ids)?
+    } else {
+        ids.into_iter().collect::<BTreeSet<_>>()
+    };
+
+    let mut report  = TransferReport::default();
+    let mut written = BTreeSet::new();
+
+    // Links of written entries, as (entry, field of the counterpart, link target)
+    let mut kept_links = Vec::new();
+
+    // Labels of the links of written entries, as (entry, label)
+    let mut kept_labels = Vec::new();
+
+    for id in ids.iter() {
+        let (mut header, content) = {
+            let entry = source
+                .get(id.clone())?
+                .ok_or_else(|| format_err!("Entry not found in source store: {}", id))?;
+            (entry.get_header().clone(), entry.get_content().clone())
+        };
+
+        let mut links = Vec::new();
+        for &(field, counterpart) in LINK_FIELDS {
+            let (kept, dropped) = retain_links(&mut header, field, |link| {
+                Ok(ids.contains(link) || target.exists(link.clone())?)
+            })?;
+
+            links.extend(kept.into_iter().map(|link| (counterpart, link)));
+            report.dropped_links.extend(dropped.into_iter().map(|link| (id.clone(), link)));
+        }
+
+        let labels = retain_labels(&mut header, |label| {
+            let target = label.target()?;
+            Ok(links.iter().any(|(_, link)| *link == target))
+        })?;
+
+        match target.get(id.clone())? {
+            None => {
+                let mut entry = target.create(id.clone())?;
+                *entry.get_header_mut()  = header;
+                *entry.get_content_mut() = content;
+                report.copied.push(id.clone());
+            },
+
+            Some(ref entry) if *entry.get_header() == header && *entry.get_content() == content => {
+                report.identical.push(id.clone());
+                continue
+            },
+
+            Some(mut entry) => if options.overwrite {
+                *entry.get_header_mut()  = header;
+                *entry.get_content_mut() = content;
+                report.overwritten.push(id.clone());
+            } else {
+                let conflict = if *entry.get_content() == content {
+                    Conflict::HeaderDiffers
+                } else {
+                    Conflict::ContentDiffers {
+                        source: hash(&content),
+                        target: hash(entry.get_content()),
+                    }
+                };
+
+                report.conflicts.push((id.clone(), conflict));
+                continue
+            },
+        }
+
+        written.insert(id.clone());
+        kept_links.extend(links.into_iter().map(|(field, link)| (id.clone(), field, link)));
+        kept_labels.extend(labels.into_iter().map(|label| (id.clone(), label)));
+    }
+
+    // Entries which were not written by us do not know about the links of the written ones yet
+    for (id, field, link) in kept_links {
+        if written.contains(&link) {
+            continue
+        }
+
+        if let Some(mut entry) = target.get(link)? {
+            add_link_string(entry.get_header_mut(), field, id.to_str()?)?;
+        }
+    }
+
+    for (id, label) in kept_labels {
+        let link = label.target()?;
+        if written.contains(&link) {
+            continue
+        }
+
+        if let Some(mut entry) = target.get(link)? {
+            add_label(entry.get_header_mut(), label.mirrored(id.to_str()?))?;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Get the entries `ids` and all entries reachable from them via links
+pub fn link_closure<I>(store: &Store, ids: I) -> Result<BTreeSet<StoreId>>
+    where I: IntoIterator<Item = StoreId>
+{
+    let mut closure = BTreeSet::new();
+    let mut queue   = ids.into_iter().collect::<Vec<_>>();
+
+    while let Some(id) = queue.pop() {
+        if closure.contains(&id) {
+            continue
+        }
+
+        let entry = store
+            .get(id.clone())?
+            .ok_or_else(|| format_err!("Entry not found in source store: {}", id))?;
+
+        queue.extend(entry.links()?.map(|link| link.get_store_id().clone()));
+        closure.insert(id);
+    }
+
+    Ok(closure)
+}
+
+/// Remove the links at `field` in `header` for which `keep` returns false
+///
+/// Returns the kept and the removed links.
+fn retain_links<F>(header: &mut Value, field: &str, keep: F) -> Result<(Vec<StoreId>, Vec<StoreId>)>
+    where F: Fn(&StoreId) -> Result<bool>
+{
+    let links : Vec<String> = match header.read_deserialized(field)? {
+        Some(links) => links,
+        None        => return Ok((vec![], vec![])),
+    };
+
+    let mut kept    = Vec::new();
+    let mut dropped = Vec::new();
+    let mut strings = Vec::new();
+
+    for link in links {
+        let id = StoreId::new(link.clone().into())?;
+        if keep(&id)? {
+            kept.push(id);
+            strings.push(link);
+        } else {
+            dropped.push(id);
+        }
+    }
+
+    if !dropped.is_empty() {
+        let _ = header.insert_serialized(field, strings)?;
+    }
+
+    Ok((kept, dropped))
+}
+
+fn add_link_string(header: &mut Value, field: &str, link: String) -> Result<()> {
+    let mut partial = header
+        .read_partial::<LinkPartial>()?
+        .unwrap_or_else(Default::default);
+
+    {
+        let links = match field {
+            "links.to"   => &mut partial.to,
+            "links.from" => &mut partial.from,
+            _            => &mut partial.internal,
+        }.get_or_insert_with(Vec::new);
+
+        if links.contains(&link) {
+            return Ok(())
+        }
+
+        links.push(link);
+        links.sort_unstable();
+    }
+
+    let _ = header.insert_serialized("links", partial)?;
+    Ok(())
+}
+
+/// Remove the labels in `header` for which `keep` returns false
+///
+/// Returns the kept labels.
+fn retain_labels<F>(header: &mut Value, keep: F) -> Result<Vec<LabeledLink>>
+    where F: Fn(&LabeledLink) -> Result<bool>
+{
+    let labels : Vec<LabeledLink> = match header.read_deserialized("links.labels")? {
+        Some(labels) => labels,
+        None         => return Ok(vec![]),
+    };
+
+    let count = labels.len();
+    let mut kept = Vec::new();
+    for label in labels {
+        if keep(&label)? {
+            kept.push(label);
+        }
+    }
+
+    if kept.len() != count {
+        let _ = header.insert_serialized("links.labels", kept.clone())?;
+    }
+
+    Ok(kept)
+}
+
+fn add_label(header: &mut Value, label: LabeledLink) -> Result<()> {
+    let mut partial = header
+        .read_partial::<LinkPartial>()?
+        .unwrap_or_else(Default::default);
+
+    set_label(&mut partial.labels, label);
+
+    let _ = header.insert_serialized("links", partial)?;
+    Ok(())
+}
+
+fn hash(content: &str) -> String {
+    format!("{:x}", Sha1::digest(content.as_bytes()))
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use libimagstore::store::Store;
+    use libimagstore::storeid::StoreId;
+
+    use super::*;
+    use crate::linkable::Linkable;
+
+    fn get_store() -> Store {
+        Store::new_inmemory(PathBuf::from("/"), &None).unwrap()
+    }
+
+    fn id(s: &str) -> StoreId {
+        StoreId::new(PathBuf::from(s)).unwrap()
+    }
+
+    fn link_ids(store: &Store, s: &str) -> Vec<StoreId> {
+        store.get(id(s)).unwrap().unwrap()
+            .links().unwrap()
+            .map(|l| l.get_store_id().clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_transfer_with_links() {
+        let source = get_store();
+        let target = get_store();
+
+        {
+            let mut a = source.create(id("a")).unwrap();
+            let mut b = source.create(id("b")).unwrap();
+            let mut c = source.create(id("c")).unwrap();
+            *a.get_content_mut() = String::from("a");
+            a.add_link(&mut b).unwrap();
+            b.add_link(&mut c).unwrap();
+        }
+
+        let options = TransferOptions::default().with_links(true);
+        let report  = transfer(&source, &target, vec![id("a")], &options).unwrap();
+
+        assert_eq!(report.copied(), &[id("a"), id("b"), id("c")]);
+        assert!(report.conflicts().is_empty());
+        assert!(report.dropped_links().is_empty());
+        assert_eq!(link_ids(&target, "b"), vec![id("a"), id("c")]);
+        assert_eq!(target.get(id("a")).unwrap().unwrap().get_content(), "a");
+    }
+
+    #[test]
+    fn test_transfer_drops_and_completes_links() {
+        let source = get_store();
+        let target = get_store();
+
+        {
+            let mut a = source.create(id("a")).unwrap();
+            let mut b = source.create(id("b")).unwrap();
+            let mut c = source.create(id("c")).unwrap();
+            a.add_link(&mut b).unwrap();
+            a.add_link(&mut c).unwrap();
+
+            let _ = target.create(id("c")).unwrap();
+        }
+
+        let report = transfer(&source, &target, vec![id("a")], &TransferOptions::default()).unwrap();
+
+        assert_eq!(report.copied(), &[id("a")]);
+        assert_eq!(report.dropped_links(), &[(id("a"), id("b"))]);
+        assert_eq!(link_ids(&target, "a"), vec![id("c")]);
+        assert_eq!(link_ids(&target, "c"), vec![id("a")]);
+    }
+
+    #[test]
+    fn test_transfer_labels() {
+        let source = get_store();
+        let target = get_store();
+
+        {
+            let mut a = source.create(id("a")).unwrap();
+            let mut b = source.create(id("b")).unwrap();
+            let mut c = source.create(id("c")).unwrap();
+            a.add_labeled_link_to(&mut b, "blocks", None).unwrap();
+            a.add_labeled_link_to(&mut c, "cites", None).unwrap();
+
+            let _ = target.create(id("c")).unwrap();
+        }
+
+        let _ = transfer(&source, &target, vec![id("a")], &TransferOptions::default()).unwrap();
+
+        let a = target.get(id("a")).unwrap().unwrap();
+        let labels = a.labeled_links().unwrap();
+        assert_eq!(labels.len(), 1);
+        assert_eq!(labels[0].label(), "cites");
+
+        let c = target.get(id("c")).unwrap().unwrap();
+        let labels = c.labeled_links().unwrap();
+        assert_eq!(labels.len(), 1);
+        assert_eq!(labels[0].target().unwrap(), id("a"));
+    }
+
+    #[test]
+    fn test_transfer_conflicts() {
+        let source = get_store();
+        let target = get_store();
+
+        {
+            let mut a = source.create(id("a")).unwrap();
+            *a.get_content_mut() = String::from("source");
+            let _ = source.create(id("b")).unwrap();
+
+            let mut a = target.create(id("a")).unwrap();
+            *a.get_content_mut() = String::from("target");
+            let _ = target.create(id("b")).unwrap();
+        }
+
+        let ids     = vec![id("a"), id("b")];
+        let report  = transfer(&source, &target, ids.clone(), &TransferOptions::default()).unwrap();
+
+        assert!(report.copied().is_empty());
+        assert_eq!(report.identical(), &[id("b")]);
+        assert_eq!(report.conflicts().len(), 1);
+        match report.conflicts()[0] {
+            (ref i, Conflict::ContentDiffers { .. }) => assert_eq!(*i, id("a")),
+            ref other => panic!("Unexpected conflict: {:?}", other),
+        }
+        assert_eq!(target.get(id("a")).unwrap().unwrap().get_content(), "target");
+
+        let options = TransferOptions::default().overwrite(true);
+        let report  = transfer(&source, &target, ids, &options).unwrap();
+
+        assert_eq!(report.overwritten(), &[id("a")]);
+        assert_eq!(target.get(id("a")).unwrap().unwrap().get_content(), "source");
+    }
+
+}
@@ -18,8 +18,11 @@
 //
 
 use std::path::Path;
+use std::fs::File;
+use std::io::Read;
 
 use failure::Fallible as Result;
+use digest::Digest;
 
 pub trait Hasher {
     const NAME: &'static str;
@@ -28,6 +31,51 @@ pub trait Hasher {
     fn hash<P: AsRef<Path>>(path: P) -> Result<String>;
 }
 
+/// The names of all hashers in this module, usable with `hash_with_name()`
+pub const NAMES: &[&str] = &[
+    sha1::Sha1Hasher::NAME,
+    sha256::Sha256Hasher::NAME,
+    blake2b::Blake2bHasher::NAME,
+    partial::PartialHasher::NAME,
+];
+
+/// Hash the file at `path` with the hasher named `name`
+///
+/// Returns `None` if there is no hasher with that name in this module.
+pub fn hash_with_name<P: AsRef<Path>>(name: &str, path: P) -> Option<Result<String>> {
+    match name {
+        sha1::Sha1Hasher::NAME       => Some(sha1::Sha1Hasher::hash(path)),
+        sha256::Sha256Hasher::NAME   => Some(sha256::Sha256Hasher::hash(path)),
+        blake2b::Blake2bHasher::NAME => Some(blake2b::Blake2bHasher::hash(path)),
+        partial::PartialHasher::NAME => Some(partial::PartialHasher::hash(path)),
+        _ => None,
+    }
+}
+
+/// Feed everything `reader` yields into the digest `D`, without reading it into memory at once
+fn digest_reader<D: Digest, R: Read>(mut reader: R) -> Result<D> {
+    let mut digest = D::new();
+    let mut buffer = [0; 8 * 1024];
+
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            return Ok(digest)
+        }
+        digest.input(&buffer[..n]);
+    }
+}
+
+/// Hash the file at `path` with the digest `D`, returning the hex string of the hash
+fn hash_file<D: Digest, P: AsRef<Path>>(path: P) -> Result<String> {
+    let digest = digest_reader::<D, _>(File::open(path)?)?;
+    Ok(to_hex(&digest.result()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
 pub mod default {
     pub use super::sha1::Sha1Hasher as DefaultHasher;
 }
@@ -51,10 +99,152 @@ pub mod sha1 {
     impl Hasher for Sha1Hasher {
         const NAME : &'static str = "sha1";
 
+        /// The file is read in chunks, so this works for binary files as well. For text files,
+        /// the hash is the same as `Sha1Hasher::sha1_hash()` over the file content.
+        fn hash<P: AsRef<Path>>(path: P) -> Result<String> {
+            super::hash_file::<Sha1, _>(path)
+        }
+    }
+
+}
+
+pub mod sha256 {
+    use std::path::Path;
+
+    use failure::Fallible as Result;
+    use sha2::Sha256;
+
+    use crate::hasher::Hasher;
+
+    pub struct Sha256Hasher;
+
+    impl Hasher for Sha256Hasher {
+        const NAME : &'static str = "sha256";
+
+        fn hash<P: AsRef<Path>>(path: P) -> Result<String> {
+            super::hash_file::<Sha256, _>(path)
+        }
+    }
+
+}
+
+pub mod blake2b {
+    use std::path::Path;
+
+    use failure::Fallible as Result;
+    use blake2::Blake2b;
+
+    use crate::hasher::Hasher;
+
+    pub struct Blake2bHasher;
+
+    impl Hasher for Blake2bHasher {
+        const NAME : &'static str = "blake2b";
+
         fn hash<P: AsRef<Path>>(path: P) -> Result<String> {
-            Ok(Sha1Hasher::sha1_hash(&::std::fs::read_to_string(path)?))
+            super::hash_file::<Blake2b, _>(path)
+        }
+    }
+
+}
+
+pub mod partial {
+    use std::path::Path;
+    use std::fs::File;
+    use std::io::Read;
+
+    use failure::Fallible as Result;
+    use sha2::{Sha256, Digest};
+
+    use crate::hasher::Hasher;
+
+    /// The number of bytes from the beginning of the file the `PartialHasher` hashes
+    pub const PARTIAL_HASH_BYTES: u64 = 64 * 1024;
+
+    /// A hasher for (very large) files where hashing the whole file is too expensive
+    ///
+    /// Only the first `PARTIAL_HASH_BYTES` of the file are hashed (with SHA-256), together with the
+    /// size of the file. This is fast, but changes after the first `PARTIAL_HASH_BYTES` which do
+    /// not alter the file size are not detected.
+    pub struct PartialHasher;
+
+    impl Hasher for PartialHasher {
+        const NAME : &'static str = "sha256_partial";
+
+        fn hash<P: AsRef<Path>>(path: P) -> Result<String> {
+            let file       = File::open(path)?;
+            let size       = file.metadata()?.len();
+            let mut digest = super::digest_reader::<Sha256, _>(file.take(PARTIAL_HASH_BYTES))?;
+
+            digest.input(size.to_le_bytes());
+            Ok(super::to_hex(&digest.result()))
         }
     }
 
 }
 
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+    use std::io::Write;
+
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn tmp_file(dir: &TempDir, name: &str, content: &[u8]) -> PathBuf {
+        let path = dir.path().join(name);
+        File::create(&path).unwrap().write_all(content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_hashes() {
+        let dir  = TempDir::new("libimagentryref-test").unwrap();
+        let path = tmp_file(&dir, "hashes", b"hello\n");
+
+        assert_eq!(sha1::Sha1Hasher::hash(&path).unwrap(),
+                   "f572d396fae9206628714fb2ce00f72e94f2258f");
+        assert_eq!(sha1::Sha1Hasher::hash(&path).unwrap(),
+                   sha1::Sha1Hasher::sha1_hash("hello\n"));
+        assert_eq!(sha256::Sha256Hasher::hash(&path).unwrap(),
+                   "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03");
+        assert_eq!(blake2b::Blake2bHasher::hash(&path).unwrap(),
+                   "f60ce482e5cc1229f39d71313171a8d9f4ca3a87d066bf4b205effb528192a75f14f3271e2c1a90e1de53f275b4d4793eef2f5e31ea90d2ce29d2e481c36435f");
+    }
+
+    #[test]
+    fn test_hash_binary_file() {
+        let dir  = TempDir::new("libimagentryref-test").unwrap();
+        let path = tmp_file(&dir, "binary", &[0xff, 0xfe, 0x00, 0xc3]);
+
+        for name in NAMES {
+            let res = hash_with_name(name, &path);
+            assert!(res.is_some(), "No hasher named {}", name);
+            assert!(res.unwrap().is_ok(), "Hasher {} failed on binary file", name);
+        }
+    }
+
+    #[test]
+    fn test_partial_hash() {
+        let dir       = TempDir::new("libimagentryref-test").unwrap();
+        let size      = partial::PARTIAL_HASH_BYTES as usize;
+        let mut data  = vec![0; size + 10];
+        let original  = tmp_file(&dir, "partial-1", &data);
+
+        data[size + 5] = 1; // change after the hashed part
+        let tail_changed = tmp_file(&dir, "partial-2", &data);
+
+        data.push(0); // change the size
+        let size_changed = tmp_file(&dir, "partial-3", &data);
+
+        let hash = |p| partial::PartialHasher::hash(p).unwrap();
+        assert_eq!(hash(&original), hash(&tail_changed));
+        assert_ne!(hash(&original), hash(&size_changed));
+    }
+
+    #[test]
+    fn test_hash_with_unknown_name() {
+        assert!(hash_with_name("md5", "/tmp").is_none());
+    }
+}
@@ -43,6 +43,9 @@ extern crate toml;
 extern crate toml_query;
 #[macro_use] extern crate serde_derive;
 extern crate sha1;
+extern crate sha2;
+extern crate blake2;
+extern crate digest;
 
 extern crate libimagstore;
 extern crate libimagrt;
@@ -52,6 +55,8 @@ extern crate libimagerror;
 
 #[cfg(test)]
 extern crate env_logger;
+#[cfg(test)]
+extern crate tempdir;
 
 pub mod hasher;
 pub mod reference;
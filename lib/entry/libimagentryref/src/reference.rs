@@ -59,6 +59,18 @@ impl Config {
     pub fn new(map: BTreeMap<String, PathBuf>) -> Self {
         Config(map)
     }
+
+    /// Find the name of the basepath `path` is located in
+    ///
+    /// If `path` is located in more than one of the configured basepathes, the most specific
+    /// (longest) one is returned.
+    pub fn find_basepath_for<P: AsRef<Path>>(&self, path: P) -> Option<&String> {
+        self.0
+            .iter()
+            .filter(|(_, basepath)| path.as_ref().starts_with(basepath))
+            .max_by_key(|(_, basepath)| basepath.components().count())
+            .map(|(name, _)| name)
+    }
 }
 
 impl Deref for Config {
@@ -159,6 +171,9 @@ pub trait Ref {
 
     /// Check whether the referenced file still matches its hash
     fn hash_valid(&self, config: &Config) -> Result<bool>;
+
+    /// Get all stored hashes, by name of the hasher which created them
+    fn get_hashes(&self) -> Result<BTreeMap<String, String>>;
 }
 
 impl<'a, H: Hasher> Ref for RefWithHasher<'a, H> {
@@ -248,16 +263,23 @@ impl<'a, H: Hasher> Ref for RefWithHasher<'a, H> {
 
         let file_path = get_file_path(config, basepath_name.as_ref(), &path)?;
 
-        ref_header
-            .read(H::NAME)
-            .context(format_err!("Failed to read header at 'ref.{}'", H::NAME))?
-            .ok_or_else(|| format_err!("Header missing at 'ref.{}'", H::NAME))
-            .and_then(|v| {
-                v.as_str().ok_or_else(|| {
-                    Error::from(EM::EntryHeaderTypeError2("ref.hash.<hash>", "string"))
+        let hash = self.get_hash()?;
+        H::hash(file_path).map(|h| h == hash)
+    }
+
+    fn get_hashes(&self) -> Result<BTreeMap<String, String>> {
+        match self.0.get_header().read("ref.hash").context("Failed to read header at 'ref.hash'")? {
+            Some(Value::Table(tbl)) => tbl
+                .iter()
+                .map(|(name, hash)| {
+                    hash.as_str()
+                        .map(|hash| (name.clone(), String::from(hash)))
+                        .ok_or_else(|| Error::from(EM::EntryHeaderTypeError2("ref.hash.<hash>", "string")))
                 })
-            })
-            .and_then(|hash| H::hash(file_path).map(|h| h == hash))
+                .collect(),
+            Some(_) => Err(Error::from(EM::EntryHeaderTypeError2("ref.hash", "table"))),
+            None    => Err(Error::from(EM::EntryHeaderFieldMissing("ref.hash"))),
+        }
     }
 
 }
@@ -272,6 +294,21 @@ pub trait MutRef {
         -> Result<()>
         where P: AsRef<Path>,
               Coll: AsRef<str>;
+
+    /// Point the ref to `path` (located in the basepath `basepath_name`) without touching the
+    /// stored hashes.
+    ///
+    /// This is meant for repairing refs to files which were moved. The caller has to make sure that
+    /// `path` is actually the referenced file, for example by comparing hashes.
+    fn relocate<P, Coll>(&mut self, path: P, basepath_name: Coll, config: &Config) -> Result<()>
+        where P: AsRef<Path>,
+              Coll: AsRef<str>;
+
+    /// Hash the referenced file with the hasher of this ref and store the hash.
+    ///
+    /// This can be used to migrate refs to another hasher. If `keep_other_hashes` is false, the
+    /// hashes of all other hashers are removed from the entry.
+    fn rehash(&mut self, config: &Config, keep_other_hashes: bool) -> Result<()>;
 }
 
 
@@ -370,6 +407,49 @@ impl<'a, H> MutRef for MutRefWithHasher<'a, H>
             .map(|_| ())
     }
 
+    fn relocate<P, Coll>(&mut self, path: P, basepath_name: Coll, config: &Config) -> Result<()>
+        where P: AsRef<Path>,
+              Coll: AsRef<str>
+    {
+        if !self.0.is::<IsRef>()? {
+            return Err(err_msg("Entry is not a reference")).context("Relocating ref").map_err(Error::from)
+        }
+
+        let prefix  = get_basepath(basepath_name.as_ref(), config)?;
+        let relpath = path
+            .as_ref()
+            .strip_prefix(prefix)
+            .context(format_err!("'{}' is not located in basepath '{}'",
+                                 path.as_ref().display(),
+                                 basepath_name.as_ref()))?
+            .to_str()
+            .map(String::from)
+            .ok_or_else(|| Error::from(EM::UTF8Error))?;
+
+        trace!("Relocating ref to {} in {}", relpath, basepath_name.as_ref());
+        let header = self.0.get_header_mut();
+        let _ = header.insert("ref.relpath", Value::String(relpath))?;
+        let _ = header.insert("ref.basepath", Value::String(String::from(basepath_name.as_ref())))?;
+        Ok(())
+    }
+
+    fn rehash(&mut self, config: &Config, keep_other_hashes: bool) -> Result<()> {
+        if !self.0.is::<IsRef>()? {
+            return Err(err_msg("Entry is not a reference")).context("Rehashing ref").map_err(Error::from)
+        }
+
+        let path = self.0.as_ref_with_hasher::<H>().get_path(config)?;
+        let hash = H::hash(&path).context(format_err!("Failed to hash '{}'", path.display()))?;
+        trace!("Rehashing ref: {} = {}", H::NAME, hash);
+
+        let header = self.0.get_header_mut();
+        if !keep_other_hashes {
+            let _ = header.insert("ref.hash", Value::Table(Map::new()))?;
+        }
+        let _ = header.insert(&format!("ref.hash.{}", H::NAME), Value::String(hash))?;
+        Ok(())
+    }
+
 }
 
 /// Create a new header section for a "ref".
@@ -563,5 +643,100 @@ mod test {
         assert!(!entry.as_ref_with_hasher::<TestHasher>().is_ref().unwrap());
     }
 
+    #[test]
+    fn test_makeref_relocate() {
+        setup_logging();
+        let store     = get_store();
+        let mut entry = store.retrieve(PathBuf::from("test_makeref_relocate")).unwrap();
+        let config    = Config({
+            let mut c = BTreeMap::new();
+            c.insert(String::from("root"), PathBuf::from("/"));
+            c.insert(String::from("tmp"), PathBuf::from("/tmp"));
+            c
+        });
+
+        assert!(entry.as_ref_with_hasher_mut::<TestHasher>().make_ref("/tmp", "root", &config, false).is_ok());
+
+        let res = entry.as_ref_with_hasher_mut::<TestHasher>().relocate("/tmp/moved", "tmp", &config);
+        assert!(res.is_ok(), "Expected to be ok: {:?}", res);
+
+        let r = entry.as_ref_with_hasher::<TestHasher>();
+        assert_eq!(r.get_relative_path().unwrap(), PathBuf::from("moved"));
+        assert_eq!(r.get_path(&config).unwrap(), PathBuf::from("/tmp/moved"));
+        assert_eq!(r.get_hash().unwrap(), "/tmp"); // the hash is not touched
+
+        let res = entry.as_ref_with_hasher_mut::<TestHasher>().relocate("/elsewhere", "tmp", &config);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_relocate_noref() {
+        setup_logging();
+        let store     = get_store();
+        let mut entry = store.retrieve(PathBuf::from("test_relocate_noref")).unwrap();
+        let config    = Config({
+            let mut c = BTreeMap::new();
+            c.insert(String::from("root"), PathBuf::from("/"));
+            c
+        });
+
+        assert!(entry.as_ref_with_hasher_mut::<TestHasher>().relocate("/tmp", "root", &config).is_err());
+    }
+
+    #[test]
+    fn test_hash_valid_rehash() {
+        use std::io::Write;
+        use crate::hasher::sha1::Sha1Hasher;
+        use crate::hasher::sha256::Sha256Hasher;
+
+        setup_logging();
+        let store     = get_store();
+        let mut entry = store.retrieve(PathBuf::from("test_hash_valid_rehash")).unwrap();
+        let dir       = ::std::env::temp_dir();
+        let file      = dir.join("libimagentryref-test-hash-valid");
+        let config    = Config({
+            let mut c = BTreeMap::new();
+            c.insert(String::from("tmp"), dir.clone());
+            c
+        });
+
+        let write = |content: &[u8]| ::std::fs::File::create(&file).unwrap().write_all(content).unwrap();
+        write(b"content");
+
+        assert!(entry.as_ref_with_hasher_mut::<Sha1Hasher>().make_ref(&file, "tmp", &config, false).is_ok());
+        assert!(entry.as_ref_with_hasher::<Sha1Hasher>().hash_valid(&config).unwrap());
+        assert!(entry.as_ref_with_hasher::<Sha256Hasher>().hash_valid(&config).is_err()); // no sha256 yet
+
+        assert!(entry.as_ref_with_hasher_mut::<Sha256Hasher>().rehash(&config, true).is_ok());
+        assert!(entry.as_ref_with_hasher::<Sha256Hasher>().hash_valid(&config).unwrap());
+        assert_eq!(entry.as_ref_with_hasher::<Sha1Hasher>().get_hashes().unwrap().len(), 2);
+
+        write(b"changed content");
+        assert!(!entry.as_ref_with_hasher::<Sha1Hasher>().hash_valid(&config).unwrap());
+        assert!(!entry.as_ref_with_hasher::<Sha256Hasher>().hash_valid(&config).unwrap());
+
+        assert!(entry.as_ref_with_hasher_mut::<Sha256Hasher>().rehash(&config, false).is_ok());
+        let hashes = entry.as_ref_with_hasher::<Sha1Hasher>().get_hashes().unwrap();
+        assert_eq!(hashes.keys().collect::<Vec<_>>(), vec!["sha256"]);
+        assert!(entry.as_ref_with_hasher::<Sha256Hasher>().hash_valid(&config).unwrap());
+    }
+
+    #[test]
+    fn test_find_basepath_for() {
+        let config = Config({
+            let mut c = BTreeMap::new();
+            c.insert(String::from("home"), PathBuf::from("/home/alice"));
+            c.insert(String::from("music"), PathBuf::from("/home/alice/music"));
+            c
+        });
+
+        let find = |p: &str| config.find_basepath_for(p).map(String::as_str);
+
+        assert_eq!(find("/home/alice/music/song.mp3"), Some("music"));
+        assert_eq!(find("/home/alice/doc/letter.txt"), Some("home"));
+        assert_eq!(find("/home/alice/musical.txt"),    Some("home"));
+        assert_eq!(find("/tmp/file"),                  None);
+    }
+
 }
 
@@ -20,6 +20,7 @@
 use failure::Fallible as Result;
 
 use libimagrt::runtime::Runtime;
+use libimagrt::schema::ConfigSchema;
 
 use crate::reference::Config as RefConfig;
 
@@ -35,3 +36,8 @@ pub fn get_ref_config(rt: &Runtime, app_name: &'static str) -> Result<RefConfig>
 }
 
 
+
+/// Register the configuration keys of libimagentryref
+pub fn register_config_schema(schema: &mut ConfigSchema) {
+    schema.register_table("ref.basepathes", "The base paths of the collections refs point into, by collection name");
+}
@@ -0,0 +1,72 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+use libimagstore::store::Store;
+use libimagentrylink::graph::LinkGraph;
+
+use failure::Fallible as Result;
+use url::Url;
+
+use crate::link::Link;
+
+pub trait StoreUrlGraphExt {
+    /// Build the link graph of the store, with the URL of each external link entry attached to
+    /// its node
+    fn link_graph_with_urls(&self) -> Result<LinkGraph>;
+}
+
+impl StoreUrlGraphExt for Store {
+    fn link_graph_with_urls(&self) -> Result<LinkGraph> {
+        LinkGraph::build(self, |entry| {
+            if entry.get_location().is_in_collection(&["url"]) {
+                entry.get_url().map(|url| url.map(Url::into_string))
+            } else {
+                Ok(None)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    use libimagstore::store::Store;
+
+    use crate::linker::UrlLinker;
+
+    #[test]
+    fn test_graph_with_urls() {
+        let _     = ::env_logger::try_init();
+        let store = Store::new_inmemory(PathBuf::from("/"), &None).unwrap();
+        {
+            let mut e = store.create(PathBuf::from("base-test_graph_with_urls")).unwrap();
+            let url   = Url::parse("http://example.com").unwrap();
+            assert!(e.add_url(&store, url).is_ok());
+        }
+
+        let graph = store.link_graph_with_urls().unwrap();
+        assert_eq!(graph.nodes().len(), 2);
+        assert_eq!(graph.edges().len(), 1);
+
+        let urls = graph.nodes().iter().filter_map(|n| n.url()).collect::<Vec<_>>();
+        assert_eq!(urls, vec!["http://example.com/"]);
+    }
+}
@@ -69,6 +69,7 @@ extern crate libimagentrylink;
 
 module_entry_path_mod!("url");
 
+pub mod graph;
 pub mod iter;
 pub mod link;
 pub mod linker;
@@ -37,7 +37,6 @@
     while_true,
 )]
 
-extern crate interactor;
 extern crate ansi_term;
 #[macro_use] extern crate lazy_static;
 extern crate regex;
@@ -46,6 +45,7 @@ extern crate toml;
 extern crate handlebars;
 extern crate serde_json;
 extern crate failure;
+extern crate termion;
 
 extern crate libimagstore;
 extern crate libimagerror;
@@ -53,5 +53,6 @@ extern crate libimagerror;
 pub mod ask;
 pub mod filter;
 pub mod format;
+pub mod pick;
 pub mod ui;
 
@@ -0,0 +1,430 @@
+//
+// imag - the personal information management suite for the commandline
+// Copyright (C) 2015-2019 Matthias Beyer <mail@beyermatthias.de> and contributors
+//
+// This library is free software; you can redistribute it and/or
+// modify it under the terms of the GNU Lesser General Public
+// License as published by the Free Software Foundation; version
+// 2.1 of the License.
+//
+// This library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this library; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
+//
+
+//! A fuzzy finder for picking entries on the terminal
+//!
+//! The picker reads keys from and draws on the controlling terminal (`/dev/tty`), so it also
+//! works if stdout is piped into another imag command.
+//!
+//! The query is split at whitespace. Each word has to match the id of an entry as a fuzzy
+//! subsequence, except for words starting with `@`, which restrict the entries to a collection:
+//! `@diary 2019` finds all entries in the "diary" collection matching "2019".
+//!
+//! Keys: `Up`/`Down` (or `Ctrl-p`/`Ctrl-n`) move, `Tab` (de)selects an entry if more than one
+//! entry can be picked, `Enter` accepts, `Ctrl-v` toggles the preview of the entry, `Ctrl-u`
+//! clears the query and `Esc`/`Ctrl-c` aborts.
+
+use std::collections::BTreeSet;
+use std::io::Write;
+
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::screen::AlternateScreen;
+use termion::{clear, cursor, style};
+
+use libimagstore::store::Store;
+use libimagstore::storeid::StoreId;
+
+use failure::Fallible as Result;
+
+/// Score how well `pattern` matches `candidate`
+///
+/// Returns `None` if the characters of `pattern` do not appear in `candidate` in order (ignoring
+/// case). Consecutive matches and matches at the beginning of a path component or word get a
+/// higher score, skipped characters lower the score.
+pub fn fuzzy_score(pattern: &str, candidate: &str) -> Option<i64> {
+    let mut score    = 0;
+    let mut previous = None;
+    let mut chars    = candidate.chars().enumerate().peekable();
+
+    for p in pattern.chars().flat_map(char::to_lowercase) {
+        loop {
+            let (i, c) = chars.next()?;
+
+            if c.to_lowercase().eq(Some(p).into_iter()) {
+                score += 16;
+
+                if previous.map(|prev| prev + 1 == i).unwrap_or(false) {
+                    score += 8;
+                }
+
+                let at_boundary = i == 0 || candidate
+                    .chars()
+                    .nth(i - 1)
+                    .map(|c| "/-_. ".contains(c))
+                    .unwrap_or(false);
+
+                if at_boundary {
+                    score += 8;
+                }
+
+                previous = Some(i);
+                break
+            } else {
+                score -= 1;
+            }
+        }
+    }
+
+    Some(score)
+}
+
+/// Score `candidate` for a whole query, see the module documentation
+fn query_score(query: &str, candidate: &str) -> Option<i64> {
+    query.split_whitespace().fold(Some(0), |score, word| {
+        score.and_then(|score| if word.starts_with('@') {
+            let collection = &word[1..];
+            let in_collection = candidate.starts_with(collection)
+                && candidate[collection.len()..].starts_with('/');
+
+            if in_collection { Some(score) } else { None }
+        } else {
+            fuzzy_score(word, candidate).map(|s| score + s)
+        })
+    })
+}
+
+/// The state of a picker, independent of the terminal
+struct State {
+    candidates: Vec<String>,
+    query: String,
+    matches: Vec<usize>,
+    cursor: usize,
+    selected: BTreeSet<usize>,
+    multi: bool,
+}
+
+impl State {
+
+    fn new(candidates: Vec<String>, multi: bool) -> State {
+        let mut state = State {
+            candidates,
+            query: String::new(),
+            matches: vec![],
+            cursor: 0,
+            selected: BTreeSet::new(),
+            multi,
+        };
+        state.update();
+        state
+    }
+
+    /// Re-calculate the matches after the query changed
+    fn update(&mut self) {
+        let query = &self.query;
+        let mut matches = self.candidates
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| query_score(query, c).map(|score| (score, i)))
+            .collect::<Vec<_>>();
+
+        // best score first, candidates with equal score keep their order
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+        self.matches = matches.into_iter().map(|(_, i)| i).collect();
+        self.cursor  = 0;
+    }
+
+    fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.update();
+    }
+
+    fn pop(&mut self) {
+        if self.query.pop().is_some() {
+            self.update();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.query.clear();
+        self.update();
+    }
+
+    fn up(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    fn down(&mut self) {
+        if self.cursor + 1 < self.matches.len() {
+            self.cursor += 1;
+        }
+    }
+
+    /// (De)select the candidate under the cursor and move down
+    fn toggle(&mut self) {
+        if let (true, Some(current)) = (self.multi, self.current()) {
+            if !self.selected.remove(&current) {
+                self.selected.insert(current);
+            }
+            self.down();
+        }
+    }
+
+    /// The candidate under the cursor
+    fn current(&self) -> Option<usize> {
+        self.matches.get(self.cursor).cloned()
+    }
+
+    /// The picked candidates: the selected ones or the one under the cursor if none is selected
+    fn result(&self) -> Vec<usize> {
+        if self.selected.is_empty() {
+            self.current().into_iter().collect()
+        } else {
+            self.selected.iter().cloned().collect()
+        }
+    }
+
+}
+
+/// A fuzzy finder over the entries of a store
+pub struct Picker<'a> {
+    store: &'a Store,
+    multi: bool,
+    preview: bool,
+    collection: Option<String>,
+}
+
+impl<'a> Picker<'a> {
+
+    pub fn new(store: &'a Store) -> Picker<'a> {
+        Picker {
+            store,
+            multi: false,
+            preview: true,
+            collection: None,
+        }
+    }
+
+    /// Allow picking more than one entry
+    pub fn multi(mut self, multi: bool) -> Self {
+        self.multi = multi;
+        self
+    }
+
+    /// Show the header and content of the entry under the cursor (can be toggled with `Ctrl-v`)
+    pub fn preview(mut self, preview: bool) -> Self {
+        self.preview = preview;
+        self
+    }
+
+    /// Only offer the entries of a collection
+    pub fn collection<S: Into<String>>(mut self, collection: S) -> Self {
+        self.collection = Some(collection.into());
+        self
+    }
+
+    /// Let the user pick entries
+    ///
+    /// Returns an empty list if the user aborted or there was nothing to pick from.
+    pub fn pick(&self) -> Result<Vec<StoreId>> {
+        let entries = self.store.entries()?;
+        let entries = match self.collection {
+            Some(ref collection) => entries.in_collection(collection)?,
+            None                 => entries,
+        };
+
+        let mut ids = entries.into_storeid_iter().collect::<Result<Vec<StoreId>>>()?;
+        ids.sort();
+
+        if ids.is_empty() {
+            return Ok(vec![])
+        }
+
+        let candidates = ids.iter().map(StoreId::local_display_string).collect();
+        let state      = self.run(State::new(candidates, self.multi))?;
+
+        Ok(state.map(|s| s.result().into_iter().map(|i| ids[i].clone()).collect()).unwrap_or_default())
+    }
+
+    /// Run the picker on the terminal, returns `None` if the user aborted
+    fn run(&self, mut state: State) -> Result<Option<State>> {
+        let tty        = termion::get_tty()?;
+        let mut screen = AlternateScreen::from(tty.try_clone()?.into_raw_mode()?);
+        let mut keys   = tty.keys();
+
+        let mut preview = self.preview;
+        let mut cache : Option<(usize, Vec<String>)> = None;
+
+        loop {
+            if preview {
+                if let Some(current) = state.current() {
+                    if cache.as_ref().map(|c| c.0 != current).unwrap_or(true) {
+                        // A broken entry must not end the picker
+                        let lines = self
+                            .preview_lines(&state.candidates[current])
+                            .unwrap_or_else(|e| vec![format!("Cannot show entry: {}", e)]);
+                        cache = Some((current, lines));
+                    }
+                }
+            }
+
+            let preview_lines = cache
+                .as_ref()
+                .filter(|c| preview && state.current() == Some(c.0))
+                .map(|c| &c.1[..]);
+
+            render(&mut screen, &state, preview_lines)?;
+
+            match keys.next() {
+                None         => return Ok(None),
+                Some(key)    => match key? {
+                    Key::Esc | Key::Ctrl('c')   => return Ok(None),
+                    Key::Char('\n')             => return Ok(Some(state)),
+                    Key::Char('\t')             => state.toggle(),
+                    Key::Up | Key::Ctrl('p')    => state.up(),
+                    Key::Down | Key::Ctrl('n')  => state.down(),
+                    Key::Backspace              => state.pop(),
+                    Key::Ctrl('u')              => state.clear(),
+                    Key::Ctrl('v')              => preview = !preview,
+                    Key::Char(c)                => state.push(c),
+                    _                           => {},
+                },
+            }
+        }
+    }
+
+    /// The lines of the entry `id`, read without borrowing it from the store
+    fn preview_lines(&self, id: &str) -> Result<Vec<String>> {
+        let id = StoreId::new(id.into())?;
+        if !self.store.exists(id.clone())? {
+            return Ok(vec![])
+        }
+
+        let entry = self.store.get_copy(id)?;
+        Ok(entry.to_str()?.lines().map(String::from).collect())
+    }
+
+}
+
+fn render<W: Write>(out: &mut W, state: &State, preview: Option<&[String]>) -> Result<()> {
+    let (width, height) = termion::terminal_size()
+        .ok()
+        .filter(|&(w, h)| w > 0 && h > 0)
+        .unwrap_or((80, 24));
+    let width           = width as usize;
+    let height          = height as usize;
+
+    // one line for the query, the rest is shared by the list and the preview
+    let list_height = match preview {
+        Some(_) => (height.saturating_sub(2) / 2).max(1),
+        None    => height.saturating_sub(1).max(1),
+    };
+    let offset = (state.cursor + 1).saturating_sub(list_height);
+
+    write!(out, "{}{}", clear::All, cursor::Goto(1, 1))?;
+
+    let info = if state.selected.is_empty() {
+        format!("  {}/{}", state.matches.len(), state.candidates.len())
+    } else {
+        format!("  {}/{} ({} selected)", state.matches.len(), state.candidates.len(), state.selected.len())
+    };
+    write!(out, "> {}{}{}{}", state.query, style::Faint, info, style::Reset)?;
+
+    let rows = state.matches.iter().enumerate().skip(offset).take(list_height);
+    for (row, (i, &candidate)) in rows.enumerate() {
+        let marker = if state.selected.contains(&candidate) { "*" } else { " " };
+        let line   = truncate(&format!("{} {}", marker, state.candidates[candidate]), width);
+
+        write!(out, "{}", cursor::Goto(1, (row + 2) as u16))?;
+        if i == state.cursor {
+            write!(out, "{}{}{}", style::Invert, line, style::Reset)?;
+        } else {
+            write!(out, "{}", line)?;
+        }
+    }
+
+    if let Some(lines) = preview {
+        let separator = list_height + 2;
+        write!(out, "{}{}", cursor::Goto(1, separator as u16), "─".repeat(width))?;
+
+        for (row, line) in lines.iter().take(height.saturating_sub(separator)).enumerate() {
+            write!(out, "{}{}", cursor::Goto(1, (separator + row + 1) as u16), truncate(line, width))?;
+        }
+    }
+
+    write!(out, "{}", cursor::Goto((state.query.chars().count() + 3) as u16, 1))?;
+    out.flush().map_err(From::from)
+}
+
+fn truncate(s: &str, width: usize) -> String {
+    s.chars().take(width).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn state(candidates: &[&str], multi: bool) -> State {
+        State::new(candidates.iter().map(|s| String::from(*s)).collect(), multi)
+    }
+
+    fn matches(state: &State) -> Vec<&str> {
+        state.matches.iter().map(|&i| state.candidates[i].as_ref()).collect()
+    }
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+        assert!(fuzzy_score("ntx", "notes/text").is_some());
+        assert!(fuzzy_score("NOTES", "notes/text").is_some());
+        assert!(fuzzy_score("xn", "notes/text").is_none());
+        assert!(fuzzy_score("notes", "notes/a") > fuzzy_score("notes", "n/o/t/e/s"));
+        assert!(fuzzy_score("t", "a/t") > fuzzy_score("t", "aat"));
+    }
+
+    #[test]
+    fn test_query_matches_in_score_order() {
+        let mut s = state(&["todo/1", "notes/todo", "diary/2019/1"], false);
+        assert_eq!(matches(&s).len(), 3);
+
+        for c in "todo".chars() {
+            s.push(c);
+        }
+        assert_eq!(matches(&s), vec!["todo/1", "notes/todo"]);
+
+        s.clear();
+        for c in "@diary 1".chars() {
+            s.push(c);
+        }
+        assert_eq!(matches(&s), vec!["diary/2019/1"]);
+    }
+
+    #[test]
+    fn test_selection() {
+        let mut s = state(&["a", "b", "c"], true);
+        assert_eq!(s.result(), vec![0]);
+
+        s.down();
+        s.toggle();
+        s.toggle();
+        assert_eq!(s.result(), vec![1, 2]);
+
+        s.up();
+        s.toggle();
+        assert_eq!(s.result(), vec![2]);
+
+        let mut s = state(&["a", "b"], false);
+        s.down();
+        s.toggle();
+        assert_eq!(s.result(), vec![1]);
+    }
+
+}
@@ -21,11 +21,16 @@ use std::path::PathBuf;
 
 use clap::{Arg, ArgMatches};
 
+use libimagstore::store::Store;
 use libimagstore::storeid::StoreId;
+use libimagstore::schema::ConfigKey;
+use libimagstore::schema::ConfigSchema;
 
 use failure::err_msg;
 use failure::Fallible as Result;
 
+use crate::pick::Picker;
+
 pub fn id_argument<'a, 'b>() -> Arg<'a, 'b> {
     Arg::with_name(id_argument_name())
         .short(id_argument_short())
@@ -63,15 +68,19 @@ pub fn get_id(matches: &ArgMatches) -> Result<Vec<StoreId>> {
         })
 }
 
-pub fn get_or_select_id(matches: &ArgMatches, store_path: &PathBuf) -> Result<Vec<StoreId>> {
-    use interactor::{pick_file, default_menu_cmd};
-
-    get_id(matches)
-        .or_else(|_| {
-            let path = store_path.clone();
-            let p    = pick_file(default_menu_cmd, path)?;
-            let id   = StoreId::new(p)?;
-            Ok(vec![id])
-        })
+pub fn get_or_select_id(matches: &ArgMatches, store: &Store) -> Result<Vec<StoreId>> {
+    get_id(matches).or_else(|_| Picker::new(store).multi(true).pick())
 }
 
+
+/// Register the keys of the commandline user interface
+pub fn register_config_schema(schema: &mut ConfigSchema) {
+    use libimagstore::schema::ValueKind::*;
+
+    schema
+        .register(ConfigKey::new("ui.cli.readline_history_file", String, "History file path for readline"))
+        .register(ConfigKey::new("ui.cli.readline_history_size", Integer, "Number of lines to save in the readline history file"))
+        .register(ConfigKey::new("ui.cli.readline_history_ignore_dups", Boolean, "Ignore duplicated lines in the readline history"))
+        .register(ConfigKey::new("ui.cli.readline_history_ignore_space", Boolean, "Do not save lines which begin with a space in the readline history"))
+        .register(ConfigKey::new("ui.cli.readline_prompt", String, "The prompt string to use"));
+}
@@ -52,7 +52,10 @@ CRATES=(
     ./bin/core/imag-ref
     ./bin/core/imag-gps
     ./bin/core/imag-diagnostics
+    ./bin/core/imag-history
     ./bin/core/imag-mv
+    ./bin/core/imag-profile
+    ./bin/core/imag-config
     ./bin/core/imag-store
     ./bin/core/imag-tag
     ./bin/core/imag-grep